@@ -1,17 +1,16 @@
-use std::convert::Infallible;
-
 use aws_config::meta::credentials::CredentialsProviderChain;
-use aws_types_integration::provider::ProvideCredentials;
 
-use aws_sso_flow::SsoFlow;
+use aws_sso_flow::{
+    aws::{ProvideCredentials, SsoCredentialsProvider},
+    prompts, SsoFlow,
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let flow = SsoFlow::builder().verification_prompt(|url| async move {
-        println!("Go to {url} to sign in with SSO");
-        Ok::<_, Infallible>(())
-    });
-    let provider = CredentialsProviderChain::first_try("SsoFlow", flow)
+    let sso = SsoCredentialsProvider::new(
+        SsoFlow::builder().verification_prompt(prompts::print_to_stderr()),
+    );
+    let provider = CredentialsProviderChain::first_try("SsoFlow", sso)
         .or_default_provider()
         .await;
 
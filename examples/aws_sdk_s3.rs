@@ -0,0 +1,40 @@
+//! List S3 buckets using credentials from an SSO flow.
+//!
+//! Set `RUST_LOG` to see what the SDK is doing, e.g.
+//! `RUST_LOG=aws_config=debug,aws_smithy_runtime=debug`.
+
+use std::convert::Infallible;
+
+use aws_config::{meta::credentials::CredentialsProviderChain, BehaviorVersion};
+use tracing_subscriber::EnvFilter;
+
+use aws_sso_flow::SsoFlow;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    let flow = SsoFlow::builder().verification_prompt(|url| async move {
+        eprintln!("Go to {url} to sign in with SSO");
+        Ok::<_, Infallible>(())
+    });
+    let provider = CredentialsProviderChain::first_try("SsoFlow", flow)
+        .or_default_provider()
+        .await;
+
+    let config = aws_config::defaults(BehaviorVersion::latest())
+        .credentials_provider(provider)
+        .load()
+        .await;
+    let s3 = aws_sdk_s3::Client::new(&config);
+
+    let res = s3.list_buckets().send().await?;
+    for bucket in res.buckets() {
+        println!("{}", bucket.name().unwrap_or_default());
+    }
+
+    Ok(())
+}
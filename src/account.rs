@@ -0,0 +1,26 @@
+/// An AWS account available to the user via SSO.
+///
+/// See [`SsoFlow::accounts`](crate::SsoFlow::accounts).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct AccountInfo {
+    /// The account ID.
+    pub account_id: String,
+
+    /// The display name of the account, if any.
+    pub account_name: Option<String>,
+
+    /// The email address of the account's root user, if any.
+    pub email_address: Option<String>,
+}
+
+/// A role available to the user in an AWS account via SSO.
+///
+/// See [`SsoFlow::account_roles`](crate::SsoFlow::account_roles).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RoleInfo {
+    /// The ID of the account the role is in.
+    pub account_id: String,
+
+    /// The name of the role, as it appears in SSO configuration.
+    pub role_name: String,
+}
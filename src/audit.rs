@@ -0,0 +1,183 @@
+// An append-only log of authentication outcomes.
+//
+// Each outcome is written as a single JSON line, so the log can be tailed or shipped by standard
+// tooling. Credentials and tokens are never written, only what was authenticated and when.
+
+use std::{
+    cell::RefCell,
+    future::Future,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::{
+    AccountId, AccountSelector, CacheStage, ErrorCode, FlowId, RoleName, RoleSelector, SsoConfig,
+};
+
+tokio::task_local! {
+    // The cache entries written by the authentication running in the current task
+    static CACHE_WRITES: RefCell<Vec<CacheWrite>>;
+}
+
+#[derive(Debug)]
+pub(crate) struct AuditLog {
+    path: PathBuf,
+}
+
+/// The account and role being authenticated, as far as they've been resolved.
+pub(crate) struct Subject {
+    pub(crate) account_id: Option<AccountId>,
+    pub(crate) role_name: Option<RoleName>,
+    pub(crate) cache_writes: Vec<CacheWrite>,
+}
+
+/// A cache entry written while authenticating.
+#[derive(serde::Serialize)]
+pub(crate) struct CacheWrite {
+    stage: &'static str,
+    // Unset for stages cached in memory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+}
+
+pub(crate) enum Outcome {
+    /// Credentials were served from the cache.
+    Cached { expires_at: DateTime<Utc> },
+
+    /// Credentials were obtained from AWS SSO.
+    Issued { expires_at: DateTime<Utc> },
+
+    /// Credentials were obtained from AWS SSO by a background refresh.
+    Refreshed { expires_at: DateTime<Utc> },
+
+    /// Authentication failed.
+    Failed { code: ErrorCode, error: String },
+}
+
+#[derive(serde::Serialize)]
+struct Event<'a> {
+    timestamp: DateTime<Utc>,
+    flow_id: String,
+    outcome: &'static str,
+    region: &'a str,
+    start_url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role_name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    cache_writes: &'a [CacheWrite],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+impl AuditLog {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an event to the log.
+    ///
+    /// The event is serialized immediately, so the returned future doesn't borrow its inputs.
+    /// Failures to write the log are ignored, so that auditing never prevents authentication.
+    pub(crate) fn record(
+        &self,
+        timestamp: DateTime<Utc>,
+        flow_id: FlowId,
+        config: &SsoConfig,
+        subject: &Subject,
+        outcome: &Outcome,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        let (outcome, expires_at, error_code, error) = match outcome {
+            Outcome::Cached { expires_at } => ("cached", Some(*expires_at), None, None),
+            Outcome::Issued { expires_at } => ("issued", Some(*expires_at), None, None),
+            Outcome::Refreshed { expires_at } => ("refreshed", Some(*expires_at), None, None),
+            Outcome::Failed { code, error } => {
+                ("failed", None, Some(code.as_str()), Some(error.as_str()))
+            }
+        };
+        let event = Event {
+            timestamp,
+            flow_id: flow_id.to_string(),
+            outcome,
+            region: config.region.as_ref(),
+            start_url: &config.start_url,
+            account_id: subject.account_id.as_ref().map(AccountId::as_str),
+            role_name: subject.role_name.as_ref().map(RoleName::as_str),
+            expires_at,
+            cache_writes: &subject.cache_writes,
+            error_code,
+            error,
+        };
+        let mut line = serde_json::to_string(&event).expect("audit event should serialize");
+        line.push('\n');
+
+        let path = self.path.clone();
+        async move {
+            let _ = append(path, line).await;
+        }
+    }
+}
+
+impl Subject {
+    /// The subject as far as it's known from `config`, without calling AWS.
+    pub(crate) fn new(config: &SsoConfig) -> Self {
+        Self {
+            account_id: match config.account() {
+                AccountSelector::Id(account_id) => Some(account_id),
+                AccountSelector::Name(_) => None,
+            },
+            role_name: match config.role() {
+                RoleSelector::Name(role_name) => Some(role_name),
+                _ => None,
+            },
+            cache_writes: Vec::new(),
+        }
+    }
+}
+
+/// Run `future`, returning its output and the cache entries it wrote.
+pub(crate) async fn collect_cache_writes<F: Future>(future: F) -> (F::Output, Vec<CacheWrite>) {
+    CACHE_WRITES
+        .scope(RefCell::default(), async move {
+            let output = future.await;
+            (output, CACHE_WRITES.with(RefCell::take))
+        })
+        .await
+}
+
+/// Note that the cache entry for `stage` was written, at `path` if it's on disk.
+///
+/// This does nothing outside [`collect_cache_writes`], e.g. in calls that aren't audited.
+pub(crate) fn note_cache_write(stage: CacheStage, path: Option<&Path>) {
+    let _ = CACHE_WRITES.try_with(|writes| {
+        writes.borrow_mut().push(CacheWrite {
+            stage: stage.prefix(),
+            path: path.map(|path| path.display().to_string()),
+        });
+    });
+}
+
+async fn append(path: PathBuf, line: String) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await?;
+    // Lines are written in a single call, so lines from concurrent flows aren't interleaved
+    file.write_all(line.as_bytes()).await?;
+    file.flush().await
+}
@@ -0,0 +1,405 @@
+//! Integration with aws-sdk.
+//!
+//! [`SsoCredentialsProvider`] implements [`ProvideCredentials`], so it can be used as a
+//! credentials provider for aws-sdk clients. The relevant `aws-credential-types` items are
+//! re-exported here so that the version used by this crate never needs to be named directly.
+//!
+//! ```no_run
+//! # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use aws_sso_flow::{
+//!     aws::{ProvideCredentials, SsoCredentialsProvider},
+//!     prompts, SsoFlow,
+//! };
+//!
+//! let provider = SsoCredentialsProvider::new(
+//!     SsoFlow::builder().verification_prompt(prompts::print_to_stderr()),
+//! );
+//!
+//! let credentials = provider.provide_credentials().await?;
+//! # Ok(()) }
+//! ```
+//!
+//! [`SsoFlow`] and [`SsoFlowBuilder`] also implement [`ProvideCredentials`] directly, but the
+//! builder loads its configuration and constructs new clients on every call, and the flow can't
+//! distinguish configuration errors from authentication errors.
+//!
+//! [`SessionCredentials`] convert into [`Credentials`], and into the [`Identity`] that
+//! `aws-sigv4` signing params take, so they can be used with custom `SigV4` clients too.
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use futures::future::BoxFuture;
+use tokio::sync::OnceCell;
+
+pub use aws_credential_types::{
+    provider::{error::CredentialsError, ProvideCredentials},
+    Credentials,
+};
+pub use aws_smithy_runtime_api::client::identity::Identity;
+
+use aws_credential_types::provider::future::ProvideCredentials as ProvideCredentialsFut;
+
+use crate::{
+    BoxError, BoxedSsoFlow, IdentityManager, SessionCredentials, SsoConfig, SsoConfigSource,
+    SsoFlow, SsoFlowBuilder, VerificationPrompt,
+};
+
+/// An aws-sdk credentials provider backed by an [`SsoFlow`].
+///
+/// The provider is constructed from an [`SsoFlowBuilder`] with [`new`](Self::new), or from an
+/// existing flow with [`from_flow`](Self::from_flow). A builder is only built on the first call to
+/// [`provide_credentials`](ProvideCredentials::provide_credentials) that loads its configuration
+/// successfully, and the resulting flow (along with its clients and their connections) is reused
+/// for subsequent calls. If the configuration can't be loaded, it's loaded again on the next call.
+///
+/// Errors are classified as [`CredentialsError::CredentialsNotLoaded`], which lets a credentials
+/// chain try the next provider, or [`CredentialsError::ProviderError`], which stops the chain,
+/// according to the provider's [`Fallback`] policy. By default, only configuration errors fall
+/// through.
+///
+/// `SsoCredentialsProvider` is cheap to clone, and clones share the same flow.
+///
+/// ```no_run
+/// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use aws_config::meta::credentials::CredentialsProviderChain;
+/// use aws_sso_flow::{
+///     aws::{Fallback, ProvideCredentials, SsoCredentialsProvider},
+///     prompts, SsoFlow,
+/// };
+///
+/// let sso = SsoCredentialsProvider::new(SsoFlow::builder().verification_prompt(prompts::auto()))
+///     .fallback(Fallback::Always);
+///
+/// // Try SSO first, falling back to the default chain if SSO fails for any reason
+/// let provider = CredentialsProviderChain::first_try("SsoFlow", sso)
+///     .or_default_provider()
+///     .await;
+///
+/// let credentials = provider.provide_credentials().await?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone)]
+pub struct SsoCredentialsProvider {
+    load: Arc<LoadFn>,
+    flow: Arc<OnceCell<BoxedSsoFlow>>,
+    fallback: Fallback,
+    provider_name_context: bool,
+}
+
+type LoadFn = dyn Fn() -> BoxFuture<'static, Result<BoxedSsoFlow, BoxError>> + Send + Sync;
+
+impl SsoCredentialsProvider {
+    /// Construct a provider that builds a flow from the given `builder` when first used.
+    pub fn new<S, V>(builder: SsoFlowBuilder<S, V>) -> Self
+    where
+        S: SsoConfigSource + Clone + Send + Sync + 'static,
+        S::Future: Send,
+        V: VerificationPrompt + 'static,
+        V::Future: 'static,
+    {
+        Self {
+            load: Arc::new(move || {
+                let builder = builder.clone();
+                Box::pin(async move {
+                    let flow = builder.build().await?;
+                    Ok(flow.boxed())
+                })
+            }),
+            flow: Arc::new(OnceCell::new()),
+            fallback: Fallback::default(),
+            provider_name_context: false,
+        }
+    }
+
+    /// Construct a provider that uses the given `flow`.
+    ///
+    /// Since the flow's configuration has already been loaded, [`Fallback::OnConfigError`] has
+    /// the same effect as [`Fallback::Never`] for this provider.
+    pub fn from_flow<V>(flow: SsoFlow<V>) -> Self
+    where
+        V: VerificationPrompt + 'static,
+        V::Future: 'static,
+    {
+        let flow = flow.boxed();
+        Self {
+            load: Arc::new({
+                let flow = flow.clone();
+                move || {
+                    let flow = flow.clone();
+                    Box::pin(async move { Ok(flow) })
+                }
+            }),
+            flow: Arc::new(OnceCell::from(flow)),
+            fallback: Fallback::default(),
+            provider_name_context: false,
+        }
+    }
+
+    /// Set which errors are reported as [`CredentialsError::CredentialsNotLoaded`].
+    ///
+    /// By default, this is [`Fallback::OnConfigError`].
+    #[must_use]
+    pub fn fallback(mut self, fallback: Fallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Include the profile, account and role in the provider name of the [`Credentials`].
+    ///
+    /// By default, the provider name is `SsoFlow`. With context, it's e.g.
+    /// `SsoFlow(profile=dev, account=012345678910, role=PowerUser)`, which helps to identify which
+    /// configuration produced credentials in debug logs when multiple providers are in use. The
+    /// profile is omitted if the configuration wasn't loaded from a profile (see
+    /// [`SsoFlow::profile`]).
+    ///
+    /// Provider names must be `'static`, so each distinct name is allocated once and never freed.
+    /// This is only a concern if a process uses an unbounded number of SSO configurations.
+    #[must_use]
+    pub fn provider_name_context(mut self, enabled: bool) -> Self {
+        self.provider_name_context = enabled;
+        self
+    }
+
+    async fn credentials(&self) -> Result<Credentials, CredentialsError> {
+        let flow = self
+            .flow
+            .get_or_try_init(|| (self.load)())
+            .await
+            .map_err(|error| match self.fallback {
+                Fallback::OnConfigError | Fallback::Always => CredentialsError::not_loaded(error),
+                Fallback::Never => CredentialsError::provider_error(error),
+            })?;
+
+        let credentials = flow
+            .authenticate()
+            .await
+            .map_err(|error| match self.fallback {
+                Fallback::Always => CredentialsError::not_loaded(error),
+                Fallback::OnConfigError | Fallback::Never => {
+                    CredentialsError::provider_error(error)
+                }
+            })?;
+
+        let provider_name = if self.provider_name_context {
+            provider_name(flow.profile(), flow.config())
+        } else {
+            PROVIDER_NAME
+        };
+        Ok(to_credentials(credentials, provider_name))
+    }
+}
+
+impl fmt::Debug for SsoCredentialsProvider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SsoCredentialsProvider")
+            .field("flow", &self.flow)
+            .field("fallback", &self.fallback)
+            .field("provider_name_context", &self.provider_name_context)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ProvideCredentials for SsoCredentialsProvider {
+    fn provide_credentials<'a>(&'a self) -> ProvideCredentialsFut<'a>
+    where
+        Self: 'a,
+    {
+        ProvideCredentialsFut::new(self.credentials())
+    }
+}
+
+impl<V> IdentityManager<V>
+where
+    V: VerificationPrompt + 'static,
+    V::Future: 'static,
+{
+    /// An aws-sdk credentials provider for the identity with `label`, if there is one.
+    ///
+    /// The provider uses the identity's flow, so it's unaffected if the identity is later replaced
+    /// or removed. Its provider name includes the account and role (see
+    /// [`SsoCredentialsProvider::provider_name_context`]), to tell identities apart in logs.
+    #[must_use]
+    pub fn provider(&self, label: &str) -> Option<SsoCredentialsProvider> {
+        let flow = self.get(label)?;
+        Some(SsoCredentialsProvider::from_flow(flow).provider_name_context(true))
+    }
+}
+
+/// Which [`SsoCredentialsProvider`] errors allow a credentials chain to try the next provider.
+///
+/// Errors that fall through are reported as [`CredentialsError::CredentialsNotLoaded`], and other
+/// errors are reported as [`CredentialsError::ProviderError`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Fallback {
+    /// Fall through if the SSO configuration can't be loaded (e.g. there's no SSO profile), but
+    /// not if authentication fails.
+    #[default]
+    OnConfigError,
+
+    /// Never fall through.
+    Never,
+
+    /// Always fall through, e.g. if SSO is an optional convenience.
+    Always,
+}
+
+/// Provide credentials via an [`SsoFlowBuilder`].
+///
+/// The builder is built on every call, so [`SsoCredentialsProvider`] is usually a better choice.
+///
+/// If SSO configuration can't be loaded for any reason, errors are converted to
+/// [`CredentialsError::CredentialsNotLoaded`], which won't stop resolution if the builder is used
+/// as part of a credentials chain. If an SSO profile is loaded successfully, then any subsequent
+/// authentication errors are converted to [`CredentialsError::ProviderError`] which will stop
+/// resolution.
+///
+/// The aws-sdk's SSO credentials provider behaves similarly but relies on a fresh SSO OIDC access
+/// token being cached (at at `~/.aws/sso/cache/{sha1(start_url)}.json`). As such, `SsoFlowBuilder`s
+/// should be set to run *before* the default provider chain.
+///
+/// ```no_run
+/// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::convert::Infallible;
+///
+/// use aws_config::meta::credentials::CredentialsProviderChain;
+/// use aws_sso_flow::{aws::ProvideCredentials, Region, SsoConfig, SsoFlow};
+///
+/// // Configure an SSO flow that loads SSO from shared config and prints the verification URL
+/// let flow = SsoFlow::builder().verification_prompt(|url| async move {
+///     println!("Go to {url} to sign in with SSO");
+///     Ok::<_, Infallible>(())
+/// });
+///
+/// // Try the SSO flow *first*, and fall back to the default provider chain
+/// let provider = CredentialsProviderChain::first_try("SsoFlow", flow)
+///     .or_default_provider()
+///     .await;
+///
+/// // `flow` will be attempted first, falling back to the default chain if SSO configuration can't
+/// // be loaded.
+/// let creds = provider.provide_credentials().await?;
+///
+/// // Configure an SSO flow that uses static configuration
+/// let flow = SsoFlow::builder()
+///     .config(SsoConfig {
+///         region: Region::new("eu-west-1"),
+///         start_url: "myorg.signin.amazonaws.com/start".to_string(),
+///         account_id: "012345678910".into(),
+///         role_name: "developer".into(),
+///         account_name: None,
+///         role_selector: None,
+///     })
+///     .verification_prompt(|url| async move {
+///         println!("Go to {url} to sign in with SSO");
+///         Ok::<_, Infallible>(())
+///     });
+///
+/// // Try the default chain, and fall back to the statically configured SSO flow
+/// let provider = CredentialsProviderChain::default_provider()
+///     .await
+///     .or_else("SsoFlow", flow);
+///
+/// // the default chain will be attempted first, falling back to `flow` only if no default
+/// // providers could be loaded.
+/// let creds = provider.provide_credentials().await?;
+/// # Ok(()) }
+/// ```
+impl<S, V> ProvideCredentials for SsoFlowBuilder<S, V>
+where
+    S: SsoConfigSource + Clone + fmt::Debug + Send + Sync,
+    S::Future: Send,
+    V: VerificationPrompt + Clone + Send + Sync,
+{
+    fn provide_credentials<'a>(&'a self) -> ProvideCredentialsFut<'a>
+    where
+        Self: 'a,
+    {
+        ProvideCredentialsFut::new(async {
+            let flow = self
+                .clone()
+                .build()
+                .await
+                .map_err(CredentialsError::not_loaded)?;
+
+            let creds = flow
+                .authenticate()
+                .await
+                .map(Into::into)
+                .map_err(CredentialsError::provider_error)?;
+
+            Ok(creds)
+        })
+    }
+}
+
+impl<V> ProvideCredentials for SsoFlow<V>
+where
+    V: VerificationPrompt + Send + Sync,
+{
+    fn provide_credentials<'a>(&'a self) -> ProvideCredentialsFut<'a>
+    where
+        Self: 'a,
+    {
+        ProvideCredentialsFut::new(async {
+            let creds = self
+                .authenticate()
+                .await
+                .map(Into::into)
+                .map_err(CredentialsError::provider_error)?;
+
+            Ok(creds)
+        })
+    }
+}
+
+const PROVIDER_NAME: &str = "SsoFlow";
+
+/// The provider name for credentials from a flow with the given `profile` and `config`, including
+/// context.
+///
+/// Names are leaked to make them `'static`, but only once each.
+fn provider_name(profile: Option<&str>, config: &SsoConfig) -> &'static str {
+    static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+    let profile = profile
+        .map(|profile| format!("profile={profile}, "))
+        .unwrap_or_default();
+    let name = format!(
+        "{PROVIDER_NAME}({profile}account={}, role={})",
+        config.account(),
+        config.role()
+    );
+    let mut names = NAMES.lock().expect("poisoned");
+    if let Some(name) = names.get(name.as_str()) {
+        return name;
+    }
+    let name: &'static str = Box::leak(name.into_boxed_str());
+    names.insert(name);
+    name
+}
+
+fn to_credentials(creds: SessionCredentials, provider_name: &'static str) -> Credentials {
+    Credentials::new(
+        creds.access_key_id,
+        creds.secret_access_key,
+        Some(creds.session_token),
+        Some(creds.expires_at.into()),
+        provider_name,
+    )
+}
+
+impl From<SessionCredentials> for Credentials {
+    fn from(creds: SessionCredentials) -> Self {
+        to_credentials(creds, PROVIDER_NAME)
+    }
+}
+
+impl From<SessionCredentials> for Identity {
+    fn from(creds: SessionCredentials) -> Self {
+        Credentials::from(creds).into()
+    }
+}
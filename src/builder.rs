@@ -1,10 +1,30 @@
-use std::{convert::Infallible, fmt, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    env, fmt,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use crate::{ProfileSource, Region, SsoFlow, VerificationPrompt, CLIENT_NAME};
+use url::Url;
+
+use crate::{
+    cache,
+    fault::Faults,
+    prompts::{self, FromFn},
+    AccountId, AccountSelector, CacheStage, Clock, ProfileSource, PromptErrorPolicy, Region,
+    RoleName, RoleSelector, SsoFlow, VerificationPrompt, CLIENT_NAME,
+};
+
+const AWS_SSO_FLOW_CACHE_DIR: &str = "AWS_SSO_FLOW_CACHE_DIR";
 
 /// Builder for [`SsoFlow`].
 ///
-/// This allows aspects of the authentication flow to be configured.
+/// This allows aspects of the authentication flow to be configured. The builder is [`Clone`] when
+/// its config source and verification prompt are, so a partially configured builder can be used
+/// as a template, and inspected with getters such as [`config_source`](Self::config_source).
 ///
 /// # Example
 ///
@@ -21,8 +41,10 @@ use crate::{ProfileSource, Region, SsoFlow, VerificationPrompt, CLIENT_NAME};
 ///     .config(SsoConfig {
 ///         region: Region::new("eu-west-1"),
 ///         start_url: "myorg.awsapps.com/start".to_string(),
-///         account_id: "012345678910".to_string(),
-///         role_name: "PowerUser".to_string(),
+///         account_id: "012345678910".into(),
+///         role_name: "PowerUser".into(),
+///         account_name: None,
+///         role_selector: None,
 ///     })
 ///     // always error if prompted (auth still possible if tokens are cached)
 ///     .verification_prompt(|url| async move {
@@ -48,23 +70,177 @@ use crate::{ProfileSource, Region, SsoFlow, VerificationPrompt, CLIENT_NAME};
 #[derive(Clone)]
 #[allow(clippy::module_name_repetitions)]
 pub struct SsoFlowBuilder<S = ProfileSource, V = Infallible> {
-    cache_dir: Option<PathBuf>,
+    options: FlowOptions,
     config_source: S,
     verification_prompt: Option<V>,
 }
 
+/// Builder options that don't affect the builder's type.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FlowOptions {
+    pub(crate) cache_dir: Option<PathBuf>,
+    pub(crate) stage_cache_dirs: HashMap<CacheStage, PathBuf>,
+    pub(crate) memory_cache_stages: HashSet<CacheStage>,
+    pub(crate) cache_limits: cache::Limits,
+    pub(crate) offline: bool,
+    pub(crate) clock: Option<Arc<dyn Clock>>,
+    pub(crate) refresh_ahead: Option<Duration>,
+    pub(crate) api_timeout: Option<Duration>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) min_poll_interval: Option<Duration>,
+    pub(crate) prompt_error_policy: PromptErrorPolicy,
+    pub(crate) verbatim_start_url: bool,
+    pub(crate) self_contained: bool,
+    pub(crate) audit_log: Option<PathBuf>,
+    pub(crate) cli_sso_session: Option<String>,
+    pub(crate) previous_cache_dirs: Vec<PathBuf>,
+    pub(crate) config_source: Option<String>,
+    pub(crate) profile: Option<String>,
+    pub(crate) endpoint_url: Option<Url>,
+    pub(crate) faults: Faults,
+}
+
 impl SsoFlowBuilder<ProfileSource, Infallible> {
     /// Construct an [`SsoFlow`] builder with default configuration.
     #[must_use]
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Construct a builder with defaults suited to command-line tools run by a user.
+    ///
+    /// - The verification prompt is [`prompts::auto`], which prints the URL and opens the browser
+    ///   if there is one.
+    /// - Tokens and credentials are cached on disk, so later invocations don't prompt again.
+    /// - Connecting times out after 5 seconds, and each API call after 30 seconds, so the user
+    ///   isn't left waiting on an unreachable endpoint.
+    ///
+    /// Like the other presets, every option can still be changed, e.g. to load a particular
+    /// profile:
+    ///
+    /// ```
+    /// use aws_sso_flow::{ProfileSource, SsoFlowBuilder};
+    ///
+    /// let builder = SsoFlowBuilder::interactive_cli()
+    ///     .config(ProfileSource::default().with_profile("dev"));
+    /// ```
+    #[must_use]
+    pub fn interactive_cli() -> SsoFlowBuilder<ProfileSource, prompts::Chain> {
+        Self::new()
+            .connect_timeout(Duration::from_secs(5))
+            .api_timeout(Duration::from_secs(30))
+            .verification_prompt(prompts::auto())
+    }
+
+    /// Construct a builder with defaults suited to long-running services without a user present.
+    ///
+    /// - The verification prompt is [`prompts::print_to_stderr`], so an operator can find the URL
+    ///   in the service's logs.
+    /// - Role credentials are only cached in memory (see
+    ///   [`secure_defaults`](Self::secure_defaults)), and each cache directory is limited to
+    ///   1,000 entries, since services often act as many identities.
+    /// - Credentials are refreshed in the background 15 minutes before they expire (see
+    ///   [`refresh_ahead`](Self::refresh_ahead)), so requests don't wait on AWS.
+    /// - Connecting times out after 3 seconds, and each API call after 10 seconds, so failures
+    ///   surface quickly rather than stalling requests.
+    ///
+    /// ```
+    /// use aws_sso_flow::SsoFlowBuilder;
+    ///
+    /// let builder = SsoFlowBuilder::headless_service().cache_dir("/var/cache/my-service");
+    /// ```
+    #[must_use]
+    pub fn headless_service() -> SsoFlowBuilder<ProfileSource, FromFn<fn(&Url)>> {
+        Self::new()
+            .secure_defaults()
+            .max_cache_entries(1000)
+            .refresh_ahead(Duration::from_secs(15 * 60))
+            .connect_timeout(Duration::from_secs(3))
+            .api_timeout(Duration::from_secs(10))
+            .verification_prompt(prompts::print_to_stderr())
+    }
+
+    /// Construct a builder with defaults suited to desktop applications.
+    ///
+    /// - The verification prompt is [`prompts::auto`], which opens the browser, falling back to
+    ///   printing the URL. Apps that show the URL in their own UI can replace it with
+    ///   [`verification_prompt`](Self::verification_prompt), keeping the other defaults.
+    /// - Tokens and credentials are cached on disk, so the user isn't prompted when the app
+    ///   restarts.
+    /// - Credentials are refreshed in the background 10 minutes before they expire, so the UI
+    ///   doesn't stall on AWS.
+    /// - Connecting times out after 10 seconds, and each API call after 30 seconds, allowing for
+    ///   slow or captive networks.
+    ///
+    /// ```
+    /// use aws_sso_flow::SsoFlowBuilder;
+    ///
+    /// let builder = SsoFlowBuilder::desktop_app();
+    /// ```
+    #[must_use]
+    pub fn desktop_app() -> SsoFlowBuilder<ProfileSource, prompts::Chain> {
+        Self::new()
+            .refresh_ahead(Duration::from_secs(10 * 60))
+            .connect_timeout(Duration::from_secs(10))
+            .api_timeout(Duration::from_secs(30))
+            .verification_prompt(prompts::auto())
+    }
+}
+
+impl SsoFlowBuilder<SsoConfig, Infallible> {
+    /// Construct an [`SsoFlow`] builder that doesn't consult the environment.
+    ///
+    /// Flows built this way use only the given configuration and the builder's options. No
+    /// environment variables or config files are read, and there's no default cache directory, so
+    /// tokens and credentials are only cached if [`cache_dir`](Self::cache_dir) or
+    /// [`stage_cache_dir`](Self::stage_cache_dir) is set. The API clients are configured
+    /// explicitly, so no AWS SDK default chains (e.g. environment, profile, or instance metadata
+    /// lookups) run either.
+    ///
+    /// The only network requests made are to the SSO OIDC API in the configured region
+    /// (`RegisterClient`, `StartDeviceAuthorization`, and `CreateToken`) and the SSO API
+    /// (`GetRoleCredentials`, plus `ListAccounts` and `ListAccountRoles` when accounts or roles are
+    /// selected by name). Combined with the `lite-http` and `rustls` features, which use bundled
+    /// root certificates, this makes the crate's I/O straightforward to audit:
+    ///
+    /// ```
+    /// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use aws_sso_flow::{Region, SsoConfig, SsoFlowBuilder};
+    ///
+    /// let config = SsoConfig::builder()
+    ///     .region(Region::EU_WEST_1)
+    ///     .start_url("https://myorg.awsapps.com/start")
+    ///     .account("012345678910")
+    ///     .role("PowerUser")
+    ///     .build()?;
+    /// let flow = SsoFlowBuilder::self_contained(config)
+    ///     .cache_dir("/var/cache/my-tool")
+    ///     .verification_prompt_fn(|url| eprintln!("Go to {url} to sign in with SSO"))
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Changing the configuration source with [`config`](Self::config) keeps the other options,
+    /// but the new source may read from the environment.
+    #[must_use]
+    pub fn self_contained(config: SsoConfig) -> Self {
+        Self {
+            options: FlowOptions {
+                self_contained: true,
+                ..FlowOptions::default()
+            },
+            config_source: config,
+            verification_prompt: None,
+        }
+    }
 }
 
 impl Default for SsoFlowBuilder<ProfileSource, Infallible> {
     fn default() -> Self {
         Self {
-            cache_dir: None,
+            options: FlowOptions::default(),
             config_source: ProfileSource::default(),
             verification_prompt: None,
         }
@@ -74,7 +250,7 @@ impl Default for SsoFlowBuilder<ProfileSource, Infallible> {
 impl<S: fmt::Debug, V> fmt::Debug for SsoFlowBuilder<S, V> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("SsoFlowBuilder")
-            .field("cache_dir", &self.cache_dir)
+            .field("options", &self.options)
             .field("config_source", &self.config_source)
             .field(
                 "verification_prompt",
@@ -93,16 +269,321 @@ impl<S, V> SsoFlowBuilder<S, V> {
     ///
     /// The AWS SSO authentication flow involves obtaining tokens from AWS SSO OIDC and session
     /// credentials from AWS SSO. Tokens and credentials will be cached in a directory called
-    /// `aws_sso_flow@0.1`. The cache format is considered part of the crate's API.
+    /// `aws_sso_flow@0.6` (for version 0.6 of the crate). The cache format is considered part of
+    /// the crate's API.
     ///
     /// By default, caches are created under the user's cache directory (see
-    /// [`dirs_next::cache_dir`]).
+    /// [`dirs_next::cache_dir`]), unless the builder was constructed with
+    /// [`self_contained`](SsoFlowBuilder::self_contained). On Linux and other Unix platforms
+    /// (except macOS), an absolute `XDG_CACHE_HOME` is used as the user's cache directory.
+    ///
+    /// Operators can relocate the default cache with the `AWS_SSO_FLOW_CACHE_DIR` environment
+    /// variable, which is used as the cache directory as-is. A directory set with this method
+    /// takes precedence over the environment variable.
+    ///
+    /// On Windows, UNC and extended-length paths are supported, as for config files (see
+    /// [`ProfileSource::resolve_paths`]), so caches can live on a network share.
+    ///
+    /// The default directory is specific to the crate's minor version. Fresh entries from the
+    /// directories of previous versions are reused (and copied forward), so upgrading doesn't
+    /// require users to sign in again. This doesn't apply to custom cache directories, including
+    /// `AWS_SSO_FLOW_CACHE_DIR`.
     #[must_use]
-    pub fn cache_dir(self, path: impl Into<PathBuf>) -> Self {
-        Self {
-            cache_dir: Some(path.into()),
-            ..self
-        }
+    pub fn cache_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.cache_dir = Some(path.into());
+        self
+    }
+
+    /// Set the cache directory for a specific stage of the flow.
+    ///
+    /// This overrides [`cache_dir`](Self::cache_dir) for the given `stage`. For example, role
+    /// credentials could be cached on a tmpfs while tokens are cached on disk:
+    ///
+    /// ```
+    /// use aws_sso_flow::{CacheStage, SsoFlowBuilder};
+    ///
+    /// let builder = SsoFlowBuilder::new()
+    ///     .stage_cache_dir(CacheStage::Credentials, "/dev/shm/aws_sso_flow");
+    /// ```
+    ///
+    /// Cache file names within the directory are unchanged. The path used for each stage can be
+    /// found with [`SsoFlow::cache_path`].
+    #[must_use]
+    pub fn stage_cache_dir(mut self, stage: CacheStage, path: impl Into<PathBuf>) -> Self {
+        self.options.stage_cache_dirs.insert(stage, path.into());
+        self
+    }
+
+    /// Share the SSO OIDC client registration with other tools by caching it in `path`.
+    ///
+    /// By default each tool built on this crate registers its own client, and each registration
+    /// shows up in the Identity Center console. Tools configured with the same directory reuse one
+    /// registration per start URL and region instead:
+    ///
+    /// ```
+    /// use std::path::Path;
+    ///
+    /// use aws_sso_flow::SsoFlowBuilder;
+    ///
+    /// let home = std::env::var_os("HOME").unwrap_or_default();
+    /// let builder = SsoFlowBuilder::new()
+    ///     .shared_registration_dir(Path::new(&home).join(".aws/sso/registration"));
+    /// ```
+    ///
+    /// This is equivalent to [`stage_cache_dir`](Self::stage_cache_dir) for
+    /// [`CacheStage::Client`], since registrations are cached under names that depend only on the
+    /// start URL and region.
+    #[must_use]
+    pub fn shared_registration_dir(self, path: impl Into<PathBuf>) -> Self {
+        self.stage_cache_dir(CacheStage::Client, path)
+    }
+
+    /// Use security-conscious defaults.
+    ///
+    /// Role credentials are cached in memory only, so they're never written to disk, while the
+    /// client registration and access token are still cached on disk so users aren't prompted every
+    /// time the process starts. Each process obtains fresh credentials the first time it
+    /// authenticates, and [`SsoFlow::cache_path`] returns `None` for
+    /// [`CacheStage::Credentials`].
+    ///
+    /// ```
+    /// use aws_sso_flow::SsoFlowBuilder;
+    ///
+    /// let builder = SsoFlowBuilder::new().secure_defaults();
+    /// ```
+    #[must_use]
+    pub fn secure_defaults(mut self) -> Self {
+        self.options
+            .memory_cache_stages
+            .insert(CacheStage::Credentials);
+        self
+    }
+
+    /// Limit the number of entries in each cache directory.
+    ///
+    /// Whenever an entry is written to a directory holding more than `max` entries, entries are
+    /// evicted until it's back within the limit: expired entries first, least recently written
+    /// first, followed by the least recently written fresh entries. The entry just written is never
+    /// evicted. This bounds the cache when many configurations share it, e.g. in multi-profile
+    /// setups.
+    ///
+    /// Only files named like the flow's cache entries are counted or evicted. By default, the
+    /// number of entries is unlimited.
+    ///
+    /// ```
+    /// use aws_sso_flow::SsoFlowBuilder;
+    ///
+    /// let builder = SsoFlowBuilder::new()
+    ///     .max_cache_entries(500)
+    ///     .max_cache_bytes(1024 * 1024);
+    /// ```
+    #[must_use]
+    pub fn max_cache_entries(mut self, max: usize) -> Self {
+        self.options.cache_limits.max_entries = Some(max);
+        self
+    }
+
+    /// Limit the total size in bytes of the entries in each cache directory.
+    ///
+    /// Entries are evicted in the same way as for
+    /// [`max_cache_entries`](Self::max_cache_entries). By default, the size is unlimited.
+    #[must_use]
+    pub fn max_cache_bytes(mut self, max: u64) -> Self {
+        self.options.cache_limits.max_bytes = Some(max);
+        self
+    }
+
+    /// Set whether the flow is offline.
+    ///
+    /// An offline flow never performs network calls. Cached credentials are returned if they're
+    /// fresh, otherwise authentication fails with
+    /// [`SsoFlowError::OfflineUnavailable`](crate::SsoFlowError::OfflineUnavailable). This is
+    /// useful for tools that must behave deterministically, e.g. in air-gapped test environments.
+    ///
+    /// By default, flows are online.
+    #[must_use]
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.options.offline = offline;
+        self
+    }
+
+    /// Refresh cached credentials in the background when they're close to expiry.
+    ///
+    /// If the cached credentials expire within `window`, they are returned immediately but a
+    /// background task is spawned to obtain new credentials and update the cache, so the next call
+    /// to [`authenticate`](SsoFlow::authenticate) needn't wait on AWS. The refresh only happens if
+    /// there's a valid cached access token, so the user is never prompted in the background.
+    ///
+    /// Background refreshes are spawned on the current tokio runtime, and are skipped if there
+    /// isn't one or the flow has been shut down with its
+    /// [`shutdown_handle`](SsoFlow::shutdown_handle). By default, credentials are only refreshed
+    /// once they've expired.
+    #[must_use]
+    pub fn refresh_ahead(mut self, window: Duration) -> Self {
+        self.options.refresh_ahead = Some(window);
+        self
+    }
+
+    /// Set the timeout for each SSO and SSO OIDC API call attempt.
+    ///
+    /// This bounds the time spent waiting for a response, including connecting, so an unreachable
+    /// endpoint fails promptly. It doesn't include time spent waiting for the user to grant access
+    /// in the verification prompt. By default, the AWS SDK's timeouts are used.
+    #[must_use]
+    pub fn api_timeout(mut self, timeout: Duration) -> Self {
+        self.options.api_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the timeout for connecting to SSO and SSO OIDC endpoints.
+    ///
+    /// By default, the AWS SDK's connect timeout is used.
+    #[must_use]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.options.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Send SSO and SSO OIDC API calls to `url`, instead of the regional AWS endpoints.
+    ///
+    /// Both APIs are called under the same base URL, with their usual paths (e.g. `/token` for
+    /// `CreateToken`). This is mainly for tests, e.g. against the `test-server` feature's
+    /// `TestServer`, but could also route calls through a proxy. Plain `http` URLs are allowed.
+    #[must_use]
+    pub fn endpoint_url(mut self, url: Url) -> Self {
+        self.options.endpoint_url = Some(url);
+        self
+    }
+
+    /// Set the minimum interval between polls for an access token, while waiting for the user to
+    /// grant access.
+    ///
+    /// The flow polls at the interval requested by the server, but never more often than this. It
+    /// guards against endpoints that request an interval of zero, which would otherwise poll in a
+    /// busy loop. The default is one second.
+    #[must_use]
+    pub fn min_poll_interval(mut self, interval: Duration) -> Self {
+        self.options.min_poll_interval = Some(interval);
+        self
+    }
+
+    /// Set whether start URLs are normalised when comparing them.
+    ///
+    /// Flows share client registrations and access tokens with other flows for the same start URL
+    /// (see [`SsoFlow`]), and only reuse AWS CLI tokens (see
+    /// [`aws_cli_sso_session`](Self::aws_cli_sso_session)) for the same start URL. By default,
+    /// start URLs are compared after normalising them, so that e.g.
+    /// `https://SSO.Example.com/start/#/` and `sso.example.com/start` are the same portal. This
+    /// works with any HTTPS start URL, including custom domains.
+    ///
+    /// If `normalize` is `false`, start URLs must match exactly, apart from trailing slashes. This
+    /// is an escape hatch for portals whose URLs differ only in ways normalisation ignores.
+    #[must_use]
+    pub fn normalize_start_url(mut self, normalize: bool) -> Self {
+        self.options.verbatim_start_url = !normalize;
+        self
+    }
+
+    /// Set what to do when the verification prompt fails.
+    ///
+    /// By default ([`PromptErrorPolicy::FailFast`]), authentication fails as soon as the prompt
+    /// returns an error. With [`PromptErrorPolicy::ContinuePolling`], the flow waits for the user
+    /// to grant access regardless, which suits prompts whose errors don't mean the user wasn't
+    /// reached.
+    #[must_use]
+    pub fn on_prompt_error(mut self, policy: PromptErrorPolicy) -> Self {
+        self.options.prompt_error_policy = policy;
+        self
+    }
+
+    /// Set the clock used to check for expiry.
+    ///
+    /// By default, the system clock is used. See [`Clock`] for more information.
+    #[must_use]
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.options.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Inject failures into the flow according to `policy`.
+    ///
+    /// This is for chaos-testing how applications handle unreadable caches, failing API calls,
+    /// and prompts that never reach the user. For example, to throttle the first two
+    /// `GetRoleCredentials` calls:
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    ///
+    /// use aws_sso_flow::{Fault, FaultPoint, FaultPolicy, SsoFlowBuilder};
+    ///
+    /// /// Throttle the first `n` `GetRoleCredentials` calls.
+    /// #[derive(Debug)]
+    /// struct Throttle(AtomicU32);
+    ///
+    /// impl FaultPolicy for Throttle {
+    ///     fn inject(&self, point: FaultPoint) -> Option<Fault> {
+    ///         if point != FaultPoint::Api("GetRoleCredentials") {
+    ///             return None;
+    ///         }
+    ///         let remaining = self.0.load(Ordering::Relaxed);
+    ///         (remaining > 0).then(|| {
+    ///             self.0.store(remaining - 1, Ordering::Relaxed);
+    ///             Fault::api_error("TooManyRequestsException", 429)
+    ///         })
+    ///     }
+    /// }
+    ///
+    /// let builder = SsoFlowBuilder::new().fault_policy(Throttle(AtomicU32::new(2)));
+    /// ```
+    ///
+    /// See [`FaultPolicy`](crate::FaultPolicy) for more information.
+    #[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+    #[cfg(feature = "test-util")]
+    #[must_use]
+    pub fn fault_policy(mut self, policy: impl crate::FaultPolicy + 'static) -> Self {
+        self.options.faults = Faults::new(Arc::new(policy));
+        self
+    }
+
+    /// Append a record of each authentication outcome to the file at `path`.
+    ///
+    /// Each call to [`authenticate`](SsoFlow::authenticate) appends a single line of JSON with
+    /// the time, the call's [`FlowId`](crate::FlowId), the outcome (`cached`, `issued`, or
+    /// `failed`), the region and start URL, and the
+    /// account ID and role name as far as they were resolved. Successful outcomes include the
+    /// credentials' expiry, and failures include the [`ErrorCode`](crate::ErrorCode) and message.
+    /// Background refreshes (see [`refresh_ahead`](Self::refresh_ahead) and
+    /// [`SsoFlow::refresh_if_possible`]) are recorded with the outcome `refreshed`. Events also
+    /// list the cache entries written while authenticating, with their stage and, unless they're
+    /// cached in memory, their path. Credentials and tokens are never written.
+    ///
+    /// ```json
+    /// {"timestamp":"2024-01-01T12:00:00Z","flow_id":"3f9a1c0e7b2d4a65","outcome":"issued","region":"eu-west-1","start_url":"https://myorg.awsapps.com/start","account_id":"012345678910","role_name":"PowerUser","expires_at":"2024-01-01T13:00:00Z","cache_writes":[{"stage":"token","path":"/home/user/.cache/aws_sso_flow/token-3b2c9d1e.json"},{"stage":"credentials","path":"/home/user/.cache/aws_sso_flow/credentials-8f7e6a5d.json"}]}
+    /// ```
+    ///
+    /// The file and its parent directories are created if necessary. Failures to write the log
+    /// are ignored, so that auditing never prevents authentication.
+    #[must_use]
+    pub fn audit_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.audit_log = Some(path.into());
+        self
+    }
+
+    /// Reuse access tokens from the AWS CLI's `sso-session` login.
+    ///
+    /// `aws sso login --sso-session <name>` caches an access token for the named session, along
+    /// with a refresh token. When a flow needs a new access token, it first looks for one in the
+    /// CLI's cache (`~/.aws/sso/cache`). If the CLI's access token has expired, it's refreshed
+    /// with the refresh token, and the refreshed token is written back for the CLI and other tools
+    /// to use. This means users only need to log in with the CLI when the refresh token expires,
+    /// rather than per tool.
+    ///
+    /// The CLI's token is only used if it's for the flow's start URL and region. Otherwise, or if
+    /// it can't be refreshed, the user is prompted as usual.
+    #[must_use]
+    pub fn aws_cli_sso_session(mut self, name: impl Into<String>) -> Self {
+        self.options.cli_sso_session = Some(name.into());
+        self
     }
 
     /// Set the SSO configuration source.
@@ -115,7 +596,7 @@ impl<S, V> SsoFlowBuilder<S, V> {
         S: SsoConfigSource,
     {
         SsoFlowBuilder {
-            cache_dir: self.cache_dir,
+            options: self.options,
             config_source,
             verification_prompt: self.verification_prompt,
         }
@@ -132,11 +613,66 @@ impl<S, V> SsoFlowBuilder<S, V> {
         NewV: VerificationPrompt,
     {
         SsoFlowBuilder {
-            cache_dir: self.cache_dir,
+            options: self.options,
             config_source: self.config_source,
             verification_prompt: Some(verification_prompt),
         }
     }
+
+    /// Set the verification prompt handler to a synchronous, infallible function.
+    ///
+    /// This is a convenience for [`verification_prompt`](Self::verification_prompt) with
+    /// [`prompts::from_fn`]:
+    ///
+    /// ```
+    /// use aws_sso_flow::SsoFlowBuilder;
+    ///
+    /// let builder = SsoFlowBuilder::new()
+    ///     .verification_prompt_fn(|url| println!("Go to {url} to sign in with SSO"));
+    /// ```
+    #[must_use]
+    pub fn verification_prompt_fn<F>(self, verification_prompt: F) -> SsoFlowBuilder<S, FromFn<F>>
+    where
+        F: Fn(&Url) + Clone + Send + Sync,
+    {
+        self.verification_prompt(prompts::from_fn(verification_prompt))
+    }
+
+    /// The cache directory set with [`cache_dir`](Self::cache_dir), if any.
+    ///
+    /// This is `None` if the default directory will be used (or none, for a
+    /// [`self_contained`](SsoFlowBuilder::self_contained) builder).
+    #[must_use]
+    pub fn configured_cache_dir(&self) -> Option<&Path> {
+        self.options.cache_dir.as_deref()
+    }
+
+    /// The source of SSO configuration set with [`config`](Self::config).
+    #[must_use]
+    pub fn config_source(&self) -> &S {
+        &self.config_source
+    }
+
+    /// A mutable reference to the source of SSO configuration, e.g. to adjust it in place.
+    ///
+    /// ```
+    /// use aws_sso_flow::SsoFlowBuilder;
+    ///
+    /// let template = SsoFlowBuilder::new().cache_dir("/var/cache/my-tool");
+    ///
+    /// let mut builder = template.clone();
+    /// let source = builder.config_source().clone().with_profile("dev");
+    /// *builder.config_source_mut() = source;
+    ///
+    /// assert_eq!(
+    ///     builder.configured_cache_dir(),
+    ///     Some(std::path::Path::new("/var/cache/my-tool")),
+    /// );
+    /// ```
+    #[must_use]
+    pub fn config_source_mut(&mut self) -> &mut S {
+        &mut self.config_source
+    }
 }
 
 impl<S, V> SsoFlowBuilder<S, V>
@@ -151,6 +687,8 @@ where
     /// Returns any errors encountered when loading the [`SsoConfigSource`].
     #[allow(clippy::missing_panics_doc)]
     pub async fn build(self) -> Result<SsoFlow<V>, S::Error> {
+        let config_source = self.config_source.describe();
+        let profile = self.config_source.profile();
         let config = self.config_source.load().await?;
 
         // We can unwrap here because the only way to construct a new `SsoFlowBuilder` is via
@@ -161,21 +699,79 @@ where
             .verification_prompt
             .expect("verification_prompt must be set");
 
-        Ok(SsoFlow::build(
-            self.cache_dir.or_else(Self::default_cache_dir),
-            config,
-            verification_prompt,
-        ))
+        let mut options = self.options;
+        if !options.self_contained && options.cache_dir.is_none() {
+            options.cache_dir = default_cache_dir();
+            options.previous_cache_dirs = previous_cache_dirs();
+        }
+        options.config_source = Some(config_source);
+        options.profile = profile;
+
+        Ok(SsoFlow::build(options, config, verification_prompt))
     }
+}
 
-    fn default_cache_dir() -> Option<PathBuf> {
-        dirs_next::cache_dir().map(|mut path| {
-            path.push(CLIENT_NAME);
-            path
-        })
+#[cfg(feature = "doctor")]
+impl<V> SsoFlowBuilder<ProfileSource, V> {
+    /// Diagnose common problems with the builder's configuration.
+    ///
+    /// This is like [`diagnose`](crate::diagnose), but uses the builder's profile and cache
+    /// directory.
+    #[cfg_attr(docsrs, doc(cfg(feature = "doctor")))]
+    pub async fn diagnose(&self) -> crate::DiagnosticReport {
+        crate::doctor::diagnose_with(
+            &self.config_source,
+            self.options.cache_dir.clone().or_else(default_cache_dir),
+        )
+        .await
     }
 }
 
+pub(crate) fn default_cache_dir() -> Option<PathBuf> {
+    if let Some(path) = env_cache_dir() {
+        return Some(path);
+    }
+    user_cache_dir().map(|mut path| {
+        path.push(CLIENT_NAME);
+        path
+    })
+}
+
+/// The default cache directories of previous minor versions of the crate, newest first.
+pub(crate) fn previous_cache_dirs() -> Vec<PathBuf> {
+    if env_cache_dir().is_some() {
+        return Vec::new();
+    }
+    let Some(cache_dir) = user_cache_dir() else {
+        return Vec::new();
+    };
+    let minor: u32 = env!("CARGO_PKG_VERSION_MINOR")
+        .parse()
+        .expect("minor version should be a number");
+    (0..minor)
+        .rev()
+        .map(|minor| cache_dir.join(format!("{}@0.{minor}", env!("CARGO_PKG_NAME"))))
+        .collect()
+}
+
+fn env_cache_dir() -> Option<PathBuf> {
+    env::var_os(AWS_SSO_FLOW_CACHE_DIR)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+fn user_cache_dir() -> Option<PathBuf> {
+    // The XDG base directory spec says relative paths should be ignored
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if let Some(path) = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+    {
+        return Some(path);
+    }
+    dirs_next::cache_dir()
+}
+
 /// A source of SSO configuration.
 ///
 /// This trait is more intended to facilitate precise error handling in [`SsoFlowBuilder::build`],
@@ -189,10 +785,26 @@ pub trait SsoConfigSource {
 
     /// Load the SSO configuration.
     fn load(self) -> Self::Future;
+
+    /// A short description of where the configuration is loaded from, for diagnostics.
+    ///
+    /// This appears in [`SsoFlow::debug_snapshot`]. By default it's the source's type name.
+    fn describe(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
+
+    /// The name of the AWS shared config profile the configuration is loaded from, if any.
+    ///
+    /// This identifies the configuration in credential provider names (see
+    /// `aws::SsoCredentialsProvider::provider_name_context`, with the `aws-sdk` feature). By
+    /// default there's no profile.
+    fn profile(&self) -> Option<String> {
+        None
+    }
 }
 
 /// AWS SSO configuration.
-#[derive(Clone, Debug, Hash)]
+#[derive(Clone, Debug)]
 pub struct SsoConfig {
     /// The AWS region in which SSO was setup.
     ///
@@ -202,15 +814,377 @@ pub struct SsoConfig {
     /// The URL for the AWS SSO user portal.
     pub start_url: String,
 
-    /// The AWS account to sign in to.
-    pub account_id: String,
+    /// The ID of the AWS account to sign in to.
+    ///
+    /// This is ignored if [`account_name`](Self::account_name) is set.
+    pub account_id: AccountId,
 
     /// The name of the AWS IAM Role to assume in the account.
     ///
-    /// This should be the role name as it appears in SSO configuration.
-    pub role_name: String,
+    /// This is ignored if [`role_selector`](Self::role_selector) is set.
+    pub role_name: RoleName,
+
+    /// The name of the AWS account to sign in to, instead of [`account_id`](Self::account_id).
+    ///
+    /// See [`AccountSelector::Name`].
+    pub account_name: Option<String>,
+
+    /// Select the role some other way than by [`role_name`](Self::role_name), e.g. by pattern or
+    /// ARN.
+    ///
+    /// See [`RoleSelector`].
+    pub role_selector: Option<RoleSelector>,
+}
+
+impl SsoConfig {
+    /// Construct an [`SsoConfig`] builder, which validates the configuration when it's built.
+    ///
+    /// ```
+    /// use aws_sso_flow::{Region, SsoConfig};
+    ///
+    /// let config = SsoConfig::builder()
+    ///     .region(Region::EU_WEST_1)
+    ///     .start_url("https://myorg.awsapps.com/start")
+    ///     .account("012345678910")
+    ///     .role("PowerUser")
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let error = SsoConfig::builder()
+    ///     .region(Region::EU_WEST_1)
+    ///     .start_url("https://myorg.awsapps.com/start")
+    ///     .account("production")
+    ///     .role("PowerUser")
+    ///     .build()
+    ///     .unwrap_err();
+    /// assert_eq!(error.field(), "account");
+    /// ```
+    #[must_use]
+    pub fn builder() -> SsoConfigBuilder {
+        SsoConfigBuilder::default()
+    }
+
+    /// The account to sign in to.
+    ///
+    /// This is the account named [`account_name`](Self::account_name) if it's set, otherwise the
+    /// account with ID [`account_id`](Self::account_id).
+    ///
+    /// ```
+    /// use aws_sso_flow::{AccountSelector, Region, SsoConfig};
+    ///
+    /// let config = SsoConfig {
+    ///     region: Region::EU_WEST_1,
+    ///     start_url: "https://myorg.awsapps.com/start".to_string(),
+    ///     account_id: "012345678910".into(),
+    ///     role_name: "PowerUser".into(),
+    ///     account_name: None,
+    ///     role_selector: None,
+    /// };
+    /// assert_eq!(config.account(), AccountSelector::from("012345678910"));
+    ///
+    /// let config = SsoConfig {
+    ///     account_name: Some("production".to_string()),
+    ///     ..config
+    /// };
+    /// assert_eq!(config.account().to_string(), "\"production\"");
+    /// ```
+    #[must_use]
+    pub fn account(&self) -> AccountSelector {
+        match &self.account_name {
+            Some(name) => AccountSelector::Name(name.clone()),
+            None => AccountSelector::Id(self.account_id.clone()),
+        }
+    }
+
+    /// The role to assume in the account.
+    ///
+    /// This is [`role_selector`](Self::role_selector) if it's set, otherwise the role named
+    /// [`role_name`](Self::role_name).
+    #[must_use]
+    pub fn role(&self) -> RoleSelector {
+        self.role_selector
+            .clone()
+            .unwrap_or_else(|| RoleSelector::Name(self.role_name.clone()))
+    }
+
+    /// The name of the AWS access portal, parsed from the start URL.
+    ///
+    /// This is the subdomain of start URLs like `https://myorg.awsapps.com/start`, and is suitable
+    /// for showing users where they're signing in, e.g. "Signing in to myorg". `None` is returned
+    /// if the start URL doesn't have that form, e.g. if the portal has a custom domain.
+    ///
+    /// ```
+    /// use aws_sso_flow::{Region, SsoConfig};
+    ///
+    /// let config = SsoConfig {
+    ///     region: Region::EU_WEST_1,
+    ///     start_url: "https://MyOrg.awsapps.com/start#/".to_string(),
+    ///     account_id: "012345678910".into(),
+    ///     role_name: "PowerUser".into(),
+    ///     account_name: None,
+    ///     role_selector: None,
+    /// };
+    /// assert_eq!(config.portal_name().as_deref(), Some("myorg"));
+    ///
+    /// let config = SsoConfig {
+    ///     start_url: "https://sso.example.com/start".to_string(),
+    ///     ..config
+    /// };
+    /// assert_eq!(config.portal_name(), None);
+    /// ```
+    #[must_use]
+    pub fn portal_name(&self) -> Option<String> {
+        let url = parse_start_url(&self.start_url).ok()?;
+        let host = url.host_str()?;
+        let name = host
+            .strip_suffix(".awsapps.com")
+            .or_else(|| host.strip_suffix(".awsapps.cn"))?;
+        (!name.is_empty() && !name.contains('.')).then(|| name.to_string())
+    }
+}
+
+// Only the selected account and role are hashed, and selecting them by ID and name hashes the same
+// as a bare `String`, so cache keys are stable for configurations that existed before selectors.
+impl Hash for SsoConfig {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.region.hash(state);
+        self.start_url.hash(state);
+        self.account().hash(state);
+        self.role().hash(state);
+    }
+}
+
+/// Describes the configuration for users, e.g. before authenticating.
+///
+/// Only the host of the start URL is shown, since its path may identify the organisation's
+/// directory. The alternate form (`{:#}`) shows the full start URL.
+///
+/// ```
+/// use aws_sso_flow::{Region, SsoConfig};
+///
+/// let config = SsoConfig {
+///     region: Region::EU_WEST_1,
+///     start_url: "https://myorg.awsapps.com/start#/".to_string(),
+///     account_id: "012345678910".into(),
+///     role_name: "PowerUser".into(),
+///     account_name: None,
+///     role_selector: None,
+/// };
+/// assert_eq!(
+///     config.to_string(),
+///     "account 012345678910, role PowerUser, region eu-west-1, portal myorg.awsapps.com",
+/// );
+/// assert_eq!(
+///     format!("{config:#}"),
+///     "account 012345678910, role PowerUser, region eu-west-1, portal https://myorg.awsapps.com/start#/",
+/// );
+/// ```
+impl fmt::Display for SsoConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "account {}, role {}, region {}, portal ",
+            self.account(),
+            self.role(),
+            self.region
+        )?;
+        if f.alternate() {
+            return write!(f, "{}", self.start_url);
+        }
+        let url = parse_start_url(&self.start_url).ok();
+        match url.as_ref().and_then(Url::host_str) {
+            Some(host) => write!(f, "{host}"),
+            None => write!(f, "(invalid start URL)"),
+        }
+    }
+}
+
+/// The form of a start URL that's used to tell whether two configurations use the same portal.
+///
+/// Trailing slashes are ignored. Unless `verbatim`, the URL is also normalised, so that e.g.
+/// `https://SSO.Example.com/start/#/` and `sso.example.com/start` are the same portal: a missing
+/// scheme is taken to be HTTPS, the host is lowercased, and default ports and fragments are
+/// dropped. This works for any host, including custom domains.
+pub(crate) fn start_url_key(start_url: &str, verbatim: bool) -> String {
+    if !verbatim {
+        if let Ok(mut url) = parse_start_url(start_url) {
+            if url.has_host() {
+                url.set_fragment(None);
+                return url.as_str().trim_end_matches('/').to_string();
+            }
+        }
+    }
+    start_url.trim_end_matches('/').to_string()
+}
+
+/// Parse a start URL, which may omit the scheme.
+pub(crate) fn parse_start_url(start_url: &str) -> Result<Url, url::ParseError> {
+    if start_url.contains("://") {
+        Url::parse(start_url)
+    } else {
+        Url::parse(&format!("https://{start_url}"))
+    }
+}
+
+/// Builder for [`SsoConfig`].
+///
+/// See [`SsoConfig::builder`].
+#[derive(Clone, Debug, Default)]
+pub struct SsoConfigBuilder {
+    region: Option<Region>,
+    start_url: Option<String>,
+    account: Option<AccountSelector>,
+    role: Option<RoleSelector>,
+}
+
+impl SsoConfigBuilder {
+    /// Set the AWS region in which SSO was setup.
+    #[must_use]
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Set the URL for the AWS SSO user portal.
+    ///
+    /// The `https://` scheme may be omitted.
+    #[must_use]
+    pub fn start_url(mut self, start_url: impl Into<String>) -> Self {
+        self.start_url = Some(start_url.into());
+        self
+    }
+
+    /// Set the AWS account to sign in to.
+    #[must_use]
+    pub fn account(mut self, account: impl Into<AccountSelector>) -> Self {
+        self.account = Some(account.into());
+        self
+    }
+
+    /// Set the AWS IAM Role to assume in the account.
+    #[must_use]
+    pub fn role(mut self, role: impl Into<RoleSelector>) -> Self {
+        self.role = Some(role.into());
+        self
+    }
+
+    /// Validate the configuration and build an [`SsoConfig`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any field is missing, if the region is not a syntactically valid region
+    /// name, if the start URL is not a valid URL, if an account ID is not 12 digits, if a role name
+    /// isn't a valid [`RoleName`], or if an account name or case-insensitive role name is empty.
+    pub fn build(self) -> Result<SsoConfig, SsoConfigError> {
+        let region = self.region.ok_or(SsoConfigError::missing("region"))?;
+        if region.as_ref().parse::<Region>().is_err() {
+            return Err(SsoConfigError::invalid(
+                "region",
+                format!("{region} is not a valid region name"),
+            ));
+        }
+
+        let start_url = self.start_url.ok_or(SsoConfigError::missing("start_url"))?;
+        match parse_start_url(&start_url) {
+            Ok(url) if url.has_host() => {}
+            Ok(_) => {
+                return Err(SsoConfigError::invalid(
+                    "start_url",
+                    format!("{start_url} has no host"),
+                ))
+            }
+            Err(error) => {
+                return Err(SsoConfigError::invalid(
+                    "start_url",
+                    format!("{start_url} is not a valid URL ({error})"),
+                ))
+            }
+        }
+
+        let account = self.account.ok_or(SsoConfigError::missing("account"))?;
+        match &account {
+            AccountSelector::Id(id) if id.as_str().parse::<AccountId>().is_err() => {
+                return Err(SsoConfigError::invalid(
+                    "account",
+                    format!("{id} is not a 12-digit account ID"),
+                ));
+            }
+            AccountSelector::Name(name) if name.is_empty() => {
+                return Err(SsoConfigError::invalid("account", "account name is empty"));
+            }
+            _ => {}
+        }
+
+        let role = self.role.ok_or(SsoConfigError::missing("role"))?;
+        match &role {
+            RoleSelector::Name(name) if name.as_str().parse::<RoleName>().is_err() => {
+                return Err(SsoConfigError::invalid(
+                    "role",
+                    format!("{name:?} is not a valid role name"),
+                ));
+            }
+            RoleSelector::NameIgnoreCase(name) if name.is_empty() => {
+                return Err(SsoConfigError::invalid("role", "role name is empty"));
+            }
+            _ => {}
+        }
+
+        let (account_id, account_name) = match account {
+            AccountSelector::Id(id) => (id, None),
+            AccountSelector::Name(name) => (AccountId::new(""), Some(name)),
+        };
+        let (role_name, role_selector) = match role {
+            RoleSelector::Name(name) => (name, None),
+            selector => (RoleName::new(""), Some(selector)),
+        };
+        Ok(SsoConfig {
+            region,
+            start_url,
+            account_id,
+            role_name,
+            account_name,
+            role_selector,
+        })
+    }
+}
+
+/// The error returned by [`SsoConfigBuilder::build`] when the configuration is invalid.
+#[derive(Clone, Debug)]
+pub struct SsoConfigError {
+    field: &'static str,
+    message: String,
 }
 
+impl SsoConfigError {
+    fn missing(field: &'static str) -> Self {
+        Self {
+            field,
+            message: format!("{field} is required"),
+        }
+    }
+
+    fn invalid(field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            message: message.into(),
+        }
+    }
+
+    /// The name of the invalid field, e.g. `start_url`.
+    #[must_use]
+    pub fn field(&self) -> &'static str {
+        self.field
+    }
+}
+
+impl fmt::Display for SsoConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid SSO configuration: {}", self.message)
+    }
+}
+
+impl std::error::Error for SsoConfigError {}
+
 impl SsoConfigSource for SsoConfig {
     type Future = std::future::Ready<Result<Self, Self::Error>>;
 
@@ -219,4 +1193,8 @@ impl SsoConfigSource for SsoConfig {
     fn load(self) -> Self::Future {
         std::future::ready(Ok(self))
     }
+
+    fn describe(&self) -> String {
+        "explicit configuration".to_string()
+    }
 }
@@ -1,37 +1,222 @@
 use std::{
     cell::Cell,
+    collections::{HashMap, HashSet},
+    convert::Infallible,
     hash::{Hash, Hasher},
     io,
     path::{Path, PathBuf},
+    sync::{self, Arc},
 };
 
 use chrono::{DateTime, Utc};
 use futures::TryFutureExt;
 use md5::{Digest, Md5};
-use tokio::fs;
+use tokio::{fs, sync::Mutex};
 
-const CACHE_BUFFER: std::time::Duration = std::time::Duration::from_secs(60);
+use crate::{
+    audit,
+    fault::{FaultPoint, Faults},
+    paths, BoxError, CacheEntryStatus, Clock,
+};
+
+pub(crate) const CACHE_BUFFER: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// A stage of the authentication flow whose result is cached.
+///
+/// See [`SsoFlowBuilder::stage_cache_dir`](crate::SsoFlowBuilder::stage_cache_dir) and
+/// [`SsoFlow::cache_path`](crate::SsoFlow::cache_path).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum CacheStage {
+    /// The SSO OIDC client registration.
+    ///
+    /// This is shared by every configuration with the same start URL and region.
+    Client,
+
+    /// The SSO access token.
+    Token,
+
+    /// The account ID resolved from an [`AccountSelector`](crate::AccountSelector).
+    Account,
+
+    /// The role name resolved from a [`RoleSelector`](crate::RoleSelector).
+    Role,
+
+    /// The role credentials.
+    Credentials,
+}
+
+impl CacheStage {
+    pub(crate) const ALL: [Self; 5] = [
+        Self::Client,
+        Self::Token,
+        Self::Account,
+        Self::Role,
+        Self::Credentials,
+    ];
+
+    pub(crate) fn prefix(self) -> &'static str {
+        match self {
+            Self::Client => "client",
+            Self::Token => "token",
+            Self::Account => "account",
+            Self::Role => "role",
+            Self::Credentials => "credentials",
+        }
+    }
+}
+
+/// Limits on the size of a cache directory, enforced when entries are written.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Limits {
+    pub(crate) max_entries: Option<usize>,
+    pub(crate) max_bytes: Option<u64>,
+}
+
+impl Limits {
+    fn is_unlimited(self) -> bool {
+        self.max_entries.is_none() && self.max_bytes.is_none()
+    }
+
+    fn allows(self, entries: usize, bytes: u64) -> bool {
+        self.max_entries.is_none_or(|max| entries <= max)
+            && self.max_bytes.is_none_or(|max| bytes <= max)
+    }
+}
 
 #[derive(Debug)]
 pub(crate) struct Cache {
     dir: Option<PathBuf>,
+    stage_dirs: HashMap<CacheStage, PathBuf>,
+    // Default cache directories of previous versions of the crate, newest first
+    previous_dirs: Vec<PathBuf>,
     suffix: String,
+    // Suffixes for stages that are shared more widely than the rest of the cache
+    stage_suffixes: HashMap<CacheStage, String>,
+    // Stages cached in `memory` instead of on disk
+    memory_stages: HashSet<CacheStage>,
+    memory: sync::Mutex<HashMap<CacheStage, String>>,
+    limits: Limits,
+    clock: Arc<dyn Clock>,
+    faults: Faults,
+
+    // Held while reading, initialising, and writing an entry so that concurrent callers wait for
+    // the first initialisation (e.g. a verification prompt) rather than duplicating it.
+    lock: Mutex<()>,
 }
 
 impl Cache {
-    pub(crate) fn new<S: Hash>(dir: Option<PathBuf>, suffix: S) -> Self {
-        let mut hasher = Md5Hasher::new();
-        suffix.hash(&mut hasher);
-
+    pub(crate) fn new<S: Hash>(
+        dir: Option<PathBuf>,
+        stage_dirs: HashMap<CacheStage, PathBuf>,
+        clock: Arc<dyn Clock>,
+        suffix: S,
+    ) -> Self {
         Self {
-            dir,
-            suffix: format!("{:x}", hasher.finish()),
+            dir: dir.map(paths::normalize),
+            stage_dirs: stage_dirs
+                .into_iter()
+                .map(|(stage, dir)| (stage, paths::normalize(dir)))
+                .collect(),
+            previous_dirs: Vec::new(),
+            suffix: hash_suffix(suffix),
+            stage_suffixes: HashMap::new(),
+            memory_stages: HashSet::new(),
+            memory: sync::Mutex::default(),
+            limits: Limits::default(),
+            clock,
+            faults: Faults::default(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Look for entries missing from the cache in the given directories, which should be the
+    /// default cache directories of previous versions of the crate, newest first.
+    ///
+    /// Entries found there are copied into the cache, so sessions survive upgrades.
+    pub(crate) fn with_previous_dirs(mut self, previous_dirs: Vec<PathBuf>) -> Self {
+        self.previous_dirs = previous_dirs.into_iter().map(paths::normalize).collect();
+        self
+    }
+
+    /// Inject failures into reads and writes according to `faults`.
+    pub(crate) fn with_faults(mut self, faults: Faults) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    /// Cache the given stages in memory only, so they're never written to disk.
+    pub(crate) fn with_memory_stages(mut self, memory_stages: HashSet<CacheStage>) -> Self {
+        self.memory_stages = memory_stages;
+        self
+    }
+
+    /// Evict entries from a cache directory whenever a write takes it over `limits`.
+    pub(crate) fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Use a different suffix for `stage`, e.g. so it can be shared between configurations.
+    pub(crate) fn with_stage_suffix<S: Hash>(mut self, stage: CacheStage, suffix: S) -> Self {
+        self.stage_suffixes.insert(stage, hash_suffix(suffix));
+        self
+    }
+
+    /// Construct a cache in the same directories, with a different suffix.
+    ///
+    /// Stage suffixes and in-memory entries are not preserved.
+    pub(crate) fn with_suffix<S: Hash>(&self, suffix: S) -> Self {
+        Self::new(
+            self.dir.clone(),
+            self.stage_dirs.clone(),
+            Arc::clone(&self.clock),
+            suffix,
+        )
+        .with_previous_dirs(self.previous_dirs.clone())
+        .with_memory_stages(self.memory_stages.clone())
+        .with_limits(self.limits)
+        .with_faults(self.faults.clone())
+    }
+
+    pub(crate) fn path(&self, stage: CacheStage) -> Option<PathBuf> {
+        if self.memory_stages.contains(&stage) {
+            return None;
         }
+        self.stage_dirs
+            .get(&stage)
+            .or(self.dir.as_ref())
+            .map(|dir| dir.join(self.file_name(stage)))
+    }
+
+    fn file_name(&self, stage: CacheStage) -> String {
+        let suffix = self.stage_suffixes.get(&stage).unwrap_or(&self.suffix);
+        format!("{}-{}.json", stage.prefix(), suffix)
+    }
+
+    /// Get the cached value for `stage`, if it exists and is fresh.
+    pub(crate) async fn get<T>(&self, stage: CacheStage) -> Result<Option<T>, Error<Infallible>>
+    where
+        T: Expiry + serde::de::DeserializeOwned,
+    {
+        let _guard = self.lock.lock().await;
+        self.read(stage).await
+    }
+
+    /// Like [`get`](Self::get), but without waiting for concurrent initialisations.
+    ///
+    /// This allows multiple stages to be read concurrently. A `None` result should be followed by
+    /// [`get_or_init`](Self::get_or_init), which will re-check the cache under the lock.
+    pub(crate) async fn peek<T>(&self, stage: CacheStage) -> Result<Option<T>, Error<Infallible>>
+    where
+        T: Expiry + serde::de::DeserializeOwned,
+    {
+        self.read(stage).await
     }
 
     pub(crate) async fn get_or_init<F, Fut, T, E>(
         &self,
-        prefix: &str,
+        stage: CacheStage,
         init: F,
     ) -> Result<T, Error<E>>
     where
@@ -39,64 +224,315 @@ impl Cache {
         Fut: std::future::Future<Output = Result<T, E>>,
         T: Expiry + serde::de::DeserializeOwned + serde::Serialize,
     {
-        let path = self
-            .dir
-            .as_deref()
-            .map(|dir| dir.join(format!("{}-{}.json", prefix, self.suffix)));
+        let _guard = self.lock.lock().await;
 
-        if let Some(path) = &path {
-            match fs::read_to_string(&path).await {
-                Ok(content) => {
-                    let value: T = serde_json::from_str(&content)
-                        .map_err(|error| Error::cache("corrupt", path, error))?;
-                    if value.expires_at()
-                        + chrono::Duration::from_std(CACHE_BUFFER).expect("expiry overflow")
-                        > Utc::now()
-                    {
-                        return Ok(value);
-                    }
-                }
-                Err(error) if error.kind() != io::ErrorKind::NotFound => {
-                    return Err(Error::cache("failed to read", path, error));
-                }
-                Err(_) => {
-                    // continue
-                }
-            }
+        if let Some(value) = self.read(stage).await? {
+            return Ok(value);
         }
 
         let value = init().await.map_err(Error::Init)?;
+        self.write(stage, &value).await?;
+
+        Ok(value)
+    }
+
+    /// Write `value` for `stage`, replacing any cached value.
+    pub(crate) async fn put<T>(&self, stage: CacheStage, value: &T) -> Result<(), Error<Infallible>>
+    where
+        T: serde::Serialize,
+    {
+        let _guard = self.lock.lock().await;
+        self.write(stage, value).await
+    }
+
+    /// Initialise and write the entry for `stage`, regardless of whether it's fresh.
+    pub(crate) async fn refresh<F, Fut, T, E>(
+        &self,
+        stage: CacheStage,
+        init: F,
+    ) -> Result<T, Error<E>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<T, E>>,
+        T: serde::Serialize,
+    {
+        let _guard = self.lock.lock().await;
+
+        let value = init().await.map_err(Error::Init)?;
+        self.write(stage, &value).await?;
 
-        if let Some(path) = &path {
+        Ok(value)
+    }
+
+    /// Delete the entry for `stage` if it's fresh and matches `predicate`.
+    ///
+    /// This is best-effort: an entry that can't be read or deleted is left as it is.
+    pub(crate) async fn remove_if<T>(&self, stage: CacheStage, predicate: impl FnOnce(&T) -> bool)
+    where
+        T: Expiry + serde::de::DeserializeOwned,
+    {
+        let _guard = self.lock.lock().await;
+
+        let Ok(Some(value)) = self.read::<T, Infallible>(stage).await else {
+            return;
+        };
+        if !predicate(&value) {
+            return;
+        }
+        if self.memory_stages.contains(&stage) {
+            self.memory.lock().expect("poisoned").remove(&stage);
+        } else if let Some(path) = self.path(stage) {
+            let _ = fs::remove_file(path).await;
+        }
+    }
+
+    /// Check the entry for `stage`, deleting it if it's bad and `repair` is set.
+    ///
+    /// Returns the entry's status, and whether it was deleted.
+    pub(crate) async fn verify<T>(
+        &self,
+        stage: CacheStage,
+        repair: bool,
+    ) -> (CacheEntryStatus, bool)
+    where
+        T: Expiry + serde::de::DeserializeOwned,
+    {
+        let _guard = self.lock.lock().await;
+
+        let status = self.check::<T>(stage).await;
+        if !(repair && status.is_bad()) {
+            return (status, false);
+        }
+
+        let repaired = if self.memory_stages.contains(&stage) {
+            self.memory.lock().expect("poisoned").remove(&stage);
+            true
+        } else if let Some(path) = self.path(stage) {
+            fs::remove_file(path).await.is_ok()
+        } else {
+            false
+        };
+        (status, repaired)
+    }
+}
+
+impl Cache {
+    async fn check<T>(&self, stage: CacheStage) -> CacheEntryStatus
+    where
+        T: Expiry + serde::de::DeserializeOwned,
+    {
+        let content = if self.memory_stages.contains(&stage) {
+            self.memory.lock().expect("poisoned").get(&stage).cloned()
+        } else if let Some(path) = self.path(stage) {
+            match fs::read_to_string(&path).await {
+                Ok(content) => Some(content),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+                Err(error) => return CacheEntryStatus::Unreadable(error.to_string()),
+            }
+        } else {
+            None
+        };
+        let Some(content) = content else {
+            return CacheEntryStatus::Missing;
+        };
+
+        // Parse as JSON first, to distinguish corrupt files from entries written in another format
+        if let Err(error) = serde_json::from_str::<serde::de::IgnoredAny>(&content) {
+            return CacheEntryStatus::Corrupt(error.to_string());
+        }
+        match serde_json::from_str::<T>(&content) {
+            Ok(value) if self.is_fresh(&value) => CacheEntryStatus::Fresh,
+            Ok(_) => CacheEntryStatus::Expired,
+            Err(error) => CacheEntryStatus::Incompatible(error.to_string()),
+        }
+    }
+
+    async fn write<T, E>(&self, stage: CacheStage, value: &T) -> Result<(), Error<E>>
+    where
+        T: serde::Serialize,
+    {
+        self.faults
+            .cache(FaultPoint::CacheWrite(stage))
+            .await
+            .map_err(|error| Error::fault("failed to write", stage, error))?;
+        if self.memory_stages.contains(&stage) {
             let content =
-                serde_json::to_string_pretty(&value).expect("tried to cache unserializable value");
+                serde_json::to_string(value).expect("tried to cache unserializable value");
+            self.memory.lock().expect("poisoned").insert(stage, content);
+            audit::note_cache_write(stage, None);
+        } else if let Some(path) = self.path(stage) {
+            let content =
+                serde_json::to_string_pretty(value).expect("tried to cache unserializable value");
             fs::create_dir_all(path.parent().expect("path in dir"))
-                .and_then(|()| fs::write(path, &content))
+                .and_then(|()| fs::write(&path, &content))
                 .await
-                .map_err(|error| Error::cache("failed to write", path, error))?;
+                .map_err(|error| Error::cache("failed to write", &path, error))?;
+            audit::note_cache_write(stage, Some(&path));
+            self.evict(&path).await;
         }
+        Ok(())
+    }
 
-        Ok(value)
+    /// Evict entries from the directory containing `path` until it's within the cache's limits.
+    ///
+    /// Expired entries are evicted first, least recently written first, followed by the least
+    /// recently written fresh entries. The entry at `path`, which has just been written, is never
+    /// evicted. Eviction is best-effort: entries that can't be inspected or removed are skipped,
+    /// since the write itself succeeded.
+    async fn evict(&self, path: &Path) {
+        if self.limits.is_unlimited() {
+            return;
+        }
+        let Ok(mut dir) = fs::read_dir(path.parent().expect("path in dir")).await else {
+            return;
+        };
+
+        let (mut entries, mut bytes) = (0, 0);
+        let mut candidates = Vec::new();
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            if !is_entry_name(&entry.file_name()) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            entries += 1;
+            bytes += metadata.len();
+
+            let entry_path = entry.path();
+            if entry_path == path {
+                continue;
+            }
+            // Entries that can't be parsed are unusable, so they're treated as expired
+            let fresh = fs::read_to_string(&entry_path)
+                .await
+                .ok()
+                .and_then(|content| serde_json::from_str::<EntryExpiry>(&content).ok())
+                .is_some_and(|expiry| self.is_fresh(&expiry));
+            candidates.push((fresh, metadata.modified().ok(), metadata.len(), entry_path));
+        }
+
+        candidates.sort_by_key(|&(fresh, modified, ..)| (fresh, modified));
+        for (_, _, len, candidate) in candidates {
+            if self.limits.allows(entries, bytes) {
+                break;
+            }
+            if fs::remove_file(&candidate).await.is_ok() {
+                entries -= 1;
+                bytes -= len;
+            }
+        }
+    }
+
+    async fn read<T, E>(&self, stage: CacheStage) -> Result<Option<T>, Error<E>>
+    where
+        T: Expiry + serde::de::DeserializeOwned,
+    {
+        self.faults
+            .cache(FaultPoint::CacheRead(stage))
+            .await
+            .map_err(|error| Error::fault("failed to read", stage, error))?;
+        if self.memory_stages.contains(&stage) {
+            let memory = self.memory.lock().expect("poisoned");
+            let value = memory.get(&stage).map(|content| {
+                serde_json::from_str::<T>(content).expect("cached value should deserialize")
+            });
+            return Ok(value.filter(|value| self.is_fresh(value)));
+        }
+        let Some(path) = self.path(stage) else {
+            return Ok(None);
+        };
+
+        match fs::read_to_string(&path).await {
+            Ok(content) => {
+                let value: T = serde_json::from_str(&content)
+                    .map_err(|error| Error::cache("corrupt", &path, error))?;
+                Ok(self.is_fresh(&value).then_some(value))
+            }
+            Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                Err(Error::cache("failed to read", &path, error))
+            }
+            Err(_) => Ok(self.migrate(stage, &path).await),
+        }
+    }
+
+    /// Look for a fresh entry for `stage` in the previous versions' directories, and copy it to
+    /// `path` if found.
+    ///
+    /// Entries that can't be read or parsed are skipped, since the cache format may have changed
+    /// between versions.
+    async fn migrate<T>(&self, stage: CacheStage, path: &Path) -> Option<T>
+    where
+        T: Expiry + serde::de::DeserializeOwned,
+    {
+        if self.stage_dirs.contains_key(&stage) {
+            return None;
+        }
+        let candidates = self.previous_dirs.iter().flat_map(|dir| {
+            self.previous_file_names(stage)
+                .into_iter()
+                .map(move |file_name| dir.join(file_name))
+        });
+        for candidate in candidates {
+            let Ok(content) = fs::read_to_string(candidate).await else {
+                continue;
+            };
+            let Ok(value) = serde_json::from_str::<T>(&content) else {
+                continue;
+            };
+            if !self.is_fresh(&value) {
+                continue;
+            }
+            // The entry is still usable if it can't be copied, it'll just be looked up again
+            let copied = fs::create_dir_all(path.parent().expect("path in dir"))
+                .and_then(|()| fs::write(path, &content))
+                .await;
+            if copied.is_ok() {
+                audit::note_cache_write(stage, Some(path));
+            }
+            return Some(value);
+        }
+        None
+    }
+
+    /// The names `stage`'s entry may have in previous versions' directories, newest layout first.
+    fn previous_file_names(&self, stage: CacheStage) -> Vec<String> {
+        let mut file_names = vec![self.file_name(stage)];
+        // Stages with their own suffix used the cache's suffix before 0.5, e.g. client
+        // registrations before they were shared by start URL and region
+        if self.stage_suffixes.contains_key(&stage) {
+            file_names.push(format!("{}-{}.json", stage.prefix(), self.suffix));
+        }
+        file_names
+    }
+
+    fn is_fresh<T: Expiry>(&self, value: &T) -> bool {
+        value.expires_at() - chrono::Duration::from_std(CACHE_BUFFER).expect("expiry overflow")
+            > self.clock.now()
     }
 }
 
 pub(crate) enum Error<E> {
-    Cache(String),
+    Cache { message: String, source: BoxError },
     Init(E),
 }
 
 impl<E> Error<E> {
-    fn cache(
-        msg: &'static str,
-        path: &Path,
-        error: impl Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
-    ) -> Self {
-        Self::Cache(format!(
-            "{} cache file {} due to: {}",
-            msg,
-            path.display(),
-            error.into()
-        ))
+    fn cache(msg: &'static str, path: &Path, error: impl Into<BoxError>) -> Self {
+        Self::Cache {
+            message: format!("{} cache file {}", msg, path.display()),
+            source: error.into(),
+        }
+    }
+
+    fn fault(msg: &'static str, stage: CacheStage, error: io::Error) -> Self {
+        Self::Cache {
+            message: format!("{msg} {} cache entry", stage.prefix()),
+            source: error.into(),
+        }
     }
 }
 
@@ -104,6 +540,39 @@ pub(crate) trait Expiry {
     fn expires_at(&self) -> DateTime<Utc>;
 }
 
+/// The expiry of any cache entry, regardless of its stage.
+#[derive(serde::Deserialize)]
+struct EntryExpiry {
+    #[serde(alias = "client_secret_expires_at")]
+    expires_at: DateTime<Utc>,
+}
+
+impl Expiry for EntryExpiry {
+    fn expires_at(&self) -> DateTime<Utc> {
+        self.expires_at
+    }
+}
+
+/// Whether `file_name` looks like a cache entry written by [`Cache`].
+fn is_entry_name(file_name: &std::ffi::OsStr) -> bool {
+    let Some(stem) = file_name
+        .to_str()
+        .and_then(|name| name.strip_suffix(".json"))
+    else {
+        return false;
+    };
+    CacheStage::ALL.iter().any(|stage| {
+        stem.strip_prefix(stage.prefix())
+            .is_some_and(|rest| rest.starts_with('-'))
+    })
+}
+
+fn hash_suffix<S: Hash>(suffix: S) -> String {
+    let mut hasher = Md5Hasher::new();
+    suffix.hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
 struct Md5Hasher {
     inner: Cell<Option<Md5>>,
 }
@@ -0,0 +1,115 @@
+use std::{fmt, path::PathBuf};
+
+use crate::CacheStage;
+
+/// The result of checking an [`SsoFlow`](crate::SsoFlow)'s cache.
+///
+/// See [`SsoFlow::verify_cache`](crate::SsoFlow::verify_cache).
+#[derive(Clone, Debug)]
+pub struct CacheReport {
+    /// The entry for each stage, in the order the flow uses them.
+    pub entries: Vec<CacheEntryReport>,
+}
+
+impl CacheReport {
+    /// Whether every entry is usable, or was repaired.
+    ///
+    /// Missing and expired entries are healthy, since the flow will replace them.
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|entry| entry.repaired || !entry.status.is_bad())
+    }
+}
+
+impl fmt::Display for CacheReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{entry}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The state of a single cache entry in a [`CacheReport`].
+#[derive(Clone, Debug)]
+pub struct CacheEntryReport {
+    /// The stage of the flow.
+    pub stage: CacheStage,
+
+    /// The path of the stage's cache file, if any.
+    ///
+    /// This is `None` if there's no cache directory for the stage, or if it's only cached in
+    /// memory.
+    pub path: Option<PathBuf>,
+
+    /// The state of the entry.
+    pub status: CacheEntryStatus,
+
+    /// Whether the entry was deleted because it was bad.
+    pub repaired: bool,
+}
+
+impl fmt::Display for CacheEntryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}: {}", self.stage, self.status)?;
+        if let Some(path) = &self.path {
+            write!(f, " ({})", path.display())?;
+        }
+        if self.repaired {
+            write!(f, ", deleted")?;
+        }
+        Ok(())
+    }
+}
+
+/// The state of a cache entry.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum CacheEntryStatus {
+    /// There's no entry, or no cache directory for the stage.
+    Missing,
+
+    /// The entry is valid and fresh, so it would be used.
+    Fresh,
+
+    /// The entry is valid, but has expired (or is about to), so it would be replaced.
+    Expired,
+
+    /// The entry's file couldn't be read, e.g. because of its permissions.
+    Unreadable(String),
+
+    /// The entry isn't valid JSON.
+    Corrupt(String),
+
+    /// The entry is valid JSON, but doesn't match the format of this version of the crate.
+    Incompatible(String),
+}
+
+impl CacheEntryStatus {
+    /// Whether the entry would cause the flow to fail.
+    #[must_use]
+    pub fn is_bad(&self) -> bool {
+        matches!(
+            self,
+            Self::Unreadable(_) | Self::Corrupt(_) | Self::Incompatible(_)
+        )
+    }
+}
+
+impl fmt::Display for CacheEntryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "missing"),
+            Self::Fresh => write!(f, "fresh"),
+            Self::Expired => write!(f, "expired"),
+            Self::Unreadable(error) => write!(f, "unreadable: {error}"),
+            Self::Corrupt(error) => write!(f, "corrupt: {error}"),
+            Self::Incompatible(error) => write!(f, "incompatible: {error}"),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+// Interop with the AWS CLI's SSO token cache.
+//
+// `aws sso login --sso-session <name>` caches the session's access token at
+// `~/.aws/sso/cache/<sha1(name)>.json`, along with the client registration and a refresh token.
+// Reading that file lets flows reuse the CLI's login, and refresh the access token silently once it
+// expires. Refreshed tokens are written back, since refresh tokens may be rotated.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use sha1::{Digest, Sha1};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::{
+    builder::start_url_key,
+    hex,
+    sso_oidc::{self, CreateTokenResponse, RefreshTokenRequest, RefreshTokenResponse},
+    SsoConfig,
+};
+
+// Access tokens expiring sooner than this are refreshed rather than used.
+const EXPIRY_BUFFER: chrono::Duration = chrono::Duration::minutes(5);
+
+#[derive(Debug)]
+pub(crate) struct CliSession {
+    path: PathBuf,
+}
+
+/// A token file in the AWS CLI's SSO cache.
+///
+/// Only the fields that are used are read. See [`refreshed_content`] for how the file is written
+/// back.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CliToken {
+    start_url: Option<String>,
+    region: Option<String>,
+    access_token: Option<String>,
+    expires_at: Option<DateTime<Utc>>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    registration_expires_at: Option<DateTime<Utc>>,
+    refresh_token: Option<String>,
+}
+
+/// The name of the CLI's cache file for the session with the given name.
+fn file_name(session_name: &str) -> String {
+    format!(
+        "{}.json",
+        hex::encode(&Sha1::digest(session_name.as_bytes()))
+    )
+}
+
+impl CliSession {
+    /// The CLI session with the given name, or `None` if there's no home directory.
+    pub(crate) fn new(name: &str) -> Option<Self> {
+        let mut path = dirs_next::home_dir()?;
+        path.extend([".aws", "sso", "cache"]);
+        path.push(file_name(name));
+        Some(Self { path })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get an access token for `config` from the CLI's cache, refreshing it if necessary.
+    ///
+    /// `None` is returned if the cache file is missing or unusable (e.g. it's for a different start
+    /// URL), or if the token can't be refreshed, in which case the flow falls back to prompting.
+    pub(crate) async fn token(
+        &self,
+        client: &sso_oidc::Client,
+        config: &SsoConfig,
+        verbatim: bool,
+        now: DateTime<Utc>,
+    ) -> Option<CreateTokenResponse> {
+        let content = fs::read_to_string(&self.path).await.ok()?;
+        let cached: CliToken = serde_json::from_str(&content).ok()?;
+
+        let start_url = cached.start_url.as_deref()?;
+        if start_url_key(start_url, verbatim) != start_url_key(&config.start_url, verbatim)
+            || cached.region.as_deref() != Some(config.region.as_ref())
+        {
+            return None;
+        }
+
+        if let (Some(access_token), Some(expires_at)) = (&cached.access_token, cached.expires_at) {
+            if expires_at - EXPIRY_BUFFER > now {
+                return Some(CreateTokenResponse {
+                    access_token: access_token.clone(),
+                    expires_at,
+                });
+            }
+        }
+
+        if cached
+            .registration_expires_at
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            return None;
+        }
+        let res = client
+            .refresh_token(RefreshTokenRequest {
+                client_id: cached.client_id.clone()?,
+                client_secret: cached.client_secret.clone()?,
+                refresh_token: cached.refresh_token.clone()?,
+            })
+            .await
+            .ok()?;
+
+        // The refreshed token is still usable if it can't be written back, but the CLI (and other
+        // flows) will have to refresh it again
+        if let Some(content) = refreshed_content(&content, &res) {
+            let _ = write(&self.path, &content).await;
+        }
+
+        Some(res.token)
+    }
+}
+
+/// The CLI's cache file `content`, updated with a refreshed token.
+///
+/// Only the refreshed keys are replaced, so other keys are preserved as they are, including any
+/// that are `null`.
+fn refreshed_content(content: &str, res: &RefreshTokenResponse) -> Option<String> {
+    let mut cached: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(content).ok()?;
+    cached.insert("accessToken".into(), res.token.access_token.clone().into());
+    cached.insert(
+        "expiresAt".into(),
+        serde_json::to_value(res.token.expires_at).ok()?,
+    );
+    if let Some(refresh_token) = &res.refresh_token {
+        cached.insert("refreshToken".into(), refresh_token.clone().into());
+    }
+    serde_json::to_string(&cached).ok()
+}
+
+/// Replace the file at `path` with `content`.
+///
+/// The file is written to a temporary file in the same directory and renamed into place, so the
+/// CLI never sees a partial file. On Unix, it's only readable by the current user, as the CLI
+/// leaves it.
+async fn write(path: &Path, content: &str) -> io::Result<()> {
+    let Some(file_name) = path.file_name() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "path is not a file",
+        ));
+    };
+    let mut temp_name = file_name.to_os_string();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let result = async {
+        let mut file = options.open(&temp_path).await?;
+        file.write_all(content.as_bytes()).await?;
+        file.sync_all().await?;
+        fs::rename(&temp_path, path).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path).await;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_name_is_sha1_of_session_name() {
+        assert_eq!(
+            file_name("my-sso"),
+            "0ad374308c5a4e22f723adf10145eafad7c4031c.json"
+        );
+    }
+
+    #[test]
+    fn refresh_preserves_other_keys() {
+        let content = r#"{
+            "startUrl": "https://example.awsapps.com/start",
+            "region": "eu-west-1",
+            "accessToken": "token",
+            "expiresAt": "2024-03-05T12:00:00Z",
+            "refreshToken": "refresh",
+            "registrationExpiresAt": null,
+            "scopes": ["sso:account:access"]
+        }"#;
+        let res = RefreshTokenResponse {
+            token: CreateTokenResponse {
+                access_token: "refreshed".to_string(),
+                expires_at: "2024-03-05T13:00:00Z".parse().unwrap(),
+            },
+            refresh_token: None,
+        };
+        let content = refreshed_content(content, &res).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&content).unwrap(),
+            serde_json::json!({
+                "startUrl": "https://example.awsapps.com/start",
+                "region": "eu-west-1",
+                "accessToken": "refreshed",
+                "expiresAt": "2024-03-05T13:00:00Z",
+                "refreshToken": "refresh",
+                "registrationExpiresAt": null,
+                "scopes": ["sso:account:access"],
+            })
+        );
+    }
+}
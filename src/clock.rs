@@ -0,0 +1,40 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+
+/// A source of the current time.
+///
+/// The flow uses this to decide whether cached tokens and credentials have expired. The default
+/// is [`SystemClock`], but a custom clock can be set with
+/// [`SsoFlowBuilder::clock`](crate::SsoFlowBuilder::clock), e.g. to make expiry deterministic in
+/// tests or to correct for known skew:
+///
+/// ```
+/// use aws_sso_flow::{Clock, SsoFlowBuilder};
+/// use chrono::{DateTime, Duration, Utc};
+///
+/// #[derive(Debug)]
+/// struct SkewedClock(Duration);
+///
+/// impl Clock for SkewedClock {
+///     fn now(&self) -> DateTime<Utc> {
+///         Utc::now() + self.0
+///     }
+/// }
+///
+/// let builder = SsoFlowBuilder::new().clock(SkewedClock(Duration::seconds(-90)));
+/// ```
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// The current time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// A [`Clock`] that reads the system time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
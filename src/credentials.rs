@@ -1,13 +1,21 @@
 use std::fmt;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use sha1::{Digest, Sha1};
 
-use crate::{cache::Expiry, sso};
+use crate::{cache::Expiry, hex, process_file, sso};
+
+/// Environment variables that would override or conflict with credentials set in the environment.
+const CONFLICTING_ENV_VARS: &[&str] = &["AWS_PROFILE", "AWS_DEFAULT_PROFILE", "AWS_SECURITY_TOKEN"];
 
 /// AWS session credentials.
 ///
 /// The fields of this struct are obviously pretty sensitive, and should be handled with care.
 /// The secret and session token are not printed in `Debug` output.
+///
+/// With the `http` feature, `http::HeaderMap::try_from(&credentials)` returns the
+/// `x-amz-security-token` header that requests signed with the credentials must include. This
+/// fails if the session token isn't a valid header value.
 #[allow(clippy::module_name_repetitions)]
 pub struct SessionCredentials {
     /// The access key ID.
@@ -23,6 +31,113 @@ pub struct SessionCredentials {
     pub expires_at: DateTime<Utc>,
 }
 
+impl SessionCredentials {
+    /// The time remaining until the credentials expire.
+    ///
+    /// This is negative if the credentials have already expired.
+    #[must_use]
+    pub fn remaining(&self) -> Duration {
+        self.expires_at - Utc::now()
+    }
+
+    /// Whether the credentials have expired.
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.expires_within(Duration::zero())
+    }
+
+    /// Whether the credentials expire within the given duration (or have already expired).
+    ///
+    /// ```
+    /// # fn check(credentials: aws_sso_flow::SessionCredentials) {
+    /// use chrono::Duration;
+    ///
+    /// if credentials.expires_within(Duration::minutes(5)) {
+    ///     println!("credentials expire soon");
+    /// }
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn expires_within(&self, duration: Duration) -> bool {
+        self.remaining() <= duration
+    }
+
+    /// A short, stable identifier for the credentials that's safe to log.
+    ///
+    /// This is a truncated hash of the access key ID, so operators can correlate log lines that
+    /// used the same credentials without logging key material. Temporary access key IDs are unique
+    /// to the role session they were issued for, so credentials for different accounts or roles
+    /// have different fingerprints, and the fingerprint changes whenever credentials are rotated.
+    ///
+    /// ```
+    /// # fn log(credentials: aws_sso_flow::SessionCredentials) {
+    /// eprintln!("using credentials {}", credentials.fingerprint());
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn fingerprint(&self) -> String {
+        let hash = Sha1::digest(self.access_key_id.as_bytes());
+        hex::encode(&hash[..6])
+    }
+
+    /// Render the credentials as the JSON a [`credential_process`][process] prints.
+    ///
+    /// This lets a command act as a `credential_process` for other AWS SDKs. See also
+    /// [`SsoFlow::sync_credential_process_file`](crate::SsoFlow::sync_credential_process_file).
+    ///
+    /// [process]: https://docs.aws.amazon.com/sdkref/latest/guide/feature-process-credentials.html
+    #[must_use]
+    pub fn to_credential_process_json(&self) -> String {
+        process_file::to_json(self)
+    }
+
+    /// Set the credentials in the environment of a child process.
+    ///
+    /// This sets `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, `AWS_SESSION_TOKEN`, and
+    /// `AWS_CREDENTIAL_EXPIRATION`, and removes variables that would take precedence over them or
+    /// conflict with them, like `AWS_PROFILE`. This suits wrapper commands that run tools like
+    /// `terraform` or the AWS CLI with SSO credentials.
+    ///
+    /// ```no_run
+    /// # fn run(credentials: aws_sso_flow::SessionCredentials) -> std::io::Result<()> {
+    /// let mut command = std::process::Command::new("aws");
+    /// command.args(["sts", "get-caller-identity"]);
+    /// credentials.apply_to_command(&mut command);
+    /// command.status()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn apply_to_command(&self, command: &mut std::process::Command) {
+        command.envs(self.env_vars());
+        for name in CONFLICTING_ENV_VARS {
+            command.env_remove(name);
+        }
+    }
+
+    /// Set the credentials in the environment of a tokio child process.
+    ///
+    /// See [`apply_to_command`](Self::apply_to_command).
+    #[cfg_attr(docsrs, doc(cfg(feature = "process")))]
+    #[cfg(feature = "process")]
+    pub fn apply_to_tokio_command(&self, command: &mut tokio::process::Command) {
+        command.envs(self.env_vars());
+        for name in CONFLICTING_ENV_VARS {
+            command.env_remove(name);
+        }
+    }
+
+    fn env_vars(&self) -> [(&'static str, String); 4] {
+        [
+            ("AWS_ACCESS_KEY_ID", self.access_key_id.clone()),
+            ("AWS_SECRET_ACCESS_KEY", self.secret_access_key.clone()),
+            ("AWS_SESSION_TOKEN", self.session_token.clone()),
+            (
+                "AWS_CREDENTIAL_EXPIRATION",
+                self.expires_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            ),
+        ]
+    }
+}
+
 impl fmt::Debug for SessionCredentials {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("SessionCredentials")
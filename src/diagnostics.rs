@@ -0,0 +1,148 @@
+use std::fmt;
+
+use miette::Diagnostic;
+
+use crate::{
+    profile::SsoProfileErrorKind, SsoApiError, SsoCacheError, SsoError, SsoFlowError,
+    SsoProfileError, SsoSelectorError, SsoTimeoutError,
+};
+
+impl Diagnostic for SsoProfileError {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(self.code()))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let help = match &self.kind {
+            SsoProfileErrorKind::Environment => {
+                "check the AWS_CONFIG_FILE, AWS_PROFILE, AWS_SSO_ACCOUNT_ID, and AWS_SSO_ROLE_NAME \
+                 environment variables"
+                    .to_string()
+            }
+            SsoProfileErrorKind::ConfigFile => {
+                "run `aws configure sso` to create a config file with an SSO profile".to_string()
+            }
+            SsoProfileErrorKind::ProfileMissing { profile }
+            | SsoProfileErrorKind::ProfileIncomplete { profile } => {
+                format!("run `aws configure sso --profile {profile}` to set up profile {profile}")
+            }
+            SsoProfileErrorKind::ProfileInvalid { profile } => {
+                format!("correct the value in profile {profile}, or run `aws configure sso --profile {profile}`")
+            }
+            SsoProfileErrorKind::ProfileDuplicate { profile } => {
+                format!("merge or remove the duplicate sections for profile {profile}")
+            }
+        };
+        Some(Box::new(help))
+    }
+}
+
+impl Diagnostic for SsoApiError {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(self.code()))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let help = "check your network connection and that the SSO configuration is correct";
+        Some(match self.request_id() {
+            Some(request_id) => Box::new(format!(
+                "{help}; if contacting AWS support, quote request ID {request_id}"
+            )),
+            None => Box::new(help),
+        })
+    }
+}
+
+impl Diagnostic for SsoCacheError {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(self.code()))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(
+            "check the permissions of the cache directory, or delete the cache file to start over",
+        ))
+    }
+}
+
+impl Diagnostic for SsoSelectorError {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(self.code()))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(
+            "check the account and role selectors match exactly one of the available accounts and roles",
+        ))
+    }
+}
+
+impl Diagnostic for SsoTimeoutError {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(self.code()))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(
+            "try again, and grant access in the browser before the verification code expires",
+        ))
+    }
+}
+
+impl<P> Diagnostic for SsoFlowError<P>
+where
+    P: std::error::Error + Send + Sync + 'static,
+{
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(self.code()))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        match self {
+            Self::Api(error) => error.help(),
+            Self::AccessRevoked(_) => Some(Box::new(
+                "your access was removed; contact your administrator to restore it",
+            )),
+            Self::Cache(error) => error.help(),
+            Self::Selector(error) => error.help(),
+            Self::VerificationPrompt(_) => None,
+            Self::VerificationPromptTimeout(error) => error.help(),
+            Self::OfflineUnavailable { .. } => Some(Box::new(
+                "authenticate while online to refresh the cached credentials",
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "process")]
+impl<P> Diagnostic for crate::RunError<P>
+where
+    P: std::error::Error + Send + Sync + 'static,
+{
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(self.code()))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        match self {
+            Self::Flow(error) => error.help(),
+            Self::Command(_) => Some(Box::new("check the command exists and is executable")),
+        }
+    }
+}
+
+impl<P> Diagnostic for SsoError<P>
+where
+    P: std::error::Error + Send + Sync + 'static,
+{
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(self.code()))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        match self {
+            Self::Config(error) => error.help(),
+            Self::Flow(error) => error.help(),
+        }
+    }
+}
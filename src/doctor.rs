@@ -0,0 +1,344 @@
+use std::{fmt, path::PathBuf, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use tokio::{fs, net::TcpStream, time::timeout};
+
+use crate::{
+    builder::default_cache_dir,
+    fault::Faults,
+    sso_oidc,
+    transport::{self, Transport},
+    ProfileSource, SsoConfigSource, SystemClock,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const CLOCK_SKEW_WARN: Duration = Duration::from_secs(60);
+const CLOCK_SKEW_FAIL: Duration = Duration::from_secs(5 * 60);
+
+/// Diagnose common problems with the default SSO configuration.
+///
+/// This checks the AWS shared config file and profile (located with `AWS_CONFIG_FILE` and
+/// `AWS_PROFILE`), that the default cache directory is writable, that the SSO endpoints are
+/// reachable, and that the local clock agrees with AWS. No credentials are required and the user
+/// is never prompted.
+///
+/// See [`SsoFlowBuilder::diagnose`](crate::SsoFlowBuilder::diagnose) to diagnose a custom
+/// configuration.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[tokio::main] async fn main() {
+/// let report = aws_sso_flow::diagnose().await;
+/// println!("{report}");
+/// # }
+/// ```
+pub async fn diagnose() -> DiagnosticReport {
+    diagnose_with(&ProfileSource::default(), default_cache_dir()).await
+}
+
+pub(crate) async fn diagnose_with(
+    source: &ProfileSource,
+    cache_dir: Option<PathBuf>,
+) -> DiagnosticReport {
+    let mut checks = Vec::with_capacity(5);
+
+    let config = match source.resolve_paths() {
+        Ok((paths, _)) => match check_config_files(&paths).await {
+            Ok(message) => {
+                checks.push(DiagnosticCheck::pass(CheckKind::ConfigFile, message));
+                let result = source.clone().load().await;
+                checks.push(match &result {
+                    Ok(config) => DiagnosticCheck::pass(
+                        CheckKind::Profile,
+                        format!("SSO configured for {}", config.start_url),
+                    ),
+                    Err(error) => DiagnosticCheck::fail(CheckKind::Profile, error.to_string()),
+                });
+                result.ok()
+            }
+            Err(message) => {
+                checks.push(DiagnosticCheck::fail(CheckKind::ConfigFile, message));
+                checks.push(DiagnosticCheck::skipped(CheckKind::Profile));
+                None
+            }
+        },
+        Err(error) => {
+            checks.push(DiagnosticCheck::fail(
+                CheckKind::ConfigFile,
+                error.to_string(),
+            ));
+            checks.push(DiagnosticCheck::skipped(CheckKind::Profile));
+            None
+        }
+    };
+
+    checks.push(check_cache_dir(cache_dir).await);
+
+    if let Some(config) = config {
+        checks.push(check_connectivity(config.region.as_ref()).await);
+        checks.push(check_clock_skew(&config.region).await);
+    } else {
+        checks.push(DiagnosticCheck::skipped(CheckKind::Connectivity));
+        checks.push(DiagnosticCheck::skipped(CheckKind::ClockSkew));
+    }
+
+    DiagnosticReport { checks }
+}
+
+/// Check every config file exists, returning a message for the check either way.
+async fn check_config_files(paths: &[PathBuf]) -> Result<String, String> {
+    for path in paths {
+        match fs::metadata(path).await {
+            Ok(metadata) if metadata.is_file() => {}
+            Ok(_) => return Err(format!("{} is not a file", path.display())),
+            Err(error) => return Err(format!("couldn't read {}: {error}", path.display())),
+        }
+    }
+    let paths: Vec<_> = paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect();
+    Ok(format!("found {}", paths.join(", ")))
+}
+
+async fn check_cache_dir(cache_dir: Option<PathBuf>) -> DiagnosticCheck {
+    let Some(dir) = cache_dir else {
+        return DiagnosticCheck::warn(
+            CheckKind::CacheDir,
+            "no cache directory available, tokens will not be cached".to_string(),
+        );
+    };
+
+    if let Err(error) = fs::create_dir_all(&dir).await {
+        return DiagnosticCheck::fail(
+            CheckKind::CacheDir,
+            format!("couldn't create {}: {error}", dir.display()),
+        );
+    }
+
+    let probe = dir.join(format!(".doctor-{}", std::process::id()));
+    if let Err(error) = fs::write(&probe, b"").await {
+        return DiagnosticCheck::fail(
+            CheckKind::CacheDir,
+            format!("couldn't write to {}: {error}", dir.display()),
+        );
+    }
+    let _ = fs::remove_file(&probe).await;
+
+    DiagnosticCheck::pass(
+        CheckKind::CacheDir,
+        format!("{} is writable", dir.display()),
+    )
+}
+
+async fn check_connectivity(region: &str) -> DiagnosticCheck {
+    let hosts = [
+        format!("oidc.{region}.amazonaws.com"),
+        format!("portal.sso.{region}.amazonaws.com"),
+    ];
+
+    for host in &hosts {
+        match timeout(CONNECT_TIMEOUT, TcpStream::connect((host.as_str(), 443))).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(error)) => {
+                return DiagnosticCheck::fail(
+                    CheckKind::Connectivity,
+                    format!("couldn't connect to {host}: {error}"),
+                );
+            }
+            Err(_) => {
+                return DiagnosticCheck::fail(
+                    CheckKind::Connectivity,
+                    format!(
+                        "timed out connecting to {host} after {}s",
+                        CONNECT_TIMEOUT.as_secs()
+                    ),
+                );
+            }
+        }
+    }
+
+    DiagnosticCheck::pass(
+        CheckKind::Connectivity,
+        format!("connected to {}", hosts.join(" and ")),
+    )
+}
+
+async fn check_clock_skew(region: &crate::Region) -> DiagnosticCheck {
+    let transport = Transport::new(transport::Config {
+        region: region.clone(),
+        api_timeout: None,
+        connect_timeout: None,
+        endpoint_url: None,
+        faults: Faults::default(),
+    });
+    let client = sso_oidc::Client::new(Arc::new(transport), Arc::new(SystemClock));
+
+    let server_time = match client.server_time().await {
+        Ok(server_time) => server_time,
+        Err(error) => {
+            return DiagnosticCheck::warn(
+                CheckKind::ClockSkew,
+                format!("couldn't determine server time: {error}"),
+            );
+        }
+    };
+
+    let skew = (Utc::now() - server_time).abs();
+    let Ok(skew) = skew.to_std() else {
+        return DiagnosticCheck::fail(CheckKind::ClockSkew, "clock skew out of range".to_string());
+    };
+    let message = format!("local clock differs from AWS by {}s", skew.as_secs());
+    if skew > CLOCK_SKEW_FAIL {
+        DiagnosticCheck::fail(CheckKind::ClockSkew, message)
+    } else if skew > CLOCK_SKEW_WARN {
+        DiagnosticCheck::warn(CheckKind::ClockSkew, message)
+    } else {
+        DiagnosticCheck::pass(CheckKind::ClockSkew, message)
+    }
+}
+
+/// The result of [`diagnose`](crate::diagnose).
+#[derive(Clone, Debug)]
+pub struct DiagnosticReport {
+    /// The checks that were performed, in order.
+    pub checks: Vec<DiagnosticCheck>,
+}
+
+impl DiagnosticReport {
+    /// Whether no checks failed.
+    ///
+    /// Warnings and skipped checks are not considered failures, though checks are only skipped if
+    /// an earlier check failed.
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Fail)
+    }
+}
+
+impl fmt::Display for DiagnosticReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, check) in self.checks.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{check}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A single check in a [`DiagnosticReport`].
+#[derive(Clone, Debug)]
+pub struct DiagnosticCheck {
+    /// What was checked.
+    pub kind: CheckKind,
+
+    /// The outcome of the check.
+    pub status: CheckStatus,
+
+    /// A human-readable description of the outcome.
+    pub message: String,
+}
+
+impl DiagnosticCheck {
+    fn pass(kind: CheckKind, message: String) -> Self {
+        Self {
+            kind,
+            status: CheckStatus::Pass,
+            message,
+        }
+    }
+
+    fn warn(kind: CheckKind, message: String) -> Self {
+        Self {
+            kind,
+            status: CheckStatus::Warn,
+            message,
+        }
+    }
+
+    fn fail(kind: CheckKind, message: String) -> Self {
+        Self {
+            kind,
+            status: CheckStatus::Fail,
+            message,
+        }
+    }
+
+    fn skipped(kind: CheckKind) -> Self {
+        Self {
+            kind,
+            status: CheckStatus::Skipped,
+            message: "skipped due to an earlier failure".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.status, self.kind, self.message)
+    }
+}
+
+/// The kinds of check performed by [`diagnose`](crate::diagnose).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum CheckKind {
+    /// The AWS shared config file exists.
+    ConfigFile,
+
+    /// The selected profile contains complete SSO configuration.
+    Profile,
+
+    /// The cache directory is writable.
+    CacheDir,
+
+    /// The SSO endpoints for the configured region are reachable.
+    Connectivity,
+
+    /// The local clock agrees with AWS.
+    ClockSkew,
+}
+
+impl fmt::Display for CheckKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::ConfigFile => "config file",
+            Self::Profile => "profile",
+            Self::CacheDir => "cache directory",
+            Self::Connectivity => "connectivity",
+            Self::ClockSkew => "clock skew",
+        })
+    }
+}
+
+/// The outcome of a [`DiagnosticCheck`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CheckStatus {
+    /// The check passed.
+    Pass,
+
+    /// The check found a potential problem.
+    Warn,
+
+    /// The check failed.
+    Fail,
+
+    /// The check was not performed because an earlier check failed.
+    Skipped,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Pass => "pass",
+            Self::Warn => "warn",
+            Self::Fail => "FAIL",
+            Self::Skipped => "skip",
+        })
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+
+/// A stable, machine-readable code identifying the cause of an error.
+///
+/// Error messages are intended for end-users and may change between releases. Error codes are
+/// stable and can be used to map errors to localized messages, or for other programmatic handling.
+///
+/// The string form of each code (see [`as_str`](Self::as_str)) is also stable.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[tokio::main] async fn main() {
+/// use std::convert::Infallible;
+///
+/// use aws_sso_flow::ErrorCode;
+///
+/// let result = aws_sso_flow::authenticate(|url| async move {
+///     println!("Go to {url} to sign in with SSO");
+///     Ok::<_, Infallible>(())
+/// }).await;
+///
+/// if let Err(error) = result {
+///     match error.code() {
+///         ErrorCode::ProfileMissingKeys => eprintln!("Your SSO profile is incomplete"),
+///         code => eprintln!("Sign in failed ({code})"),
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// An environment variable was invalid, or the home directory could not be determined.
+    ProfileEnvironment,
+
+    /// The AWS config file could not be read.
+    ProfileConfigFile,
+
+    /// The profile is not defined in the AWS config file.
+    ProfileMissing,
+
+    /// The profile is missing some SSO configuration keys.
+    ProfileMissingKeys,
+
+    /// The profile has an invalid value for an SSO configuration key, e.g. an account ID that isn't
+    /// 12 digits.
+    ProfileInvalidValue,
+
+    /// The profile is defined more than once in the AWS config file, and the
+    /// [`ProfileSource`](crate::ProfileSource) is strict.
+    ProfileDuplicate,
+
+    /// An AWS API call returned an error.
+    Api,
+
+    /// The user's access to the account or role was removed by their organisation.
+    AccessRevoked,
+
+    /// There was an issue with the token cache(s).
+    Cache,
+
+    /// No account available to the user matched the [`AccountSelector`](crate::AccountSelector).
+    AccountNotFound,
+
+    /// Multiple accounts available to the user matched the
+    /// [`AccountSelector`](crate::AccountSelector).
+    AccountAmbiguous,
+
+    /// No role in the account matched the [`RoleSelector`](crate::RoleSelector).
+    RoleNotFound,
+
+    /// Multiple roles in the account matched the [`RoleSelector`](crate::RoleSelector).
+    RoleAmbiguous,
+
+    /// An error occurred during the verification prompt.
+    VerificationPrompt,
+
+    /// The verification prompt timed out.
+    VerificationPromptTimeout,
+
+    /// The flow is offline and there are no fresh cached credentials.
+    OfflineUnavailable,
+
+    /// A command couldn't be run with credentials.
+    Command,
+}
+
+impl ErrorCode {
+    /// The stable string form of the code, e.g. `AWS_SSO_FLOW::PROFILE_MISSING_KEYS`.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ProfileEnvironment => "AWS_SSO_FLOW::PROFILE_ENVIRONMENT",
+            Self::ProfileConfigFile => "AWS_SSO_FLOW::PROFILE_CONFIG_FILE",
+            Self::ProfileMissing => "AWS_SSO_FLOW::PROFILE_MISSING",
+            Self::ProfileMissingKeys => "AWS_SSO_FLOW::PROFILE_MISSING_KEYS",
+            Self::ProfileInvalidValue => "AWS_SSO_FLOW::PROFILE_INVALID_VALUE",
+            Self::ProfileDuplicate => "AWS_SSO_FLOW::PROFILE_DUPLICATE",
+            Self::Api => "AWS_SSO_FLOW::API",
+            Self::AccessRevoked => "AWS_SSO_FLOW::ACCESS_REVOKED",
+            Self::Cache => "AWS_SSO_FLOW::CACHE",
+            Self::AccountNotFound => "AWS_SSO_FLOW::ACCOUNT_NOT_FOUND",
+            Self::AccountAmbiguous => "AWS_SSO_FLOW::ACCOUNT_AMBIGUOUS",
+            Self::RoleNotFound => "AWS_SSO_FLOW::ROLE_NOT_FOUND",
+            Self::RoleAmbiguous => "AWS_SSO_FLOW::ROLE_AMBIGUOUS",
+            Self::VerificationPrompt => "AWS_SSO_FLOW::VERIFICATION_PROMPT",
+            Self::VerificationPromptTimeout => "AWS_SSO_FLOW::VERIFICATION_PROMPT_TIMEOUT",
+            Self::OfflineUnavailable => "AWS_SSO_FLOW::OFFLINE_UNAVAILABLE",
+            Self::Command => "AWS_SSO_FLOW::COMMAND",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
@@ -0,0 +1,159 @@
+// Running child processes with credentials that are kept fresh while they run.
+//
+// A running process's environment can't be changed, so rather than setting credentials in the
+// child's environment, it's given an AWS config file with a profile that reads credentials with a
+// `credential_process`. The process prints a credentials file that's replaced whenever the
+// credentials are rotated, and SDKs in the child run it again when their credentials expire.
+
+use std::{ffi::OsStr, fmt, io, path::PathBuf, time::Duration};
+
+use tokio::{fs, io::AsyncWriteExt, process::Command};
+
+use crate::{ErrorCode, Region, SsoFlowError};
+
+/// The name of the profile the child process uses.
+const PROFILE: &str = "aws_sso_flow";
+
+/// Environment variables that would take precedence over the child's profile.
+const OVERRIDING_ENV_VARS: &[&str] = &[
+    "AWS_ACCESS_KEY_ID",
+    "AWS_SECRET_ACCESS_KEY",
+    "AWS_SESSION_TOKEN",
+    "AWS_SECURITY_TOKEN",
+    "AWS_CREDENTIAL_EXPIRATION",
+    "AWS_DEFAULT_PROFILE",
+];
+
+/// How long before credentials expire to rotate them.
+///
+/// This is longer than the window in which SDKs start refreshing credentials (up to 15 minutes for
+/// botocore), so the child finds new credentials when it re-reads the file.
+pub(crate) const REFRESH_MARGIN: Duration = Duration::from_secs(20 * 60);
+
+/// How long to wait before trying again when credentials couldn't be rotated.
+pub(crate) const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// A temporary directory holding the child's config and credentials files.
+///
+/// The config file names the command the child runs for credentials, so the directory is created
+/// like `mkdtemp`: with a random name, only accessible by the current user, and never reusing a
+/// directory that already exists.
+pub(crate) struct Workspace {
+    dir: tempfile::TempDir,
+}
+
+impl Workspace {
+    pub(crate) fn new() -> io::Result<Self> {
+        let mut builder = tempfile::Builder::new();
+        builder.prefix("aws_sso_flow-exec-");
+        #[cfg(unix)]
+        builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o700));
+        let dir = builder.tempdir()?;
+        Ok(Self { dir })
+    }
+
+    pub(crate) fn credentials_path(&self) -> PathBuf {
+        self.dir.path().join("credentials.json")
+    }
+
+    fn config_path(&self) -> PathBuf {
+        self.dir.path().join("config")
+    }
+
+    /// Write the config file for the child's profile.
+    ///
+    /// The child's own config file is replaced, so if the environment doesn't set a region, the
+    /// SSO region is used.
+    pub(crate) async fn write_config(&self, region: &Region) -> io::Result<()> {
+        let credentials_path = self.credentials_path();
+        let credential_process = if cfg!(windows) {
+            format!("cmd /C type \"{}\"", credentials_path.display())
+        } else {
+            format!("cat \"{}\"", credentials_path.display())
+        };
+        let region = if std::env::var_os("AWS_REGION").is_none()
+            && std::env::var_os("AWS_DEFAULT_REGION").is_none()
+        {
+            format!("region = {region}\n")
+        } else {
+            String::new()
+        };
+        let config =
+            format!("[profile {PROFILE}]\ncredential_process = {credential_process}\n{region}");
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(self.config_path()).await?;
+        file.write_all(config.as_bytes()).await?;
+        file.sync_all().await
+    }
+
+    /// Construct a command that runs with the workspace's profile.
+    pub(crate) fn command<I, S>(&self, program: impl AsRef<OsStr>, args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .env("AWS_CONFIG_FILE", self.config_path())
+            .env("AWS_PROFILE", PROFILE);
+        for name in OVERRIDING_ENV_VARS {
+            command.env_remove(name);
+        }
+        command
+    }
+
+    /// Remove the workspace, ignoring errors.
+    pub(crate) fn remove(self) {
+        let _ = self.dir.close();
+    }
+}
+
+/// An error returned by [`SsoFlow::run_with_credentials`](crate::SsoFlow::run_with_credentials).
+#[derive(Debug)]
+pub enum RunError<P: std::error::Error + Send + Sync + 'static> {
+    /// Credentials couldn't be obtained, or written for the command.
+    Flow(SsoFlowError<P>),
+
+    /// The command couldn't be run.
+    Command(io::Error),
+}
+
+impl<P: std::error::Error + Send + Sync + 'static> RunError<P> {
+    /// A stable, machine-readable code identifying the cause of the error.
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Flow(error) => error.code(),
+            Self::Command(_) => ErrorCode::Command,
+        }
+    }
+}
+
+impl<P: std::error::Error + Send + Sync + 'static> fmt::Display for RunError<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Flow(error) => error.fmt(f),
+            Self::Command(_) => write!(f, "failed to run command"),
+        }
+    }
+}
+
+impl<P: std::error::Error + Send + Sync + 'static> std::error::Error for RunError<P> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Flow(error) => error.source(),
+            Self::Command(error) => Some(error),
+        }
+    }
+}
+
+impl<P: std::error::Error + Send + Sync + 'static> From<SsoFlowError<P>> for RunError<P> {
+    fn from(error: SsoFlowError<P>) -> Self {
+        Self::Flow(error)
+    }
+}
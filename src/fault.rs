@@ -0,0 +1,164 @@
+// Failure injection at each stage of the flow, for testing how applications degrade.
+//
+// The injection points are compiled in regardless of the `test-util` feature, so the flow's code
+// is the same either way, but without the feature there's no way to set a policy and `Faults`
+// never injects anything.
+
+#[cfg(feature = "test-util")]
+use std::sync::Arc;
+use std::{fmt, future::Future, io, time::Duration};
+
+use crate::{CacheStage, SsoApiError};
+
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+/// Decides whether to inject a failure when the flow reaches a [`FaultPoint`].
+///
+/// This is for chaos-testing applications' handling of degraded conditions, such as unwritable
+/// caches or flaky networks, and is set with
+/// [`SsoFlowBuilder::fault_policy`](crate::SsoFlowBuilder::fault_policy), which has an example.
+/// The policy is consulted every time the flow reaches a point, so it can keep state to e.g. fail
+/// only the first attempt.
+#[cfg_attr(not(feature = "test-util"), allow(dead_code))]
+pub trait FaultPolicy: fmt::Debug + Send + Sync {
+    /// The failure to inject at `point`, if any.
+    fn inject(&self, point: FaultPoint) -> Option<Fault>;
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+/// A point in the flow at which a [`FaultPolicy`] can inject a failure.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum FaultPoint {
+    /// Reading the cache entry for a stage.
+    CacheRead(CacheStage),
+
+    /// Writing the cache entry for a stage.
+    CacheWrite(CacheStage),
+
+    /// Calling an SSO or SSO OIDC API operation, e.g. `CreateToken`.
+    ///
+    /// Each `CreateToken` poll is a separate call.
+    Api(&'static str),
+
+    /// Prompting the user to grant access.
+    Prompt,
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+/// A failure injected by a [`FaultPolicy`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+#[cfg_attr(not(feature = "test-util"), allow(dead_code))]
+pub enum Fault {
+    /// Fail the stage.
+    ///
+    /// Cache reads and writes fail with an I/O error, and API calls fail as if the endpoint was
+    /// unreachable. The prompt isn't called, as if it silently failed to reach the user, so the
+    /// flow keeps polling until the user code expires.
+    Fail,
+
+    /// Fail an API call with an AWS error, e.g. `ThrottlingException`.
+    ///
+    /// At other points this is the same as [`Fail`](Self::Fail).
+    ApiError {
+        /// The AWS error code.
+        code: String,
+
+        /// The HTTP status of the response.
+        status: u16,
+    },
+
+    /// Wait before continuing the stage as normal.
+    Delay(Duration),
+}
+
+#[cfg_attr(not(feature = "test-util"), allow(dead_code))]
+impl Fault {
+    /// An [`ApiError`](Self::ApiError) fault.
+    pub fn api_error(code: impl Into<String>, status: u16) -> Self {
+        Self::ApiError {
+            code: code.into(),
+            status,
+        }
+    }
+}
+
+/// The [`FaultPolicy`] of a flow, if any.
+#[derive(Clone, Default)]
+pub(crate) struct Faults {
+    #[cfg(feature = "test-util")]
+    policy: Option<Arc<dyn FaultPolicy>>,
+}
+
+impl Faults {
+    #[cfg(feature = "test-util")]
+    pub(crate) fn new(policy: Arc<dyn FaultPolicy>) -> Self {
+        Self {
+            policy: Some(policy),
+        }
+    }
+
+    /// Consult the policy at `point`, waiting out any delay.
+    ///
+    /// Returns the fault if the stage should fail.
+    #[cfg_attr(not(feature = "test-util"), allow(clippy::unused_async))]
+    async fn inject(&self, point: FaultPoint) -> Option<Fault> {
+        #[cfg(feature = "test-util")]
+        if let Some(policy) = &self.policy {
+            return match policy.inject(point)? {
+                Fault::Delay(delay) => {
+                    tokio::time::sleep(delay).await;
+                    None
+                }
+                fault => Some(fault),
+            };
+        }
+        let _ = point;
+        None
+    }
+
+    /// Inject a failure before reading or writing a cache entry.
+    pub(crate) async fn cache(&self, point: FaultPoint) -> io::Result<()> {
+        match self.inject(point).await {
+            Some(_) => Err(io::Error::other("injected fault")),
+            None => Ok(()),
+        }
+    }
+
+    /// Inject a failure before calling the API `operation`.
+    pub(crate) async fn api(&self, operation: &'static str) -> Result<(), SsoApiError> {
+        match self.inject(FaultPoint::Api(operation)).await {
+            Some(Fault::ApiError { code, status }) => Err(SsoApiError::from_response(
+                None,
+                Some(status),
+                Some(code),
+                "injected fault",
+            )),
+            Some(_) => Err(SsoApiError::new("injected fault").transient()),
+            None => Ok(()),
+        }
+    }
+
+    /// Inject a failure before calling a verification prompt.
+    ///
+    /// A failed prompt completes successfully without being called.
+    pub(crate) async fn prompt<F, Fut, E>(&self, prompt: F) -> Result<(), E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+    {
+        match self.inject(FaultPoint::Prompt).await {
+            Some(_) => Ok(()),
+            None => prompt().await,
+        }
+    }
+}
+
+impl fmt::Debug for Faults {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_tuple("Faults");
+        #[cfg(feature = "test-util")]
+        debug.field(&self.policy);
+        debug.finish()
+    }
+}
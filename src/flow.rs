@@ -1,27 +1,106 @@
-use std::{convert::Infallible, fmt, path::PathBuf};
+use std::{
+    convert::Infallible,
+    fmt::{self, Write as _},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use aws_config::{BehaviorVersion, SdkConfig};
-use url::Url;
+use chrono::{DateTime, Utc};
+use futures::{
+    future,
+    stream::{self, Stream},
+    FutureExt, TryStreamExt,
+};
 
 use crate::{
-    cache::{self, Cache},
-    sso::{self, GetRoleCredentialsRequest},
-    sso_oidc::{self, CreateTokenError, CreateTokenRequest, RegisterClientRequest},
-    SessionCredentials, SsoConfig, SsoFlowBuilder, SsoProfileError, CLIENT_NAME,
+    audit::{self, AuditLog, Outcome, Subject},
+    builder::FlowOptions,
+    cache::{self, Cache, CacheStage},
+    cli_cache::CliSession,
+    lease::{CredentialLease, LeaseInfo, Leases},
+    plan::{FlowPlan, PlanStep},
+    process_file,
+    shared::{self, session_key, SharedSession},
+    shutdown::{self, BackgroundTasks},
+    snapshot::{self, DebugSnapshot, SnapshotCacheEntry},
+    sso::{
+        self, GetRoleCredentialsRequest, GetRoleCredentialsResponse, ListAccountRolesRequest,
+        ListAccountsRequest,
+    },
+    sso_oidc::{
+        self, CreateTokenError, CreateTokenRequest, CreateTokenResponse, RegisterClientRequest,
+        RegisterClientResponse,
+    },
+    transport::{self, Transport},
+    AccountId, AccountInfo, AccountSelector, BoxError, BoxedPromptError, BoxedVerificationPrompt,
+    CacheEntryReport, CacheReport, Clock, ErrorCode, FlowId, FlowStepper, RefreshOutcome, RoleInfo,
+    RoleName, RoleSelector, SessionCredentials, Shutdown, SsoConfig, SsoConfigSource,
+    SsoFlowBuilder, SsoProfileError, VerificationPrompt, CLIENT_NAME,
 };
 
+/// The delay before retrying a failed credential rotation, doubled on each further failure.
+const ROTATE_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+const ROTATE_RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
 /// A configured AWS SSO authentication flow.
 ///
 /// A default flow can be constructed with [`new`](Self::new).
+///
+/// # Concurrency
+///
+/// `SsoFlow` is cheap to clone, and clones share the same underlying clients and cache. It is
+/// `Send` and `Sync` (if the verification prompt is), so it can be shared between tasks either by
+/// cloning or by reference.
+///
+/// Concurrent calls to [`authenticate`](Self::authenticate) on the same flow (or its clones) are
+/// coordinated so that only one of them will prompt for verification. The others will wait, and
+/// then use the token obtained by the first (as long as a cache directory is available).
+///
+/// The same applies across flows in a process that share a start URL and region, e.g. flows for
+/// several roles in the same organisation. Start URLs are compared after normalising them (see
+/// [`SsoFlowBuilder::normalize_start_url`]). Only one of them prompts at a time, and the access token
+/// it obtains is reused by the others, even if they have no cache directory. Similarly, they share
+/// a single client registration, since Identity Center throttles bursts of registrations. If the
+/// server rejects a cached registration (e.g. because its secret was revoked), it's replaced with a
+/// new one, once per attempt.
+///
+/// An access token obtained by prompting is also remembered in memory for the rest of the process,
+/// so the user is prompted at most once per start URL while it's valid. If the token can't be
+/// written to the cache, or the cache is later found to be corrupt, the remembered token is used
+/// rather than prompting again.
 #[allow(clippy::module_name_repetitions)]
+#[derive(Clone)]
 pub struct SsoFlow<V> {
-    cache: Cache,
-    sso_oidc_client: sso_oidc::Client,
+    pub(crate) cache: Arc<Cache>,
+    pub(crate) sso_oidc_client: sso_oidc::Client,
     sso_client: sso::Client,
-    config: SsoConfig,
+    transport: Arc<Transport>,
+    pub(crate) config: Arc<SsoConfig>,
     verification_prompt: V,
+    pub(crate) offline: bool,
+    pub(crate) clock: Arc<dyn Clock>,
+    refresh_ahead: Option<Duration>,
+    refreshing: Arc<AtomicBool>,
+    background: Arc<BackgroundTasks>,
+    audit_log: Option<Arc<AuditLog>>,
+    leases: Arc<Leases>,
+    cli_session: Option<Arc<CliSession>>,
+    verbatim_start_url: bool,
+    config_source: Arc<str>,
+    profile: Option<Arc<str>>,
+    #[cfg(feature = "rusoto")]
+    pub(crate) rusoto_credentials: Arc<crate::rusoto::CredentialsMemo>,
 }
 
+/// An [`SsoFlow`] with a type-erased verification prompt.
+///
+/// See [`BoxedVerificationPrompt`] for more information.
+pub type BoxedSsoFlow = SsoFlow<BoxedVerificationPrompt>;
+
 impl SsoFlow<Infallible> {
     /// Construct a builder for an SSO flow.
     ///
@@ -44,8 +123,9 @@ where
     /// verification URL and granted access before the user code expires (default 10 mins).
     ///
     /// SSO configuration is sourced from AWS shared config (located with `AWS_CONFIG_FILE` and
-    /// `AWS_PROFILE`) and intermediate tokens are cached in the user's OS cache directory in
-    /// `aws_sso_flow/0.1/*`. The cache format is considered part of the crate's API.
+    /// `AWS_PROFILE`) and intermediate tokens are cached in `AWS_SSO_FLOW_CACHE_DIR`, if it's set,
+    /// or else in `aws_sso_flow@0.6` in the user's cache directory (`XDG_CACHE_HOME`, if it's set
+    /// on Linux). The cache format is considered part of the crate's API.
     ///
     /// For more flexible configuration see [`SsoFlowBuilder`].
     ///
@@ -76,219 +156,2198 @@ where
             .await
     }
 
-    pub(crate) fn build(
-        cache_dir: Option<PathBuf>,
-        config: SsoConfig,
-        verification_prompt: V,
-    ) -> Self {
-        let sdk_config = SdkConfig::builder()
-            .behavior_version(BehaviorVersion::latest())
-            .region(config.region.0.clone())
-            .build();
+    pub(crate) fn build(options: FlowOptions, config: SsoConfig, verification_prompt: V) -> Self {
+        let transport = Arc::new(Transport::new(transport::Config {
+            region: config.region.clone(),
+            api_timeout: options.api_timeout,
+            connect_timeout: options.connect_timeout,
+            endpoint_url: options.endpoint_url,
+            faults: options.faults.clone(),
+        }));
+        let clock = options
+            .clock
+            .unwrap_or_else(|| Arc::new(crate::SystemClock));
+        let config_source = options
+            .config_source
+            .unwrap_or_else(|| config.describe())
+            .into();
 
         Self {
-            cache: Cache::new(cache_dir, &config),
-            sso_oidc_client: sso_oidc::Client::new(&sdk_config),
-            sso_client: sso::Client::new(&sdk_config),
-            config,
+            cache: Arc::new(
+                Cache::new(
+                    options.cache_dir,
+                    options.stage_cache_dirs,
+                    Arc::clone(&clock),
+                    &config,
+                )
+                .with_previous_dirs(options.previous_cache_dirs)
+                .with_memory_stages(options.memory_cache_stages)
+                .with_limits(options.cache_limits)
+                .with_faults(options.faults)
+                .with_stage_suffix(
+                    CacheStage::Client,
+                    session_key(&config, options.verbatim_start_url),
+                ),
+            ),
+            sso_oidc_client: sso_oidc::Client::new(Arc::clone(&transport), Arc::clone(&clock))
+                .with_min_poll_interval(
+                    options
+                        .min_poll_interval
+                        .unwrap_or(sso_oidc::DEFAULT_MIN_POLL_INTERVAL),
+                )
+                .with_prompt_error_policy(options.prompt_error_policy),
+            sso_client: sso::Client::new(Arc::clone(&transport)),
+            transport,
+            config: Arc::new(config),
             verification_prompt,
+            offline: options.offline,
+            clock,
+            refresh_ahead: options.refresh_ahead,
+            refreshing: Arc::default(),
+            background: Arc::default(),
+            audit_log: options.audit_log.map(|path| Arc::new(AuditLog::new(path))),
+            leases: Arc::default(),
+            cli_session: options
+                .cli_sso_session
+                .and_then(|name| CliSession::new(&name))
+                .map(Arc::new),
+            verbatim_start_url: options.verbatim_start_url,
+            config_source,
+            profile: options.profile.map(Into::into),
+            #[cfg(feature = "rusoto")]
+            rusoto_credentials: Arc::default(),
+        }
+    }
+
+    /// Construct a flow for a different SSO configuration, sharing this flow's options.
+    ///
+    /// The new flow uses the same cache directories, clock, and verification prompt, and shares
+    /// this flow's clients if the region is unchanged. This lets e.g. an interactive tool switch
+    /// between roles without reconstructing a builder or re-reading config files:
+    ///
+    /// ```no_run
+    /// # async fn example(flow: aws_sso_flow::BoxedSsoFlow) -> Result<(), Box<dyn std::error::Error>> {
+    /// use aws_sso_flow::SsoConfig;
+    ///
+    /// let admin = flow.with_config(SsoConfig {
+    ///     role_name: "Admin".into(),
+    ///     ..flow.config().clone()
+    /// });
+    /// let credentials = admin.authenticate().await?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// Each configuration is cached separately, as for flows built independently.
+    #[must_use]
+    pub fn with_config(&self, config: SsoConfig) -> Self {
+        let (transport, sso_oidc_client, sso_client) = if config.region == self.config.region {
+            (
+                Arc::clone(&self.transport),
+                self.sso_oidc_client.clone(),
+                self.sso_client.clone(),
+            )
+        } else {
+            let transport = Arc::new(Transport::new(transport::Config {
+                region: config.region.clone(),
+                ..self.transport.config().clone()
+            }));
+            let sso_oidc_client =
+                sso_oidc::Client::new(Arc::clone(&transport), Arc::clone(&self.clock))
+                    .with_min_poll_interval(self.sso_oidc_client.min_poll_interval)
+                    .with_prompt_error_policy(self.sso_oidc_client.prompt_error_policy);
+            let sso_client = sso::Client::new(Arc::clone(&transport));
+            (transport, sso_oidc_client, sso_client)
+        };
+
+        let config_source = config.describe().into();
+        Self {
+            cache: Arc::new(self.cache.with_suffix(&config).with_stage_suffix(
+                CacheStage::Client,
+                session_key(&config, self.verbatim_start_url),
+            )),
+            sso_oidc_client,
+            sso_client,
+            transport,
+            config: Arc::new(config),
+            verification_prompt: self.verification_prompt.clone(),
+            offline: self.offline,
+            clock: Arc::clone(&self.clock),
+            refresh_ahead: self.refresh_ahead,
+            refreshing: Arc::default(),
+            background: Arc::clone(&self.background),
+            audit_log: self.audit_log.clone(),
+            leases: Arc::default(),
+            cli_session: self.cli_session.clone(),
+            verbatim_start_url: self.verbatim_start_url,
+            config_source,
+            profile: None,
+            #[cfg(feature = "rusoto")]
+            rusoto_credentials: Arc::default(),
         }
     }
 
     /// Perform the AWS SSO authentication flow and obtain credentials.
     ///
+    /// If the cached access token is rejected by the server (e.g. because the user signed out of
+    /// the access portal), it's discarded and a new one is obtained, prompting if necessary. This
+    /// is only tried once.
+    ///
     /// # Errors
     ///
     /// An errors is returned if the authentication flow cannot complete. See [`SsoFlowError`] for
     /// details of possible errors.
     pub async fn authenticate(&self) -> Result<SessionCredentials, SsoFlowError<V::Error>> {
-        let client = self
-            .cache
-            .get_or_init("client", || {
-                self.sso_oidc_client.register_client(RegisterClientRequest {
-                    client_name: CLIENT_NAME.to_string(),
-                })
-            })
-            .await
-            .map_err(|error| match error {
-                cache::Error::Init(error) => SsoFlowError::Api(SsoApiError(error)),
-                cache::Error::Cache(error) => SsoFlowError::Cache(SsoCacheError(error)),
-            })?;
+        let flow_id = FlowId::new();
+        let mut subject = Subject::new(&self.config);
+        let (result, cache_writes) = flow_id
+            .instrument(audit::collect_cache_writes(
+                self.authenticate_inner(flow_id, &mut subject).boxed(),
+            ))
+            .await;
+        let result = result.map_err(|error| error.with_flow_id(flow_id));
+        subject.cache_writes = cache_writes;
 
-        let token = self
+        if let Some(audit_log) = &self.audit_log {
+            let outcome = match &result {
+                Ok((credentials, true)) => Outcome::Cached {
+                    expires_at: credentials.expires_at,
+                },
+                Ok((credentials, false)) => Outcome::Issued {
+                    expires_at: credentials.expires_at,
+                },
+                Err(error) => Outcome::Failed {
+                    code: error.code(),
+                    error: error_chain(error),
+                },
+            };
+            audit_log
+                .record(self.clock.now(), flow_id, &self.config, &subject, &outcome)
+                .await;
+        }
+
+        result.map(|(credentials, _)| credentials.into())
+    }
+
+    /// Obtain credentials as a [`CredentialLease`].
+    ///
+    /// This authenticates like [`authenticate`](Self::authenticate), but the credentials are
+    /// zeroized when the lease is dropped, and are reported by
+    /// [`active_leases`](Self::active_leases) until then.
+    ///
+    /// ```no_run
+    /// # async fn example(flow: aws_sso_flow::BoxedSsoFlow) -> Result<(), Box<dyn std::error::Error>> {
+    /// let lease = flow.lease().await?;
+    /// println!("leased {}", lease.access_key_id);
+    /// assert_eq!(flow.active_leases().len(), 1);
+    ///
+    /// drop(lease);
+    /// assert!(flow.active_leases().is_empty());
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The same errors as [`authenticate`](Self::authenticate) may be returned.
+    pub async fn lease(&self) -> Result<CredentialLease, SsoFlowError<V::Error>> {
+        let credentials = self.authenticate().await?;
+        Ok(self.leases.lease(credentials, self.clock.now()))
+    }
+
+    /// The flow's active [`CredentialLease`]s, in the order they were taken.
+    ///
+    /// Leases are shared between clones of a flow, but not with flows constructed by
+    /// [`with_config`](Self::with_config).
+    #[must_use]
+    pub fn active_leases(&self) -> Vec<LeaseInfo> {
+        self.leases.active()
+    }
+
+    /// Refresh the cached credentials if that's possible without prompting, before `deadline`.
+    ///
+    /// This is intended to be called periodically from a background job, to keep the cache warm
+    /// so that [`authenticate`](Self::authenticate) doesn't need to call AWS. The user is never
+    /// prompted: new credentials are only obtained with an access token from the cache, another
+    /// flow for the same start URL, or the AWS CLI's cache (see
+    /// [`SsoFlowBuilder::aws_cli_sso_session`], which can renew tokens with a refresh token).
+    ///
+    /// Credentials are refreshed if they're missing, expired, or within the
+    /// [`refresh_ahead`](SsoFlowBuilder::refresh_ahead) window. To keep credentials from
+    /// expiring, the window should be longer than the interval between calls.
+    ///
+    /// ```no_run
+    /// # async fn example(flow: aws_sso_flow::BoxedSsoFlow) -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::time::{Duration, Instant};
+    ///
+    /// use aws_sso_flow::RefreshOutcome;
+    ///
+    /// let deadline = Instant::now() + Duration::from_secs(30);
+    /// match flow.refresh_if_possible(deadline).await? {
+    ///     RefreshOutcome::InteractionRequired => eprintln!("sign in to keep credentials fresh"),
+    ///     outcome => eprintln!("{outcome:?}"),
+    /// }
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the cache can't be read or written, or an AWS API call fails. If
+    /// the flow is [offline](SsoFlowBuilder::offline), credentials can't be refreshed, so
+    /// [`SsoFlowError::OfflineUnavailable`] is returned unless they're fresh.
+    pub async fn refresh_if_possible(
+        &self,
+        deadline: std::time::Instant,
+    ) -> Result<RefreshOutcome, SsoFlowError<V::Error>> {
+        let flow_id = FlowId::new();
+        let window = self
+            .refresh_ahead
+            .and_then(|window| chrono::Duration::from_std(window).ok())
+            .unwrap_or_else(chrono::Duration::zero);
+        let refresh =
+            tokio::time::timeout_at(deadline.into(), self.refresh_silently(flow_id, window));
+        match refresh.await {
+            Ok(result) => result.map_err(|error| error.with_flow_id(flow_id)),
+            Err(_) => Ok(RefreshOutcome::DeadlineExceeded),
+        }
+    }
+
+    /// Refresh credentials without prompting, if they're missing or expire within `window`.
+    async fn refresh_silently(
+        &self,
+        flow_id: FlowId,
+        window: chrono::Duration,
+    ) -> Result<RefreshOutcome, SsoFlowError<V::Error>> {
+        let mut subject = Subject::new(&self.config);
+        let (result, cache_writes) = flow_id
+            .instrument(audit::collect_cache_writes(
+                self.refresh_silently_inner(window, &mut subject).boxed(),
+            ))
+            .await;
+        let outcome = result?;
+
+        if let (Some(audit_log), RefreshOutcome::Refreshed { expires_at }) =
+            (&self.audit_log, &outcome)
+        {
+            subject.cache_writes = cache_writes;
+            let outcome = Outcome::Refreshed {
+                expires_at: *expires_at,
+            };
+            audit_log
+                .record(self.clock.now(), flow_id, &self.config, &subject, &outcome)
+                .await;
+        }
+        Ok(outcome)
+    }
+
+    async fn refresh_silently_inner(
+        &self,
+        window: chrono::Duration,
+        subject: &mut Subject,
+    ) -> Result<RefreshOutcome, SsoFlowError<V::Error>> {
+        let credentials: Option<GetRoleCredentialsResponse> = self
             .cache
-            .get_or_init("token", || {
-                self.sso_oidc_client.create_token(
-                    CreateTokenRequest {
-                        client_id: client.client_id,
-                        client_secret: client.client_secret,
-                        start_url: self.config.start_url.clone(),
-                    },
-                    self.verification_prompt.clone(),
-                )
-            })
+            .get(CacheStage::Credentials)
             .await
-            .map_err(|error| match error {
-                cache::Error::Init(CreateTokenError::Api(error)) => {
-                    SsoFlowError::Api(SsoApiError(error))
-                }
-                cache::Error::Init(CreateTokenError::VerificationPrompt(error)) => {
-                    SsoFlowError::VerificationPrompt(error)
-                }
-                cache::Error::Init(CreateTokenError::VerificationPromptTimeout) => {
-                    SsoFlowError::VerificationPromptTimeout
-                }
-                cache::Error::Cache(error) => SsoFlowError::Cache(SsoCacheError(error)),
-            })?;
+            .map_err(|error| SsoFlowError::from_cache(error, |never| match never {}))?;
+        if let Some(credentials) = &credentials {
+            if self.offline || credentials.expires_at - window > self.clock.now() {
+                return Ok(RefreshOutcome::Fresh {
+                    expires_at: credentials.expires_at,
+                });
+            }
+        }
+        if self.offline {
+            return Err(SsoFlowError::OfflineUnavailable { flow_id: None });
+        }
+
+        let Some(token) = self.silent_access_token().await? else {
+            return Ok(RefreshOutcome::InteractionRequired);
+        };
+        let (account, role) = futures::try_join!(
+            self.peek::<ResolvedAccount>(
+                CacheStage::Account,
+                matches!(self.config.account(), AccountSelector::Name(_)),
+            ),
+            self.peek::<ResolvedRole>(
+                CacheStage::Role,
+                !matches!(self.config.role(), RoleSelector::Name(_)),
+            ),
+        )?;
+        let account_id = match account {
+            Some(account) => account.account_id,
+            None => self.account_id(&token).await?,
+        };
+        subject.account_id = Some(account_id.clone());
+        let role_name = match role {
+            Some(role) => role.role_name,
+            None => self.role_name(&token, &account_id).await?,
+        };
+        subject.role_name = Some(role_name.clone());
 
         let credentials = self
             .cache
-            .get_or_init("credentials", || {
+            .refresh(CacheStage::Credentials, || {
                 self.sso_client
                     .get_role_credentials(GetRoleCredentialsRequest {
                         access_token: token.access_token,
-                        account_id: self.config.account_id.clone(),
-                        role_name: self.config.role_name.clone(),
+                        account_id,
+                        role_name,
                     })
             })
             .await
-            .map_err(|error| match error {
-                cache::Error::Init(error) => SsoFlowError::Api(SsoApiError(error)),
-                cache::Error::Cache(error) => SsoFlowError::Cache(SsoCacheError(error)),
+            .map_err(|error| {
+                SsoFlowError::from_cache(error, SsoFlowError::from_role_credentials)
             })?;
+        Ok(RefreshOutcome::Refreshed {
+            expires_at: credentials.expires_at,
+        })
+    }
 
-        Ok(credentials.into())
+    /// Obtain credentials, and new credentials each time they're rotated.
+    ///
+    /// The stream yields the current credentials first, like [`authenticate`](Self::authenticate).
+    /// Then, `refresh_margin` before they expire, it obtains new credentials and yields them. This
+    /// suits consumers that need to react to rotation, such as credential file writers or signing
+    /// layers.
+    ///
+    /// Rotation doesn't prompt the user while the access token is valid. Otherwise the old
+    /// credentials are kept until they expire, and the flow then authenticates as usual, which may
+    /// prompt. Retryable errors while rotating (see [`SsoFlowError::is_retryable`]) are retried
+    /// with backoff until the old credentials expire, after which the flow also authenticates as
+    /// usual.
+    ///
+    /// ```no_run
+    /// # async fn example(flow: aws_sso_flow::BoxedSsoFlow) -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::time::Duration;
+    ///
+    /// use futures::TryStreamExt;
+    ///
+    /// let mut credentials = std::pin::pin!(flow.credentials_stream(Duration::from_secs(300)));
+    /// while let Some(credentials) = credentials.try_next().await? {
+    ///     println!("rotated to {}", credentials.access_key_id);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The stream yields an error if credentials can't be obtained, or if rotation fails with an
+    /// error that isn't retryable, and then ends.
+    pub fn credentials_stream(
+        &self,
+        refresh_margin: Duration,
+    ) -> impl Stream<Item = Result<SessionCredentials, SsoFlowError<V::Error>>> + Send + '_ {
+        let margin =
+            chrono::Duration::from_std(refresh_margin).unwrap_or_else(|_| chrono::Duration::zero());
+        // The state is the previous credentials' access key ID and expiry, or `None` once the
+        // stream has ended
+        stream::unfold(Some(None), move |previous| async move {
+            let previous: Option<(String, DateTime<Utc>)> = previous?;
+            match self.rotate(previous, margin).await {
+                Ok(credentials) => {
+                    let next = (credentials.access_key_id.clone(), credentials.expires_at);
+                    Some((Ok(credentials), Some(Some(next))))
+                }
+                Err(error) => Some((Err(error), None)),
+            }
+        })
     }
-}
 
-impl<V> fmt::Debug for SsoFlow<V> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.debug_struct("SsoFlow")
-            .field("cache", &self.cache)
-            .field("sso_oidc_client", &self.sso_oidc_client)
-            .field("sso_client", &self.sso_client)
-            .field("config", &self.config)
-            .field("verification_prompt", &"_")
-            .finish()
+    /// Obtain credentials to follow `previous` in a [`credentials_stream`](Self::credentials_stream).
+    async fn rotate(
+        &self,
+        previous: Option<(String, DateTime<Utc>)>,
+        margin: chrono::Duration,
+    ) -> Result<SessionCredentials, SsoFlowError<V::Error>> {
+        let Some((access_key_id, expires_at)) = previous else {
+            return self.authenticate().await;
+        };
+
+        self.sleep_until(expires_at - margin).await;
+        if !self.offline {
+            // Transient errors are retried while the current credentials are still valid
+            let mut delay = ROTATE_RETRY_BASE_DELAY;
+            loop {
+                let flow_id = FlowId::new();
+                match self.refresh_silently(flow_id, margin).await {
+                    Ok(_) => break,
+                    Err(error) if error.is_retryable() => {
+                        let Some(retry_at) = chrono::Duration::from_std(delay)
+                            .ok()
+                            .map(|delay| self.clock.now() + delay)
+                            .filter(|retry_at| *retry_at < expires_at)
+                        else {
+                            break;
+                        };
+                        self.sleep_until(retry_at).await;
+                        delay = (delay * 2).min(ROTATE_RETRY_MAX_DELAY);
+                    }
+                    Err(error) => return Err(error.with_flow_id(flow_id)),
+                }
+            }
+        }
+        let credentials = self.authenticate().await?;
+        if credentials.access_key_id != access_key_id {
+            return Ok(credentials);
+        }
+
+        // The credentials couldn't be refreshed silently, so wait for them to expire
+        self.sleep_until(expires_at).await;
+        self.authenticate().await
     }
-}
 
-/// An SSO verification prompt.
-///
-/// The AWS SSO authentication flow requires users to explicitly grant access by visiting a URL and
-/// clicking a button. There are many ways this could be implemented depending on the context, so
-/// verification prompts are modelled with this trait.
-///
-/// The trait is implemented for async functions with a single `Url` argument and returning
-/// `Result<(), E>`, so a trivial prompt could look like:
-///
-/// ```
-/// use std::convert::Infallible;
-///
-/// use aws_sso_flow::VerificationPrompt;
-///
-/// fn prompt() -> impl VerificationPrompt {
-///     |verification_url| async move {
-///         println!("Go to {verification_url} to grant access");
-///         Ok::<_, Infallible>(())
-///     }
-/// }
-/// ```
-///
-/// The `Error` associated type can be used if the prompt is fallible. Type information is preserved
-/// in the event of any subsequent [`SsoFlowError`].
-pub trait VerificationPrompt: Clone + Send + Sync {
-    /// The future returned by the prompt.
-    type Future: std::future::Future<Output = Result<(), Self::Error>> + Send;
+    async fn sleep_until(&self, at: DateTime<Utc>) {
+        tokio::time::sleep((at - self.clock.now()).to_std().unwrap_or_default()).await;
+    }
 
-    /// An error that could occur when attempting to prompt.
-    type Error: std::error::Error + Send + Sync + 'static;
+    /// Authenticate, returning the credentials and whether they were served from the cache.
+    ///
+    /// `subject` is updated as the account and role are resolved, for auditing.
+    async fn authenticate_inner(
+        &self,
+        flow_id: FlowId,
+        subject: &mut Subject,
+    ) -> Result<(GetRoleCredentialsResponse, bool), SsoFlowError<V::Error>> {
+        if self.offline {
+            let credentials: Option<GetRoleCredentialsResponse> = self
+                .cache
+                .get(CacheStage::Credentials)
+                .await
+                .map_err(|error| SsoFlowError::from_cache(error, |never| match never {}))?;
+            return credentials
+                .map(|credentials| (credentials, true))
+                .ok_or(SsoFlowError::OfflineUnavailable { flow_id: None });
+        }
 
-    /// Prompt the user to grant access via the given `verification_url`.
-    fn prompt(self, verification_url: Url) -> Self::Future;
-}
+        // Read every stage that might be needed concurrently, then run only the missing stages
+        let needs_account = matches!(self.config.account(), AccountSelector::Name(_));
+        let needs_role = !matches!(self.config.role(), RoleSelector::Name(_));
+        let (credentials, token, client, account, role) = futures::try_join!(
+            self.peek::<GetRoleCredentialsResponse>(CacheStage::Credentials, true),
+            self.peek_token(),
+            self.peek::<RegisterClientResponse>(CacheStage::Client, true),
+            self.peek::<ResolvedAccount>(CacheStage::Account, needs_account),
+            self.peek::<ResolvedRole>(CacheStage::Role, needs_role),
+        )?;
+        if let Some(credentials) = credentials {
+            if let Some(account) = &account {
+                subject.account_id = Some(account.account_id.clone());
+            }
+            if let Some(role) = &role {
+                subject.role_name = Some(role.role_name.clone());
+            }
+            self.maybe_refresh_ahead(&credentials, token, account, role);
+            return Ok((credentials, true));
+        }
 
-impl<F, Fut, E> VerificationPrompt for F
-where
-    F: FnOnce(Url) -> Fut + Clone + Send + Sync,
-    Fut: std::future::Future<Output = Result<(), E>> + Send,
-    E: std::error::Error + Send + Sync + 'static,
-{
-    type Future = Fut;
-    type Error = E;
+        let token = match token {
+            Some(token) => token,
+            None => self.access_token(flow_id, client).await?,
+        };
+        let account_id = match account {
+            Some(account) => account.account_id,
+            None => self.account_id(&token).await?,
+        };
+        subject.account_id = Some(account_id.clone());
+        let role_name = match role {
+            Some(role) => role.role_name,
+            None => self.role_name(&token, &account_id).await?,
+        };
+        subject.role_name = Some(role_name.clone());
+        let credentials = match self
+            .credentials(token.clone(), account_id.clone(), role_name.clone())
+            .await
+        {
+            // The token was revoked server-side, e.g. by signing out of the portal, so get a new
+            // one rather than failing until it expires
+            Err(SsoFlowError::Api(error)) if error.is("UnauthorizedException") => {
+                self.forget_token(&token).await;
+                let client = self.client().await?;
+                let token = self.token(flow_id, client).await?;
+                self.credentials(token, account_id, role_name).await?
+            }
+            result => result?,
+        };
+        Ok((credentials, false))
+    }
+
+    /// Spawn a background refresh if `credentials` are within the refresh-ahead window, and the
+    /// other stages needed to refresh them are cached.
+    fn maybe_refresh_ahead(
+        &self,
+        credentials: &GetRoleCredentialsResponse,
+        token: Option<CreateTokenResponse>,
+        account: Option<ResolvedAccount>,
+        role: Option<ResolvedRole>,
+    ) {
+        let Some(window) = self
+            .refresh_ahead
+            .and_then(|window| chrono::Duration::from_std(window).ok())
+        else {
+            return;
+        };
+        if credentials.expires_at - window > self.clock.now() {
+            return;
+        }
+
+        let account_id = match self.config.account() {
+            AccountSelector::Id(account_id) => Some(account_id),
+            AccountSelector::Name(_) => account.map(|account| account.account_id),
+        };
+        let role_name = match self.config.role() {
+            RoleSelector::Name(role_name) => Some(role_name),
+            _ => role.map(|role| role.role_name),
+        };
+        let (Some(token), Some(account_id), Some(role_name)) = (token, account_id, role_name)
+        else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        if self.background.is_shut_down() || self.refreshing.swap(true, Ordering::AcqRel) {
+            return;
+        }
 
-    fn prompt(self, verification_url: Url) -> Fut {
-        self(verification_url)
+        let cache = Arc::clone(&self.cache);
+        let sso_client = self.sso_client.clone();
+        let refreshing = Arc::clone(&self.refreshing);
+        let audit_log = self.audit_log.clone();
+        let config = Arc::clone(&self.config);
+        let clock = Arc::clone(&self.clock);
+        let mut subject = Subject {
+            account_id: Some(account_id.clone()),
+            role_name: Some(role_name.clone()),
+            cache_writes: Vec::new(),
+        };
+        let flow_id = FlowId::new();
+        let task = flow_id.instrument(async move {
+            // Errors are ignored, since the cached credentials are still valid and the next call to
+            // `authenticate` will try again once they expire
+            let (result, cache_writes) = audit::collect_cache_writes(Box::pin(cache.refresh(
+                CacheStage::Credentials,
+                || {
+                    sso_client.get_role_credentials(GetRoleCredentialsRequest {
+                        access_token: token.access_token,
+                        account_id,
+                        role_name,
+                    })
+                },
+            )))
+            .await;
+            subject.cache_writes = cache_writes;
+            if let (Some(audit_log), Ok(credentials)) = (audit_log, result) {
+                let outcome = Outcome::Refreshed {
+                    expires_at: credentials.expires_at,
+                };
+                audit_log
+                    .record(clock.now(), flow_id, &config, &subject, &outcome)
+                    .await;
+            }
+            refreshing.store(false, Ordering::Release);
+        });
+        if !self
+            .background
+            .spawn(&runtime, shutdown::REFRESH_AHEAD_TASK, task)
+        {
+            self.refreshing.store(false, Ordering::Release);
+        }
     }
-}
 
-/// An error that occurred during the SSO authentication flow.
-#[derive(Debug)]
-pub enum SsoFlowError<P: std::error::Error + Send + Sync + 'static> {
-    /// Indicates that an AWS API call returned an error.
+    async fn peek<T>(
+        &self,
+        stage: CacheStage,
+        needed: bool,
+    ) -> Result<Option<T>, SsoFlowError<V::Error>>
+    where
+        T: cache::Expiry + serde::de::DeserializeOwned,
+    {
+        if !needed {
+            return Ok(None);
+        }
+        self.cache
+            .peek(stage)
+            .await
+            .map_err(|error| SsoFlowError::from_cache(error, |never| match never {}))
+    }
+
+    async fn peek_token(&self) -> Result<Option<CreateTokenResponse>, SsoFlowError<V::Error>> {
+        self.cache
+            .peek(CacheStage::Token)
+            .await
+            .or_else(|error| self.recover_token(error).map(Some))
+            .map_err(|error| SsoFlowError::from_cache(error, |never| match never {}))
+    }
+
+    /// The session shared with other flows for the same start URL, region and endpoint.
+    pub(crate) fn shared_session(&self) -> Arc<SharedSession> {
+        shared::shared_session(
+            &self.config,
+            self.verbatim_start_url,
+            self.transport.config().endpoint_url.as_ref(),
+        )
+    }
+
+    /// Forget an access token the server rejected, so the token stage runs again.
+    pub(crate) async fn forget_token(&self, rejected: &CreateTokenResponse) {
+        self.shared_session()
+            .forget_token(&rejected.access_token)
+            .await;
+        self.cache
+            .remove_if(CacheStage::Token, |token: &CreateTokenResponse| {
+                token.access_token == rejected.access_token
+            })
+            .await;
+    }
+
+    /// Recover from a failure to read or write the token cache with the token this process last
+    /// obtained for the start URL, if it's still fresh.
     ///
-    /// This could be due to invalid configuration caught by the server, or a network issue. The
-    /// error message should be sufficient to aid end-user debugging.
-    Api(SsoApiError),
+    /// This keeps a corrupt or unwritable cache from causing the user to be prompted again.
+    fn recover_token<E>(
+        &self,
+        error: cache::Error<E>,
+    ) -> Result<CreateTokenResponse, cache::Error<E>> {
+        if let cache::Error::Cache { .. } = error {
+            if let Some(token) = self.shared_session().token(&*self.clock) {
+                return Ok(token);
+            }
+        }
+        Err(error)
+    }
 
-    /// Indicates an issue with the token cache(s).
+    /// Seed the cache with an access token obtained outside of this crate.
     ///
-    /// This could be due to insufficient permissions, corrupt data, or an usual OS configuration.
-    /// The error message should be sufficient to aid end-user debugging.
-    Cache(SsoCacheError),
+    /// This is for environments where SSO login is performed centrally, e.g. by a device management
+    /// tool. The token is cached as if the flow had obtained it, so subsequent calls to
+    /// [`authenticate`](Self::authenticate) use it to get credentials without prompting, until it
+    /// expires at `expires_at`. The token must have been issued for the flow's start URL and
+    /// region.
+    ///
+    /// ```no_run
+    /// # async fn example(flow: aws_sso_flow::BoxedSsoFlow, token: String) -> Result<(), Box<dyn std::error::Error>> {
+    /// use chrono::{Duration, Utc};
+    ///
+    /// flow.import_access_token(token, Utc::now() + Duration::hours(8)).await?;
+    /// let credentials = flow.authenticate().await?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the flow has no cache directory for tokens, or the token can't be
+    /// written.
+    pub async fn import_access_token(
+        &self,
+        access_token: impl Into<String>,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), SsoCacheError> {
+        if self.cache.path(CacheStage::Token).is_none() {
+            return Err(SsoCacheError {
+                message: "failed to import access token".to_string(),
+                source: "no cache directory is configured".into(),
+                flow_id: None,
+            });
+        }
+        let token = CreateTokenResponse {
+            access_token: access_token.into(),
+            expires_at,
+        };
+        self.cache
+            .put(CacheStage::Token, &token)
+            .await
+            .map_err(|error| match error {
+                cache::Error::Cache { message, source } => SsoCacheError {
+                    message,
+                    source,
+                    flow_id: None,
+                },
+                cache::Error::Init(never) => match never {},
+            })
+    }
 
-    /// Indicates that an error occurred during the verification prompt.
+    /// Keep a `credential_process`-style credentials file at `path` up to date.
     ///
-    /// See [`VerificationPrompt`] for more information.
-    VerificationPrompt(P),
+    /// If the file holds credentials that are still fresh, they're returned as they are. Otherwise
+    /// credentials are obtained with [`authenticate`](Self::authenticate), which may prompt the
+    /// user, and the file is replaced with them. The file contains the JSON that a
+    /// [`credential_process`][process] is expected to print, including the `Expiration`, so SDKs
+    /// for other languages on the same machine can share credentials with this flow, e.g. with a
+    /// small command that calls this method and prints the file, or with `cat` while this process
+    /// keeps the file fresh:
+    ///
+    /// ```ini
+    /// [profile shared]
+    /// credential_process = cat /home/me/.cache/mytool/credentials.json
+    /// ```
+    ///
+    /// The file is replaced atomically, and on Unix it's only readable by the current user.
+    ///
+    /// [process]: https://docs.aws.amazon.com/sdkref/latest/guide/feature-process-credentials.html
+    ///
+    /// ```no_run
+    /// # async fn example(flow: aws_sso_flow::BoxedSsoFlow) -> Result<(), Box<dyn std::error::Error>> {
+    /// let credentials = flow.sync_credential_process_file("credentials.json").await?;
+    /// print!("{}", credentials.to_credential_process_json());
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if authentication fails, or the file can't be written.
+    pub async fn sync_credential_process_file(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<SessionCredentials, SsoFlowError<V::Error>> {
+        let path = path.as_ref();
+        if let Some(credentials) = process_file::read(path).await {
+            let buffer = chrono::Duration::from_std(cache::CACHE_BUFFER)
+                .unwrap_or_else(|_| chrono::Duration::zero());
+            if credentials.expires_at - buffer > self.clock.now() {
+                return Ok(credentials);
+            }
+        }
 
-    /// Indicates that the verification prompt timed out.
-    VerificationPromptTimeout,
-}
+        let credentials = self.authenticate().await?;
+        process_file::write(path, &credentials)
+            .await
+            .map_err(|error| {
+                SsoFlowError::Cache(SsoCacheError {
+                    message: format!("failed to write credentials file {}", path.display()),
+                    source: error.into(),
+                    flow_id: None,
+                })
+            })?;
+        Ok(credentials)
+    }
 
-impl<P> fmt::Display for SsoFlowError<P>
-where
-    P: std::error::Error + Send + Sync + 'static,
-{
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::Api(error) => write!(f, "SSO authentication failed due to: {error}"),
-            Self::Cache(error) => write!(f, "SSO authentication failed due to: {error}"),
-            Self::VerificationPrompt(error) => {
-                write!(f, "SSO authentication failed during verification: {error}",)
+    /// Run a command with the flow's credentials, keeping them fresh while it runs.
+    ///
+    /// This is like `aws-vault exec` or `aws sso` wrapper scripts: the command is spawned with
+    /// inherited stdio, and its exit status is returned once it exits. Credentials are obtained
+    /// first with [`authenticate`](Self::authenticate), which may prompt the user.
+    ///
+    /// Rather than setting credentials in the command's environment, which can't be updated once
+    /// it's running, the command is given a temporary AWS config file (with `AWS_CONFIG_FILE` and
+    /// `AWS_PROFILE`) whose profile reads credentials from a file with a `credential_process`.
+    /// While the command runs, the file is replaced when credentials are rotated (see
+    /// [`credentials_stream`](Self::credentials_stream)), so long-running commands that use an AWS
+    /// SDK pick up new credentials when theirs expire. Credential environment variables such as
+    /// `AWS_ACCESS_KEY_ID` are removed from the command's environment, so they don't take
+    /// precedence. If the environment doesn't set a region, the profile uses the SSO region.
+    ///
+    /// ```no_run
+    /// # async fn example(flow: aws_sso_flow::BoxedSsoFlow) -> Result<(), Box<dyn std::error::Error>> {
+    /// let status = flow.run_with_credentials("terraform", ["apply"]).await?;
+    /// std::process::exit(status.code().unwrap_or(1));
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if credentials can't be obtained or written, or the command can't be
+    /// spawned. Failures to rotate credentials while the command runs don't stop the command, since
+    /// it can use its current credentials until they expire. They're recorded in the audit log, if
+    /// there is one (see [`SsoFlowBuilder::audit_log`]), and rotation is retried every minute until
+    /// it succeeds or the command exits. With the `tracing` feature, they're also logged as
+    /// warnings. Once the current credentials expire, a retry may prompt the user.
+    #[cfg_attr(docsrs, doc(cfg(feature = "process")))]
+    #[cfg(feature = "process")]
+    pub async fn run_with_credentials<I, S>(
+        &self,
+        program: impl AsRef<std::ffi::OsStr>,
+        args: I,
+    ) -> Result<std::process::ExitStatus, crate::RunError<V::Error>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        use futures::{future::Either, StreamExt};
+
+        let workspace = crate::exec::Workspace::new().map_err(|error| {
+            SsoFlowError::Cache(SsoCacheError {
+                message: "failed to create a directory for the command's credentials".to_string(),
+                source: error.into(),
+                flow_id: None,
+            })
+        })?;
+        let credentials_path = workspace.credentials_path();
+        let setup = async {
+            let credentials = self.authenticate().await?;
+            let written = async {
+                workspace.write_config(&self.config.region).await?;
+                process_file::write(&credentials_path, &credentials).await
+            };
+            written.await.map_err(|error| {
+                SsoFlowError::Cache(SsoCacheError {
+                    message: "failed to write credentials for command".to_string(),
+                    source: error.into(),
+                    flow_id: None,
+                })
+            })?;
+            workspace
+                .command(program, args)
+                .spawn()
+                .map_err(crate::RunError::Command)
+        };
+        let mut child = match setup.await {
+            Ok(child) => child,
+            Err(error) => {
+                workspace.remove();
+                return Err(error);
             }
-            Self::VerificationPromptTimeout => write!(
-                f,
-                "SSO authentication failed: timed out waiting for verification"
-            ),
-        }
+        };
+
+        // The stream starts with the current credentials, which have already been written. If it
+        // fails, or the credentials can't be written, it's restarted after a delay, and the
+        // restarted stream's first credentials are written too.
+        let rotations = || self.credentials_stream(crate::exec::REFRESH_MARGIN);
+        let rotate = async {
+            let mut stream = std::pin::pin!(rotations().skip(1));
+            loop {
+                let error = match stream.next().await {
+                    Some(Ok(credentials)) => {
+                        match process_file::write(&credentials_path, &credentials).await {
+                            Ok(()) => continue,
+                            Err(error) => SsoFlowError::Cache(SsoCacheError {
+                                message: "failed to write credentials for command".to_string(),
+                                source: error.into(),
+                                flow_id: None,
+                            }),
+                        }
+                    }
+                    Some(Err(error)) => error,
+                    None => {
+                        stream.set(rotations().skip(0));
+                        continue;
+                    }
+                };
+                self.record_rotation_failure(&error).await;
+                tokio::time::sleep(crate::exec::RETRY_DELAY).await;
+                stream.set(rotations().skip(0));
+            }
+        };
+        let status =
+            match future::select(std::pin::pin!(child.wait()), std::pin::pin!(rotate)).await {
+                Either::Left((status, _)) => status,
+                // Rotation doesn't stop by itself, but if it did the command would keep running
+                // with its last credentials
+                Either::Right(((), wait)) => wait.await,
+            };
+        workspace.remove();
+        status.map_err(crate::RunError::Command)
     }
-}
 
-impl<P: std::error::Error + Send + Sync + 'static> std::error::Error for SsoFlowError<P> {}
+    /// Record a failure to rotate credentials for [`run_with_credentials`](Self::run_with_credentials).
+    #[cfg(feature = "process")]
+    async fn record_rotation_failure(&self, error: &SsoFlowError<V::Error>) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            flow_id = error.flow_id().map(tracing::field::display),
+            error = %error_chain(error),
+            "failed to rotate credentials for command",
+        );
+        let Some(audit_log) = &self.audit_log else {
+            return;
+        };
+        let outcome = Outcome::Failed {
+            code: error.code(),
+            error: error_chain(error),
+        };
+        audit_log
+            .record(
+                self.clock.now(),
+                error.flow_id().unwrap_or_else(FlowId::new),
+                &self.config,
+                &Subject::new(&self.config),
+                &outcome,
+            )
+            .await;
+    }
 
-/// An API error that occurred during authentication.
-///
-/// This could be due to invalid configuration caught by the server, or a network issue. The error
-/// message should be sufficient to aid end-user debugging.
-#[derive(Debug)]
-pub struct SsoApiError(String);
+    /// A handle for shutting down the flow's background tasks.
+    ///
+    /// The handle covers this flow, its clones, and flows derived from it with
+    /// [`with_config`](Self::with_config). See [`Shutdown`] for details.
+    #[must_use]
+    pub fn shutdown_handle(&self) -> Shutdown {
+        Shutdown::new(Arc::clone(&self.background))
+    }
 
-impl fmt::Display for SsoApiError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "API error when attempting authentication: {}", self.0)
+    /// The SSO configuration used by the flow.
+    ///
+    /// This is the configuration after it was resolved, e.g. from the AWS config file. Its
+    /// [`Display`](SsoConfig#impl-Display-for-SsoConfig) form suits telling users what they're
+    /// about to sign in to:
+    ///
+    /// ```no_run
+    /// # async fn example(flow: aws_sso_flow::BoxedSsoFlow) {
+    /// eprintln!("Using profile config: {}", flow.config());
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn config(&self) -> &SsoConfig {
+        &self.config
     }
-}
 
-/// An error that occurred interacting with the cache during authentication.
-///
-/// This could be due to insufficient permissions, corrupt data, or an usual OS configuration. The
-/// error message should be sufficient to aid end-user debugging.
-#[derive(Debug)]
-pub struct SsoCacheError(String);
+    /// The name of the AWS shared config profile the flow's configuration was loaded from, if any.
+    ///
+    /// See [`SsoConfigSource::profile`](crate::SsoConfigSource::profile). Flows constructed with
+    /// [`with_config`](Self::with_config) have no profile.
+    #[must_use]
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
 
-impl fmt::Display for SsoCacheError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "cache error when attempting authentication: {}", self.0)
+    /// The path of the cache file used for the given `stage`, if any.
+    ///
+    /// This is useful for auditing or backing up the cache. The path is returned regardless of
+    /// whether the file exists. `None` is returned if there's no cache directory, or if the stage
+    /// is only cached in memory (see [`SsoFlowBuilder::secure_defaults`]).
+    #[must_use]
+    pub fn cache_path(&self, stage: CacheStage) -> Option<PathBuf> {
+        self.cache.path(stage)
     }
+
+    /// Start the flow as a [`FlowStepper`], to be advanced one step at a time.
+    ///
+    /// The stepper starts from the first stage that isn't cached. The verification prompt isn't
+    /// called, since showing the verification details is left to the caller. See [`FlowStepper`]
+    /// for an example.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the cache can't be read, or the flow is offline and there are no
+    /// cached credentials.
+    pub async fn stepper(&self) -> Result<FlowStepper<V>, SsoFlowError<V::Error>> {
+        FlowStepper::new(self.clone()).await
+    }
+
+    /// Describe the steps [`authenticate`](Self::authenticate) would take, without taking them.
+    ///
+    /// The cache is inspected to determine which stages would be served from the cache, and which
+    /// would require calling AWS (and, for the token, prompting the user). No network calls are
+    /// made. This is intended for diagnostics, e.g. a `mytool sso doctor` command.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::convert::Infallible;
+    ///
+    /// let flow = aws_sso_flow::SsoFlow::new(|url| async move {
+    ///     println!("Go to {url} to sign in with SSO");
+    ///     Ok::<_, Infallible>(())
+    /// }).await?;
+    ///
+    /// let plan = flow.plan().await?;
+    /// println!("{plan}");
+    /// if plan.requires_prompt() {
+    ///     println!("You will be asked to sign in");
+    /// }
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the cache can't be read.
+    pub async fn plan(&self) -> Result<FlowPlan, SsoFlowError<V::Error>> {
+        let oidc_endpoint = self.oidc_endpoint();
+        let portal_endpoint = self.portal_endpoint();
+
+        let mut stages: Vec<(CacheStage, &'static [&'static str], &str)> = vec![];
+        if !self.offline {
+            stages.push((CacheStage::Client, &["RegisterClient"], &oidc_endpoint));
+            stages.push((
+                CacheStage::Token,
+                &["StartDeviceAuthorization", "CreateToken"],
+                &oidc_endpoint,
+            ));
+            if let AccountSelector::Name(_) = self.config.account() {
+                stages.push((CacheStage::Account, &["ListAccounts"], &portal_endpoint));
+            }
+            if !matches!(self.config.role(), RoleSelector::Name(_)) {
+                stages.push((CacheStage::Role, &["ListAccountRoles"], &portal_endpoint));
+            }
+        }
+        stages.push((
+            CacheStage::Credentials,
+            &["GetRoleCredentials"],
+            &portal_endpoint,
+        ));
+
+        let mut steps = Vec::with_capacity(stages.len());
+        for (stage, operations, endpoint) in stages {
+            let cached = match stage {
+                CacheStage::Client => self.is_cached::<RegisterClientResponse>(stage).await?,
+                CacheStage::Token => self.is_cached::<CreateTokenResponse>(stage).await?,
+                CacheStage::Account => self.is_cached::<ResolvedAccount>(stage).await?,
+                CacheStage::Role => self.is_cached::<ResolvedRole>(stage).await?,
+                CacheStage::Credentials => {
+                    self.is_cached::<GetRoleCredentialsResponse>(stage).await?
+                }
+            };
+            steps.push(PlanStep {
+                stage,
+                cache_path: self.cache.path(stage),
+                cached,
+                operations,
+                endpoint: (!cached && !self.offline).then(|| endpoint.to_string()),
+            });
+        }
+
+        Ok(FlowPlan { steps })
+    }
+
+    /// Describe the flow's effective configuration, for attaching to bug reports.
+    ///
+    /// The snapshot includes where configuration came from, the cache files, API endpoints,
+    /// timeouts and retry policy, and the crate's enabled features. It doesn't include tokens or
+    /// credentials, and the start URL and account are partially masked. It's serializable, e.g. to
+    /// JSON:
+    ///
+    /// ```no_run
+    /// # fn example(flow: aws_sso_flow::BoxedSsoFlow) -> Result<(), Box<dyn std::error::Error>> {
+    /// let snapshot = serde_json::to_string_pretty(&flow.debug_snapshot())?;
+    /// eprintln!("Please attach this to your bug report:\n{snapshot}");
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// Nothing is read from the cache or the network, so this works even if the flow is failing.
+    #[must_use]
+    pub fn debug_snapshot(&self) -> DebugSnapshot {
+        DebugSnapshot {
+            version: env!("CARGO_PKG_VERSION"),
+            features: snapshot::features(),
+            transport: snapshot::transport(),
+            config_source: self.config_source.to_string(),
+            region: self.config.region.to_string(),
+            start_url: snapshot::mask_start_url(&self.config.start_url),
+            account: snapshot::mask_account(&self.config.account()),
+            role: self.config.role().to_string(),
+            oidc_endpoint: self.oidc_endpoint(),
+            portal_endpoint: self.portal_endpoint(),
+            cache: CacheStage::ALL
+                .into_iter()
+                .map(|stage| SnapshotCacheEntry::new(stage, self.cache.path(stage)))
+                .collect(),
+            offline: self.offline,
+            normalize_start_url: !self.verbatim_start_url,
+            api_timeout: self.transport.config().api_timeout,
+            connect_timeout: self.transport.config().connect_timeout,
+            min_poll_interval: self.sso_oidc_client.min_poll_interval,
+            prompt_error_policy: format!("{:?}", self.sso_oidc_client.prompt_error_policy),
+            throttle_max_attempts: sso::THROTTLE_MAX_ATTEMPTS,
+            throttle_base_delay: sso::THROTTLE_BASE_DELAY,
+            refresh_ahead: self.refresh_ahead,
+            audit_log: self
+                .audit_log
+                .as_ref()
+                .map(|audit_log| audit_log.path().to_path_buf()),
+            cli_session: self
+                .cli_session
+                .as_ref()
+                .map(|cli_session| cli_session.path().to_path_buf()),
+        }
+    }
+
+    fn oidc_endpoint(&self) -> String {
+        match &self.transport.config().endpoint_url {
+            Some(url) => url.as_str().trim_end_matches('/').to_string(),
+            None => format!("https://oidc.{}.amazonaws.com", self.config.region),
+        }
+    }
+
+    fn portal_endpoint(&self) -> String {
+        match &self.transport.config().endpoint_url {
+            Some(url) => url.as_str().trim_end_matches('/').to_string(),
+            None => format!("https://portal.sso.{}.amazonaws.com", self.config.region),
+        }
+    }
+
+    async fn is_cached<T>(&self, stage: CacheStage) -> Result<bool, SsoFlowError<V::Error>>
+    where
+        T: cache::Expiry + serde::de::DeserializeOwned,
+    {
+        let value: Option<T> = self
+            .cache
+            .get(stage)
+            .await
+            .map_err(|error| SsoFlowError::from_cache(error, |never| match never {}))?;
+        Ok(value.is_some())
+    }
+
+    /// Check the flow's cache entries, optionally deleting any that are bad.
+    ///
+    /// Each stage's entry is read and parsed, as [`authenticate`](Self::authenticate) would, and
+    /// reported as missing, fresh, expired, unreadable, corrupt, or in an incompatible format (e.g.
+    /// written by a different version of the crate). If `repair` is set, unreadable, corrupt, and
+    /// incompatible entries are deleted, so the flow can replace them rather than failing. No
+    /// network calls are made.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::convert::Infallible;
+    ///
+    /// let flow = aws_sso_flow::SsoFlow::new(|url| async move {
+    ///     println!("Go to {url} to sign in with SSO");
+    ///     Ok::<_, Infallible>(())
+    /// }).await?;
+    ///
+    /// let report = flow.verify_cache(true).await;
+    /// println!("{report}");
+    /// if !report.is_healthy() {
+    ///     println!("Some cache entries couldn't be repaired");
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub async fn verify_cache(&self, repair: bool) -> CacheReport {
+        let mut entries = Vec::new();
+        for stage in [
+            CacheStage::Client,
+            CacheStage::Token,
+            CacheStage::Account,
+            CacheStage::Role,
+            CacheStage::Credentials,
+        ] {
+            let (status, repaired) = match stage {
+                CacheStage::Client => {
+                    self.cache
+                        .verify::<RegisterClientResponse>(stage, repair)
+                        .await
+                }
+                CacheStage::Token => {
+                    self.cache
+                        .verify::<CreateTokenResponse>(stage, repair)
+                        .await
+                }
+                CacheStage::Account => self.cache.verify::<ResolvedAccount>(stage, repair).await,
+                CacheStage::Role => self.cache.verify::<ResolvedRole>(stage, repair).await,
+                CacheStage::Credentials => {
+                    self.cache
+                        .verify::<GetRoleCredentialsResponse>(stage, repair)
+                        .await
+                }
+            };
+            entries.push(CacheEntryReport {
+                stage,
+                path: self.cache.path(stage),
+                status,
+                repaired,
+            });
+        }
+        CacheReport { entries }
+    }
+
+    /// List the AWS accounts available to the user.
+    ///
+    /// Accounts are fetched lazily, a page at a time, so large organisations can be rendered
+    /// incrementally. The SSO access token is obtained first, so the user may be prompted if there's
+    /// no valid token in the cache.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// use std::convert::Infallible;
+    ///
+    /// use futures::TryStreamExt;
+    ///
+    /// let flow = aws_sso_flow::SsoFlow::new(|url| async move {
+    ///     println!("Go to {url} to sign in with SSO");
+    ///     Ok::<_, Infallible>(())
+    /// }).await?;
+    ///
+    /// let mut accounts = std::pin::pin!(flow.accounts());
+    /// while let Some(account) = accounts.try_next().await? {
+    ///     println!("{}", account.account_id);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// The stream yields an error if an access token can't be obtained, or if listing fails.
+    pub fn accounts(
+        &self,
+    ) -> impl Stream<Item = Result<AccountInfo, SsoFlowError<V::Error>>> + Send + '_ {
+        let flow_id = FlowId::new();
+        stream::once(flow_id.instrument(async move {
+            if self.offline {
+                return Err(SsoFlowError::OfflineUnavailable { flow_id: None });
+            }
+            let token = self.access_token(flow_id, None).await?;
+            Ok(self
+                .sso_client
+                .list_accounts(ListAccountsRequest {
+                    access_token: token.access_token,
+                })
+                .map_err(SsoFlowError::Api))
+        }))
+        .try_flatten()
+        .map_err(move |error| error.with_flow_id(flow_id))
+    }
+
+    /// List the roles available to the user in the given account.
+    ///
+    /// Like [`accounts`](Self::accounts), roles are fetched lazily and the user may be prompted if
+    /// there's no valid token in the cache.
+    ///
+    /// # Errors
+    ///
+    /// The stream yields an error if an access token can't be obtained, or if listing fails.
+    pub fn account_roles(
+        &self,
+        account_id: impl Into<AccountId>,
+    ) -> impl Stream<Item = Result<RoleInfo, SsoFlowError<V::Error>>> + Send + '_ {
+        let account_id = account_id.into();
+        let flow_id = FlowId::new();
+        stream::once(flow_id.instrument(async move {
+            if self.offline {
+                return Err(SsoFlowError::OfflineUnavailable { flow_id: None });
+            }
+            let token = self.access_token(flow_id, None).await?;
+            Ok(self
+                .sso_client
+                .list_account_roles(ListAccountRolesRequest {
+                    access_token: token.access_token,
+                    account_id,
+                })
+                .map_err(SsoFlowError::Api))
+        }))
+        .try_flatten()
+        .map_err(move |error| error.with_flow_id(flow_id))
+    }
+
+    pub(crate) async fn client(&self) -> Result<RegisterClientResponse, SsoFlowError<V::Error>> {
+        // Registrations are shared with other flows for the same start URL, since Identity Center
+        // throttles bursts of registrations
+        let shared_session = self.shared_session();
+        self.cache
+            .get_or_init(CacheStage::Client, || {
+                shared_session.client_or_init(&*self.clock, || {
+                    self.sso_oidc_client.register_client(RegisterClientRequest {
+                        client_name: CLIENT_NAME.to_string(),
+                    })
+                })
+            })
+            .await
+            .map_err(|error| SsoFlowError::from_cache(error, SsoFlowError::Api))
+    }
+
+    /// Replace a client registration that the server rejected, e.g. because its secret was
+    /// revoked.
+    pub(crate) async fn reregister_client(
+        &self,
+        rejected: &RegisterClientResponse,
+    ) -> Result<RegisterClientResponse, SsoFlowError<V::Error>> {
+        let shared_session = self.shared_session();
+        shared_session.forget_client(&rejected.client_id).await;
+        self.cache
+            .refresh(CacheStage::Client, || {
+                shared_session.client_or_init(&*self.clock, || {
+                    self.sso_oidc_client.register_client(RegisterClientRequest {
+                        client_name: CLIENT_NAME.to_string(),
+                    })
+                })
+            })
+            .await
+            .map_err(|error| SsoFlowError::from_cache(error, SsoFlowError::Api))
+    }
+
+    /// Get an access token from the cache, the AWS CLI's cache, or by prompting the user.
+    ///
+    /// `client` is the cached client registration, if it's already been read.
+    async fn access_token(
+        &self,
+        flow_id: FlowId,
+        client: Option<RegisterClientResponse>,
+    ) -> Result<CreateTokenResponse, SsoFlowError<V::Error>> {
+        if self.cli_session.is_some() {
+            if let Some(token) = self.silent_access_token().await? {
+                return Ok(token);
+            }
+        }
+
+        let client = match client {
+            Some(client) => client,
+            None => self.client().await?,
+        };
+        self.token(flow_id, client).await
+    }
+
+    /// Get an access token from the cache, another flow, or the AWS CLI's cache, without
+    /// prompting.
+    pub(crate) async fn silent_access_token(
+        &self,
+    ) -> Result<Option<CreateTokenResponse>, SsoFlowError<V::Error>> {
+        let token = self
+            .cache
+            .get(CacheStage::Token)
+            .await
+            .or_else(|error| self.recover_token(error).map(Some))
+            .map_err(|error| SsoFlowError::from_cache(error, |never| match never {}))?;
+        if let Some(token) = token {
+            return Ok(Some(token));
+        }
+        if let Some(token) = self.shared_session().token(&*self.clock) {
+            return Ok(Some(token));
+        }
+
+        let Some(cli_session) = &self.cli_session else {
+            return Ok(None);
+        };
+        let Some(token) = cli_session
+            .token(
+                &self.sso_oidc_client,
+                &self.config,
+                self.verbatim_start_url,
+                self.clock.now(),
+            )
+            .await
+        else {
+            return Ok(None);
+        };
+        self.cache
+            .put(CacheStage::Token, &token)
+            .await
+            .map_err(|error| SsoFlowError::from_cache(error, |never| match never {}))?;
+        Ok(Some(token))
+    }
+
+    /// Obtain an access token with `client`, re-registering once if the registration was rejected.
+    async fn token(
+        &self,
+        flow_id: FlowId,
+        client: RegisterClientResponse,
+    ) -> Result<CreateTokenResponse, SsoFlowError<V::Error>> {
+        match self.token_with_client(flow_id, client.clone()).await {
+            Err(SsoFlowError::Api(error)) if error.is("InvalidClientException") => {
+                let client = self.reregister_client(&client).await?;
+                self.token_with_client(flow_id, client).await
+            }
+            result => result,
+        }
+    }
+
+    async fn token_with_client(
+        &self,
+        flow_id: FlowId,
+        client: RegisterClientResponse,
+    ) -> Result<CreateTokenResponse, SsoFlowError<V::Error>> {
+        // Tokens are shared with other flows for the same start URL, so that only one of them
+        // prompts the user
+        let shared_session = self.shared_session();
+        self.cache
+            .get_or_init(CacheStage::Token, || {
+                shared_session.token_or_init(&*self.clock, || {
+                    self.sso_oidc_client.create_token(
+                        CreateTokenRequest {
+                            client_id: client.client_id,
+                            client_secret: client.client_secret,
+                            config: Arc::clone(&self.config),
+                            flow_id,
+                        },
+                        self.verification_prompt.clone(),
+                    )
+                })
+            })
+            .await
+            .or_else(|error| self.recover_token(error))
+            .map_err(|error| {
+                SsoFlowError::from_cache(error, |error| match error {
+                    CreateTokenError::Api(error) => SsoFlowError::Api(error),
+                    CreateTokenError::VerificationPrompt(error) => {
+                        SsoFlowError::VerificationPrompt(SsoPromptError::new(error))
+                    }
+                    CreateTokenError::VerificationPromptTimeout(error) => {
+                        SsoFlowError::VerificationPromptTimeout(error)
+                    }
+                })
+            })
+    }
+
+    pub(crate) async fn account_id(
+        &self,
+        token: &CreateTokenResponse,
+    ) -> Result<AccountId, SsoFlowError<V::Error>> {
+        let account_name = match self.config.account() {
+            AccountSelector::Id(account_id) => return Ok(account_id),
+            AccountSelector::Name(account_name) => account_name,
+        };
+
+        let account = self
+            .cache
+            .get_or_init(CacheStage::Account, || async {
+                let mut matches: Vec<_> = self
+                    .sso_client
+                    .list_accounts(ListAccountsRequest {
+                        access_token: token.access_token.clone(),
+                    })
+                    .try_filter_map(|account| {
+                        future::ok(
+                            (account.account_name.as_ref() == Some(&account_name))
+                                .then_some(account.account_id),
+                        )
+                    })
+                    .try_collect()
+                    .await
+                    .map_err(SsoFlowError::Api)?;
+                match matches.len() {
+                    1 => Ok(ResolvedAccount {
+                        account_id: matches.remove(0).into(),
+                        expires_at: token.expires_at,
+                    }),
+                    0 => Err(SsoFlowError::Selector(SsoSelectorError {
+                        code: ErrorCode::AccountNotFound,
+                        flow_id: None,
+                        message: format!("no account named {account_name:?}"),
+                    })),
+                    _ => Err(SsoFlowError::Selector(SsoSelectorError {
+                        code: ErrorCode::AccountAmbiguous,
+                        flow_id: None,
+                        message: format!(
+                            "multiple accounts named {account_name:?}: {}",
+                            matches.join(", ")
+                        ),
+                    })),
+                }
+            })
+            .await
+            .map_err(|error| SsoFlowError::from_cache(error, |error| error))?;
+
+        Ok(account.account_id)
+    }
+
+    pub(crate) async fn role_name(
+        &self,
+        token: &CreateTokenResponse,
+        account_id: &AccountId,
+    ) -> Result<RoleName, SsoFlowError<V::Error>> {
+        let selector = match self.config.role() {
+            RoleSelector::Name(role_name) => return Ok(role_name),
+            selector => selector,
+        };
+
+        let role = self
+            .cache
+            .get_or_init(CacheStage::Role, || async {
+                let mut matches: Vec<_> = self
+                    .sso_client
+                    .list_account_roles(ListAccountRolesRequest {
+                        access_token: token.access_token.clone(),
+                        account_id: account_id.clone(),
+                    })
+                    .try_filter_map(|role| {
+                        future::ok(selector.matches(&role.role_name).then_some(role.role_name))
+                    })
+                    .try_collect()
+                    .await
+                    .map_err(SsoFlowError::Api)?;
+                match matches.len() {
+                    1 => Ok(ResolvedRole {
+                        role_name: matches.remove(0).into(),
+                        expires_at: token.expires_at,
+                    }),
+                    0 => Err(SsoFlowError::Selector(SsoSelectorError {
+                        code: ErrorCode::RoleNotFound,
+                        flow_id: None,
+                        message: format!("no role matching {selector} in account {account_id}"),
+                    })),
+                    _ => Err(SsoFlowError::Selector(SsoSelectorError {
+                        code: ErrorCode::RoleAmbiguous,
+                        flow_id: None,
+                        message: format!(
+                            "multiple roles matching {selector} in account {account_id}: {}",
+                            matches.join(", ")
+                        ),
+                    })),
+                }
+            })
+            .await
+            .map_err(|error| SsoFlowError::from_cache(error, |error| error))?;
+
+        Ok(role.role_name)
+    }
+
+    pub(crate) async fn credentials(
+        &self,
+        token: CreateTokenResponse,
+        account_id: AccountId,
+        role_name: RoleName,
+    ) -> Result<GetRoleCredentialsResponse, SsoFlowError<V::Error>> {
+        self.cache
+            .get_or_init(CacheStage::Credentials, || {
+                self.sso_client
+                    .get_role_credentials(GetRoleCredentialsRequest {
+                        access_token: token.access_token,
+                        account_id,
+                        role_name,
+                    })
+            })
+            .await
+            .map_err(|error| SsoFlowError::from_cache(error, SsoFlowError::from_role_credentials))
+    }
+}
+
+impl<V> SsoFlow<V>
+where
+    V: VerificationPrompt + 'static,
+    V::Future: 'static,
+{
+    /// Erase the type of the flow's verification prompt.
+    ///
+    /// See [`BoxedVerificationPrompt`] for more information.
+    #[must_use]
+    pub fn boxed(self) -> BoxedSsoFlow {
+        SsoFlow {
+            cache: self.cache,
+            sso_oidc_client: self.sso_oidc_client,
+            sso_client: self.sso_client,
+            transport: self.transport,
+            config: self.config,
+            verification_prompt: BoxedVerificationPrompt::new(self.verification_prompt),
+            offline: self.offline,
+            clock: self.clock,
+            refresh_ahead: self.refresh_ahead,
+            refreshing: self.refreshing,
+            background: self.background,
+            audit_log: self.audit_log,
+            leases: self.leases,
+            cli_session: self.cli_session,
+            verbatim_start_url: self.verbatim_start_url,
+            config_source: self.config_source,
+            profile: self.profile,
+            #[cfg(feature = "rusoto")]
+            rusoto_credentials: self.rusoto_credentials,
+        }
+    }
+}
+
+/// An account ID resolved from an [`AccountSelector`], cached for the lifetime of the access token.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct ResolvedAccount {
+    account_id: AccountId,
+    expires_at: DateTime<Utc>,
+}
+
+impl cache::Expiry for ResolvedAccount {
+    fn expires_at(&self) -> DateTime<Utc> {
+        self.expires_at
+    }
+}
+
+/// A role name resolved from a [`RoleSelector`], cached for the lifetime of the access token.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct ResolvedRole {
+    role_name: RoleName,
+    expires_at: DateTime<Utc>,
+}
+
+impl cache::Expiry for ResolvedRole {
+    fn expires_at(&self) -> DateTime<Utc> {
+        self.expires_at
+    }
+}
+
+impl<V> fmt::Debug for SsoFlow<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut f = f.debug_struct("SsoFlow");
+        f.field("cache", &self.cache)
+            .field("sso_oidc_client", &self.sso_oidc_client)
+            .field("sso_client", &self.sso_client)
+            .field("transport", &self.transport)
+            .field("config", &self.config)
+            .field("verification_prompt", &"_")
+            .field("offline", &self.offline)
+            .field("clock", &self.clock)
+            .field("refresh_ahead", &self.refresh_ahead)
+            .field("refreshing", &self.refreshing)
+            .field("background", &self.background)
+            .field("audit_log", &self.audit_log)
+            .field("leases", &self.leases)
+            .field("cli_session", &self.cli_session)
+            .field("verbatim_start_url", &self.verbatim_start_url)
+            .field("config_source", &self.config_source)
+            .field("profile", &self.profile);
+        #[cfg(feature = "rusoto")]
+        f.field("rusoto_credentials", &self.rusoto_credentials);
+        f.finish()
+    }
+}
+
+/// An error that occurred during the SSO authentication flow.
+///
+/// New variants may be added in minor versions, so matches need a wildcard arm. Use
+/// [`code`](Self::code) for a stable classification.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SsoFlowError<P: std::error::Error + Send + Sync + 'static> {
+    /// Indicates that an AWS API call returned an error.
+    ///
+    /// This could be due to invalid configuration caught by the server, or a network issue. The
+    /// error message should be sufficient to aid end-user debugging.
+    Api(SsoApiError),
+
+    /// Indicates that the user's access to the account or role was removed.
+    ///
+    /// This happens when `GetRoleCredentials` is forbidden, e.g. because the user was removed from
+    /// the permission set's assignment or their account was disabled. Retrying or signing in again
+    /// won't help, so users should be told to contact their administrator.
+    AccessRevoked(SsoApiError),
+
+    /// Indicates an issue with the token cache(s).
+    ///
+    /// This could be due to insufficient permissions, corrupt data, or an usual OS configuration.
+    /// The error message should be sufficient to aid end-user debugging.
+    Cache(SsoCacheError),
+
+    /// Indicates that an [`AccountSelector`] or [`RoleSelector`] didn't match exactly one account
+    /// or role.
+    Selector(SsoSelectorError),
+
+    /// Indicates that an error occurred during the verification prompt.
+    ///
+    /// See [`VerificationPrompt`] for more information.
+    VerificationPrompt(SsoPromptError<P>),
+
+    /// Indicates that the verification prompt timed out.
+    ///
+    /// The error records how long the flow waited, and when the user code expired.
+    VerificationPromptTimeout(SsoTimeoutError),
+
+    /// Indicates that the flow is offline, and there are no fresh cached credentials.
+    ///
+    /// See [`SsoFlowBuilder::offline`].
+    OfflineUnavailable {
+        /// The ID of the authentication attempt that failed, if known.
+        flow_id: Option<FlowId>,
+    },
 }
+
+/// An [`SsoFlowError`] with a type-erased verification prompt error.
+///
+/// This can be obtained with [`SsoFlowError::boxed`], and is convenient for embedding in
+/// application error types without naming the prompt's error type.
+pub type DynSsoFlowError = SsoFlowError<BoxedPromptError>;
+
+impl<P: std::error::Error + Send + Sync + 'static> SsoFlowError<P> {
+    /// A stable, machine-readable code identifying the cause of the error.
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Api(error) => error.code(),
+            Self::AccessRevoked(_) => ErrorCode::AccessRevoked,
+            Self::Cache(error) => error.code(),
+            Self::Selector(error) => error.code(),
+            Self::VerificationPrompt(_) => ErrorCode::VerificationPrompt,
+            Self::VerificationPromptTimeout(error) => error.code(),
+            Self::OfflineUnavailable { .. } => ErrorCode::OfflineUnavailable,
+        }
+    }
+
+    /// The ID of the authentication attempt that failed, if known.
+    ///
+    /// This is set for errors returned by [`SsoFlow::authenticate`] and the other methods that
+    /// may prompt the user. The audit log (see [`SsoFlowBuilder::audit_log`]) records the same ID
+    /// alongside the error code.
+    #[must_use]
+    pub fn flow_id(&self) -> Option<FlowId> {
+        match self {
+            Self::Api(error) | Self::AccessRevoked(error) => error.flow_id,
+            Self::Cache(error) => error.flow_id,
+            Self::Selector(error) => error.flow_id,
+            Self::VerificationPrompt(error) => error.flow_id,
+            Self::VerificationPromptTimeout(error) => error.flow_id,
+            Self::OfflineUnavailable { flow_id } => *flow_id,
+        }
+    }
+
+    /// Whether authentication may succeed if it's retried, without any user interaction.
+    ///
+    /// This is the case for transient API errors, such as network errors and throttling. Retry
+    /// policies should still back off, and limit the number of attempts.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Api(error) => error.is_retryable(),
+            Self::AccessRevoked(_)
+            | Self::Cache(_)
+            | Self::Selector(_)
+            | Self::VerificationPrompt(_)
+            | Self::VerificationPromptTimeout(_)
+            | Self::OfflineUnavailable { .. } => false,
+        }
+    }
+
+    /// Whether the user needs to sign in interactively for authentication to succeed.
+    ///
+    /// This is the case if the verification prompt failed or timed out, if the user denied
+    /// access, if the SSO session was revoked, or if the flow is offline with no cached
+    /// credentials. A credential provider chain could use this to fall through to another
+    /// provider rather than retrying, or a background job could notify the user.
+    #[must_use]
+    pub fn is_interaction_required(&self) -> bool {
+        match self {
+            Self::Api(error) => error.is_interaction_required(),
+            Self::VerificationPrompt(_)
+            | Self::VerificationPromptTimeout(_)
+            | Self::OfflineUnavailable { .. } => true,
+            Self::AccessRevoked(_) | Self::Cache(_) | Self::Selector(_) => false,
+        }
+    }
+
+    /// Erase the type of the verification prompt error, if any.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aws_sso_flow::{DynSsoFlowError, SessionCredentials, SsoFlow, VerificationPrompt};
+    ///
+    /// async fn authenticate<V: VerificationPrompt>(
+    ///     flow: &SsoFlow<V>,
+    /// ) -> Result<SessionCredentials, DynSsoFlowError> {
+    ///     flow.authenticate().await.map_err(|error| error.boxed())
+    /// }
+    /// ```
+    #[must_use]
+    pub fn boxed(self) -> DynSsoFlowError {
+        match self {
+            Self::Api(error) => SsoFlowError::Api(error),
+            Self::AccessRevoked(error) => SsoFlowError::AccessRevoked(error),
+            Self::Cache(error) => SsoFlowError::Cache(error),
+            Self::Selector(error) => SsoFlowError::Selector(error),
+            Self::VerificationPrompt(error) => SsoFlowError::VerificationPrompt(SsoPromptError {
+                error: BoxedPromptError::new(error.error),
+                flow_id: error.flow_id,
+            }),
+            Self::VerificationPromptTimeout(error) => {
+                SsoFlowError::VerificationPromptTimeout(error)
+            }
+            Self::OfflineUnavailable { flow_id } => SsoFlowError::OfflineUnavailable { flow_id },
+        }
+    }
+}
+
+impl<P: std::error::Error + Send + Sync + 'static> SsoFlowError<P> {
+    pub(crate) fn with_flow_id(mut self, flow_id: FlowId) -> Self {
+        match &mut self {
+            Self::Api(error) | Self::AccessRevoked(error) => error.flow_id = Some(flow_id),
+            Self::Cache(error) => error.flow_id = Some(flow_id),
+            Self::Selector(error) => error.flow_id = Some(flow_id),
+            Self::VerificationPrompt(error) => error.flow_id = Some(flow_id),
+            Self::VerificationPromptTimeout(error) => error.flow_id = Some(flow_id),
+            Self::OfflineUnavailable { flow_id: id } => *id = Some(flow_id),
+        }
+        self
+    }
+
+    /// Classify an error from `GetRoleCredentials`, which is forbidden once access is removed.
+    pub(crate) fn from_role_credentials(error: SsoApiError) -> Self {
+        if error.is_access_revoked() {
+            Self::AccessRevoked(error)
+        } else {
+            Self::Api(error)
+        }
+    }
+
+    pub(crate) fn from_cache<E>(error: cache::Error<E>, init: impl FnOnce(E) -> Self) -> Self {
+        match error {
+            cache::Error::Init(error) => init(error),
+            cache::Error::Cache { message, source } => Self::Cache(SsoCacheError {
+                message,
+                source,
+                flow_id: None,
+            }),
+        }
+    }
+}
+
+impl<P> fmt::Display for SsoFlowError<P>
+where
+    P: std::error::Error + Send + Sync + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Api(_) | Self::Cache(_) | Self::Selector(_) | Self::VerificationPromptTimeout(_) => {
+                write!(f, "SSO authentication failed")
+            }
+            Self::AccessRevoked(_) => write!(
+                f,
+                "SSO authentication failed because your access was removed, contact your administrator"
+            ),
+            Self::VerificationPrompt(_) => {
+                write!(f, "SSO authentication failed during verification")
+            }
+            Self::OfflineUnavailable { .. } => write!(
+                f,
+                "SSO authentication failed: no fresh cached credentials are available offline"
+            ),
+        }
+    }
+}
+
+impl<P: std::error::Error + Send + Sync + 'static> std::error::Error for SsoFlowError<P> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Api(error) | Self::AccessRevoked(error) => Some(error),
+            Self::Cache(error) => Some(error),
+            Self::Selector(error) => Some(error),
+            Self::VerificationPrompt(error) => Some(error),
+            Self::VerificationPromptTimeout(error) => Some(error),
+            Self::OfflineUnavailable { .. } => None,
+        }
+    }
+}
+
+/// `error`'s message followed by the messages of its sources.
+///
+/// This is for reporting errors where only a message is kept, e.g. in the audit log.
+pub(crate) fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        let _ = write!(message, ": {error}");
+        source = error.source();
+    }
+    message
+}
+
+/// An API error that occurred during authentication.
+///
+/// This could be due to invalid configuration caught by the server, or a network issue. The error
+/// message should be sufficient to aid end-user debugging.
+///
+/// If the error came from AWS, the [`request_id`](Self::request_id) and HTTP
+/// [`status`](Self::status) are available, which AWS support will ask for when investigating
+/// issues.
+#[derive(Debug)]
+pub struct SsoApiError {
+    source: BoxError,
+    request_id: Option<String>,
+    status: Option<u16>,
+    aws_code: Option<String>,
+    // Whether the request failed without a usable response, e.g. due to a network error
+    transient: bool,
+    flow_id: Option<FlowId>,
+}
+
+impl SsoApiError {
+    pub(crate) fn new(source: impl Into<BoxError>) -> Self {
+        Self {
+            source: source.into(),
+            request_id: None,
+            status: None,
+            aws_code: None,
+            transient: false,
+            flow_id: None,
+        }
+    }
+
+    pub(crate) fn from_response(
+        request_id: Option<String>,
+        status: Option<u16>,
+        aws_code: Option<String>,
+        source: impl Into<BoxError>,
+    ) -> Self {
+        Self {
+            source: source.into(),
+            request_id,
+            status,
+            aws_code,
+            transient: false,
+            flow_id: None,
+        }
+    }
+
+    /// Mark the error as transient, e.g. because the request failed due to a network error.
+    pub(crate) fn transient(mut self) -> Self {
+        self.transient = true;
+        self
+    }
+
+    /// Whether the error is an AWS error with the given code, e.g. `ExpiredTokenException`.
+    pub(crate) fn is(&self, aws_code: &str) -> bool {
+        self.aws_code.as_deref() == Some(aws_code)
+    }
+
+    /// Whether the error indicates that the user's access was removed by their organisation.
+    ///
+    /// `GetRoleCredentials` fails with `ForbiddenException` once a user is no longer assigned to
+    /// the account or role. [`SsoFlow`] reports this as [`SsoFlowError::AccessRevoked`].
+    #[must_use]
+    pub fn is_access_revoked(&self) -> bool {
+        self.is("ForbiddenException")
+    }
+
+    /// A stable, machine-readable code identifying the cause of the error.
+    #[must_use]
+    #[allow(clippy::unused_self)]
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::Api
+    }
+
+    /// The AWS request ID of the failed request, if a response was received.
+    #[must_use]
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// The HTTP status of the failed request, if a response was received.
+    #[must_use]
+    pub fn status(&self) -> Option<u16> {
+        self.status
+    }
+
+    /// The ID of the authentication attempt that failed, if known.
+    #[must_use]
+    pub fn flow_id(&self) -> Option<FlowId> {
+        self.flow_id
+    }
+
+    /// Whether the request may succeed if it's retried.
+    ///
+    /// This is the case for network errors and timeouts, throttling, and server errors.
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        self.transient
+            || self.is_throttling()
+            || matches!(self.status, Some(500..=599))
+            || matches!(
+                self.aws_code.as_deref(),
+                Some("InternalServerException" | "SlowDownException")
+            )
+    }
+
+    /// Whether the request was throttled, by status (429) or by AWS error code.
+    pub(crate) fn is_throttling(&self) -> bool {
+        self.status == Some(429)
+            || matches!(
+                self.aws_code.as_deref(),
+                Some("TooManyRequestsException" | "ThrottlingException")
+            )
+    }
+
+    /// Whether the user must sign in (again), e.g. because the access token was revoked, the user
+    /// code expired, or the user denied access.
+    fn is_interaction_required(&self) -> bool {
+        matches!(
+            self.aws_code.as_deref(),
+            Some(
+                "UnauthorizedException"
+                    | "ExpiredTokenException"
+                    | "InvalidGrantException"
+                    | "AccessDeniedException"
+            )
+        )
+    }
+}
+
+impl fmt::Display for SsoApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "API error when attempting authentication")?;
+        if let Some(request_id) = &self.request_id {
+            write!(f, " (request ID: {request_id})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SsoApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// An error that occurred interacting with the cache during authentication.
+///
+/// This could be due to insufficient permissions, corrupt data, or an usual OS configuration. The
+/// error message should be sufficient to aid end-user debugging.
+#[derive(Debug)]
+pub struct SsoCacheError {
+    message: String,
+    source: BoxError,
+    flow_id: Option<FlowId>,
+}
+
+impl SsoCacheError {
+    /// A stable, machine-readable code identifying the cause of the error.
+    #[must_use]
+    #[allow(clippy::unused_self)]
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::Cache
+    }
+
+    /// The ID of the authentication attempt that failed, if known.
+    #[must_use]
+    pub fn flow_id(&self) -> Option<FlowId> {
+        self.flow_id
+    }
+}
+
+impl fmt::Display for SsoCacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cache error when attempting authentication: {}",
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for SsoCacheError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// An error that occurred selecting an account or role during authentication.
+///
+/// This occurs when an [`AccountSelector`] or [`RoleSelector`] matches none, or several, of the
+/// available accounts or roles. The error message lists any matching account IDs or roles.
+#[derive(Debug)]
+pub struct SsoSelectorError {
+    code: ErrorCode,
+    message: String,
+    flow_id: Option<FlowId>,
+}
+
+impl SsoSelectorError {
+    /// A stable, machine-readable code identifying the cause of the error.
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    /// The ID of the authentication attempt that failed, if known.
+    #[must_use]
+    pub fn flow_id(&self) -> Option<FlowId> {
+        self.flow_id
+    }
+}
+
+impl fmt::Display for SsoSelectorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.message.fmt(f)
+    }
+}
+
+impl std::error::Error for SsoSelectorError {}
+
+/// An error returned by the verification prompt during authentication.
+///
+/// The prompt's own error is available with [`inner`](Self::inner) or
+/// [`into_inner`](Self::into_inner).
+#[derive(Debug)]
+pub struct SsoPromptError<P> {
+    error: P,
+    flow_id: Option<FlowId>,
+}
+
+impl<P> SsoPromptError<P> {
+    pub(crate) fn new(error: P) -> Self {
+        Self {
+            error,
+            flow_id: None,
+        }
+    }
+
+    /// The error returned by the verification prompt.
+    #[must_use]
+    pub fn inner(&self) -> &P {
+        &self.error
+    }
+
+    /// Take the error returned by the verification prompt.
+    #[must_use]
+    pub fn into_inner(self) -> P {
+        self.error
+    }
+
+    /// The ID of the authentication attempt that failed, if known.
+    #[must_use]
+    pub fn flow_id(&self) -> Option<FlowId> {
+        self.flow_id
+    }
+}
+
+impl<P: fmt::Display> fmt::Display for SsoPromptError<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+// The prompt's error is displayed in place of this one, so it's not also a source
+impl<P: std::error::Error> std::error::Error for SsoPromptError<P> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// The user didn't grant access before the user code expired.
+///
+/// This records how long the flow waited, so applications can explain what happened, e.g. "your
+/// code expired after 10 minutes".
+#[derive(Debug)]
+pub struct SsoTimeoutError {
+    elapsed: Duration,
+    expires_at: DateTime<Utc>,
+    attempts: u32,
+    flow_id: Option<FlowId>,
+}
+
+impl SsoTimeoutError {
+    pub(crate) fn new(elapsed: Duration, expires_at: DateTime<Utc>, attempts: u32) -> Self {
+        Self {
+            elapsed,
+            expires_at,
+            attempts,
+            flow_id: None,
+        }
+    }
+
+    /// A stable, machine-readable code identifying the cause of the error.
+    #[must_use]
+    #[allow(clippy::unused_self)]
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::VerificationPromptTimeout
+    }
+
+    /// How long the flow waited for the user to grant access, from when the user code was issued.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// When the user code expired, according to the server.
+    #[must_use]
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.expires_at
+    }
+
+    /// How many times the flow polled for an access token.
+    #[must_use]
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// The ID of the authentication attempt that failed, if known.
+    #[must_use]
+    pub fn flow_id(&self) -> Option<FlowId> {
+        self.flow_id
+    }
+}
+
+impl fmt::Display for SsoTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "timed out waiting for verification; the code expired after {}s ({} attempts)",
+            self.elapsed.as_secs(),
+            self.attempts
+        )
+    }
+}
+
+impl std::error::Error for SsoTimeoutError {}
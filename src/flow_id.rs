@@ -0,0 +1,64 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// An identifier for a single authentication attempt.
+///
+/// A new ID is generated for each call to [`SsoFlow::authenticate`](crate::SsoFlow::authenticate)
+/// (and the other methods that may prompt the user), and is included in:
+///
+/// - The [`PromptContext`](crate::PromptContext), so prompt UIs can tag their own logs.
+/// - Audit log events (see [`SsoFlowBuilder::audit_log`](crate::SsoFlowBuilder::audit_log)), as
+///   `flow_id`.
+/// - Errors, see [`SsoFlowError::flow_id`](crate::SsoFlowError::flow_id).
+/// - With the `tracing` feature, an `sso_flow` span with a `flow_id` field, which covers the
+///   attempt's API calls and prompt.
+///
+/// This allows logs from different subsystems to be correlated to one attempt. IDs are random
+/// 64-bit values, displayed as 16 hex digits.
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
+pub struct FlowId(u64);
+
+impl FlowId {
+    pub(crate) fn new() -> Self {
+        // `RandomState` is randomly seeded per process, and the counter keeps IDs unique within the
+        // process
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        Self(hasher.finish())
+    }
+
+    /// The ID as a number.
+    #[must_use]
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// Run `future` in an `sso_flow` span recording the ID, if the `tracing` feature is enabled.
+    #[cfg_attr(not(feature = "tracing"), allow(clippy::unused_self))]
+    pub(crate) fn instrument<F: Future>(self, future: F) -> impl Future<Output = F::Output> {
+        #[cfg(feature = "tracing")]
+        let future = tracing::Instrument::instrument(
+            future,
+            tracing::info_span!("sso_flow", flow_id = %self),
+        );
+        future
+    }
+}
+
+impl fmt::Debug for FlowId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FlowId({self})")
+    }
+}
+
+impl fmt::Display for FlowId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
@@ -0,0 +1,21 @@
+// Conversions into `http` types, for custom SigV4 clients.
+
+use http::{
+    header::{HeaderValue, InvalidHeaderValue},
+    HeaderMap,
+};
+
+use crate::SessionCredentials;
+
+impl TryFrom<&SessionCredentials> for HeaderMap {
+    type Error = InvalidHeaderValue;
+
+    fn try_from(credentials: &SessionCredentials) -> Result<Self, Self::Error> {
+        let mut session_token = HeaderValue::from_str(&credentials.session_token)?;
+        session_token.set_sensitive(true);
+
+        let mut headers = Self::new();
+        headers.insert("x-amz-security-token", session_token);
+        Ok(headers)
+    }
+}
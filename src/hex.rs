@@ -0,0 +1,10 @@
+// Lowercase hex encoding, for hashes in file names, fingerprints and signatures.
+
+use std::fmt::Write;
+
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
@@ -0,0 +1,117 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{SsoConfig, SsoFlow, VerificationPrompt};
+
+/// Many SSO identities in one process, each with its own [`SsoFlow`] and a label to look it up by.
+///
+/// This suits services that act as several identities, e.g. assuming a different role for each
+/// customer. Identities are added with an [`SsoConfig`], and their flows are derived from a
+/// template flow with [`SsoFlow::with_config`], so they share its options, verification prompt,
+/// clients, and [`shutdown_handle`](SsoFlow::shutdown_handle).
+///
+/// Identities under the same start URL and region also share a client registration and access
+/// token (see [`SsoFlow`]), so the user is prompted once for all of them rather than per identity.
+///
+/// With the `aws-sdk` feature, `provider` gives an aws-sdk credentials provider for each identity.
+///
+/// ```no_run
+/// # async fn example(flow: aws_sso_flow::BoxedSsoFlow) -> Result<(), Box<dyn std::error::Error>> {
+/// use aws_sso_flow::{IdentityManager, SsoConfig};
+///
+/// let identities = IdentityManager::new(flow.clone());
+/// identities.insert("acme", SsoConfig {
+///     account_id: "012345678910".into(),
+///     ..flow.config().clone()
+/// });
+/// identities.insert("globex", SsoConfig {
+///     account_id: "109876543210".into(),
+///     ..flow.config().clone()
+/// });
+///
+/// let credentials = identities.get("acme").unwrap().authenticate().await?;
+/// # Ok(()) }
+/// ```
+///
+/// `IdentityManager` is cheap to clone, and clones share the same identities.
+pub struct IdentityManager<V> {
+    template: SsoFlow<V>,
+    identities: Arc<Mutex<BTreeMap<String, SsoFlow<V>>>>,
+}
+
+impl<V: VerificationPrompt> IdentityManager<V> {
+    /// Construct a manager with no identities, whose flows are derived from `template`.
+    ///
+    /// The template's own configuration isn't an identity, unless it's also inserted.
+    #[must_use]
+    pub fn new(template: SsoFlow<V>) -> Self {
+        Self {
+            template,
+            identities: Arc::default(),
+        }
+    }
+
+    /// Add an identity with `config`, returning its flow.
+    ///
+    /// If there's already an identity with `label`, it's replaced.
+    pub fn insert(&self, label: impl Into<String>, config: SsoConfig) -> SsoFlow<V> {
+        let flow = self.template.with_config(config);
+        self.identities().insert(label.into(), flow.clone());
+        flow
+    }
+
+    /// The flow for the identity with `label`, if there is one.
+    #[must_use]
+    pub fn get(&self, label: &str) -> Option<SsoFlow<V>> {
+        self.identities().get(label).cloned()
+    }
+
+    /// Remove the identity with `label`, returning its flow if there was one.
+    pub fn remove(&self, label: &str) -> Option<SsoFlow<V>> {
+        self.identities().remove(label)
+    }
+
+    /// The labels of the identities, in order.
+    #[must_use]
+    pub fn labels(&self) -> Vec<String> {
+        self.identities().keys().cloned().collect()
+    }
+
+    /// The number of identities.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.identities().len()
+    }
+
+    /// Whether there are no identities.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.identities().is_empty()
+    }
+
+    fn identities(&self) -> MutexGuard<'_, BTreeMap<String, SsoFlow<V>>> {
+        self.identities.lock().expect("poisoned")
+    }
+}
+
+impl<V: Clone> Clone for IdentityManager<V> {
+    fn clone(&self) -> Self {
+        Self {
+            template: self.template.clone(),
+            identities: Arc::clone(&self.identities),
+        }
+    }
+}
+
+impl<V> fmt::Debug for IdentityManager<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let identities = self.identities.lock().expect("poisoned");
+        f.debug_struct("IdentityManager")
+            .field("template", &self.template)
+            .field("identities", &identities.keys())
+            .finish()
+    }
+}
@@ -0,0 +1,193 @@
+// Newtypes for account IDs and role names, so they can't be confused with each other (or with
+// other strings) in signatures.
+//
+// Both hash and serialize the same as a bare `String`, keeping cache keys and cache files stable.
+
+use std::{borrow::Borrow, fmt, str::FromStr};
+
+/// An AWS account ID.
+///
+/// Account IDs can be constructed without validation using [`new`](Self::new) (or [`From`]), or
+/// parsed with validation using [`FromStr`]:
+///
+/// ```
+/// use aws_sso_flow::AccountId;
+///
+/// let account_id: AccountId = "012345678910".parse().unwrap();
+/// assert_eq!(account_id.as_str(), "012345678910");
+///
+/// assert!("12345".parse::<AccountId>().is_err());
+/// ```
+#[derive(Clone, Eq, Hash, Ord, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+pub struct AccountId(String);
+
+impl AccountId {
+    /// Construct an account ID without validation.
+    pub fn new(account_id: impl Into<String>) -> Self {
+        Self(account_id.into())
+    }
+
+    /// The account ID as a string.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for AccountId {
+    type Err = ParseAccountIdError;
+
+    /// Parse an account ID, which must be exactly 12 ASCII digits.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() == 12 && s.bytes().all(|b| b.is_ascii_digit()) {
+            Ok(Self::new(s))
+        } else {
+            Err(ParseAccountIdError {
+                input: s.to_string(),
+            })
+        }
+    }
+}
+
+/// An AWS IAM Identity Center role name, i.e. the name of a permission set.
+///
+/// Role names can be constructed without validation using [`new`](Self::new) (or [`From`]), or
+/// parsed with validation using [`FromStr`]:
+///
+/// ```
+/// use aws_sso_flow::RoleName;
+///
+/// let role_name: RoleName = "PowerUser".parse().unwrap();
+/// assert_eq!(role_name.as_str(), "PowerUser");
+///
+/// let role_name: RoleName = "Power User".parse().unwrap();
+/// assert_eq!(role_name.as_str(), "Power User");
+///
+/// assert!("".parse::<RoleName>().is_err());
+/// assert!(" PowerUser".parse::<RoleName>().is_err());
+/// ```
+#[derive(Clone, Eq, Hash, Ord, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+pub struct RoleName(String);
+
+impl RoleName {
+    /// Construct a role name without validation.
+    pub fn new(role_name: impl Into<String>) -> Self {
+        Self(role_name.into())
+    }
+
+    /// The role name as a string.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for RoleName {
+    type Err = ParseRoleNameError;
+
+    /// Parse a role name, which must be non-empty, with no control characters or leading or
+    /// trailing whitespace.
+    ///
+    /// This is looser than for IAM role names, since role names are whatever the portal lists for
+    /// the account, which may include spaces and path prefixes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_empty() && !s.contains(char::is_control) && s.trim() == s {
+            Ok(Self::new(s))
+        } else {
+            Err(ParseRoleNameError {
+                input: s.to_string(),
+            })
+        }
+    }
+}
+
+macro_rules! string_newtype {
+    ($name:ident) => {
+        impl From<String> for $name {
+            fn from(value: String) -> Self {
+                Self(value)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                Self(value.to_string())
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Borrow<str> for $name {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_tuple(stringify!($name)).field(&self.0).finish()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+    };
+}
+
+string_newtype!(AccountId);
+string_newtype!(RoleName);
+
+/// The error returned when parsing an invalid [`AccountId`].
+#[derive(Clone, Debug)]
+pub struct ParseAccountIdError {
+    input: String,
+}
+
+impl fmt::Display for ParseAccountIdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} is not a 12-digit account ID", self.input)
+    }
+}
+
+impl std::error::Error for ParseAccountIdError {}
+
+/// The error returned when parsing an invalid [`RoleName`].
+#[derive(Clone, Debug)]
+pub struct ParseRoleNameError {
+    input: String,
+}
+
+impl fmt::Display for ParseRoleNameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid role name: {:?}", self.input)
+    }
+}
+
+impl std::error::Error for ParseRoleNameError {}
@@ -0,0 +1,208 @@
+//! Integration with Kubernetes exec credential plugins.
+//!
+//! [`ExecCredential`] renders the JSON that `kubectl` and other client-go clients expect from an
+//! [exec credential plugin][plugins], so a command built on this crate can authenticate to a
+//! cluster. With the `eks` feature, `eks_token` generates the token for an EKS cluster from SSO
+//! credentials, like `aws eks get-token`:
+//!
+//! ```no_run
+//! # #[cfg(feature = "eks")]
+//! # async fn example(flow: aws_sso_flow::BoxedSsoFlow) -> Result<(), Box<dyn std::error::Error>> {
+//! use aws_sso_flow::kubernetes;
+//!
+//! let credentials = flow.authenticate().await?;
+//! let exec_credential = kubernetes::eks_token(
+//!     &credentials,
+//!     &flow.config().region,
+//!     "my-cluster",
+//!     chrono::Utc::now(),
+//! );
+//! println!("{}", exec_credential.to_json());
+//! # Ok(()) }
+//! ```
+//!
+//! The command can then be configured as the user's credential plugin in their kubeconfig:
+//!
+//! ```yaml
+//! users:
+//! - name: my-cluster
+//!   user:
+//!     exec:
+//!       apiVersion: client.authentication.k8s.io/v1beta1
+//!       command: mytool
+//!       args: [eks-token, my-cluster]
+//!       interactiveMode: IfAvailable
+//! ```
+//!
+//! [plugins]: https://kubernetes.io/docs/reference/access-authn-authz/authentication/#client-go-credential-plugins
+
+use std::fmt;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+#[cfg(feature = "eks")]
+use crate::{Region, SessionCredentials};
+
+/// A bearer token for a Kubernetes cluster, as returned by an exec credential plugin.
+///
+/// The token is not printed in `Debug` output.
+pub struct ExecCredential {
+    /// The bearer token.
+    pub token: String,
+
+    /// When the token expires, after which clients will run the plugin again.
+    pub expires_at: DateTime<Utc>,
+}
+
+impl ExecCredential {
+    /// Render the credential as a `client.authentication.k8s.io/v1beta1` `ExecCredential`.
+    ///
+    /// This is the JSON an exec credential plugin should print to stdout.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "kind": "ExecCredential",
+            "apiVersion": "client.authentication.k8s.io/v1beta1",
+            "spec": {},
+            "status": {
+                "expirationTimestamp": self.expires_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                "token": self.token,
+            },
+        })
+        .to_string()
+    }
+}
+
+impl fmt::Debug for ExecCredential {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ExecCredential")
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Generate a token for the EKS cluster named `cluster_name`.
+///
+/// The token is a presigned STS `GetCallerIdentity` request in `region`, which EKS uses to
+/// identify the role the credentials belong to, as generated by `aws eks get-token`. No network
+/// calls are made. `now` is the signing time, which should normally be the current time.
+///
+/// Tokens are accepted for 15 minutes after they're signed. The credential's expiry is a minute
+/// earlier than that, or when `credentials` expire if that's sooner, so that clients renew it in
+/// time.
+#[cfg_attr(docsrs, doc(cfg(feature = "eks")))]
+#[cfg(feature = "eks")]
+#[must_use]
+pub fn eks_token(
+    credentials: &SessionCredentials,
+    region: &Region,
+    cluster_name: &str,
+    now: DateTime<Utc>,
+) -> ExecCredential {
+    use base64::Engine;
+
+    let url = eks::presign(credentials, region.as_ref(), cluster_name, now);
+    ExecCredential {
+        token: format!(
+            "k8s-aws-v1.{}",
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(url)
+        ),
+        expires_at: (now + chrono::Duration::minutes(14)).min(credentials.expires_at),
+    }
+}
+
+#[cfg(feature = "eks")]
+mod eks {
+    // SigV4 query-string signing, as described in
+    // https://docs.aws.amazon.com/IAM/latest/UserGuide/create-signed-request.html.
+
+    use std::fmt::Write;
+
+    use chrono::{DateTime, Utc};
+    use hmac::{Hmac, KeyInit, Mac};
+    use sha2::{Digest, Sha256};
+
+    use crate::{hex, SessionCredentials};
+
+    const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+    const SERVICE: &str = "sts";
+    const SIGNED_HEADERS: &str = "host;x-k8s-aws-id";
+    // How long the presigned request is valid for, in seconds. EKS accepts tokens for 15 minutes
+    // regardless, but `aws eks get-token` uses 60.
+    const EXPIRES: u32 = 60;
+
+    /// Presign an STS `GetCallerIdentity` request for `cluster_name`, returning its URL.
+    pub(super) fn presign(
+        credentials: &SessionCredentials,
+        region: &str,
+        cluster_name: &str,
+        now: DateTime<Utc>,
+    ) -> String {
+        let domain = if region.starts_with("cn-") {
+            "amazonaws.com.cn"
+        } else {
+            "amazonaws.com"
+        };
+        let host = format!("sts.{region}.{domain}");
+        let date = now.format("%Y%m%d").to_string();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let scope = format!("{date}/{region}/{SERVICE}/aws4_request");
+
+        // Parameters must be sorted by name
+        let query = [
+            ("Action", "GetCallerIdentity"),
+            ("Version", "2011-06-15"),
+            ("X-Amz-Algorithm", ALGORITHM),
+            (
+                "X-Amz-Credential",
+                &format!("{}/{scope}", credentials.access_key_id),
+            ),
+            ("X-Amz-Date", &timestamp),
+            ("X-Amz-Expires", &EXPIRES.to_string()),
+            ("X-Amz-Security-Token", &credentials.session_token),
+            ("X-Amz-SignedHeaders", SIGNED_HEADERS),
+        ]
+        .iter()
+        .map(|(name, value)| format!("{name}={}", encode(value)))
+        .collect::<Vec<_>>()
+        .join("&");
+
+        let canonical_request = format!(
+            "GET\n/\n{query}\nhost:{host}\nx-k8s-aws-id:{cluster_name}\n\n{SIGNED_HEADERS}\n{}",
+            hex::encode(&Sha256::digest(b""))
+        );
+        let string_to_sign = format!(
+            "{ALGORITHM}\n{timestamp}\n{scope}\n{}",
+            hex::encode(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = [region, SERVICE, "aws4_request"].iter().fold(
+            hmac(
+                format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+                date.as_bytes(),
+            ),
+            |key, part| hmac(&key, part.as_bytes()),
+        );
+        let signature = hex::encode(&hmac(&key, string_to_sign.as_bytes()));
+
+        format!("https://{host}/?{query}&X-Amz-Signature={signature}")
+    }
+
+    fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+        mac.update(data);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    /// Percent-encode everything but unreserved characters, as `SigV4` requires.
+    fn encode(value: &str) -> String {
+        value.bytes().fold(String::new(), |mut encoded, byte| {
+            if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+                encoded.push(char::from(byte));
+            } else {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+            encoded
+        })
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use chrono::{DateTime, Utc};
+use zeroize::Zeroize;
+
+use crate::SessionCredentials;
+
+/// Session credentials leased from an [`SsoFlow`](crate::SsoFlow).
+///
+/// Leases are obtained with [`SsoFlow::lease`](crate::SsoFlow::lease), and dereference to
+/// [`SessionCredentials`]. When a lease is dropped, its secret access key and session token are
+/// zeroized, and it's no longer reported by
+/// [`SsoFlow::active_leases`](crate::SsoFlow::active_leases). This helps long-running applications
+/// bound how long credentials are held in memory.
+///
+/// Only the lease's own copy of the credentials is zeroized. Copies made from it (e.g. when
+/// configuring an SDK client), and credentials in the flow's cache directory, are unaffected.
+pub struct CredentialLease {
+    credentials: SessionCredentials,
+    id: u64,
+    leases: Arc<Leases>,
+}
+
+/// Information about an active [`CredentialLease`].
+///
+/// This deliberately excludes the secret parts of the credentials.
+#[derive(Clone, Debug)]
+pub struct LeaseInfo {
+    /// The access key ID of the leased credentials.
+    pub access_key_id: String,
+
+    /// When the leased credentials expire.
+    pub expires_at: DateTime<Utc>,
+
+    /// When the lease was taken.
+    pub leased_at: DateTime<Utc>,
+}
+
+/// The active leases of a flow, shared by its clones.
+#[derive(Debug, Default)]
+pub(crate) struct Leases {
+    next_id: AtomicU64,
+    active: Mutex<BTreeMap<u64, LeaseInfo>>,
+}
+
+impl Leases {
+    pub(crate) fn lease(
+        self: &Arc<Self>,
+        credentials: SessionCredentials,
+        leased_at: DateTime<Utc>,
+    ) -> CredentialLease {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.active.lock().expect("poisoned").insert(
+            id,
+            LeaseInfo {
+                access_key_id: credentials.access_key_id.clone(),
+                expires_at: credentials.expires_at,
+                leased_at,
+            },
+        );
+        CredentialLease {
+            credentials,
+            id,
+            leases: Arc::clone(self),
+        }
+    }
+
+    /// The active leases, in the order they were taken.
+    pub(crate) fn active(&self) -> Vec<LeaseInfo> {
+        self.active
+            .lock()
+            .expect("poisoned")
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+impl Deref for CredentialLease {
+    type Target = SessionCredentials;
+
+    fn deref(&self) -> &Self::Target {
+        &self.credentials
+    }
+}
+
+impl Drop for CredentialLease {
+    fn drop(&mut self) {
+        self.credentials.secret_access_key.zeroize();
+        self.credentials.session_token.zeroize();
+        if let Ok(mut active) = self.leases.active.lock() {
+            active.remove(&self.id);
+        }
+    }
+}
+
+impl fmt::Debug for CredentialLease {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CredentialLease")
+            .field("credentials", &self.credentials)
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
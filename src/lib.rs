@@ -16,34 +16,110 @@
 //! # Ok(()) }
 //! ```
 
+mod account;
+mod audit;
 #[cfg_attr(docsrs, doc(cfg(feature = "aws-sdk")))]
 #[cfg(feature = "aws-sdk")]
-mod aws_sdk;
+pub mod aws;
 mod builder;
 mod cache;
+mod cache_report;
+mod cli_cache;
+mod clock;
 mod credentials;
+#[cfg_attr(docsrs, doc(cfg(feature = "diagnostics")))]
+#[cfg(feature = "diagnostics")]
+mod diagnostics;
+#[cfg_attr(docsrs, doc(cfg(feature = "doctor")))]
+#[cfg(feature = "doctor")]
+mod doctor;
+mod error_code;
+#[cfg_attr(docsrs, doc(cfg(feature = "process")))]
+#[cfg(feature = "process")]
+mod exec;
+mod fault;
 mod flow;
-mod profile;
+mod flow_id;
+#[cfg(feature = "http")]
+mod headers;
+mod hex;
+mod identities;
+mod ids;
+pub mod kubernetes;
+mod lease;
+pub mod oidc;
+mod paths;
+mod pending;
+mod plan;
+pub mod portal;
+mod process_file;
+pub mod profile;
+mod prompt;
+pub mod prompts;
+mod refresh;
 mod region;
 #[cfg_attr(docsrs, doc(cfg(feature = "rusoto")))]
 #[cfg(feature = "rusoto")]
 mod rusoto;
+mod selector;
+mod shared;
+mod shutdown;
+mod snapshot;
 mod sso;
 mod sso_oidc;
+mod stepper;
+#[cfg_attr(docsrs, doc(cfg(feature = "test-server")))]
+#[cfg(feature = "test-server")]
+pub mod test_server;
+mod timestamp;
+mod tls;
+mod transport;
 
 use std::fmt;
 
 pub use crate::{
-    builder::{SsoConfig, SsoConfigSource, SsoFlowBuilder},
+    account::{AccountInfo, RoleInfo},
+    builder::{SsoConfig, SsoConfigBuilder, SsoConfigError, SsoConfigSource, SsoFlowBuilder},
+    cache::CacheStage,
+    cache_report::{CacheEntryReport, CacheEntryStatus, CacheReport},
+    clock::{Clock, SystemClock},
     credentials::SessionCredentials,
-    flow::{SsoApiError, SsoCacheError, SsoFlow, SsoFlowError, VerificationPrompt},
-    profile::{ProfileSource, SsoProfileError},
-    region::Region,
+    error_code::ErrorCode,
+    flow::{
+        BoxedSsoFlow, DynSsoFlowError, SsoApiError, SsoCacheError, SsoFlow, SsoFlowError,
+        SsoPromptError, SsoSelectorError, SsoTimeoutError,
+    },
+    flow_id::FlowId,
+    identities::IdentityManager,
+    ids::{AccountId, ParseAccountIdError, ParseRoleNameError, RoleName},
+    lease::{CredentialLease, LeaseInfo},
+    pending::{PendingVerification, VerificationStatus},
+    plan::{FlowPlan, PlanStep},
+    profile::{ProfileSource, SsoConfigOverrides, SsoProfileError},
+    prompt::{
+        BoxedPromptError, BoxedVerificationPrompt, PromptContext, PromptErrorPolicy,
+        VerificationPrompt,
+    },
+    prompts::VerificationPromptExt,
+    refresh::RefreshOutcome,
+    region::{ParseRegionError, Region},
+    selector::{AccountSelector, ParseRoleArnError, RoleSelector},
+    shutdown::Shutdown,
+    snapshot::{DebugSnapshot, SnapshotCacheEntry},
+    stepper::{FlowState, FlowStepper, VerificationInfo},
 };
 
+#[cfg(feature = "doctor")]
+pub use crate::doctor::{diagnose, CheckKind, CheckStatus, DiagnosticCheck, DiagnosticReport};
+#[cfg(feature = "process")]
+pub use crate::exec::RunError;
+#[cfg(feature = "test-util")]
+pub use crate::fault::{Fault, FaultPoint, FaultPolicy};
 #[cfg(feature = "rusoto")]
 pub use crate::rusoto::ChainProvider;
 
+pub(crate) type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
 const _: () = assert!(
     const_str::equal!(env!("CARGO_PKG_VERSION_MAJOR"), "0"),
     "client naming scheme needs updated for 1.0"
@@ -63,8 +139,9 @@ const CLIENT_NAME: &str = concat!(
 /// verification URL and granted access before the user code expires (default 10 mins).
 ///
 /// SSO configuration is sourced from AWS shared config (located with `AWS_CONFIG_FILE` and
-/// `AWS_PROFILE`) and intermediate tokens are cached in the user's OS cache directory in
-/// `aws_sso_flow/0.1/*`. The cache format is considered part of the crate's API.
+/// `AWS_PROFILE`) and intermediate tokens are cached in `AWS_SSO_FLOW_CACHE_DIR`, if it's set,
+/// or else in `aws_sso_flow@0.6` in the user's cache directory (`XDG_CACHE_HOME`, if it's set
+/// on Linux). The cache format is considered part of the crate's API.
 ///
 /// For more flexible configuration see [`SsoFlowBuilder`].
 ///
@@ -108,6 +185,17 @@ pub enum SsoError<P: std::error::Error + Send + Sync + 'static> {
     Flow(SsoFlowError<P>),
 }
 
+impl<P: std::error::Error + Send + Sync + 'static> SsoError<P> {
+    /// A stable, machine-readable code identifying the cause of the error.
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Config(error) => error.code(),
+            Self::Flow(error) => error.code(),
+        }
+    }
+}
+
 impl<P: std::error::Error + Send + Sync + 'static> fmt::Display for SsoError<P> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -0,0 +1,292 @@
+//! Low-level SSO OIDC API client.
+//!
+//! [`Client`] makes single calls to the SSO OIDC API, validating the responses in the same way as
+//! [`SsoFlow`](crate::SsoFlow). It's intended for advanced users building custom flows, e.g. with
+//! their own token storage or prompting. Most applications should use [`SsoFlow`](crate::SsoFlow),
+//! which also caches the results, polls for the access token, and prompts the user.
+//!
+//! ```no_run
+//! # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use aws_sso_flow::{oidc, Region};
+//!
+//! let client = oidc::Client::new(Region::EU_WEST_1);
+//! let registration = client.register_client("mytool").await?;
+//! let authorization = client
+//!     .start_device_authorization(&registration, "https://myorg.awsapps.com/start")
+//!     .await?;
+//! eprintln!("Go to {} to sign in with SSO", authorization.verification_url);
+//!
+//! let mut interval = authorization.interval;
+//! let token = loop {
+//!     tokio::time::sleep(interval).await;
+//!     match client.create_token(&registration, &authorization).await {
+//!         Ok(token) => break token,
+//!         Err(oidc::CreateTokenError::AuthorizationPending) => {}
+//!         Err(oidc::CreateTokenError::SlowDown) => interval += std::time::Duration::from_secs(5),
+//!         Err(error) => return Err(error.into()),
+//!     }
+//! };
+//! # Ok(()) }
+//! ```
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+use crate::{
+    fault::Faults,
+    sso_oidc::{self, StartDeviceAuthorizationResponse},
+    transport::{self, Transport},
+    Clock, Region, SsoApiError, SystemClock,
+};
+
+/// A client for the SSO OIDC API in a particular region.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone, Debug)]
+pub struct Client {
+    inner: sso_oidc::Client,
+    clock: Arc<dyn Clock>,
+}
+
+impl Client {
+    /// Construct a client for the SSO OIDC API in `region`.
+    #[must_use]
+    pub fn new(region: Region) -> Self {
+        let transport = Arc::new(Transport::new(transport::Config {
+            region,
+            api_timeout: None,
+            connect_timeout: None,
+            endpoint_url: None,
+            faults: Faults::default(),
+        }));
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        Self {
+            inner: sso_oidc::Client::new(transport, Arc::clone(&clock)),
+            clock,
+        }
+    }
+
+    /// Register a client named `client_name` (`RegisterClient`).
+    ///
+    /// Registrations last for several months, and should be reused until they expire.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the API call fails, or the response is invalid.
+    pub async fn register_client(
+        &self,
+        client_name: &str,
+    ) -> Result<ClientRegistration, SsoApiError> {
+        let res = self
+            .inner
+            .register_client(sso_oidc::RegisterClientRequest {
+                client_name: client_name.to_string(),
+            })
+            .await?;
+        Ok(ClientRegistration {
+            client_id: res.client_id,
+            client_secret: res.client_secret,
+            expires_at: res.client_secret_expires_at,
+        })
+    }
+
+    /// Start signing in to the AWS access portal at `start_url` (`StartDeviceAuthorization`).
+    ///
+    /// The user should be directed to the returned
+    /// [`verification_url`](DeviceAuthorization::verification_url) to grant access, while
+    /// [`create_token`](Self::create_token) is polled.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the API call fails, or the response is invalid.
+    pub async fn start_device_authorization(
+        &self,
+        registration: &ClientRegistration,
+        start_url: &str,
+    ) -> Result<DeviceAuthorization, SsoApiError> {
+        let res = self
+            .inner
+            .start_device_authorization(
+                &registration.client_id,
+                &registration.client_secret,
+                start_url,
+            )
+            .await?;
+        Ok(DeviceAuthorization {
+            expires_at: self.clock.now() + res.expires_in,
+            device_code: res.device_code,
+            user_code: res.user_code,
+            verification_url: res.verification_uri_complete,
+            interval: res.interval,
+        })
+    }
+
+    /// Try to obtain an access token for a device authorization (`CreateToken`).
+    ///
+    /// This makes a single request. Until the user grants access it fails with
+    /// [`CreateTokenError::AuthorizationPending`], so it should be retried every
+    /// [`interval`](DeviceAuthorization::interval).
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the user hasn't granted access yet, the authorization expired or was
+    /// denied, or the API call otherwise fails.
+    pub async fn create_token(
+        &self,
+        registration: &ClientRegistration,
+        authorization: &DeviceAuthorization,
+    ) -> Result<AccessToken, CreateTokenError> {
+        let response = StartDeviceAuthorizationResponse {
+            device_code: authorization.device_code.clone(),
+            expires_in: chrono::Duration::zero(),
+            interval: authorization.interval,
+            user_code: authorization.user_code.clone(),
+            verification_uri_complete: authorization.verification_url.clone(),
+        };
+        let token = self
+            .inner
+            .try_create_token(
+                &registration.client_id,
+                &registration.client_secret,
+                &response,
+            )
+            .await
+            .map_err(|error| {
+                if error.is("AuthorizationPendingException") {
+                    CreateTokenError::AuthorizationPending
+                } else if error.is("SlowDownException") {
+                    CreateTokenError::SlowDown
+                } else if error.is("ExpiredTokenException") {
+                    CreateTokenError::Expired
+                } else if error.is("AccessDeniedException") {
+                    CreateTokenError::AccessDenied
+                } else {
+                    CreateTokenError::Api(error)
+                }
+            })?;
+        Ok(AccessToken {
+            access_token: token.access_token,
+            expires_at: token.expires_at,
+        })
+    }
+}
+
+/// A client registration, returned by [`Client::register_client`].
+///
+/// The client secret is not printed in `Debug` output.
+#[derive(Clone)]
+pub struct ClientRegistration {
+    /// The client ID.
+    pub client_id: String,
+
+    /// The client secret.
+    pub client_secret: String,
+
+    /// When the registration expires.
+    pub expires_at: DateTime<Utc>,
+}
+
+impl fmt::Debug for ClientRegistration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ClientRegistration")
+            .field("client_id", &self.client_id)
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A pending device authorization, returned by [`Client::start_device_authorization`].
+///
+/// The device code is not printed in `Debug` output.
+#[derive(Clone)]
+pub struct DeviceAuthorization {
+    /// The device code, which identifies the authorization when creating a token.
+    pub device_code: String,
+
+    /// The code the user should see when granting access.
+    pub user_code: String,
+
+    /// The URL at which the user grants access, which includes the user code.
+    pub verification_url: Url,
+
+    /// When the authorization expires, after which the user can no longer grant access.
+    pub expires_at: DateTime<Utc>,
+
+    /// How long to wait between attempts to create a token.
+    pub interval: Duration,
+}
+
+impl fmt::Debug for DeviceAuthorization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DeviceAuthorization")
+            .field("user_code", &self.user_code)
+            .field("verification_url", &self.verification_url)
+            .field("expires_at", &self.expires_at)
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An SSO access token, returned by [`Client::create_token`].
+///
+/// The token is not printed in `Debug` output.
+#[derive(Clone)]
+pub struct AccessToken {
+    /// The access token.
+    pub access_token: String,
+
+    /// When the access token expires.
+    pub expires_at: DateTime<Utc>,
+}
+
+impl fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AccessToken")
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An error returned by [`Client::create_token`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CreateTokenError {
+    /// The user hasn't granted access yet, so the request should be retried after the
+    /// authorization's [`interval`](DeviceAuthorization::interval).
+    AuthorizationPending,
+
+    /// Requests are being made too often, so the interval should be increased.
+    SlowDown,
+
+    /// The device authorization expired before the user granted access.
+    Expired,
+
+    /// The user denied access.
+    AccessDenied,
+
+    /// The API call failed for another reason.
+    Api(SsoApiError),
+}
+
+impl fmt::Display for CreateTokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::AuthorizationPending => write!(f, "the user has not granted access yet"),
+            Self::SlowDown => write!(f, "access tokens were requested too often"),
+            Self::Expired => write!(f, "the device authorization expired"),
+            Self::AccessDenied => write!(f, "the user denied access"),
+            Self::Api(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CreateTokenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Api(error) => error.source(),
+            _ => None,
+        }
+    }
+}
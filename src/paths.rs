@@ -0,0 +1,156 @@
+// Normalising the paths of config and cache files.
+//
+// Windows accepts `/` as a separator and resolves `.` and `..`, except in extended-length
+// (`\\?\`) paths, which are passed to the filesystem verbatim. Such paths come from e.g.
+// `std::fs::canonicalize`, and appending `/.aws/config` to one produces a path that can't be
+// opened. Paths are normalised so that they work whichever form they're given in, including UNC
+// paths (`\\server\share\...`) for roaming profiles, and paths longer than `MAX_PATH`.
+
+use std::path::PathBuf;
+
+/// The length at which Windows paths need the extended-length form.
+#[cfg_attr(not(windows), allow(dead_code))]
+const MAX_PATH: usize = 260;
+
+/// Normalise `path` so it can be used with the filesystem.
+///
+/// This only changes paths on Windows. See [`normalize_windows`].
+pub(crate) fn normalize(path: PathBuf) -> PathBuf {
+    #[cfg(windows)]
+    if let Some(normalized) = path.to_str().map(normalize_windows) {
+        return PathBuf::from(normalized);
+    }
+    path
+}
+
+/// Normalise an absolute Windows path.
+///
+/// Separators become `\`, and `.`, `..`, and empty components are resolved, as Windows does for
+/// paths that aren't verbatim. Paths that are too long for `MAX_PATH` are converted to the
+/// extended-length form, e.g. `\\?\C:\...` or `\\?\UNC\server\share\...`. Relative paths and device
+/// paths (`\\.\`) only have their separators converted, or are unchanged.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn normalize_windows(path: &str) -> String {
+    if path.starts_with(r"\\.\") || path.starts_with("//./") {
+        return path.to_string();
+    }
+    let path = path.replace('/', r"\");
+
+    let (prefix, rest, verbatim) = if let Some(rest) = path.strip_prefix(r"\\?\UNC\") {
+        let (share, rest) = split_components(rest, 2);
+        (format!(r"\\?\UNC\{share}"), rest, true)
+    } else if let Some(rest) = path.strip_prefix(r"\\?\") {
+        let (disk, rest) = split_components(rest, 1);
+        (format!(r"\\?\{disk}"), rest, true)
+    } else if let Some(rest) = path.strip_prefix(r"\\") {
+        let (share, rest) = split_components(rest, 2);
+        (format!(r"\\{share}"), rest, false)
+    } else if is_disk_absolute(&path) {
+        (path[..2].to_string(), &path[3..], false)
+    } else {
+        return path;
+    };
+
+    let mut components: Vec<&str> = Vec::new();
+    for component in rest.split('\\') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    let normalized = format!(r"{prefix}\{}", components.join(r"\"));
+
+    if verbatim || normalized.len() < MAX_PATH {
+        normalized
+    } else if let Some(unc) = normalized.strip_prefix(r"\\") {
+        format!(r"\\?\UNC\{unc}")
+    } else {
+        format!(r"\\?\{normalized}")
+    }
+}
+
+/// Split the first `n` components from `path`, returning them and the rest of the path.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn split_components(path: &str, n: usize) -> (&str, &str) {
+    match path.match_indices('\\').nth(n - 1) {
+        Some((end, _)) => (&path[..end], &path[end + 1..]),
+        None => (path, ""),
+    }
+}
+
+/// Whether `path` is an absolute path on a disk, e.g. `C:\`.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn is_disk_absolute(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_paths() {
+        assert_eq!(
+            normalize_windows(r"C:\Users\me/.aws/config"),
+            r"C:\Users\me\.aws\config"
+        );
+        assert_eq!(
+            normalize_windows(r"C:\Users\me\.\AppData\..\.aws\\config"),
+            r"C:\Users\me\.aws\config"
+        );
+        assert_eq!(normalize_windows(r"C:\"), r"C:\");
+    }
+
+    #[test]
+    fn unc_paths() {
+        assert_eq!(
+            normalize_windows(r"\\fs01\profiles$\me/.aws/config"),
+            r"\\fs01\profiles$\me\.aws\config"
+        );
+        assert_eq!(
+            normalize_windows("//fs01/profiles/me/.aws/config"),
+            r"\\fs01\profiles\me\.aws\config"
+        );
+        // `..` can't leave the share
+        assert_eq!(
+            normalize_windows(r"\\fs01\profiles\..\..\config"),
+            r"\\fs01\profiles\config"
+        );
+    }
+
+    #[test]
+    fn verbatim_paths() {
+        assert_eq!(
+            normalize_windows(r"\\?\C:\Users\me/.aws/./config"),
+            r"\\?\C:\Users\me\.aws\config"
+        );
+        assert_eq!(
+            normalize_windows(r"\\?\UNC\fs01\profiles\me\cache/../.aws/config"),
+            r"\\?\UNC\fs01\profiles\me\.aws\config"
+        );
+    }
+
+    #[test]
+    fn long_paths() {
+        let long = "a".repeat(MAX_PATH);
+        assert_eq!(
+            normalize_windows(&format!(r"C:\{long}\config")),
+            format!(r"\\?\C:\{long}\config")
+        );
+        assert_eq!(
+            normalize_windows(&format!(r"\\fs01\profiles\{long}/config")),
+            format!(r"\\?\UNC\fs01\profiles\{long}\config")
+        );
+    }
+
+    #[test]
+    fn other_paths() {
+        assert_eq!(normalize_windows(r"\\.\pipe\config"), r"\\.\pipe\config");
+        assert_eq!(normalize_windows(".aws/config"), r".aws\config");
+        assert_eq!(normalize_windows("C:config"), "C:config");
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::watch;
+
+/// A device authorization that's waiting for the user to grant access.
+///
+/// This is available to verification prompts from
+/// [`PromptContext::pending`](crate::PromptContext::pending). The flow polls for the user's
+/// approval in the background, so a prompt can return immediately and keep the handle to report
+/// progress, e.g. counting down to the code's expiry and the next check:
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use aws_sso_flow::{PromptContext, VerificationPrompt};
+/// use futures::future::{self, Ready};
+/// use url::Url;
+///
+/// #[derive(Clone)]
+/// struct ProgressPrompt;
+///
+/// impl VerificationPrompt for ProgressPrompt {
+///     type Future = Ready<Result<(), Infallible>>;
+///     type Error = Infallible;
+///
+///     fn prompt(self, verification_url: Url) -> Self::Future {
+///         eprintln!("Go to {verification_url} to sign in with SSO");
+///         future::ok(())
+///     }
+///
+///     fn prompt_with_context(self, context: PromptContext) -> Self::Future {
+///         let pending = context.pending().clone();
+///         eprintln!(
+///             "Go to {} and check the code is {}",
+///             context.verification_url(),
+///             pending.user_code(),
+///         );
+///         tokio::spawn(async move {
+///             let mut ticks = tokio::time::interval(std::time::Duration::from_secs(1));
+///             loop {
+///                 tokio::select! {
+///                     status = pending.completed() => {
+///                         eprintln!("Verification {status:?}");
+///                         break;
+///                     }
+///                     _ = ticks.tick() => {
+///                         let now = chrono::Utc::now();
+///                         let remaining = pending.expires_at() - now;
+///                         match pending.next_poll_at() {
+///                             Some(next) => eprint!(
+///                                 "\r{}s remaining, checking again in {}s",
+///                                 remaining.num_seconds(),
+///                                 (next - now).num_seconds(),
+///                             ),
+///                             None => eprint!("\r{}s remaining, checking", remaining.num_seconds()),
+///                         }
+///                     }
+///                 }
+///             }
+///         });
+///         future::ok(())
+///     }
+/// }
+/// ```
+#[derive(Clone)]
+pub struct PendingVerification {
+    shared: Arc<Shared>,
+    status: watch::Receiver<VerificationStatus>,
+}
+
+/// The state of a [`PendingVerification`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum VerificationStatus {
+    /// The flow is still waiting for the user to grant access.
+    Pending,
+
+    /// The user granted access, and the flow obtained an access token.
+    Approved,
+
+    /// The user code expired before the user granted access.
+    Expired,
+
+    /// Polling failed, e.g. because the user denied access or the prompt failed.
+    Failed,
+
+    /// The flow stopped polling before the verification completed, e.g. because the future
+    /// returned by [`SsoFlow::authenticate`](crate::SsoFlow::authenticate) was dropped.
+    Cancelled,
+}
+
+struct Shared {
+    user_code: String,
+    expires_at: DateTime<Utc>,
+    interval: Mutex<Duration>,
+    next_poll_at: Mutex<Option<DateTime<Utc>>>,
+    attempts: AtomicU32,
+    retries: AtomicU32,
+}
+
+/// The polling side of a [`PendingVerification`].
+///
+/// If this is dropped without calling [`finish`](Self::finish), the verification is reported as
+/// [`Cancelled`](VerificationStatus::Cancelled).
+pub(crate) struct Poller {
+    shared: Arc<Shared>,
+    status: watch::Sender<VerificationStatus>,
+}
+
+impl PendingVerification {
+    pub(crate) fn new(
+        user_code: String,
+        expires_at: DateTime<Utc>,
+        interval: Duration,
+    ) -> (Self, Poller) {
+        let shared = Arc::new(Shared {
+            user_code,
+            expires_at,
+            interval: Mutex::new(interval),
+            next_poll_at: Mutex::new(None),
+            attempts: AtomicU32::new(0),
+            retries: AtomicU32::new(0),
+        });
+        let (sender, receiver) = watch::channel(VerificationStatus::Pending);
+        (
+            Self {
+                shared: Arc::clone(&shared),
+                status: receiver,
+            },
+            Poller {
+                shared,
+                status: sender,
+            },
+        )
+    }
+
+    /// The code the user should see when granting access.
+    ///
+    /// This is included in the verification URL, so it only needs to be shown if the user should
+    /// check it matches.
+    #[must_use]
+    pub fn user_code(&self) -> &str {
+        &self.shared.user_code
+    }
+
+    /// When the user code expires, after which the user can no longer grant access.
+    #[must_use]
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.shared.expires_at
+    }
+
+    /// How long the flow waits between polls for an access token.
+    ///
+    /// This increases if the server asks the flow to slow down.
+    #[must_use]
+    pub fn interval(&self) -> Duration {
+        self.shared.interval()
+    }
+
+    /// When the flow will next poll for an access token.
+    ///
+    /// This is `None` while a poll is in progress and once polling has stopped, so a prompt can
+    /// show e.g. "checking again in 4s" between polls and "checking" otherwise.
+    #[must_use]
+    pub fn next_poll_at(&self) -> Option<DateTime<Utc>> {
+        if self.status() == VerificationStatus::Pending {
+            *self.shared.next_poll()
+        } else {
+            None
+        }
+    }
+
+    /// How many times the flow has polled for an access token so far.
+    #[must_use]
+    pub fn attempts(&self) -> u32 {
+        self.shared.attempts.load(Ordering::Relaxed)
+    }
+
+    /// How many polls failed with a transient error, e.g. a network error, and were retried.
+    ///
+    /// The flow keeps polling through transient errors until the user code expires, so a prompt
+    /// can use this to warn that connectivity is degraded while the user is still signing in.
+    #[must_use]
+    pub fn retries(&self) -> u32 {
+        self.shared.retries.load(Ordering::Relaxed)
+    }
+
+    /// The current status of the verification.
+    #[must_use]
+    pub fn status(&self) -> VerificationStatus {
+        let status = *self.status.borrow();
+        match status {
+            VerificationStatus::Pending if self.status.has_changed().is_err() => {
+                VerificationStatus::Cancelled
+            }
+            status => status,
+        }
+    }
+
+    /// Wait for the verification to complete, returning its final status.
+    ///
+    /// The returned status is never [`Pending`](VerificationStatus::Pending).
+    pub async fn completed(&self) -> VerificationStatus {
+        let mut status = self.status.clone();
+        loop {
+            let current = *status.borrow_and_update();
+            if current != VerificationStatus::Pending {
+                return current;
+            }
+            if status.changed().await.is_err() {
+                return VerificationStatus::Cancelled;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for PendingVerification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PendingVerification")
+            .field("user_code", &self.shared.user_code)
+            .field("expires_at", &self.shared.expires_at)
+            .field("interval", &self.interval())
+            .field("next_poll_at", &self.next_poll_at())
+            .field("attempts", &self.attempts())
+            .field("retries", &self.retries())
+            .field("status", &self.status())
+            .finish()
+    }
+}
+
+impl Shared {
+    fn interval(&self) -> Duration {
+        *self.interval.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn next_poll(&self) -> MutexGuard<'_, Option<DateTime<Utc>>> {
+        // The value is replaced whole, so a poisoned lock still holds a valid time
+        self.next_poll_at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Poller {
+    /// Record an attempt to obtain the access token.
+    pub(crate) fn attempt(&self) {
+        *self.shared.next_poll() = None;
+        self.shared.attempts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn attempts(&self) -> u32 {
+        self.shared.attempts.load(Ordering::Relaxed)
+    }
+
+    /// Record that an attempt failed with a transient error, and will be retried.
+    pub(crate) fn retry(&self) {
+        self.shared.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Increase the interval, because the server asked the flow to slow down.
+    pub(crate) fn slow_down(&self, increment: Duration) {
+        *self
+            .shared
+            .interval
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) += increment;
+    }
+
+    /// Wait for the interval before the next poll, starting `now`.
+    pub(crate) async fn wait(&self, now: DateTime<Utc>) {
+        let interval = self.shared.interval();
+        *self.shared.next_poll() = chrono::Duration::from_std(interval)
+            .ok()
+            .map(|interval| now + interval);
+        tokio::time::sleep(interval).await;
+    }
+
+    /// When the user code expires.
+    pub(crate) fn expires_at(&self) -> DateTime<Utc> {
+        self.shared.expires_at
+    }
+
+    pub(crate) fn finish(self, status: VerificationStatus) {
+        self.status.send_replace(status);
+    }
+}
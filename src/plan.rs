@@ -0,0 +1,78 @@
+use std::{fmt, path::PathBuf};
+
+use crate::CacheStage;
+
+/// A description of the steps an [`SsoFlow`](crate::SsoFlow) would take to authenticate.
+///
+/// See [`SsoFlow::plan`](crate::SsoFlow::plan).
+#[derive(Clone, Debug)]
+pub struct FlowPlan {
+    /// The steps that would be taken, in order.
+    pub steps: Vec<PlanStep>,
+}
+
+impl FlowPlan {
+    /// Whether the user would be prompted for verification.
+    #[must_use]
+    pub fn requires_prompt(&self) -> bool {
+        self.steps
+            .iter()
+            .any(|step| step.stage == CacheStage::Token && step.endpoint.is_some())
+    }
+
+    /// Whether any network calls would be made.
+    #[must_use]
+    pub fn requires_network(&self) -> bool {
+        self.steps.iter().any(|step| step.endpoint.is_some())
+    }
+}
+
+impl fmt::Display for FlowPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}. {step}", i + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single step of a [`FlowPlan`].
+#[derive(Clone, Debug)]
+pub struct PlanStep {
+    /// The stage of the flow.
+    pub stage: CacheStage,
+
+    /// The path of the stage's cache file, if any.
+    pub cache_path: Option<PathBuf>,
+
+    /// Whether the stage's result would be served from the cache.
+    pub cached: bool,
+
+    /// The AWS API operations that would be called, if the result isn't cached.
+    pub operations: &'static [&'static str],
+
+    /// The endpoint that would be called, if the result isn't cached.
+    ///
+    /// This is `None` if the result is cached, or if the flow is offline.
+    pub endpoint: Option<String>,
+}
+
+impl fmt::Display for PlanStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}: ", self.stage)?;
+        match (&self.cache_path, &self.endpoint) {
+            (Some(path), _) if self.cached => write!(f, "cached in {}", path.display()),
+            (_, Some(endpoint)) => {
+                write!(f, "call {} on {endpoint}", self.operations.join(", "))?;
+                if self.stage == CacheStage::Token {
+                    write!(f, " (prompting for verification)")?;
+                }
+                Ok(())
+            }
+            (_, None) => write!(f, "unavailable"),
+        }
+    }
+}
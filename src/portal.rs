@@ -0,0 +1,131 @@
+//! Low-level AWS access portal (SSO) API client.
+//!
+//! [`Client`] makes single calls to the SSO API with an access token, validating the responses in
+//! the same way as [`SsoFlow`](crate::SsoFlow). Together with the [`oidc`](crate::oidc) client it
+//! lets tools build custom flows, e.g. obtaining a token one way and listing roles another. Most
+//! applications should use [`SsoFlow`](crate::SsoFlow), which also obtains and caches the access
+//! token.
+//!
+//! ```no_run
+//! # async fn example(token: aws_sso_flow::oidc::AccessToken) -> Result<(), Box<dyn std::error::Error>> {
+//! use aws_sso_flow::{portal, Region};
+//! use futures::TryStreamExt;
+//!
+//! let client = portal::Client::new(Region::EU_WEST_1);
+//! let mut accounts = client.list_accounts(&token.access_token);
+//! while let Some(account) = accounts.try_next().await? {
+//!     let mut roles = client.list_account_roles(&token.access_token, account.account_id.clone());
+//!     while let Some(role) = roles.try_next().await? {
+//!         println!("{} {}", role.account_id, role.role_name);
+//!     }
+//! }
+//! # Ok(()) }
+//! ```
+
+use std::sync::Arc;
+
+use futures::stream::BoxStream;
+
+use crate::{
+    fault::Faults,
+    sso::{self, GetRoleCredentialsRequest, ListAccountRolesRequest, ListAccountsRequest},
+    transport::{self, Transport},
+    AccountId, AccountInfo, Region, RoleInfo, RoleName, SessionCredentials, SsoApiError,
+};
+
+/// A client for the SSO API in a particular region.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone, Debug)]
+pub struct Client {
+    inner: sso::Client,
+}
+
+impl Client {
+    /// Construct a client for the SSO API in `region`.
+    #[must_use]
+    pub fn new(region: Region) -> Self {
+        let transport = Arc::new(Transport::new(transport::Config {
+            region,
+            api_timeout: None,
+            connect_timeout: None,
+            endpoint_url: None,
+            faults: Faults::default(),
+        }));
+        Self {
+            inner: sso::Client::new(transport),
+        }
+    }
+
+    /// Get credentials for `role_name` in `account_id` (`GetRoleCredentials`).
+    ///
+    /// Throttled requests are retried with backoff, as they are by [`SsoFlow`](crate::SsoFlow).
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the API call fails, or the response is invalid. If the user's access
+    /// was removed, [`SsoApiError::is_access_revoked`] is true.
+    pub async fn get_role_credentials(
+        &self,
+        access_token: &str,
+        account_id: impl Into<AccountId>,
+        role_name: impl Into<RoleName>,
+    ) -> Result<SessionCredentials, SsoApiError> {
+        let res = self
+            .inner
+            .get_role_credentials(GetRoleCredentialsRequest {
+                access_token: access_token.to_string(),
+                account_id: account_id.into(),
+                role_name: role_name.into(),
+            })
+            .await?;
+        Ok(res.into())
+    }
+
+    /// List the accounts available to the user (`ListAccounts`).
+    ///
+    /// Pages are fetched lazily as the stream is consumed.
+    ///
+    /// # Errors
+    ///
+    /// The stream yields an error if an API call fails, or a response is invalid.
+    #[must_use]
+    pub fn list_accounts(
+        &self,
+        access_token: &str,
+    ) -> BoxStream<'static, Result<AccountInfo, SsoApiError>> {
+        self.inner.list_accounts(ListAccountsRequest {
+            access_token: access_token.to_string(),
+        })
+    }
+
+    /// List the roles available to the user in `account_id` (`ListAccountRoles`).
+    ///
+    /// Pages are fetched lazily as the stream is consumed.
+    ///
+    /// # Errors
+    ///
+    /// The stream yields an error if an API call fails, or a response is invalid.
+    #[must_use]
+    pub fn list_account_roles(
+        &self,
+        access_token: &str,
+        account_id: impl Into<AccountId>,
+    ) -> BoxStream<'static, Result<RoleInfo, SsoApiError>> {
+        self.inner.list_account_roles(ListAccountRolesRequest {
+            access_token: access_token.to_string(),
+            account_id: account_id.into(),
+        })
+    }
+
+    /// Sign out of the access portal, invalidating `access_token` (`Logout`).
+    ///
+    /// Role credentials already obtained with the token remain valid until they expire.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the API call fails.
+    pub async fn logout(&self, access_token: &str) -> Result<(), SsoApiError> {
+        self.inner.logout(access_token).await
+    }
+}
@@ -0,0 +1,83 @@
+// Credentials files in the format of `credential_process` output.
+//
+// See https://docs.aws.amazon.com/sdkref/latest/guide/feature-process-credentials.html.
+
+use std::{io, path::Path};
+
+use chrono::{DateTime, Utc};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::SessionCredentials;
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ProcessCredentials {
+    version: u8,
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: String,
+    expiration: DateTime<Utc>,
+}
+
+/// Render `credentials` as `credential_process` output.
+pub(crate) fn to_json(credentials: &SessionCredentials) -> String {
+    serde_json::to_string_pretty(&ProcessCredentials {
+        version: 1,
+        access_key_id: credentials.access_key_id.clone(),
+        secret_access_key: credentials.secret_access_key.clone(),
+        session_token: credentials.session_token.clone(),
+        expiration: credentials.expires_at,
+    })
+    .expect("credentials should serialize")
+}
+
+/// Read the credentials in the file at `path`.
+///
+/// `None` is returned if the file doesn't exist or can't be parsed, e.g. because it was written in
+/// a later version of the format, in which case it should be replaced.
+pub(crate) async fn read(path: &Path) -> Option<SessionCredentials> {
+    let content = fs::read_to_string(path).await.ok()?;
+    let credentials: ProcessCredentials = serde_json::from_str(&content).ok()?;
+    (credentials.version == 1).then_some(SessionCredentials {
+        access_key_id: credentials.access_key_id,
+        secret_access_key: credentials.secret_access_key,
+        session_token: credentials.session_token,
+        expires_at: credentials.expiration,
+    })
+}
+
+/// Replace the file at `path` with `credentials`.
+///
+/// The file is written to a temporary file in the same directory and renamed into place, so
+/// concurrent readers never see a partial file. On Unix, it's only readable by the current user.
+pub(crate) async fn write(path: &Path, credentials: &SessionCredentials) -> io::Result<()> {
+    let (Some(dir), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "path is not a file",
+        ));
+    };
+    if !dir.as_os_str().is_empty() {
+        fs::create_dir_all(dir).await?;
+    }
+
+    let mut temp_name = file_name.to_os_string();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let result = async {
+        let mut file = options.open(&temp_path).await?;
+        file.write_all(to_json(credentials).as_bytes()).await?;
+        file.sync_all().await?;
+        fs::rename(&temp_path, path).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path).await;
+    }
+    result
+}
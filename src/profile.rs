@@ -1,6 +1,12 @@
+//! AWS shared config profiles.
+//!
+//! Most users won't need this module directly; [`ProfileSource`] is used by default to load SSO
+//! configuration. [`parse_config`] is exposed for tools that want to reuse the parsing logic.
+
 use std::{
     env, fmt, io,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
 use futures::future::BoxFuture;
@@ -9,7 +15,9 @@ use tokio::{
     io::AsyncReadExt,
 };
 
-use crate::{SsoConfig, SsoConfigSource};
+use crate::{
+    paths, AccountId, AccountSelector, ErrorCode, Region, RoleName, SsoConfig, SsoConfigSource,
+};
 
 const AWS_CONFIG_FILE: &str = "AWS_CONFIG_FILE";
 const AWS_CONFIG_FILE_DEFAULT: &[&str] = &[".aws", "config"];
@@ -17,11 +25,21 @@ const AWS_CONFIG_FILE_DEFAULT: &[&str] = &[".aws", "config"];
 const AWS_PROFILE: &str = "AWS_PROFILE";
 const AWS_PROFILE_DEFAULT: &str = "default";
 
+const AWS_SSO_ACCOUNT_ID: &str = "AWS_SSO_ACCOUNT_ID";
+const AWS_SSO_ROLE_NAME: &str = "AWS_SSO_ROLE_NAME";
+
 /// A reference to a profile in AWS shared configuration.
 ///
 /// The default profile source uses the `AWS_CONFIG_FILE` and `AWS_PROFILE` environment variables,
 /// but this can be overridden with [`with_config_file`](Self::with_config_file) and
-/// [`with_profile`](Self::with_profile).
+/// [`with_profile`](Self::with_profile). A leading `~` and environment variable references
+/// (`$VAR`, `${VAR}`, or `%VAR%`) in `AWS_CONFIG_FILE` are expanded, leaving references to unset
+/// variables as written. As for the AWS CLI, `AWS_CONFIG_FILE` is a single path, even if it
+/// contains `:` or `;`. Several files can be merged with
+/// [`with_config_files`](Self::with_config_files).
+///
+/// With [`with_env_overrides`](Self::with_env_overrides), the `AWS_SSO_ACCOUNT_ID` and
+/// `AWS_SSO_ROLE_NAME` environment variables can also override the profile's account and role.
 ///
 /// # Example
 ///
@@ -47,8 +65,63 @@ const AWS_PROFILE_DEFAULT: &str = "default";
 #[allow(clippy::module_name_repetitions)]
 #[derive(Clone, Debug, Default)]
 pub struct ProfileSource {
-    config_file: Option<PathBuf>,
+    config_files: Option<Vec<PathBuf>>,
     profile: Option<String>,
+    strict: bool,
+    require_profile_prefix: bool,
+    symlinks: SymlinkPolicy,
+    overrides: SsoConfigOverrides,
+    env_overrides: bool,
+}
+
+/// SSO settings that take precedence over those in a [`ProfileSource`]'s profile.
+///
+/// Fields that are `None` are loaded from the profile as usual. See
+/// [`ProfileSource::with_overrides`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SsoConfigOverrides {
+    /// Overrides `sso_region`.
+    pub region: Option<Region>,
+
+    /// Overrides `sso_start_url`.
+    pub start_url: Option<String>,
+
+    /// Overrides `sso_account_id`, and `sso_account_name` if it's set instead.
+    pub account_id: Option<AccountId>,
+
+    /// Overrides `sso_role_name`.
+    pub role_name: Option<RoleName>,
+}
+
+impl SsoConfigOverrides {
+    /// The names of the overridden profile properties.
+    fn keys(&self) -> Vec<&'static str> {
+        [
+            ("sso_region", self.region.is_some()),
+            ("sso_start_url", self.start_url.is_some()),
+            ("sso_account_id", self.account_id.is_some()),
+            ("sso_role_name", self.role_name.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(key, overridden)| overridden.then_some(key))
+        .collect()
+    }
+}
+
+/// How a [`ProfileSource`] treats a config file that's a symbolic link.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SymlinkPolicy {
+    /// Follow symbolic links, as the AWS SDKs do.
+    #[default]
+    Follow,
+
+    /// Fail with [`ErrorCode::ProfileConfigFile`] if the config file is a symbolic link.
+    ///
+    /// This suits security-sensitive deployments, where a link could redirect the flow to
+    /// configuration that's controlled by someone else. Only the last path component is checked,
+    /// so links in parent directories are still followed.
+    Deny,
 }
 
 impl ProfileSource {
@@ -56,7 +129,28 @@ impl ProfileSource {
     #[must_use]
     pub fn with_config_file(self, path: impl Into<PathBuf>) -> Self {
         Self {
-            config_file: Some(path.into()),
+            config_files: Some(vec![path.into()]),
+            ..self
+        }
+    }
+
+    /// Set the locations of several AWS shared config files, which are merged.
+    ///
+    /// This suits organisations that split configuration across files, e.g. a shared base and
+    /// per-team overrides. Files are merged in order, so properties in later files take precedence
+    /// over those in earlier files, in the same way as a profile defined twice in one file. Every
+    /// file must exist.
+    ///
+    /// ```
+    /// use aws_sso_flow::ProfileSource;
+    ///
+    /// let source = ProfileSource::default()
+    ///     .with_config_files(["/etc/aws/config", "/etc/aws/config.d/team"]);
+    /// ```
+    #[must_use]
+    pub fn with_config_files<P: Into<PathBuf>>(self, paths: impl IntoIterator<Item = P>) -> Self {
+        Self {
+            config_files: Some(paths.into_iter().map(Into::into).collect()),
             ..self
         }
     }
@@ -69,6 +163,133 @@ impl ProfileSource {
             ..self
         }
     }
+
+    /// Set whether duplicate profiles are an error.
+    ///
+    /// If a profile is defined more than once in a config file, by default the sections are
+    /// merged with later properties taking precedence, as the AWS SDKs do. A strict profile source
+    /// instead fails with [`ErrorCode::ProfileDuplicate`], which can help to catch mistakes.
+    #[must_use]
+    pub fn strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+
+    /// Set whether profiles other than `default` must use `[profile name]` section headers.
+    ///
+    /// By default, `[name]` headers are also accepted, though the AWS CLI ignores them in the config
+    /// file. Requiring the prefix matches the AWS CLI exactly, so a profile that works with this
+    /// crate also works with the CLI. The `[default]` header is always accepted.
+    ///
+    /// Either way, if a profile is defined with both forms of header, as the AWS CLI does, only
+    /// the `[profile name]` sections are used. For example, `[profile default]` takes precedence
+    /// over `[default]`.
+    #[must_use]
+    pub fn require_profile_prefix(self, require_profile_prefix: bool) -> Self {
+        Self {
+            require_profile_prefix,
+            ..self
+        }
+    }
+
+    /// Override some of the profile's settings.
+    ///
+    /// This loads most settings from the profile, but takes the others from `overrides`, e.g. to
+    /// choose the role programmatically. Overridden properties needn't be in the profile at all.
+    /// These overrides take precedence over [environment overrides](Self::with_env_overrides).
+    ///
+    /// ```
+    /// use aws_sso_flow::{ProfileSource, SsoConfigOverrides};
+    ///
+    /// let source = ProfileSource::default().with_overrides(SsoConfigOverrides {
+    ///     role_name: Some("ReadOnly".into()),
+    ///     ..SsoConfigOverrides::default()
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_overrides(self, overrides: SsoConfigOverrides) -> Self {
+        Self { overrides, ..self }
+    }
+
+    /// Let the `AWS_SSO_ACCOUNT_ID` and `AWS_SSO_ROLE_NAME` environment variables override the
+    /// profile's `sso_account_id` (or `sso_account_name`) and `sso_role_name`.
+    ///
+    /// This lets a single invocation use a different account or role without editing the config
+    /// file, e.g. `AWS_SSO_ROLE_NAME=Admin my-tool`. Variables that are unset or empty are
+    /// ignored, and invalid values fail with [`ErrorCode::ProfileEnvironment`]. Overrides set with
+    /// [`with_overrides`](Self::with_overrides) take precedence over the environment.
+    ///
+    /// ```
+    /// use aws_sso_flow::ProfileSource;
+    ///
+    /// let source = ProfileSource::default().with_env_overrides();
+    /// ```
+    #[must_use]
+    pub fn with_env_overrides(self) -> Self {
+        Self {
+            env_overrides: true,
+            ..self
+        }
+    }
+
+    /// Set how a config file that's a symbolic link is treated.
+    ///
+    /// By default, symbolic links are followed.
+    #[must_use]
+    pub fn symlinks(self, symlinks: SymlinkPolicy) -> Self {
+        Self { symlinks, ..self }
+    }
+
+    /// The config file paths and profile name this source would load, after consulting the
+    /// environment.
+    ///
+    /// This is useful for diagnostics, e.g. to show users which files and profile are in use. The
+    /// files aren't read, so they may not exist.
+    ///
+    /// On Windows, paths are normalised so that UNC paths (e.g. `\\server\share\.aws\config`,
+    /// as used by roaming profiles) and extended-length (`\\?\`) paths work however they're
+    /// written: `/` becomes `\`, `.` and `..` are resolved, and paths longer than `MAX_PATH` are
+    /// converted to the extended-length form.
+    ///
+    /// ```
+    /// use aws_sso_flow::ProfileSource;
+    ///
+    /// let source = ProfileSource::default()
+    ///     .with_config_file("/etc/aws/config")
+    ///     .with_profile("dev");
+    /// let (paths, profile) = source.resolve_paths()?;
+    /// assert_eq!(paths, [std::path::Path::new("/etc/aws/config")]);
+    /// assert_eq!(profile, "dev");
+    /// # Ok::<_, aws_sso_flow::SsoProfileError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if `AWS_CONFIG_FILE` or `AWS_PROFILE` is invalid, or if the default
+    /// config file is needed but the home directory can't be determined.
+    pub fn resolve_paths(&self) -> Result<(Vec<PathBuf>, String), SsoProfileError> {
+        let paths = self
+            .config_files
+            .clone()
+            .map_or_else(|| get_config_file_from_env().map(|path| vec![path]), Ok)?;
+        let profile = self.profile.clone().map_or_else(get_profile_from_env, Ok)?;
+        Ok((paths.into_iter().map(paths::normalize).collect(), profile))
+    }
+
+    /// The overrides this source would apply, after consulting the environment if
+    /// [`with_env_overrides`](Self::with_env_overrides) is set.
+    fn resolve_overrides(&self) -> Result<SsoConfigOverrides, SsoProfileError> {
+        let mut overrides = self.overrides.clone();
+        if !self.env_overrides {
+            return Ok(overrides);
+        }
+        if overrides.account_id.is_none() {
+            overrides.account_id = get_override_from_env(AWS_SSO_ACCOUNT_ID, "account ID")?;
+        }
+        if overrides.role_name.is_none() {
+            overrides.role_name = get_override_from_env(AWS_SSO_ROLE_NAME, "role name")?;
+        }
+        Ok(overrides)
+    }
 }
 
 impl SsoConfigSource for ProfileSource {
@@ -78,29 +299,100 @@ impl SsoConfigSource for ProfileSource {
 
     fn load(self) -> Self::Future {
         Box::pin(async move {
-            let path = self.config_file.map_or_else(get_config_file_from_env, Ok)?;
-            let profile = self.profile.map_or_else(get_profile_from_env, Ok)?;
-
-            parse_profile(&path, &profile).await
+            let (paths, profile) = self.resolve_paths()?;
+            let overrides = self.resolve_overrides()?;
+            parse_profile(&paths, &profile, overrides, &self).await
         })
     }
+
+    fn describe(&self) -> String {
+        let resolved = self
+            .resolve_paths()
+            .and_then(|(paths, profile)| Ok((paths, profile, self.resolve_overrides()?)));
+        match resolved {
+            Ok((paths, profile, overrides)) => {
+                let paths: Vec<_> = paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                let overridden = overrides.keys();
+                if overridden.is_empty() {
+                    format!("profile {profile} in {}", paths.join(", "))
+                } else {
+                    format!(
+                        "profile {profile} in {}, overriding {}",
+                        paths.join(", "),
+                        overridden.join(", ")
+                    )
+                }
+            }
+            Err(error) => format!("profile ({error})"),
+        }
+    }
+
+    fn profile(&self) -> Option<String> {
+        self.profile.clone().or_else(|| get_profile_from_env().ok())
+    }
 }
 
 /// An error indicating missing or invalid SSO configuration.
 ///
 /// The error message should be sufficient to aid end-user debugging.
 #[derive(Debug)]
-pub struct SsoProfileError(String);
+pub struct SsoProfileError {
+    pub(crate) kind: SsoProfileErrorKind,
+    message: String,
+}
+
+#[derive(Debug)]
+pub(crate) enum SsoProfileErrorKind {
+    Environment,
+    ConfigFile,
+    ProfileMissing { profile: String },
+    ProfileIncomplete { profile: String },
+    ProfileInvalid { profile: String },
+    ProfileDuplicate { profile: String },
+}
 
 impl SsoProfileError {
-    fn new(error: impl Into<String>) -> Self {
-        Self(error.into())
+    fn new(kind: SsoProfileErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// A stable, machine-readable code identifying the cause of the error.
+    #[must_use]
+    pub fn code(&self) -> ErrorCode {
+        match self.kind {
+            SsoProfileErrorKind::Environment => ErrorCode::ProfileEnvironment,
+            SsoProfileErrorKind::ConfigFile => ErrorCode::ProfileConfigFile,
+            SsoProfileErrorKind::ProfileMissing { .. } => ErrorCode::ProfileMissing,
+            SsoProfileErrorKind::ProfileIncomplete { .. } => ErrorCode::ProfileMissingKeys,
+            SsoProfileErrorKind::ProfileInvalid { .. } => ErrorCode::ProfileInvalidValue,
+            SsoProfileErrorKind::ProfileDuplicate { .. } => ErrorCode::ProfileDuplicate,
+        }
+    }
+
+    /// The name of the profile the error relates to, if any.
+    ///
+    /// This is useful for including the profile in localized error messages.
+    #[must_use]
+    pub fn profile(&self) -> Option<&str> {
+        match &self.kind {
+            SsoProfileErrorKind::Environment | SsoProfileErrorKind::ConfigFile => None,
+            SsoProfileErrorKind::ProfileMissing { profile }
+            | SsoProfileErrorKind::ProfileIncomplete { profile }
+            | SsoProfileErrorKind::ProfileInvalid { profile }
+            | SsoProfileErrorKind::ProfileDuplicate { profile } => Some(profile),
+        }
     }
 }
 
 impl fmt::Display for SsoProfileError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.0.fmt(f)
+        self.message.fmt(f)
     }
 }
 
@@ -109,18 +401,24 @@ impl std::error::Error for SsoProfileError {}
 fn get_config_file_from_env() -> Result<PathBuf, SsoProfileError> {
     read_env(AWS_CONFIG_FILE)
         .and_then(|path| {
-            path.map(|path| path.parse::<PathBuf>().map_err(|error| error.to_string()))
+            path.filter(|path| !path.is_empty())
+                .map(|path| expand_path(&path))
                 .transpose()
         })
         .map_err(|error| {
-            SsoProfileError::new(format!(
-                "invalid path in environment variable {AWS_CONFIG_FILE}: {error}",
-            ))
+            SsoProfileError::new(
+                SsoProfileErrorKind::Environment,
+                format!("invalid path in environment variable {AWS_CONFIG_FILE}: {error}"),
+            )
         })?
         .map_or_else(
             || {
-                let mut path = dirs_next::home_dir()
-                    .ok_or_else(|| SsoProfileError::new("could not determine home directory"))?;
+                let mut path = dirs_next::home_dir().ok_or_else(|| {
+                    SsoProfileError::new(
+                        SsoProfileErrorKind::Environment,
+                        "could not determine home directory",
+                    )
+                })?;
                 for segment in AWS_CONFIG_FILE_DEFAULT {
                     path.push(segment);
                 }
@@ -130,115 +428,545 @@ fn get_config_file_from_env() -> Result<PathBuf, SsoProfileError> {
         )
 }
 
+/// Expand a leading `~`, and `$VAR`, `${VAR}`, or `%VAR%` environment variable references.
+///
+/// References to unset variables, and unterminated references (e.g. a lone `%`), are left as
+/// written, since they may be part of the file name.
+fn expand_path(path: &str) -> Result<PathBuf, String> {
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+
+    if let Some(after) = rest.strip_prefix('~') {
+        if after.is_empty() || after.starts_with(['/', '\\']) {
+            let home = dirs_next::home_dir().ok_or("could not determine home directory")?;
+            expanded.push_str(home.to_str().ok_or("home directory is not valid UTF-8")?);
+            rest = after;
+        }
+    }
+
+    while let Some(start) = rest.find(['$', '%']) {
+        expanded.push_str(&rest[..start]);
+        let is_percent = rest[start..].starts_with('%');
+        let after = &rest[start + 1..];
+        let reference = if is_percent {
+            after
+                .find('%')
+                .map(|end| (&after[..end], &after[end + 1..]))
+        } else if let Some(braced) = after.strip_prefix('{') {
+            braced
+                .find('}')
+                .map(|end| (&braced[..end], &braced[end + 1..]))
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            Some(after.split_at(end))
+        };
+
+        if let Some((name, after)) = reference.filter(|(name, _)| !name.is_empty()) {
+            if let Some(value) = read_env(name)? {
+                expanded.push_str(&value);
+                rest = after;
+                continue;
+            }
+            if !is_percent {
+                expanded.push_str(&rest[start..rest.len() - after.len()]);
+                rest = after;
+                continue;
+            }
+        }
+
+        // Not a variable reference, so keep the sigil as-is. This includes unset `%VAR%`
+        // references, since the closing `%` may open another, e.g. in `100%/%VAR%`.
+        expanded.push_str(&rest[start..=start]);
+        rest = after;
+    }
+    expanded.push_str(rest);
+
+    Ok(PathBuf::from(expanded))
+}
+
 fn get_profile_from_env() -> Result<String, SsoProfileError> {
     Ok(read_env(AWS_PROFILE)
         .map_err(|error| {
-            SsoProfileError::new(format!(
-                "invalid profile name in environment variable {AWS_PROFILE}: {error}",
-            ))
+            SsoProfileError::new(
+                SsoProfileErrorKind::Environment,
+                format!("invalid profile name in environment variable {AWS_PROFILE}: {error}"),
+            )
         })?
         .unwrap_or_else(|| AWS_PROFILE_DEFAULT.to_string()))
 }
 
+/// Read and validate an environment variable that overrides a profile property, ignoring empty
+/// values.
+fn get_override_from_env<T: FromStr>(name: &str, what: &str) -> Result<Option<T>, SsoProfileError>
+where
+    T::Err: fmt::Display,
+{
+    let invalid = |error: &dyn fmt::Display| {
+        SsoProfileError::new(
+            SsoProfileErrorKind::Environment,
+            format!("invalid {what} in environment variable {name}: {error}"),
+        )
+    };
+    read_env(name)
+        .map_err(|error| invalid(&error))?
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse().map_err(|error| invalid(&error)))
+        .transpose()
+}
+
 fn read_env(name: &str) -> Result<Option<String>, String> {
     env::var(name).map(Some).or_else(|error| match error {
         env::VarError::NotPresent => Ok(None),
-        env::VarError::NotUnicode(data) => Err(format!("{data:?} contains invalid UTF-8")),
+        env::VarError::NotUnicode(data) => {
+            Err(format!("{} contains invalid UTF-8", data.display()))
+        }
     })
 }
 
-fn parse_profile_name(line: &str) -> Option<&str> {
-    line.trim().strip_suffix(']').and_then(|line| {
-        line.strip_prefix("[profile ")
-            .or_else(|| line.strip_prefix('['))
-    })
+/// A profile parsed from AWS shared config.
+///
+/// See [`parse_config`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Profile {
+    /// The name of the profile.
+    pub name: String,
+
+    /// The profile's properties, in the order they appear.
+    pub properties: Vec<(String, String)>,
 }
 
-async fn parse_profile(path: &Path, profile: &str) -> Result<SsoConfig, SsoProfileError> {
-    let config = read_file(path).await.map_err(|error| {
-        SsoProfileError::new(format!(
-            "unable to read config file {}: {error}",
-            path.display()
-        ))
-    })?;
+impl Profile {
+    /// Get the value of a property.
+    ///
+    /// If the property is set more than once, the last value is returned.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
 
+/// Parse the profiles from the contents of an AWS shared config file.
+///
+/// Profiles are returned in the order they appear, and a profile defined more than once is
+/// returned once per definition. Both `[profile name]` and `[name]` section
+/// headers are recognised, and other sections (e.g. `[sso-session name]`) are skipped. Comments,
+/// blank lines, properties with empty values (and their indented sub-properties), and lines that
+/// aren't `key = value` pairs are ignored. Values are split from keys at the first `=`, and are
+/// otherwise kept as written, including spaces, further `=`s, and quotes. ([`ProfileSource`]
+/// removes quotes around `sso_role_name`.)
+///
+/// # Example
+///
+/// ```
+/// use aws_sso_flow::profile::parse_config;
+///
+/// let profiles = parse_config(
+///     "[default]
+///     region = eu-west-1
+///
+///     [profile dev]
+///     sso_start_url = https://myorg.awsapps.com/start
+///     ",
+/// );
+///
+/// assert_eq!(profiles[0].name, "default");
+/// assert_eq!(profiles[0].get("region"), Some("eu-west-1"));
+/// assert_eq!(profiles[1].name, "dev");
+/// assert_eq!(profiles[1].get("sso_start_url"), Some("https://myorg.awsapps.com/start"));
+/// ```
+#[must_use]
+pub fn parse_config(config: &str) -> Vec<Profile> {
+    parse_sections(config)
+        .into_iter()
+        .map(|(profile, _)| profile)
+        .collect()
+}
+
+/// Like [`parse_config`], but also returns whether each profile's header had the `profile` prefix.
+fn parse_sections(config: &str) -> Vec<(Profile, bool)> {
+    let mut profiles: Vec<(Profile, bool)> = Vec::new();
     let mut in_profile = false;
-    let mut region = None;
-    let mut start_url = None;
-    let mut account_id = None;
-    let mut role_name = None;
+    let mut in_sub_properties = false;
 
     for line in config.lines() {
-        let line = line.trim_matches(' ');
-        if line.is_empty() || line.starts_with('#') {
+        let is_indented = line.starts_with([' ', '\t']);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', ';']) {
             continue;
         }
 
-        if let Some(next_profile) = parse_profile_name(line) {
-            if in_profile {
-                break;
-            }
-            in_profile = next_profile == profile;
-        } else if in_profile {
-            let mut kv = line
-                .splitn(2, '=')
-                .map(|kv| kv.trim_matches(' '))
-                .filter(|kv| !kv.is_empty());
-            match [kv.next(), kv.next()] {
-                [Some("sso_region"), Some(value)] => region = Some(value.to_string()),
-                [Some("sso_start_url"), Some(value)] => start_url = Some(value.to_string()),
-                [Some("sso_account_id"), Some(value)] => {
-                    account_id = Some(value.to_string());
+        if line.starts_with('[') {
+            in_sub_properties = false;
+            in_profile = match parse_profile_name(line) {
+                Some((name, prefixed)) => {
+                    profiles.push((
+                        Profile {
+                            name: name.to_string(),
+                            properties: Vec::new(),
+                        },
+                        prefixed,
+                    ));
+                    true
                 }
-                [Some("sso_role_name"), Some(value)] => role_name = Some(value.to_string()),
-                _ => {}
+                None => false,
+            };
+            continue;
+        }
+
+        if in_sub_properties && is_indented {
+            continue;
+        }
+        in_sub_properties = false;
+
+        let Some((profile, _)) = profiles.last_mut().filter(|_| in_profile) else {
+            continue;
+        };
+        if let Some((key, value)) = line.split_once('=') {
+            let (key, value) = (key.trim(), value.trim());
+            if key.is_empty() {
+                continue;
             }
+            if value.is_empty() {
+                in_sub_properties = true;
+                continue;
+            }
+            profile
+                .properties
+                .push((key.to_string(), value.to_string()));
         }
     }
 
-    if !in_profile {
-        return Err(SsoProfileError::new(format!(
-            "profile {} is not defined in in config file {}",
-            profile,
-            path.display(),
-        )));
+    profiles
+}
+
+/// Parse a section header, returning the profile name and whether it had the `profile` prefix.
+fn parse_profile_name(line: &str) -> Option<(&str, bool)> {
+    let name = line.strip_prefix('[')?.strip_suffix(']')?.trim();
+    match name.strip_prefix("profile ") {
+        Some(name) => Some((name.trim(), true)),
+        None if name.contains(char::is_whitespace) => None,
+        None => Some((name, false)),
     }
+}
+
+async fn parse_profile(
+    paths: &[PathBuf],
+    profile: &str,
+    overrides: SsoConfigOverrides,
+    source: &ProfileSource,
+) -> Result<SsoConfig, SsoProfileError> {
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let config = read_file(path, source.symlinks).await.map_err(|error| {
+            SsoProfileError::new(
+                SsoProfileErrorKind::ConfigFile,
+                format!("unable to read config file {}: {error}", path.display()),
+            )
+        })?;
+        let sections: Vec<_> = parse_sections(&config)
+            .into_iter()
+            .filter(|(p, _)| p.name == profile)
+            .collect();
+        files.push((path, sections));
+    }
+    let properties = merge_sections(paths, files, profile, source)?;
+
+    let get = |key| properties.get(key).map(str::to_string);
+    let region = overrides
+        .region
+        .or_else(|| get("sso_region").map(Region::new));
+    let start_url = overrides.start_url.or_else(|| get("sso_start_url"));
+    let role_name = match overrides.role_name {
+        Some(role_name) => Some(role_name),
+        None => properties
+            .get("sso_role_name")
+            .map(|role_name| parse_value(profile, "sso_role_name", &unquote(role_name)))
+            .transpose()?,
+    };
 
-    match (region, start_url, account_id, role_name) {
-        (Some(region), Some(start_url), Some(account_id), Some(role_name)) => Ok(SsoConfig {
-            region: crate::Region::new(region),
-            start_url,
-            account_id,
-            role_name,
-        }),
-        (region, start_url, account_id, role_name) => {
+    // `sso_account_id` takes precedence, since it doesn't need to be resolved
+    let account = match overrides.account_id {
+        Some(account_id) => Some(AccountSelector::Id(account_id)),
+        None => match properties.get("sso_account_id") {
+            Some(account_id) => Some(AccountSelector::Id(parse_value(
+                profile,
+                "sso_account_id",
+                account_id,
+            )?)),
+            None => get("sso_account_name").map(AccountSelector::Name),
+        },
+    };
+
+    match (region, start_url, account, role_name) {
+        (Some(region), Some(start_url), Some(account), Some(role_name)) => {
+            let (account_id, account_name) = match account {
+                AccountSelector::Id(account_id) => (account_id, None),
+                AccountSelector::Name(account_name) => (AccountId::new(""), Some(account_name)),
+            };
+            Ok(SsoConfig {
+                region,
+                start_url,
+                account_id,
+                role_name,
+                account_name,
+                role_selector: None,
+            })
+        }
+        (region, start_url, account, role_name) => {
             let missing: Vec<_> = region
                 .map_or_else(|| Some("sso_region"), |_| None)
                 .into_iter()
                 .chain(start_url.map_or_else(|| Some("sso_start_url"), |_| None))
-                .chain(account_id.map_or_else(|| Some("sso_account_id"), |_| None))
+                .chain(account.map_or_else(|| Some("sso_account_id"), |_| None))
                 .chain(role_name.map_or_else(|| Some("sso_role_name"), |_| None))
                 .collect();
-            Err(SsoProfileError::new(format!(
-                "incomplete SSO configuration in profile {}; missing: {}",
-                profile,
-                missing.join(", ")
-            )))
+            Err(SsoProfileError::new(
+                SsoProfileErrorKind::ProfileIncomplete {
+                    profile: profile.to_string(),
+                },
+                format!(
+                    "incomplete SSO configuration in profile {}; missing: {}",
+                    profile,
+                    missing.join(", ")
+                ),
+            ))
         }
     }
 }
 
-async fn read_file(path: &Path) -> Result<String, io::Error> {
-    let meta = fs::metadata(&path).await?;
+/// Parse the value of `key` in `profile`, e.g. to validate an account ID.
+fn parse_value<T>(profile: &str, key: &str, value: &str) -> Result<T, SsoProfileError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value.parse().map_err(|error| {
+        SsoProfileError::new(
+            SsoProfileErrorKind::ProfileInvalid {
+                profile: profile.to_string(),
+            },
+            format!("invalid {key} in profile {profile}: {error}"),
+        )
+    })
+}
+
+/// Remove the quotes from an `sso_role_name` value that's entirely quoted.
+///
+/// The config file format has no quoting, and values are used as-is (including any `=` or spaces
+/// within them), but users sometimes quote values with spaces, e.g. `sso_role_name = "Power
+/// User"`. In double quotes, `\"` and `\\` escape a quote and a backslash. Single quotes have no
+/// escapes. Values that aren't entirely quoted, or whose quotes are unbalanced, are unchanged.
+fn unquote(value: &str) -> String {
+    if let Some(inner) = value
+        .strip_prefix('\'')
+        .and_then(|value| value.strip_suffix('\''))
+        .filter(|inner| !inner.contains('\''))
+    {
+        return inner.to_string();
+    }
+    let Some(inner) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    else {
+        return value.to_string();
+    };
+    let mut unquoted = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped @ ('"' | '\\')) => unquoted.push(escaped),
+                Some(other) => {
+                    unquoted.push('\\');
+                    unquoted.push(other);
+                }
+                // The closing quote was escaped
+                None => return value.to_string(),
+            },
+            '"' => return value.to_string(),
+            c => unquoted.push(c),
+        }
+    }
+    unquoted
+}
+
+/// Merge the sections defining `profile` in each file.
+fn merge_sections(
+    paths: &[PathBuf],
+    files: Vec<(&PathBuf, Vec<(Profile, bool)>)>,
+    profile: &str,
+    source: &ProfileSource,
+) -> Result<Profile, SsoProfileError> {
+    // As in the AWS CLI, `[profile name]` sections take precedence over `[name]` sections
+    let any_sections = files.iter().any(|(_, sections)| !sections.is_empty());
+    let any_prefixed = files
+        .iter()
+        .flat_map(|(_, sections)| sections)
+        .any(|(_, prefixed)| *prefixed);
+    let unprefixed_allowed = profile == AWS_PROFILE_DEFAULT || !source.require_profile_prefix;
+    let ignored_unprefixed = any_sections && !any_prefixed && !unprefixed_allowed;
+
+    // Sections are merged in order, so later files take precedence. Duplicates are only an error
+    // within a single file.
+    let mut properties: Option<Profile> = None;
+    for (path, sections) in files {
+        let mut sections = sections
+            .into_iter()
+            .filter(|(_, prefixed)| *prefixed || (!any_prefixed && unprefixed_allowed))
+            .map(|(p, _)| p);
+        let Some(mut merged) = sections.next() else {
+            continue;
+        };
+        for duplicate in sections {
+            if source.strict {
+                return Err(SsoProfileError::new(
+                    SsoProfileErrorKind::ProfileDuplicate {
+                        profile: profile.to_string(),
+                    },
+                    format!(
+                        "profile {} is defined more than once in config file {}",
+                        profile,
+                        path.display(),
+                    ),
+                ));
+            }
+            merged.properties.extend(duplicate.properties);
+        }
+        match &mut properties {
+            Some(properties) => properties.properties.extend(merged.properties),
+            None => properties = Some(merged),
+        }
+    }
+    properties.ok_or_else(|| {
+        let hint = if ignored_unprefixed {
+            format!(
+                " (the [{profile}] section was ignored, since profiles must be written as \
+                 [profile {profile}])"
+            )
+        } else {
+            String::new()
+        };
+        let paths: Vec<_> = paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        SsoProfileError::new(
+            SsoProfileErrorKind::ProfileMissing {
+                profile: profile.to_string(),
+            },
+            format!(
+                "profile {} is not defined in in config file {}{hint}",
+                profile,
+                paths.join(", "),
+            ),
+        )
+    })
+}
+
+async fn read_file(path: &Path, symlinks: SymlinkPolicy) -> Result<String, io::Error> {
+    let link_meta = match symlinks {
+        SymlinkPolicy::Follow => None,
+        SymlinkPolicy::Deny => {
+            let meta = fs::symlink_metadata(&path).await?;
+            if meta.is_symlink() {
+                return Err(io::Error::other("is a symbolic link"));
+            }
+            Some(meta)
+        }
+    };
+
+    // Check the metadata of the opened file, rather than the path, so the file can't be swapped
+    // between the checks and reading it
+    let mut file = File::open(&path).await?;
+    let meta = file.metadata().await?;
     if !meta.is_file() {
-        return Err(io::Error::new(io::ErrorKind::Other, "not a file"));
+        return Err(io::Error::other("not a file"));
     }
+    #[cfg(unix)]
+    if let Some(link_meta) = link_meta {
+        use std::os::unix::fs::MetadataExt;
+        if (link_meta.dev(), link_meta.ino()) != (meta.dev(), meta.ino()) {
+            return Err(io::Error::other("file was replaced while opening it"));
+        }
+    }
+    #[cfg(not(unix))]
+    drop(link_meta);
 
-    let mut file = File::open(&path)
-        .await
-        .expect("couldn't open file after stat");
     let mut file_content = String::new();
     file.read_to_string(&mut file_content).await?;
 
     Ok(file_content)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each test uses its own variables, since tests run concurrently
+    fn expand(path: &str) -> String {
+        expand_path(path)
+            .unwrap()
+            .into_os_string()
+            .into_string()
+            .unwrap()
+    }
+
+    #[test]
+    fn home_directory() {
+        let home = dirs_next::home_dir().unwrap();
+        let home = home.to_str().unwrap();
+        assert_eq!(expand("~"), home);
+        assert_eq!(expand("~/.aws/config"), format!("{home}/.aws/config"));
+        // Only a leading `~` on its own is the home directory
+        assert_eq!(expand("~other/config"), "~other/config");
+        assert_eq!(expand("config~"), "config~");
+    }
+
+    #[test]
+    fn variables() {
+        env::set_var("AWS_SSO_FLOW_TEST_EXPAND_DIR", "/etc/aws");
+        assert_eq!(
+            expand("$AWS_SSO_FLOW_TEST_EXPAND_DIR/config"),
+            "/etc/aws/config"
+        );
+        assert_eq!(
+            expand("${AWS_SSO_FLOW_TEST_EXPAND_DIR}config"),
+            "/etc/awsconfig"
+        );
+        assert_eq!(
+            expand("%AWS_SSO_FLOW_TEST_EXPAND_DIR%/config"),
+            "/etc/aws/config"
+        );
+    }
+
+    #[test]
+    fn unset_variables_are_kept() {
+        env::remove_var("AWS_SSO_FLOW_TEST_EXPAND_UNSET");
+        for path in [
+            "$AWS_SSO_FLOW_TEST_EXPAND_UNSET/config",
+            "${AWS_SSO_FLOW_TEST_EXPAND_UNSET}/config",
+            "%AWS_SSO_FLOW_TEST_EXPAND_UNSET%/config",
+        ] {
+            assert_eq!(expand(path), path);
+        }
+    }
+
+    #[test]
+    fn non_references_are_kept() {
+        env::set_var("AWS_SSO_FLOW_TEST_EXPAND_NAME", "config");
+        assert_eq!(expand("/etc/100%/config"), "/etc/100%/config");
+        assert_eq!(
+            expand("/etc/100%/%AWS_SSO_FLOW_TEST_EXPAND_NAME%"),
+            "/etc/100%/config"
+        );
+        assert_eq!(expand("/etc/%%/config"), "/etc/%%/config");
+        assert_eq!(expand("/etc/$/config"), "/etc/$/config");
+        assert_eq!(expand("/etc/${unterminated"), "/etc/${unterminated");
+    }
+}
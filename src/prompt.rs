@@ -0,0 +1,331 @@
+use std::{fmt, sync::Arc};
+
+use futures::future::BoxFuture;
+use url::Url;
+
+use crate::{AccountSelector, FlowId, PendingVerification, Region, RoleSelector, SsoConfig};
+
+/// An SSO verification prompt.
+///
+/// The AWS SSO authentication flow requires users to explicitly grant access by visiting a URL and
+/// clicking a button. There are many ways this could be implemented depending on the context, so
+/// verification prompts are modelled with this trait.
+///
+/// The trait is implemented for async functions with a single `Url` argument and returning
+/// `Result<(), E>`, so a trivial prompt could look like:
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use aws_sso_flow::VerificationPrompt;
+///
+/// fn prompt() -> impl VerificationPrompt {
+///     |verification_url| async move {
+///         println!("Go to {verification_url} to grant access");
+///         Ok::<_, Infallible>(())
+///     }
+/// }
+/// ```
+///
+/// The `Error` associated type can be used if the prompt is fallible. Type information is preserved
+/// in the event of any subsequent [`SsoFlowError`](crate::SsoFlowError).
+///
+/// Prompts that need more than the URL, e.g. to tell the user which organisation they're signing
+/// in to, can implement [`prompt_with_context`](Self::prompt_with_context). See [`PromptContext`]
+/// for an example.
+///
+/// The flow polls for the user's approval while the prompt runs, so a prompt may wait for the user
+/// (e.g. until a browser window closes) without delaying authentication. If the user grants access
+/// before the prompt completes, its future is dropped.
+pub trait VerificationPrompt: Clone + Send + Sync {
+    /// The future returned by the prompt.
+    type Future: std::future::Future<Output = Result<(), Self::Error>> + Send;
+
+    /// An error that could occur when attempting to prompt.
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Prompt the user to grant access via the given `verification_url`.
+    fn prompt(self, verification_url: Url) -> Self::Future;
+
+    /// Prompt the user to grant access, with context about the flow that's prompting.
+    ///
+    /// This is what [`SsoFlow`](crate::SsoFlow) calls. The default implementation calls
+    /// [`prompt`](Self::prompt) with the context's verification URL.
+    fn prompt_with_context(self, context: PromptContext) -> Self::Future {
+        self.prompt(context.verification_url)
+    }
+}
+
+/// What to do when a [`VerificationPrompt`] fails.
+///
+/// See [`SsoFlowBuilder::on_prompt_error`](crate::SsoFlowBuilder::on_prompt_error).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum PromptErrorPolicy {
+    /// Fail with [`SsoFlowError::VerificationPrompt`](crate::SsoFlowError::VerificationPrompt)
+    /// without waiting for the user to grant access.
+    #[default]
+    FailFast,
+
+    /// Ignore the error and wait for the user to grant access anyway.
+    ///
+    /// This suits prompts that may report spurious errors, e.g. fire-and-forget notifications that
+    /// can't tell whether they were delivered. If the user code expires before the user grants
+    /// access, the prompt's error is returned rather than a timeout.
+    ContinuePolling,
+}
+
+/// Context for a [`VerificationPrompt`].
+///
+/// This describes the flow that's prompting, so that prompts can explain what the user is signing
+/// in to, e.g. in their own language:
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use aws_sso_flow::{PromptContext, VerificationPrompt};
+/// use futures::future::{self, Ready};
+/// use url::Url;
+///
+/// #[derive(Clone)]
+/// struct GermanPrompt;
+///
+/// impl VerificationPrompt for GermanPrompt {
+///     type Future = Ready<Result<(), Infallible>>;
+///     type Error = Infallible;
+///
+///     fn prompt(self, verification_url: Url) -> Self::Future {
+///         eprintln!("Melden Sie sich unter {verification_url} an");
+///         future::ok(())
+///     }
+///
+///     fn prompt_with_context(self, context: PromptContext) -> Self::Future {
+///         eprintln!(
+///             "Melden Sie sich bei {} ({}) unter {} an",
+///             context.start_url(),
+///             context.region(),
+///             context.verification_url(),
+///         );
+///         future::ok(())
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PromptContext {
+    verification_url: Url,
+    config: Arc<SsoConfig>,
+    pending: PendingVerification,
+    flow_id: FlowId,
+}
+
+impl PromptContext {
+    pub(crate) fn new(
+        verification_url: Url,
+        config: Arc<SsoConfig>,
+        pending: PendingVerification,
+        flow_id: FlowId,
+    ) -> Self {
+        Self {
+            verification_url,
+            config,
+            pending,
+            flow_id,
+        }
+    }
+
+    /// The URL the user should visit to grant access.
+    #[must_use]
+    pub fn verification_url(&self) -> &Url {
+        &self.verification_url
+    }
+
+    /// The SSO start URL of the flow.
+    #[must_use]
+    pub fn start_url(&self) -> &str {
+        &self.config.start_url
+    }
+
+    /// The region of the flow.
+    #[must_use]
+    pub fn region(&self) -> &Region {
+        &self.config.region
+    }
+
+    /// The account the flow is authenticating for.
+    #[must_use]
+    pub fn account(&self) -> AccountSelector {
+        self.config.account()
+    }
+
+    /// The role the flow is authenticating for.
+    #[must_use]
+    pub fn role(&self) -> RoleSelector {
+        self.config.role()
+    }
+
+    /// The pending verification, which can be used to follow the flow's progress after prompting.
+    ///
+    /// See [`PendingVerification`] for an example.
+    #[must_use]
+    pub fn pending(&self) -> &PendingVerification {
+        &self.pending
+    }
+
+    /// The ID of the authentication attempt that's prompting.
+    ///
+    /// Flows with the same start URL share a single prompt, so this may be the ID of another
+    /// flow's attempt.
+    #[must_use]
+    pub fn flow_id(&self) -> FlowId {
+        self.flow_id
+    }
+}
+
+/// The input to a prompt, so that wrapping prompts can forward either [`VerificationPrompt`]
+/// method to the prompts they wrap.
+#[derive(Clone)]
+pub(crate) enum PromptInput {
+    Url(Url),
+    Context(PromptContext),
+}
+
+impl PromptInput {
+    pub(crate) fn prompt<V: VerificationPrompt>(self, prompt: V) -> V::Future {
+        match self {
+            Self::Url(verification_url) => prompt.prompt(verification_url),
+            Self::Context(context) => prompt.prompt_with_context(context),
+        }
+    }
+}
+
+impl<F, Fut, E> VerificationPrompt for F
+where
+    F: FnOnce(Url) -> Fut + Clone + Send + Sync,
+    Fut: std::future::Future<Output = Result<(), E>> + Send,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    type Future = Fut;
+    type Error = E;
+
+    fn prompt(self, verification_url: Url) -> Fut {
+        self(verification_url)
+    }
+}
+
+/// A type-erased [`VerificationPrompt`].
+///
+/// Verification prompts are usually closures, whose types can't be named. This makes it awkward to
+/// store an [`SsoFlow`](crate::SsoFlow) in a struct, or to pass it across crate boundaries. Boxing
+/// the prompt erases its type (and the type of its error), at the cost of an allocation per prompt.
+///
+/// See [`BoxedSsoFlow`](crate::BoxedSsoFlow) for a flow using a boxed prompt.
+///
+/// # Example
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use aws_sso_flow::{BoxedSsoFlow, BoxedVerificationPrompt, SsoConfig, SsoFlow};
+///
+/// struct App {
+///     sso: BoxedSsoFlow,
+/// }
+///
+/// # async fn app(config: SsoConfig) -> App {
+/// let prompt = BoxedVerificationPrompt::new(|url| async move {
+///     println!("Go to {url} to sign in with SSO");
+///     Ok::<_, Infallible>(())
+/// });
+///
+/// let sso = SsoFlow::builder()
+///     .config(config)
+///     .verification_prompt(prompt)
+///     .build()
+///     .await
+///     .expect("infallible");
+///
+/// App { sso }
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BoxedVerificationPrompt(Arc<BoxedPromptFn>);
+
+type BoxedPromptFn =
+    dyn Fn(PromptInput) -> BoxFuture<'static, Result<(), BoxedPromptError>> + Send + Sync;
+
+impl BoxedVerificationPrompt {
+    /// Erase the type of the given `verification_prompt`.
+    pub fn new<V>(verification_prompt: V) -> Self
+    where
+        V: VerificationPrompt + 'static,
+        V::Future: 'static,
+    {
+        Self(Arc::new(move |input: PromptInput| {
+            let verification_prompt = verification_prompt.clone();
+            Box::pin(async move {
+                input
+                    .prompt(verification_prompt)
+                    .await
+                    .map_err(BoxedPromptError::new)
+            })
+        }))
+    }
+}
+
+impl fmt::Debug for BoxedVerificationPrompt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("BoxedVerificationPrompt")
+            .field(&"_")
+            .finish()
+    }
+}
+
+impl VerificationPrompt for BoxedVerificationPrompt {
+    type Future = BoxFuture<'static, Result<(), Self::Error>>;
+
+    type Error = BoxedPromptError;
+
+    fn prompt(self, verification_url: Url) -> Self::Future {
+        (self.0)(PromptInput::Url(verification_url))
+    }
+
+    fn prompt_with_context(self, context: PromptContext) -> Self::Future {
+        (self.0)(PromptInput::Context(context))
+    }
+}
+
+/// The error returned by a [`BoxedVerificationPrompt`].
+///
+/// This wraps the error returned by the original prompt, which can be recovered with
+/// [`into_inner`](Self::into_inner).
+pub struct BoxedPromptError(Box<dyn std::error::Error + Send + Sync>);
+
+impl BoxedPromptError {
+    pub(crate) fn new(error: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self(Box::new(error))
+    }
+
+    /// Get the original prompt error.
+    #[must_use]
+    pub fn into_inner(self) -> Box<dyn std::error::Error + Send + Sync> {
+        self.0
+    }
+}
+
+impl fmt::Debug for BoxedPromptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for BoxedPromptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for BoxedPromptError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
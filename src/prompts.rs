@@ -0,0 +1,592 @@
+//! Verification prompt adapters.
+//!
+//! [`VerificationPromptExt`] adds combinators to every [`VerificationPrompt`], so prompts can be
+//! composed. For example, a prompt could try to open a browser and fall back to printing the URL
+//! if that fails:
+//!
+//! ```
+//! use std::{io, time::Duration};
+//!
+//! use aws_sso_flow::{prompts, SsoFlowBuilder, VerificationPromptExt};
+//!
+//! let open_browser = |url: url::Url| async move {
+//!     // e.g. `webbrowser::open(url.as_str())`
+//!     Err::<(), _>(io::Error::other(format!("no browser to open {url}")))
+//! };
+//! let print_url = prompts::from_fn(|url| eprintln!("Go to {url} to sign in with SSO"));
+//!
+//! let builder = SsoFlowBuilder::new().verification_prompt(
+//!     open_browser
+//!         .with_timeout(Duration::from_secs(5))
+//!         .or_else(print_url),
+//! );
+//! ```
+//!
+//! Longer fallback sequences can be built with [`chain`], which also records which prompt was
+//! used.
+
+use std::{
+    convert::Infallible,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use futures::{
+    future::{self, BoxFuture, Ready},
+    TryFutureExt,
+};
+use url::Url;
+
+use crate::{
+    prompt::PromptInput, BoxedPromptError, BoxedVerificationPrompt, PromptContext,
+    VerificationPrompt,
+};
+
+#[cfg(feature = "notify")]
+mod notify;
+#[cfg_attr(docsrs, doc(cfg(feature = "webview")))]
+#[cfg(feature = "webview")]
+mod webview;
+
+#[cfg(feature = "notify")]
+pub use self::notify::{notify, Notify};
+#[cfg(feature = "webview")]
+pub use self::webview::{webview, Webview, WebviewHandoff, WebviewHandoffs};
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl<V: crate::VerificationPrompt> Sealed for V {}
+}
+
+/// Combinators for [`VerificationPrompt`]s.
+///
+/// This trait is implemented for every verification prompt, and can't be implemented outside of
+/// this crate. See the [module documentation](self) for an example.
+pub trait VerificationPromptExt: VerificationPrompt + sealed::Sealed {
+    /// Convert the prompt's error with the given function.
+    fn map_err<F, E>(self, f: F) -> MapErr<Self, F>
+    where
+        F: FnOnce(Self::Error) -> E + Clone + Send + Sync,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        MapErr { prompt: self, f }
+    }
+
+    /// Run `next` after this prompt, if this prompt succeeds.
+    ///
+    /// This could be used to e.g. print the URL *and* try to open it in a browser.
+    fn and_then<P>(self, next: P) -> AndThen<Self, P>
+    where
+        P: VerificationPrompt<Error = Self::Error>,
+    {
+        AndThen { prompt: self, next }
+    }
+
+    /// Run `fallback` if this prompt fails.
+    ///
+    /// The error from this prompt is discarded, and the overall result is the result of the
+    /// `fallback`.
+    fn or_else<P>(self, fallback: P) -> OrElse<Self, P>
+    where
+        P: VerificationPrompt,
+    {
+        OrElse {
+            prompt: self,
+            fallback,
+        }
+    }
+
+    /// Fail with [`PromptTimeoutError::Elapsed`] if the prompt doesn't complete within `timeout`.
+    ///
+    /// Note that this limits the prompt itself (e.g. launching a browser), not the time the user
+    /// has to grant access, which is determined by AWS.
+    fn with_timeout(self, timeout: Duration) -> WithTimeout<Self> {
+        WithTimeout {
+            prompt: self,
+            timeout,
+        }
+    }
+
+    /// Erase the type of the prompt.
+    ///
+    /// This is equivalent to [`BoxedVerificationPrompt::new`], and is mostly useful when
+    /// collecting prompts of different types, e.g. for [`chain`].
+    fn boxed(self) -> BoxedVerificationPrompt
+    where
+        Self: 'static,
+        Self::Future: 'static,
+    {
+        BoxedVerificationPrompt::new(self)
+    }
+}
+
+impl<V: VerificationPrompt> VerificationPromptExt for V {}
+
+/// Construct a verification prompt from a synchronous, infallible function.
+///
+/// This avoids the boilerplate of an `async` closure and error type for simple prompts. See also
+/// [`SsoFlowBuilder::verification_prompt_fn`](crate::SsoFlowBuilder::verification_prompt_fn).
+///
+/// ```
+/// use aws_sso_flow::prompts;
+///
+/// let prompt = prompts::from_fn(|url| println!("Go to {url} to sign in with SSO"));
+/// ```
+pub fn from_fn<F>(f: F) -> FromFn<F>
+where
+    F: Fn(&Url) + Clone + Send + Sync,
+{
+    FromFn(f)
+}
+
+/// A verification prompt constructed with [`from_fn`].
+#[derive(Clone)]
+pub struct FromFn<F>(F);
+
+impl<F> fmt::Debug for FromFn<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("FromFn").field(&"_").finish()
+    }
+}
+
+impl<F> VerificationPrompt for FromFn<F>
+where
+    F: Fn(&Url) + Clone + Send + Sync,
+{
+    type Future = Ready<Result<(), Infallible>>;
+
+    type Error = Infallible;
+
+    fn prompt(self, verification_url: Url) -> Self::Future {
+        (self.0)(&verification_url);
+        future::ok(())
+    }
+}
+
+/// A verification prompt that prints the verification URL to stderr.
+///
+/// This never fails, so it makes a good last resort in a [`chain`].
+#[must_use]
+pub fn print_to_stderr() -> FromFn<fn(&Url)> {
+    from_fn(|url| eprintln!("Go to {url} to sign in with SSO"))
+}
+
+/// A verification prompt that opens the verification URL in the user's browser.
+///
+/// The browser is launched with the command in the `BROWSER` environment variable, if set, or else
+/// the platform's default opener (`open` on macOS, `xdg-open` on other Unix systems, and the URL
+/// protocol handler on Windows). The prompt fails if the command can't be spawned or exits
+/// unsuccessfully, which is common in headless sessions, so it's usually combined with a fallback
+/// using [`chain`] or [`VerificationPromptExt::or_else`].
+#[cfg_attr(docsrs, doc(cfg(feature = "browser")))]
+#[cfg(feature = "browser")]
+#[must_use]
+pub fn open_browser() -> OpenBrowser {
+    OpenBrowser(())
+}
+
+/// The prompt returned by [`open_browser`].
+#[cfg_attr(docsrs, doc(cfg(feature = "browser")))]
+#[cfg(feature = "browser")]
+#[derive(Clone, Debug)]
+pub struct OpenBrowser(());
+
+#[cfg(feature = "browser")]
+impl VerificationPrompt for OpenBrowser {
+    type Future = BoxFuture<'static, Result<(), Self::Error>>;
+
+    type Error = std::io::Error;
+
+    fn prompt(self, verification_url: Url) -> Self::Future {
+        Box::pin(async move {
+            let mut command = tokio::process::Command::from(browser_command());
+            let program = command
+                .as_std()
+                .get_program()
+                .to_string_lossy()
+                .into_owned();
+            let status = command
+                .arg(verification_url.as_str())
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .status()
+                .await?;
+            if status.success() {
+                Ok(())
+            } else {
+                Err(std::io::Error::other(format!(
+                    "browser command `{program}` exited with {status}"
+                )))
+            }
+        })
+    }
+}
+
+#[cfg(feature = "browser")]
+fn browser_command() -> std::process::Command {
+    if let Some(browser) = std::env::var_os("BROWSER").filter(|browser| !browser.is_empty()) {
+        return std::process::Command::new(browser);
+    }
+    if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = std::process::Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    }
+}
+
+/// Construct a verification prompt suited to the current environment.
+///
+/// - In a headless session (connected over SSH, or with no `DISPLAY` or `WAYLAND_DISPLAY` on Unix
+///   systems other than macOS) the URL is only printed to stderr.
+/// - Otherwise, if stderr is a terminal, the URL is printed and the browser is opened on a
+///   best-effort basis, so the user can still copy the URL if the browser doesn't open.
+/// - Otherwise (e.g. in a desktop app) the browser is opened, falling back to printing the URL if
+///   that fails.
+///
+/// Without the `browser` feature, the URL is always printed to stderr.
+///
+/// ```
+/// use aws_sso_flow::{prompts, SsoFlowBuilder};
+///
+/// let builder = SsoFlowBuilder::new().verification_prompt(prompts::auto());
+/// ```
+#[must_use]
+pub fn auto() -> Chain {
+    #[cfg(feature = "browser")]
+    if !is_headless() {
+        use std::io::{self, IsTerminal};
+
+        return if io::stderr().is_terminal() {
+            chain([print_to_stderr()
+                .and_then(open_browser().or_else(from_fn(|_| {})))
+                .boxed()])
+        } else {
+            chain([open_browser().boxed(), print_to_stderr().boxed()])
+        };
+    }
+    chain([print_to_stderr().boxed()])
+}
+
+#[cfg(feature = "browser")]
+fn is_headless() -> bool {
+    let is_set = |var| std::env::var_os(var).is_some_and(|value| !value.is_empty());
+    if is_set("SSH_CONNECTION") || is_set("SSH_TTY") {
+        return true;
+    }
+    cfg!(unix) && !cfg!(target_os = "macos") && !is_set("DISPLAY") && !is_set("WAYLAND_DISPLAY")
+}
+
+/// Construct a verification prompt that tries each of the given `prompts` in order until one
+/// succeeds.
+///
+/// The index of the prompt that succeeded can be retrieved with [`Chain::used`]. If every prompt
+/// fails, the errors are returned in a [`ChainError`].
+///
+/// ```
+/// # #[cfg(feature = "browser")] {
+/// use aws_sso_flow::{prompts, SsoFlowBuilder, VerificationPromptExt};
+///
+/// let prompt = prompts::chain([
+///     prompts::open_browser().boxed(),
+///     prompts::print_to_stderr().boxed(),
+/// ]);
+/// let builder = SsoFlowBuilder::new().verification_prompt(prompt.clone());
+///
+/// // ...after authenticating
+/// if prompt.used() == Some(1) {
+///     // the browser couldn't be opened
+/// }
+/// # }
+/// ```
+pub fn chain<I>(prompts: I) -> Chain
+where
+    I: IntoIterator<Item = BoxedVerificationPrompt>,
+{
+    Chain {
+        prompts: prompts.into_iter().collect(),
+        used: Arc::new(AtomicUsize::new(NOT_USED)),
+    }
+}
+
+const NOT_USED: usize = usize::MAX;
+
+/// The prompt returned by [`chain`].
+///
+/// Clones share the record of which prompt was used, so a clone can be kept to inspect it after
+/// the original has been given to a flow.
+#[derive(Clone, Debug)]
+pub struct Chain {
+    prompts: Arc<[BoxedVerificationPrompt]>,
+    used: Arc<AtomicUsize>,
+}
+
+impl Chain {
+    /// The index of the prompt that most recently succeeded, if any.
+    #[must_use]
+    pub fn used(&self) -> Option<usize> {
+        match self.used.load(Ordering::Acquire) {
+            NOT_USED => None,
+            index => Some(index),
+        }
+    }
+}
+
+impl VerificationPrompt for Chain {
+    type Future = BoxFuture<'static, Result<(), Self::Error>>;
+
+    type Error = ChainError;
+
+    fn prompt(self, verification_url: Url) -> Self::Future {
+        self.call(PromptInput::Url(verification_url))
+    }
+
+    fn prompt_with_context(self, context: PromptContext) -> Self::Future {
+        self.call(PromptInput::Context(context))
+    }
+}
+
+impl Chain {
+    fn call(self, input: PromptInput) -> BoxFuture<'static, Result<(), ChainError>> {
+        Box::pin(async move {
+            let mut errors = Vec::new();
+            for (index, prompt) in self.prompts.iter().enumerate() {
+                match input.clone().prompt(prompt.clone()).await {
+                    Ok(()) => {
+                        self.used.store(index, Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(error) => errors.push(error),
+                }
+            }
+            Err(ChainError { errors })
+        })
+    }
+}
+
+/// The error returned by a [`Chain`] prompt when every prompt failed.
+#[derive(Debug)]
+pub struct ChainError {
+    errors: Vec<BoxedPromptError>,
+}
+
+impl ChainError {
+    /// The errors from each prompt, in the order the prompts were tried.
+    #[must_use]
+    pub fn errors(&self) -> &[BoxedPromptError] {
+        &self.errors
+    }
+
+    /// Consume the error, returning the errors from each prompt.
+    #[must_use]
+    pub fn into_errors(self) -> Vec<BoxedPromptError> {
+        self.errors
+    }
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.errors.is_empty() {
+            return f.write_str("no verification prompts to try");
+        }
+        write!(f, "all verification prompts failed")?;
+        for (index, error) in self.errors.iter().enumerate() {
+            write!(f, "{} {index}: {error}", if index == 0 { ":" } else { ";" })?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+/// The prompt returned by [`VerificationPromptExt::map_err`].
+#[derive(Clone, Debug)]
+pub struct MapErr<V, F> {
+    prompt: V,
+    f: F,
+}
+
+impl<V, F, E> VerificationPrompt for MapErr<V, F>
+where
+    V: VerificationPrompt,
+    F: FnOnce(V::Error) -> E + Clone + Send + Sync,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    type Future = futures::future::MapErr<V::Future, F>;
+
+    type Error = E;
+
+    fn prompt(self, verification_url: Url) -> Self::Future {
+        self.prompt.prompt(verification_url).map_err(self.f)
+    }
+
+    fn prompt_with_context(self, context: PromptContext) -> Self::Future {
+        self.prompt.prompt_with_context(context).map_err(self.f)
+    }
+}
+
+/// The prompt returned by [`VerificationPromptExt::and_then`].
+#[derive(Clone, Debug)]
+pub struct AndThen<V, P> {
+    prompt: V,
+    next: P,
+}
+
+impl<V, P> VerificationPrompt for AndThen<V, P>
+where
+    V: VerificationPrompt + 'static,
+    V::Future: 'static,
+    P: VerificationPrompt<Error = V::Error> + 'static,
+    P::Future: 'static,
+{
+    type Future = BoxFuture<'static, Result<(), Self::Error>>;
+
+    type Error = V::Error;
+
+    fn prompt(self, verification_url: Url) -> Self::Future {
+        self.call(PromptInput::Url(verification_url))
+    }
+
+    fn prompt_with_context(self, context: PromptContext) -> Self::Future {
+        self.call(PromptInput::Context(context))
+    }
+}
+
+impl<V, P> AndThen<V, P>
+where
+    V: VerificationPrompt + 'static,
+    V::Future: 'static,
+    P: VerificationPrompt<Error = V::Error> + 'static,
+    P::Future: 'static,
+{
+    fn call(self, input: PromptInput) -> BoxFuture<'static, Result<(), V::Error>> {
+        Box::pin(async move {
+            input.clone().prompt(self.prompt).await?;
+            input.prompt(self.next).await
+        })
+    }
+}
+
+/// The prompt returned by [`VerificationPromptExt::or_else`].
+#[derive(Clone, Debug)]
+pub struct OrElse<V, P> {
+    prompt: V,
+    fallback: P,
+}
+
+impl<V, P> VerificationPrompt for OrElse<V, P>
+where
+    V: VerificationPrompt + 'static,
+    V::Future: 'static,
+    P: VerificationPrompt + 'static,
+    P::Future: 'static,
+{
+    type Future = BoxFuture<'static, Result<(), Self::Error>>;
+
+    type Error = P::Error;
+
+    fn prompt(self, verification_url: Url) -> Self::Future {
+        self.call(PromptInput::Url(verification_url))
+    }
+
+    fn prompt_with_context(self, context: PromptContext) -> Self::Future {
+        self.call(PromptInput::Context(context))
+    }
+}
+
+impl<V, P> OrElse<V, P>
+where
+    V: VerificationPrompt + 'static,
+    V::Future: 'static,
+    P: VerificationPrompt + 'static,
+    P::Future: 'static,
+{
+    fn call(self, input: PromptInput) -> BoxFuture<'static, Result<(), P::Error>> {
+        Box::pin(async move {
+            match input.clone().prompt(self.prompt).await {
+                Ok(()) => Ok(()),
+                Err(_) => input.prompt(self.fallback).await,
+            }
+        })
+    }
+}
+
+/// The prompt returned by [`VerificationPromptExt::with_timeout`].
+#[derive(Clone, Debug)]
+pub struct WithTimeout<V> {
+    prompt: V,
+    timeout: Duration,
+}
+
+impl<V> VerificationPrompt for WithTimeout<V>
+where
+    V: VerificationPrompt + 'static,
+    V::Future: 'static,
+{
+    type Future = BoxFuture<'static, Result<(), Self::Error>>;
+
+    type Error = PromptTimeoutError<V::Error>;
+
+    fn prompt(self, verification_url: Url) -> Self::Future {
+        self.call(PromptInput::Url(verification_url))
+    }
+
+    fn prompt_with_context(self, context: PromptContext) -> Self::Future {
+        self.call(PromptInput::Context(context))
+    }
+}
+
+impl<V> WithTimeout<V>
+where
+    V: VerificationPrompt + 'static,
+    V::Future: 'static,
+{
+    fn call(
+        self,
+        input: PromptInput,
+    ) -> BoxFuture<'static, Result<(), PromptTimeoutError<V::Error>>> {
+        Box::pin(async move {
+            tokio::time::timeout(self.timeout, input.prompt(self.prompt))
+                .await
+                .map_err(|_| PromptTimeoutError::Elapsed(self.timeout))?
+                .map_err(PromptTimeoutError::Prompt)
+        })
+    }
+}
+
+/// The error returned by a prompt constructed with [`VerificationPromptExt::with_timeout`].
+#[derive(Debug)]
+pub enum PromptTimeoutError<E> {
+    /// The prompt didn't complete within the given duration.
+    Elapsed(Duration),
+
+    /// The prompt failed.
+    Prompt(E),
+}
+
+impl<E: fmt::Display> fmt::Display for PromptTimeoutError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Elapsed(timeout) => write!(f, "prompt timed out after {timeout:?}"),
+            Self::Prompt(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for PromptTimeoutError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Elapsed(_) => None,
+            Self::Prompt(error) => error.source(),
+        }
+    }
+}
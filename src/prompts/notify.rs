@@ -0,0 +1,104 @@
+use std::io;
+
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use url::Url;
+
+use crate::{PromptContext, VerificationPrompt};
+
+/// A verification prompt that raises a desktop notification.
+///
+/// Clicking the notification (or its "Sign in" action) opens the verification URL in the user's
+/// browser, as for [`open_browser`](super::open_browser). This suits background agents that run in
+/// a user's desktop session, but have no terminal to print the URL to.
+///
+/// Actions are only supported by freedesktop.org notification servers, e.g. on Linux. On other
+/// platforms the notification shows the verification URL instead. The notification is dismissed
+/// when the user code expires.
+///
+/// The prompt fails if the notification can't be shown, e.g. if there's no notification server,
+/// so it's usually combined with a fallback:
+///
+/// ```
+/// use aws_sso_flow::{prompts, SsoFlowBuilder, VerificationPromptExt};
+///
+/// let builder = SsoFlowBuilder::new()
+///     .verification_prompt(prompts::notify().or_else(prompts::print_to_stderr()));
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "notify")))]
+#[must_use]
+pub fn notify() -> Notify {
+    Notify(())
+}
+
+/// The prompt returned by [`notify`].
+#[cfg_attr(docsrs, doc(cfg(feature = "notify")))]
+#[derive(Clone, Debug)]
+pub struct Notify(());
+
+impl VerificationPrompt for Notify {
+    type Future = BoxFuture<'static, Result<(), Self::Error>>;
+
+    type Error = io::Error;
+
+    fn prompt(self, verification_url: Url) -> Self::Future {
+        Box::pin(show(
+            verification_url,
+            "Sign in with AWS SSO to continue".to_string(),
+            None,
+        ))
+    }
+
+    fn prompt_with_context(self, context: PromptContext) -> Self::Future {
+        Box::pin(show(
+            context.verification_url().clone(),
+            format!("Sign in to {} to continue", context.start_url()),
+            Some(context.pending().expires_at()),
+        ))
+    }
+}
+
+async fn show(
+    verification_url: Url,
+    body: String,
+    expires_at: Option<DateTime<Utc>>,
+) -> io::Result<()> {
+    let mut notification = notify_rust::Notification::new();
+    notification.summary("AWS SSO");
+    if cfg!(all(unix, not(target_os = "macos"))) {
+        notification.body(&body).action("default", "Sign in");
+    } else {
+        notification.body(&format!("{body}: {verification_url}"));
+    }
+    if let Some(expires_at) = expires_at {
+        let remaining = (expires_at - Utc::now()).num_milliseconds();
+        let remaining = u32::try_from(remaining.max(0)).unwrap_or(u32::MAX);
+        notification.timeout(notify_rust::Timeout::Milliseconds(remaining));
+    }
+
+    // Showing the notification and waiting for actions block on D-Bus (or the platform's
+    // equivalent), so they run off the async runtime
+    let handle = tokio::task::spawn_blocking(move || notification.show())
+        .await
+        .map_err(io::Error::other)?
+        .map_err(io::Error::other)?;
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    tokio::task::spawn_blocking(move || {
+        handle.wait_for_action(|action| {
+            if action == "default" {
+                // The user can still visit the URL some other way if the browser doesn't open
+                let _ = super::browser_command()
+                    .arg(verification_url.as_str())
+                    .stdin(std::process::Stdio::null())
+                    .stdout(std::process::Stdio::null())
+                    .stderr(std::process::Stdio::null())
+                    .status();
+            }
+        });
+    });
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    drop((handle, verification_url));
+
+    Ok(())
+}
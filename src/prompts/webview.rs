@@ -0,0 +1,129 @@
+use std::io;
+
+use futures::future::{self, Ready};
+use tokio::sync::mpsc;
+use url::Url;
+
+use crate::{PendingVerification, PromptContext, VerificationPrompt, VerificationStatus};
+
+/// Construct a verification prompt that hands verification off to an embedded webview.
+///
+/// Desktop apps (e.g. built with Tauri or egui) often want to show the verification page in their
+/// own window, rather than in the user's browser or via a callback. When the flow prompts, this
+/// prompt sends a [`WebviewHandoff`] to the returned [`WebviewHandoffs`] receiver, and returns
+/// immediately so the flow can start polling. The app should:
+///
+/// 1. Receive handoffs, e.g. with [`WebviewHandoffs::try_recv`] each frame.
+/// 2. Open a webview at [`WebviewHandoff::verification_url`], where the user signs in and grants
+///    access.
+/// 3. Close the webview when [`WebviewHandoff::completed`] resolves. The status says whether the
+///    user granted access, and the flow continues (or fails) on its own either way.
+///
+/// The prompt fails if the receiver has been dropped.
+///
+/// ```no_run
+/// # async fn example() {
+/// use aws_sso_flow::{prompts, SsoFlow, VerificationStatus};
+///
+/// let (prompt, mut handoffs) = prompts::webview();
+/// let flow = SsoFlow::builder().verification_prompt(prompt).build().await.unwrap();
+///
+/// tokio::spawn(async move {
+///     while let Some(handoff) = handoffs.recv().await {
+///         // e.g. open a Tauri `WebviewWindow` at this URL
+///         println!("open webview at {}", handoff.verification_url());
+///         let status = handoff.completed().await;
+///         println!("close webview ({status:?})");
+///     }
+/// });
+///
+/// let credentials = flow.authenticate().await.unwrap();
+/// # }
+/// ```
+#[must_use]
+pub fn webview() -> (Webview, WebviewHandoffs) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    (Webview(sender), WebviewHandoffs(receiver))
+}
+
+/// The prompt returned by [`webview`].
+#[derive(Clone, Debug)]
+pub struct Webview(mpsc::UnboundedSender<WebviewHandoff>);
+
+/// Receives the [`WebviewHandoff`]s sent by a [`Webview`] prompt.
+#[derive(Debug)]
+pub struct WebviewHandoffs(mpsc::UnboundedReceiver<WebviewHandoff>);
+
+/// A verification to show in an embedded webview.
+///
+/// See [`webview`] for how to use it.
+#[derive(Clone, Debug)]
+pub struct WebviewHandoff {
+    context: PromptContext,
+}
+
+impl VerificationPrompt for Webview {
+    type Future = Ready<Result<(), Self::Error>>;
+
+    type Error = io::Error;
+
+    /// Always fails, since a handoff needs the context provided by
+    /// [`prompt_with_context`](VerificationPrompt::prompt_with_context), which is what
+    /// [`SsoFlow`](crate::SsoFlow) calls.
+    fn prompt(self, _verification_url: Url) -> Self::Future {
+        future::err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the webview prompt needs the context from `prompt_with_context`",
+        ))
+    }
+
+    fn prompt_with_context(self, context: PromptContext) -> Self::Future {
+        future::ready(self.0.send(WebviewHandoff { context }).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the webview handoff receiver was dropped",
+            )
+        }))
+    }
+}
+
+impl WebviewHandoffs {
+    /// Wait for the next handoff.
+    ///
+    /// Returns `None` once every [`Webview`] prompt (including clones held by flows) is dropped.
+    pub async fn recv(&mut self) -> Option<WebviewHandoff> {
+        self.0.recv().await
+    }
+
+    /// Get the next handoff if there is one, without waiting.
+    ///
+    /// This suits immediate-mode UIs that check for handoffs each frame.
+    pub fn try_recv(&mut self) -> Option<WebviewHandoff> {
+        self.0.try_recv().ok()
+    }
+}
+
+impl WebviewHandoff {
+    /// The URL the webview should open.
+    #[must_use]
+    pub fn verification_url(&self) -> &Url {
+        self.context.verification_url()
+    }
+
+    /// The context of the flow that's prompting, e.g. to title the webview window.
+    #[must_use]
+    pub fn context(&self) -> &PromptContext {
+        &self.context
+    }
+
+    /// The pending verification, e.g. to show the user code or count down to its expiry.
+    #[must_use]
+    pub fn pending(&self) -> &PendingVerification {
+        self.context.pending()
+    }
+
+    /// Wait for the verification to complete, after which the webview should be closed.
+    pub async fn completed(&self) -> VerificationStatus {
+        self.context.pending().completed().await
+    }
+}
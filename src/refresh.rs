@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+
+/// The result of [`SsoFlow::refresh_if_possible`](crate::SsoFlow::refresh_if_possible).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum RefreshOutcome {
+    /// The cached credentials are fresh, and outside the refresh-ahead window, so nothing was
+    /// done.
+    Fresh {
+        /// When the cached credentials expire.
+        expires_at: DateTime<Utc>,
+    },
+
+    /// New credentials were obtained and cached.
+    Refreshed {
+        /// When the new credentials expire.
+        expires_at: DateTime<Utc>,
+    },
+
+    /// There's no access token that can be used without prompting, so the user must sign in
+    /// (e.g. with [`SsoFlow::authenticate`](crate::SsoFlow::authenticate)) before credentials can
+    /// be refreshed.
+    InteractionRequired,
+
+    /// The deadline passed before the refresh completed.
+    ///
+    /// The cache is left as it was, or with newly cached intermediate results.
+    DeadlineExceeded,
+}
@@ -1,32 +1,134 @@
-// A wrapper around `aws_types::Region` to avoid exposing it in the API.
+// A region type that's independent of `aws_types::Region`, so it's available without the AWS SDK
+// (see the `lite-http` feature) and so that the SDK's types aren't exposed in the API.
 
-use std::{borrow::Cow, fmt};
-
-// Use `Region` from `aws_sdk_sso` to avoid depending directly on `aws_types`. It's hoped this will
-// make it possible to integrate with other versions of aws-sdk than the one used to implement this
-// crate, since the `aws-types` dependency can be more flexible.
-use aws_sdk_sso::config::Region as SdkRegion;
+use std::{borrow::Cow, fmt, str::FromStr};
 
 /// An AWS region.
+///
+/// Regions can be constructed without validation using [`new`](Self::new), or parsed with
+/// validation using [`FromStr`]:
+///
+/// ```
+/// use aws_sso_flow::Region;
+///
+/// let region: Region = "eu-west-1".parse().unwrap();
+/// assert_eq!(region, Region::EU_WEST_1);
+///
+/// assert!("EU-West-1".parse::<Region>().is_err());
+/// ```
 #[derive(Clone, Eq, Hash, PartialEq)]
-pub struct Region(pub(crate) SdkRegion);
+pub struct Region(Cow<'static, str>);
+
+macro_rules! well_known {
+    ($($name:ident => $region:literal,)*) => {
+        impl Region {
+            $(
+                #[doc = concat!("The `", $region, "` region.")]
+                pub const $name: Self = Self(Cow::Borrowed($region));
+            )*
+
+            /// Well-known AWS regions, e.g. for offering a choice of region in a UI.
+            ///
+            /// This is not exhaustive, and doesn't change the regions that can be used. Other
+            /// regions can be constructed with [`new`](Self::new) or parsed with [`FromStr`].
+            #[must_use]
+            pub fn well_known() -> &'static [Region] {
+                const WELL_KNOWN: &[Region] = &[$(Region::$name),*];
+                WELL_KNOWN
+            }
+        }
+    };
+}
+
+well_known! {
+    AF_SOUTH_1 => "af-south-1",
+    AP_EAST_1 => "ap-east-1",
+    AP_NORTHEAST_1 => "ap-northeast-1",
+    AP_NORTHEAST_2 => "ap-northeast-2",
+    AP_NORTHEAST_3 => "ap-northeast-3",
+    AP_SOUTH_1 => "ap-south-1",
+    AP_SOUTH_2 => "ap-south-2",
+    AP_SOUTHEAST_1 => "ap-southeast-1",
+    AP_SOUTHEAST_2 => "ap-southeast-2",
+    AP_SOUTHEAST_3 => "ap-southeast-3",
+    AP_SOUTHEAST_4 => "ap-southeast-4",
+    CA_CENTRAL_1 => "ca-central-1",
+    CA_WEST_1 => "ca-west-1",
+    CN_NORTH_1 => "cn-north-1",
+    CN_NORTHWEST_1 => "cn-northwest-1",
+    EU_CENTRAL_1 => "eu-central-1",
+    EU_CENTRAL_2 => "eu-central-2",
+    EU_NORTH_1 => "eu-north-1",
+    EU_SOUTH_1 => "eu-south-1",
+    EU_SOUTH_2 => "eu-south-2",
+    EU_WEST_1 => "eu-west-1",
+    EU_WEST_2 => "eu-west-2",
+    EU_WEST_3 => "eu-west-3",
+    IL_CENTRAL_1 => "il-central-1",
+    ME_CENTRAL_1 => "me-central-1",
+    ME_SOUTH_1 => "me-south-1",
+    SA_EAST_1 => "sa-east-1",
+    US_EAST_1 => "us-east-1",
+    US_EAST_2 => "us-east-2",
+    US_GOV_EAST_1 => "us-gov-east-1",
+    US_GOV_WEST_1 => "us-gov-west-1",
+    US_WEST_1 => "us-west-1",
+    US_WEST_2 => "us-west-2",
+}
 
 impl Region {
     /// Construct a new `Region` for the given string.
+    ///
+    /// The region is not validated. Use [`FromStr`] to reject strings that aren't syntactically
+    /// valid region names.
     pub fn new(region: impl Into<Cow<'static, str>>) -> Self {
-        Self(SdkRegion::new(region))
+        Self(region.into())
+    }
+}
+
+impl FromStr for Region {
+    type Err = ParseRegionError;
+
+    /// Parse a region name, such as `eu-west-1`.
+    ///
+    /// Region names must consist of lowercase ASCII letters and digits separated by hyphens, with
+    /// at least three components: a two-letter prefix, one or more alphabetic components, and a
+    /// numeric suffix. Whether the region actually exists is not checked.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let is_alphabetic =
+            |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_lowercase());
+        let is_numeric = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+
+        let parts: Vec<_> = s.split('-').collect();
+        let valid = match parts.as_slice() {
+            [prefix, middle @ .., suffix] if !middle.is_empty() => {
+                prefix.len() == 2
+                    && is_alphabetic(prefix)
+                    && middle.iter().all(|part| is_alphabetic(part))
+                    && is_numeric(suffix)
+            }
+            _ => false,
+        };
+
+        if valid {
+            Ok(Self::new(s.to_string()))
+        } else {
+            Err(ParseRegionError {
+                input: s.to_string(),
+            })
+        }
     }
 }
 
 impl AsRef<str> for Region {
     fn as_ref(&self) -> &str {
-        self.0.as_ref()
+        &self.0
     }
 }
 
 impl fmt::Debug for Region {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.0.fmt(f)
+        f.debug_tuple("Region").field(&self.0).finish()
     }
 }
 
@@ -35,3 +137,17 @@ impl fmt::Display for Region {
         self.0.fmt(f)
     }
 }
+
+/// The error returned when parsing an invalid [`Region`].
+#[derive(Clone, Debug)]
+pub struct ParseRegionError {
+    input: String,
+}
+
+impl fmt::Display for ParseRegionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid AWS region name: {:?}", self.input)
+    }
+}
+
+impl std::error::Error for ParseRegionError {}
@@ -1,10 +1,31 @@
-use std::fmt;
+use std::{cell::Cell, fmt, future::Future, sync::Mutex};
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use rusoto_credential::{AwsCredentials, CredentialsError, ProvideAwsCredentials};
 
-use crate::{SessionCredentials, SsoConfigSource, SsoFlow, SsoFlowBuilder, VerificationPrompt};
+use crate::{
+    flow::error_chain, SessionCredentials, SsoConfigSource, SsoFlow, SsoFlowBuilder,
+    VerificationPrompt,
+};
 
+tokio::task_local! {
+    // rusoto's `CredentialsError` only carries a message, so whether an SSO flow's configuration was
+    // loaded but authentication failed is recorded alongside it, for `ChainProvider`.
+    static AUTHENTICATION_FAILED: Cell<bool>;
+}
+
+fn authentication_failed(error: impl std::error::Error) -> CredentialsError {
+    mark_authentication_failed();
+    CredentialsError::new(error_chain(&error))
+}
+
+fn mark_authentication_failed() {
+    let _ = AUTHENTICATION_FAILED.try_with(|failed| failed.set(true));
+}
+
+/// If SSO configuration can't be loaded, a [`ChainProvider`] will try the next provider. If
+/// configuration is loaded but authentication fails, a [`ChainProvider`] will stop.
 #[async_trait]
 impl<S, V> ProvideAwsCredentials for SsoFlowBuilder<S, V>
 where
@@ -20,17 +41,54 @@ where
             .authenticate()
             .await
             .map(Into::into)
-            .map_err(CredentialsError::new)
+            .map_err(authentication_failed)
     }
 }
 
+/// Credentials are memoized in memory (and shared between clones of the flow) until shortly before
+/// they expire, so repeated calls don't re-read the cache or call AWS.
+///
+/// Errors are authentication errors, so a [`ChainProvider`] will stop.
 #[async_trait]
 impl<V: VerificationPrompt> ProvideAwsCredentials for SsoFlow<V> {
     async fn credentials(&self) -> Result<AwsCredentials, CredentialsError> {
-        self.authenticate()
+        if let Some(credentials) = self.rusoto_credentials.get(self.clock.now()) {
+            return Ok(credentials);
+        }
+
+        let credentials: AwsCredentials = self
+            .authenticate()
             .await
             .map(Into::into)
-            .map_err(CredentialsError::new)
+            .map_err(authentication_failed)?;
+        self.rusoto_credentials.set(&credentials);
+
+        Ok(credentials)
+    }
+}
+
+// Credentials are considered stale this long before they actually expire, to leave time for them
+// to be used.
+const MEMO_BUFFER: chrono::Duration = chrono::Duration::minutes(1);
+
+/// The most recent credentials provided by an [`SsoFlow`].
+#[derive(Debug, Default)]
+pub(crate) struct CredentialsMemo(Mutex<Option<AwsCredentials>>);
+
+impl CredentialsMemo {
+    fn get(&self, now: DateTime<Utc>) -> Option<AwsCredentials> {
+        let memo = self.0.lock().expect("poisoned");
+        memo.as_ref()
+            .filter(|credentials| {
+                credentials
+                    .expires_at()
+                    .is_some_and(|expires_at| expires_at - MEMO_BUFFER > now)
+            })
+            .cloned()
+    }
+
+    fn set(&self, credentials: &AwsCredentials) {
+        *self.0.lock().expect("poisoned") = Some(credentials.clone());
     }
 }
 
@@ -48,6 +106,11 @@ impl From<SessionCredentials> for AwsCredentials {
 /// A generalised version of [`rusoto_credential::ChainProvider`] that provides AWS credentials from
 /// multiple arbitrary sources.
 ///
+/// Providers are tried in order until one succeeds. Unlike rusoto's `ChainProvider`, the chain
+/// stops early if an SSO flow's configuration was loaded but authentication failed (see
+/// [`track_fatal`](Self::track_fatal)), since falling through to other credentials would likely be
+/// surprising. Errors from other providers, and SSO configuration errors, fall through.
+///
 /// # Example
 ///
 /// To exhaust the default rusoto `ChainProvider` before falling back to SSO credentials you could
@@ -96,6 +159,30 @@ impl ChainProvider {
         self.providers.push(Box::new(provider));
         self
     }
+
+    /// Run `future`, e.g. a provider's `credentials()`, and return whether an SSO flow's
+    /// authentication failed while it ran, which stops the chain.
+    ///
+    /// This can be used to apply the same classification in other chains:
+    ///
+    /// ```no_run
+    /// # async fn example(provider: impl rusoto_credential::ProvideAwsCredentials) {
+    /// use aws_sso_flow::ChainProvider;
+    ///
+    /// let (result, fatal) = ChainProvider::track_fatal(provider.credentials()).await;
+    /// if result.is_err() && !fatal {
+    ///     // try another provider
+    /// }
+    /// # }
+    /// ```
+    pub async fn track_fatal<F: Future>(future: F) -> (F::Output, bool) {
+        AUTHENTICATION_FAILED
+            .scope(Cell::new(false), async {
+                let output = future.await;
+                (output, AUTHENTICATION_FAILED.with(Cell::get))
+            })
+            .await
+    }
 }
 
 impl fmt::Debug for ChainProvider {
@@ -114,9 +201,14 @@ impl ProvideAwsCredentials for ChainProvider {
     async fn credentials(&self) -> Result<AwsCredentials, CredentialsError> {
         let mut errors = vec![];
         for provider in &self.providers {
-            match provider.credentials().await {
-                Ok(credentials) => return Ok(credentials),
-                Err(error) => errors.push(error),
+            match Self::track_fatal(provider.credentials()).await {
+                (Ok(credentials), _) => return Ok(credentials),
+                (Err(error), true) => {
+                    // Chains within chains stop too
+                    mark_authentication_failed();
+                    return Err(error);
+                }
+                (Err(error), false) => errors.push(error),
             }
         }
 
@@ -0,0 +1,229 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use regex::Regex;
+
+use crate::{AccountId, RoleName};
+
+/// Identifies the AWS account to sign in to.
+///
+/// Accounts are usually identified by their ID. Since account IDs are hard to remember, an account
+/// can instead be selected by its name. In that case, the accounts available to the user are listed
+/// using the SSO access token, and exactly one account must have the given name.
+///
+/// In AWS shared config, the account name can be set with `sso_account_name` instead of
+/// `sso_account_id`.
+///
+/// # Example
+///
+/// ```
+/// use aws_sso_flow::AccountSelector;
+///
+/// let id = AccountSelector::from("012345678910");
+/// let name = AccountSelector::Name("production".to_string());
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AccountSelector {
+    /// Select the account with the given ID.
+    ///
+    /// This doesn't require the user's accounts to be listed.
+    Id(AccountId),
+
+    /// Select the account with the given name.
+    Name(String),
+}
+
+impl From<String> for AccountSelector {
+    fn from(id: String) -> Self {
+        Self::Id(id.into())
+    }
+}
+
+impl From<&str> for AccountSelector {
+    fn from(id: &str) -> Self {
+        Self::Id(id.into())
+    }
+}
+
+impl From<AccountId> for AccountSelector {
+    fn from(id: AccountId) -> Self {
+        Self::Id(id)
+    }
+}
+
+// `Id` hashes the same as a bare `String`, keeping cache keys stable for configurations that existed
+// before `AccountSelector`.
+impl Hash for AccountSelector {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Self::Id(id) => id.hash(state),
+            Self::Name(name) => {
+                "Name".hash(state);
+                name.hash(state);
+            }
+        }
+    }
+}
+
+impl fmt::Display for AccountSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Id(id) => write!(f, "{id}"),
+            Self::Name(name) => write!(f, "{name:?}"),
+        }
+    }
+}
+
+/// Identifies the role to assume in an account.
+///
+/// Roles are usually identified by their exact name, as it appears in SSO configuration. When the
+/// exact name isn't known (e.g. users only know the permission set name approximately), a role can
+/// be selected by case-insensitive name or by pattern. In that case, the account's roles are listed
+/// using the SSO access token, and exactly one role must match. Roles can also be selected by ARN,
+/// see [`from_arn`](Self::from_arn).
+///
+/// # Example
+///
+/// ```
+/// use aws_sso_flow::RoleSelector;
+/// use regex::Regex;
+///
+/// let exact = RoleSelector::from("PowerUser");
+/// let ignore_case = RoleSelector::NameIgnoreCase("poweruser".to_string());
+/// let pattern = RoleSelector::Pattern(Regex::new("^Power").unwrap());
+/// ```
+#[derive(Clone, Debug)]
+pub enum RoleSelector {
+    /// Select the role with exactly the given name.
+    ///
+    /// This doesn't require the account's roles to be listed.
+    Name(RoleName),
+
+    /// Select the role whose name matches the given name, ignoring case.
+    NameIgnoreCase(String),
+
+    /// Select the role whose name matches the given pattern.
+    Pattern(Regex),
+}
+
+impl RoleSelector {
+    /// Select the role with the given ARN.
+    ///
+    /// IAM Identity Center provisions a role in each account for each permission set, with ARNs
+    /// like `arn:aws:iam::012345678910:role/aws-reserved/sso.amazonaws.com/eu-west-1/AWSReservedSSO_PowerUser_0123456789abcdef`.
+    /// The role is selected by the permission set name (`PowerUser`), which is the role name used
+    /// by SSO. The account in the ARN isn't checked against the configured account.
+    ///
+    /// ```
+    /// use aws_sso_flow::RoleSelector;
+    ///
+    /// let role = RoleSelector::from_arn(
+    ///     "arn:aws:iam::012345678910:role/aws-reserved/sso.amazonaws.com/eu-west-1/AWSReservedSSO_PowerUser_0123456789abcdef",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(role.to_string(), "PowerUser");
+    ///
+    /// assert!(RoleSelector::from_arn("arn:aws:iam::012345678910:role/PowerUser").is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `arn` isn't the ARN of a role provisioned by IAM Identity Center.
+    pub fn from_arn(arn: &str) -> Result<Self, ParseRoleArnError> {
+        let error = || ParseRoleArnError {
+            input: arn.to_string(),
+        };
+        let [prefix, _partition, service, region, account_id, resource] =
+            arn.splitn(6, ':').collect::<Vec<_>>()[..]
+        else {
+            return Err(error());
+        };
+        if prefix != "arn" || service != "iam" || !region.is_empty() {
+            return Err(error());
+        }
+        account_id.parse::<AccountId>().map_err(|_| error())?;
+        let name = resource
+            .strip_prefix("role/aws-reserved/sso.amazonaws.com/")
+            .and_then(|path| path.rsplit('/').next())
+            .and_then(|name| name.strip_prefix("AWSReservedSSO_"))
+            .and_then(|name| name.rsplit_once('_'))
+            .map(|(name, _suffix)| name)
+            .ok_or_else(error)?;
+        let name = name.parse::<RoleName>().map_err(|_| error())?;
+        Ok(Self::Name(name))
+    }
+
+    pub(crate) fn matches(&self, role_name: &str) -> bool {
+        match self {
+            Self::Name(name) => name.as_str() == role_name,
+            Self::NameIgnoreCase(name) => name.to_lowercase() == role_name.to_lowercase(),
+            Self::Pattern(pattern) => pattern.is_match(role_name),
+        }
+    }
+}
+
+impl From<String> for RoleSelector {
+    fn from(name: String) -> Self {
+        Self::Name(name.into())
+    }
+}
+
+impl From<&str> for RoleSelector {
+    fn from(name: &str) -> Self {
+        Self::Name(name.into())
+    }
+}
+
+impl From<RoleName> for RoleSelector {
+    fn from(name: RoleName) -> Self {
+        Self::Name(name)
+    }
+}
+
+// `Hash` is implemented manually since `Regex` doesn't implement it. `Name` hashes the same as a
+// bare `String`, keeping cache keys stable for configurations that existed before `RoleSelector`.
+impl Hash for RoleSelector {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Self::Name(name) => name.hash(state),
+            Self::NameIgnoreCase(name) => {
+                "NameIgnoreCase".hash(state);
+                name.to_lowercase().hash(state);
+            }
+            Self::Pattern(pattern) => {
+                "Pattern".hash(state);
+                pattern.as_str().hash(state);
+            }
+        }
+    }
+}
+
+impl fmt::Display for RoleSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Name(name) => write!(f, "{name}"),
+            Self::NameIgnoreCase(name) => write!(f, "{name} (ignoring case)"),
+            Self::Pattern(pattern) => write!(f, "/{pattern}/"),
+        }
+    }
+}
+
+/// The error returned when [`RoleSelector::from_arn`] is given an invalid ARN.
+#[derive(Clone, Debug)]
+pub struct ParseRoleArnError {
+    input: String,
+}
+
+impl fmt::Display for ParseRoleArnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} is not the ARN of an IAM Identity Center role",
+            self.input
+        )
+    }
+}
+
+impl std::error::Error for ParseRoleArnError {}
@@ -0,0 +1,172 @@
+// Process-wide sharing of client registrations and access tokens between flows.
+//
+// Flows for different accounts or roles have separate caches, but one client registration and one
+// access token work for every account and role under the same start URL. Without sharing, each
+// flow would register its own client (which Identity Center throttles when done in bursts) and
+// prompt the user when its token expires. Instead, flows obtain registrations and tokens for a
+// start URL one at a time, and reuse those obtained by other flows.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+use crate::{
+    builder::start_url_key,
+    cache::CACHE_BUFFER,
+    sso_oidc::{CreateTokenResponse, RegisterClientResponse},
+    Clock, SsoConfig,
+};
+
+#[derive(Debug, Default)]
+pub(crate) struct SharedSession {
+    // Each is held while its value is being obtained, so other flows wait rather than duplicating
+    // the work
+    client: tokio::sync::Mutex<Option<RegisterClientResponse>>,
+    token: tokio::sync::Mutex<Option<CreateTokenResponse>>,
+}
+
+/// The key that flows share sessions by: the start URL and region.
+///
+/// See [`start_url_key`] for how start URLs are compared.
+pub(crate) fn session_key(config: &SsoConfig, verbatim: bool) -> (String, String) {
+    (
+        start_url_key(&config.start_url, verbatim),
+        config.region.to_string(),
+    )
+}
+
+/// The shared session for `config`'s start URL and region, with API calls sent to `endpoint_url`.
+///
+/// Flows using different endpoints (e.g. test servers) don't share sessions, since a registration
+/// or token from one endpoint isn't valid for another.
+pub(crate) fn shared_session(
+    config: &SsoConfig,
+    verbatim: bool,
+    endpoint_url: Option<&Url>,
+) -> Arc<SharedSession> {
+    type Key = ((String, String), Option<String>);
+    static SESSIONS: Mutex<BTreeMap<Key, Arc<SharedSession>>> = Mutex::new(BTreeMap::new());
+
+    let key = (
+        session_key(config, verbatim),
+        endpoint_url.map(ToString::to_string),
+    );
+    Arc::clone(SESSIONS.lock().expect("poisoned").entry(key).or_default())
+}
+
+impl SharedSession {
+    /// Reuse a client registration obtained by another flow, or register one with `init`.
+    pub(crate) async fn client_or_init<F, Fut, E>(
+        &self,
+        clock: &dyn Clock,
+        init: F,
+    ) -> Result<RegisterClientResponse, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<RegisterClientResponse, E>>,
+    {
+        get_or_init(
+            &self.client,
+            clock,
+            |client| client.client_secret_expires_at,
+            init,
+        )
+        .await
+    }
+
+    /// Forget the client registration with `client_id`, e.g. because the server rejected it.
+    ///
+    /// If another flow has already replaced the registration, its replacement is kept.
+    pub(crate) async fn forget_client(&self, client_id: &str) {
+        let mut slot = self.client.lock().await;
+        if slot
+            .as_ref()
+            .is_some_and(|client| client.client_id == client_id)
+        {
+            *slot = None;
+        }
+    }
+
+    /// Forget `access_token`, e.g. because the server rejected it.
+    ///
+    /// If another flow has already replaced the token, its replacement is kept.
+    pub(crate) async fn forget_token(&self, access_token: &str) {
+        let mut slot = self.token.lock().await;
+        if slot
+            .as_ref()
+            .is_some_and(|token| token.access_token == access_token)
+        {
+            *slot = None;
+        }
+    }
+
+    /// Reuse a token obtained by another flow, or obtain one with `init`.
+    pub(crate) async fn token_or_init<F, Fut, E>(
+        &self,
+        clock: &dyn Clock,
+        init: F,
+    ) -> Result<CreateTokenResponse, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<CreateTokenResponse, E>>,
+    {
+        get_or_init(&self.token, clock, |token| token.expires_at, init).await
+    }
+
+    /// A fresh token obtained by another flow, if there is one.
+    ///
+    /// This doesn't wait if another flow is obtaining a token, since that may involve prompting.
+    pub(crate) fn token(&self, clock: &dyn Clock) -> Option<CreateTokenResponse> {
+        let slot = self.token.try_lock().ok()?;
+        slot.as_ref()
+            .filter(|token| is_fresh(token.expires_at, clock))
+            .cloned()
+    }
+
+    /// Remember a token obtained outside of [`token_or_init`](Self::token_or_init).
+    ///
+    /// This is best-effort: if another flow is obtaining a token, its token will be remembered
+    /// instead.
+    pub(crate) fn remember_token(&self, token: CreateTokenResponse) {
+        if let Ok(mut slot) = self.token.try_lock() {
+            *slot = Some(token);
+        }
+    }
+}
+
+/// Reuse `slot`'s value if it's fresh, or replace it with the result of `init`.
+///
+/// Only one `init` runs at a time for a slot. If it fails, the next waiting flow tries its own
+/// `init`, since the failure may be specific to the flow (e.g. its prompt).
+async fn get_or_init<T, F, Fut, E>(
+    slot: &tokio::sync::Mutex<Option<T>>,
+    clock: &dyn Clock,
+    expires_at: impl Fn(&T) -> DateTime<Utc>,
+    init: F,
+) -> Result<T, E>
+where
+    T: Clone,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut slot = slot.lock().await;
+    if let Some(value) = slot.as_ref() {
+        if is_fresh(expires_at(value), clock) {
+            return Ok(value.clone());
+        }
+    }
+
+    let value = init().await?;
+    *slot = Some(value.clone());
+    Ok(value)
+}
+
+fn is_fresh(expires_at: DateTime<Utc>, clock: &dyn Clock) -> bool {
+    let buffer = chrono::Duration::from_std(CACHE_BUFFER).expect("expiry overflow");
+    expires_at - buffer > clock.now()
+}
@@ -0,0 +1,132 @@
+use std::{
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use tokio::{runtime::Handle, task::JoinHandle};
+
+/// The name of the task that refreshes credentials ahead of expiry.
+pub(crate) const REFRESH_AHEAD_TASK: &str = "aws_sso_flow::refresh_ahead";
+
+/// A handle for shutting down the background work of an [`SsoFlow`](crate::SsoFlow).
+///
+/// Flows spawn background tasks for some features, such as
+/// [`refresh_ahead`](crate::SsoFlowBuilder::refresh_ahead). Once shut down, no more tasks are
+/// spawned, so an application can stop them before its runtime is dropped:
+///
+/// ```no_run
+/// # async fn example(flow: aws_sso_flow::BoxedSsoFlow) {
+/// let shutdown = flow.shutdown_handle();
+/// // ... run the application ...
+/// shutdown.shutdown().await;
+/// # }
+/// ```
+///
+/// Tasks are named, e.g. `aws_sso_flow::refresh_ahead`, so they can be identified in
+/// [`running`](Self::running). With the `tokio-console` feature, and when built with
+/// `--cfg tokio_unstable`, the names are also given to tokio so they show up in `tokio-console`.
+#[derive(Clone)]
+pub struct Shutdown {
+    tasks: Arc<BackgroundTasks>,
+}
+
+impl Shutdown {
+    pub(crate) fn new(tasks: Arc<BackgroundTasks>) -> Self {
+        Self { tasks }
+    }
+
+    /// The names of background tasks that are still running.
+    #[must_use]
+    pub fn running(&self) -> Vec<&'static str> {
+        self.tasks.running()
+    }
+
+    /// Whether the flow has been shut down.
+    #[must_use]
+    pub fn is_shut_down(&self) -> bool {
+        self.tasks.is_shut_down()
+    }
+
+    /// Stop spawning background tasks, and wait for running tasks to finish.
+    ///
+    /// Tasks that panicked or were aborted are ignored.
+    pub async fn shutdown(&self) {
+        for handle in self.tasks.close() {
+            let _ = handle.await;
+        }
+    }
+
+    /// Stop spawning background tasks, and abort running tasks.
+    ///
+    /// Aborting a refresh is safe: the cache is only updated once new credentials are obtained.
+    pub fn abort(&self) {
+        for handle in self.tasks.close() {
+            handle.abort();
+        }
+    }
+}
+
+impl fmt::Debug for Shutdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Shutdown")
+            .field("running", &self.running())
+            .field("shut_down", &self.is_shut_down())
+            .finish()
+    }
+}
+
+/// The background tasks spawned by a flow, and flows derived from it.
+#[derive(Debug, Default)]
+pub(crate) struct BackgroundTasks {
+    handles: Mutex<Vec<(&'static str, JoinHandle<()>)>>,
+    shut_down: AtomicBool,
+}
+
+impl BackgroundTasks {
+    pub(crate) fn is_shut_down(&self) -> bool {
+        self.shut_down.load(Ordering::Acquire)
+    }
+
+    fn running(&self) -> Vec<&'static str> {
+        let mut handles = self.handles.lock().expect("poisoned");
+        handles.retain(|(_, handle)| !handle.is_finished());
+        handles.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// Stop spawning tasks, returning the handles of those already spawned.
+    fn close(&self) -> Vec<JoinHandle<()>> {
+        let mut handles = self.handles.lock().expect("poisoned");
+        self.shut_down.store(true, Ordering::Release);
+        handles.drain(..).map(|(_, handle)| handle).collect()
+    }
+
+    /// Spawn `task` on `runtime`, unless the flow has been shut down.
+    ///
+    /// Returns whether the task was spawned.
+    pub(crate) fn spawn<F>(&self, runtime: &Handle, name: &'static str, task: F) -> bool
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut handles = self.handles.lock().expect("poisoned");
+        // Checked under the lock, so a concurrent `shutdown` either waits for the task or prevents it
+        if self.is_shut_down() {
+            return false;
+        }
+        handles.retain(|(_, handle)| !handle.is_finished());
+
+        #[cfg(all(tokio_unstable, feature = "tokio-console"))]
+        let handle = tokio::task::Builder::new()
+            .name(name)
+            .spawn_on(task, runtime)
+            .expect("task should spawn");
+        #[cfg(not(all(tokio_unstable, feature = "tokio-console")))]
+        let handle = runtime.spawn(task);
+
+        handles.push((name, handle));
+        true
+    }
+}
@@ -0,0 +1,200 @@
+use std::{path::PathBuf, time::Duration};
+
+use serde::Serializer;
+use url::Url;
+
+use crate::{builder::parse_start_url, AccountSelector, CacheStage};
+
+/// A redacted description of an [`SsoFlow`](crate::SsoFlow)'s effective configuration.
+///
+/// This is meant to be attached to bug reports, so it includes everything that affects how the
+/// flow behaves, but no tokens or credentials. The start URL and account are partially masked, so
+/// they can be told apart without identifying the organisation. Durations are serialized as
+/// seconds.
+///
+/// See [`SsoFlow::debug_snapshot`](crate::SsoFlow::debug_snapshot).
+#[derive(Clone, Debug, serde::Serialize)]
+#[non_exhaustive]
+pub struct DebugSnapshot {
+    /// The version of the crate.
+    pub version: &'static str,
+
+    /// The crate's enabled features.
+    pub features: Vec<&'static str>,
+
+    /// The HTTP backend used for API calls, `sdk-clients` or `lite-http`.
+    pub transport: &'static str,
+
+    /// Where the SSO configuration was loaded from.
+    ///
+    /// See [`SsoConfigSource::describe`](crate::SsoConfigSource::describe).
+    pub config_source: String,
+
+    /// The region in which API calls are made.
+    pub region: String,
+
+    /// The start URL, with all but the start of its first host label masked.
+    pub start_url: String,
+
+    /// The account selector, with all but the last 4 digits of an ID, or the first character of a
+    /// name, masked.
+    pub account: String,
+
+    /// The role selector.
+    pub role: String,
+
+    /// The endpoint of the SSO OIDC API, which registers clients and issues access tokens.
+    pub oidc_endpoint: String,
+
+    /// The endpoint of the SSO API, which issues role credentials.
+    pub portal_endpoint: String,
+
+    /// The cache file for each stage, in the order the flow uses them.
+    pub cache: Vec<SnapshotCacheEntry>,
+
+    /// Whether the flow only uses cached credentials.
+    pub offline: bool,
+
+    /// Whether start URLs are normalised when matching portals.
+    pub normalize_start_url: bool,
+
+    /// The timeout for each API call, if any.
+    #[serde(serialize_with = "optional_seconds")]
+    pub api_timeout: Option<Duration>,
+
+    /// The timeout for connecting to API endpoints, if any.
+    #[serde(serialize_with = "optional_seconds")]
+    pub connect_timeout: Option<Duration>,
+
+    /// The minimum interval between polls for an access token.
+    #[serde(serialize_with = "seconds")]
+    pub min_poll_interval: Duration,
+
+    /// What happens when the verification prompt fails, e.g. `FailFast`.
+    pub prompt_error_policy: String,
+
+    /// How many times a throttled `GetRoleCredentials` call is attempted.
+    pub throttle_max_attempts: u32,
+
+    /// The delay before the first retry of a throttled call, which doubles for each retry.
+    #[serde(serialize_with = "seconds")]
+    pub throttle_base_delay: Duration,
+
+    /// How long before expiry credentials are refreshed in the background, if at all.
+    #[serde(serialize_with = "optional_seconds")]
+    pub refresh_ahead: Option<Duration>,
+
+    /// The path of the audit log, if any.
+    pub audit_log: Option<PathBuf>,
+
+    /// The path of the AWS CLI token cache file that's shared with the CLI, if any.
+    pub cli_session: Option<PathBuf>,
+}
+
+/// The cache file of a single stage in a [`DebugSnapshot`].
+#[derive(Clone, Debug, serde::Serialize)]
+#[non_exhaustive]
+pub struct SnapshotCacheEntry {
+    /// The stage of the flow, e.g. `token`.
+    pub stage: &'static str,
+
+    /// The path of the stage's cache file.
+    ///
+    /// This is `None` if there's no cache directory for the stage, or if it's only cached in
+    /// memory.
+    pub path: Option<PathBuf>,
+}
+
+impl SnapshotCacheEntry {
+    pub(crate) fn new(stage: CacheStage, path: Option<PathBuf>) -> Self {
+        Self {
+            stage: stage.prefix(),
+            path,
+        }
+    }
+}
+
+/// The crate's enabled features.
+pub(crate) fn features() -> Vec<&'static str> {
+    [
+        ("aws-sdk", cfg!(feature = "aws-sdk")),
+        ("browser", cfg!(feature = "browser")),
+        ("diagnostics", cfg!(feature = "diagnostics")),
+        ("doctor", cfg!(feature = "doctor")),
+        ("eks", cfg!(feature = "eks")),
+        ("http", cfg!(feature = "http")),
+        ("lite-http", cfg!(feature = "lite-http")),
+        ("native-tls", cfg!(feature = "native-tls")),
+        ("notify", cfg!(feature = "notify")),
+        ("process", cfg!(feature = "process")),
+        ("rusoto", cfg!(feature = "rusoto")),
+        ("rustls", cfg!(feature = "rustls")),
+        ("sdk-clients", cfg!(feature = "sdk-clients")),
+        ("test-server", cfg!(feature = "test-server")),
+        ("test-util", cfg!(feature = "test-util")),
+        ("tokio-console", cfg!(feature = "tokio-console")),
+        ("webview", cfg!(feature = "webview")),
+    ]
+    .into_iter()
+    .filter_map(|(feature, enabled)| enabled.then_some(feature))
+    .collect()
+}
+
+/// The HTTP backend used for API calls.
+pub(crate) fn transport() -> &'static str {
+    if cfg!(feature = "lite-http") {
+        "lite-http"
+    } else {
+        "sdk-clients"
+    }
+}
+
+/// Mask all but the first two characters of a start URL's first host label, e.g.
+/// `https://my****.awsapps.com/start`.
+pub(crate) fn mask_start_url(start_url: &str) -> String {
+    let Some(url) = parse_start_url(start_url).ok().filter(Url::has_host) else {
+        return "(invalid start URL)".to_string();
+    };
+    let host = url.host_str().unwrap_or_default();
+    let (label, rest) = host.split_once('.').unwrap_or((host, ""));
+    let masked = mask(label, label.chars().count().min(2));
+    let dot = if rest.is_empty() { "" } else { "." };
+    format!("{}://{masked}{dot}{rest}{}", url.scheme(), url.path())
+}
+
+/// Mask all but the last 4 digits of an account ID, or all but the first character of a name.
+pub(crate) fn mask_account(account: &AccountSelector) -> String {
+    match account {
+        AccountSelector::Id(id) => {
+            let id = id.as_str();
+            let hidden = id.len().saturating_sub(4);
+            format!("{}{}", "*".repeat(hidden), &id[hidden..])
+        }
+        AccountSelector::Name(name) => format!("{:?}", mask(name, 1)),
+    }
+}
+
+/// Keep the first `visible` characters of `value`, and replace the rest with `*`.
+fn mask(value: &str, visible: usize) -> String {
+    value
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i < visible { c } else { '*' })
+        .collect()
+}
+
+#[allow(clippy::ref_option, clippy::trivially_copy_pass_by_ref)]
+fn optional_seconds<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => seconds(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
@@ -1,37 +1,145 @@
 //! Cleaned up AWS SSO API.
 
-use std::fmt;
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
-use aws_config::SdkConfig;
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Utc};
 
-use crate::cache::Expiry;
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
 
+use crate::{
+    cache::Expiry,
+    timestamp,
+    transport::{self, Transport},
+    AccountId, AccountInfo, RoleInfo, RoleName, SsoApiError,
+};
+
+pub(crate) const THROTTLE_MAX_ATTEMPTS: u32 = 5;
+pub(crate) const THROTTLE_BASE_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Clone)]
 pub(crate) struct Client {
-    inner: aws_sdk_sso::Client,
+    transport: Arc<Transport>,
 }
 
 impl Client {
-    pub(crate) fn new(config: &SdkConfig) -> Self {
-        Self {
-            inner: aws_sdk_sso::Client::new(config),
-        }
+    pub(crate) fn new(transport: Arc<Transport>) -> Self {
+        Self { transport }
     }
 
     pub(crate) async fn get_role_credentials(
         &self,
         request: GetRoleCredentialsRequest,
-    ) -> Result<GetRoleCredentialsResponse, String> {
-        self.inner
-            .get_role_credentials()
-            .access_token(request.access_token)
-            .account_id(request.account_id)
-            .role_name(request.role_name)
-            .send()
-            .await
-            .map_err(|error| error.to_string())
-            .and_then(TryInto::try_into)
+    ) -> Result<GetRoleCredentialsResponse, SsoApiError> {
+        // Identity Center throttles `GetRoleCredentials` when many users authenticate at once, so
+        // retry throttled requests with exponential backoff. The transports don't retry this call
+        // themselves, so there are at most `THROTTLE_MAX_ATTEMPTS` requests.
+        let mut delay = THROTTLE_BASE_DELAY;
+        let mut attempt = 1;
+        loop {
+            let result = self
+                .transport
+                .get_role_credentials(
+                    &request.access_token,
+                    request.account_id.as_str(),
+                    request.role_name.as_str(),
+                )
+                .await;
+            match result {
+                Ok(res) => break res.try_into().map_err(SsoApiError::new),
+                Err(error) if error.is_throttling() && attempt < THROTTLE_MAX_ATTEMPTS => {
+                    tokio::time::sleep(jitter(delay)).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(error) => break Err(error),
+            }
+        }
+    }
+
+    pub(crate) fn list_accounts(
+        &self,
+        request: ListAccountsRequest,
+    ) -> BoxStream<'static, Result<AccountInfo, SsoApiError>> {
+        let transport = Arc::clone(&self.transport);
+        paginate(move |next_token| {
+            let transport = Arc::clone(&transport);
+            let access_token = request.access_token.clone();
+            async move {
+                let res = transport.list_accounts(&access_token, next_token).await?;
+                Ok((res.account_list, res.next_token))
+            }
+        })
+    }
+
+    pub(crate) fn list_account_roles(
+        &self,
+        request: ListAccountRolesRequest,
+    ) -> BoxStream<'static, Result<RoleInfo, SsoApiError>> {
+        let transport = Arc::clone(&self.transport);
+        paginate(move |next_token| {
+            let transport = Arc::clone(&transport);
+            let access_token = request.access_token.clone();
+            let account_id = request.account_id.clone();
+            async move {
+                let res = transport
+                    .list_account_roles(&access_token, account_id.as_str(), next_token)
+                    .await?;
+                Ok((res.role_list, res.next_token))
+            }
+        })
     }
+
+    pub(crate) async fn logout(&self, access_token: &str) -> Result<(), SsoApiError> {
+        self.transport.logout(access_token).await
+    }
+}
+
+/// Stream the items of a paginated operation, converting each to `T`.
+///
+/// `page` is called with the token for each page (`None` for the first), and returns the page's
+/// items and the token for the next page, if any.
+fn paginate<F, Fut, I, T>(page: F) -> BoxStream<'static, Result<T, SsoApiError>>
+where
+    F: Fn(Option<String>) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = Result<(Vec<I>, Option<String>), SsoApiError>>
+        + Send
+        + 'static,
+    I: TryInto<T, Error = String> + Send + 'static,
+    T: Send + 'static,
+{
+    // `None` is the first page, `Some(None)` means there are no more pages
+    stream::try_unfold(Some(None), move |next_token: Option<Option<String>>| {
+        let page = next_token.map(&page);
+        async move {
+            let Some(page) = page else {
+                return Ok(None);
+            };
+            let (items, next_token) = page.await?;
+            let items = stream::iter(items.into_iter().map(TryInto::try_into));
+            Ok(Some((items, next_token.map(Some))))
+        }
+    })
+    .map_ok(|items| items.map_err(SsoApiError::new))
+    .try_flatten()
+    .boxed()
+}
+
+/// Pick a random delay between `delay / 2` and `delay`.
+///
+/// This spreads out retries from clients that were throttled at the same time. The randomness
+/// doesn't need to be good, so the system clock's sub-second nanoseconds are used.
+fn jitter(delay: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |now| now.subsec_nanos());
+    delay / 2 + delay.mul_f64(f64::from(nanos) / 2e9)
 }
 
 impl fmt::Debug for Client {
@@ -43,8 +151,48 @@ impl fmt::Debug for Client {
 #[derive(Hash)]
 pub(crate) struct GetRoleCredentialsRequest {
     pub(crate) access_token: String,
-    pub(crate) account_id: String,
-    pub(crate) role_name: String,
+    pub(crate) account_id: AccountId,
+    pub(crate) role_name: RoleName,
+}
+
+pub(crate) struct ListAccountsRequest {
+    pub(crate) access_token: String,
+}
+
+pub(crate) struct ListAccountRolesRequest {
+    pub(crate) access_token: String,
+    pub(crate) account_id: AccountId,
+}
+
+impl TryFrom<transport::AccountInfo> for AccountInfo {
+    type Error = String;
+
+    fn try_from(account: transport::AccountInfo) -> Result<Self, Self::Error> {
+        Ok(Self {
+            account_id: account
+                .account_id
+                .ok_or("invalid ListAccounts response: missing account_id")?,
+            account_name: account.account_name,
+            email_address: account.email_address,
+        })
+    }
+}
+
+impl TryFrom<transport::RoleInfo> for RoleInfo {
+    type Error = String;
+
+    fn try_from(role: transport::RoleInfo) -> Result<Self, Self::Error> {
+        macro_rules! invalid_res {
+            ($msg:literal) => {
+                concat!("invalid ListAccountRoles response: ", $msg)
+            };
+        }
+
+        Ok(Self {
+            account_id: role.account_id.ok_or(invalid_res!("missing account_id"))?,
+            role_name: role.role_name.ok_or(invalid_res!("missing role_name"))?,
+        })
+    }
 }
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
@@ -61,14 +209,10 @@ impl Expiry for GetRoleCredentialsResponse {
     }
 }
 
-impl TryFrom<aws_sdk_sso::operation::get_role_credentials::GetRoleCredentialsOutput>
-    for GetRoleCredentialsResponse
-{
+impl TryFrom<transport::GetRoleCredentialsOutput> for GetRoleCredentialsResponse {
     type Error = String;
 
-    fn try_from(
-        res: aws_sdk_sso::operation::get_role_credentials::GetRoleCredentialsOutput,
-    ) -> Result<Self, Self::Error> {
+    fn try_from(res: transport::GetRoleCredentialsOutput) -> Result<Self, Self::Error> {
         macro_rules! invalid_res {
             ($msg:literal) => {
                 concat!("invalid GetRoleCredentials response: ", $msg)
@@ -78,9 +222,8 @@ impl TryFrom<aws_sdk_sso::operation::get_role_credentials::GetRoleCredentialsOut
         let credentials = res
             .role_credentials
             .ok_or(invalid_res!("missing role_credentials"))?;
-        let chrono::LocalResult::Single(expires_at) = Utc.timestamp_millis_opt(credentials.expiration) else {
-            panic!("invalid expires_at");
-        };
+        let expires_at = timestamp::from_millis("expiration", credentials.expiration)
+            .map_err(|error| format!(invalid_res!("{}"), error))?;
         Ok(Self {
             access_key_id: credentials
                 .access_key_id
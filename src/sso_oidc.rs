@@ -1,36 +1,95 @@
 //! Cleaned up AWS SSO OIDC API.
 
-use std::fmt;
+use std::{fmt, pin::pin, sync::Arc, time::Duration};
 
-use aws_config::SdkConfig;
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Utc};
+use futures::future::{self, Either};
 use url::Url;
 
-use crate::{cache, VerificationPrompt};
+use crate::{
+    cache,
+    pending::Poller,
+    timestamp,
+    transport::{self, Transport},
+    BoxError, Clock, FlowId, PendingVerification, PromptContext, PromptErrorPolicy, SsoApiError,
+    SsoConfig, SsoTimeoutError, VerificationPrompt, VerificationStatus,
+};
 
+/// The default minimum interval between `CreateToken` polls.
+pub(crate) const DEFAULT_MIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How much to increase the poll interval by when the server asks clients to slow down.
+pub(crate) const SLOW_DOWN_INCREMENT: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
 pub(crate) struct Client {
-    inner: aws_sdk_ssooidc::Client,
+    transport: Arc<Transport>,
+    clock: Arc<dyn Clock>,
+    pub(crate) min_poll_interval: Duration,
+    pub(crate) prompt_error_policy: PromptErrorPolicy,
 }
 
 impl Client {
-    pub(crate) fn new(config: &SdkConfig) -> Self {
+    pub(crate) fn new(transport: Arc<Transport>, clock: Arc<dyn Clock>) -> Self {
         Self {
-            inner: aws_sdk_ssooidc::Client::new(config),
+            transport,
+            clock,
+            min_poll_interval: DEFAULT_MIN_POLL_INTERVAL,
+            prompt_error_policy: PromptErrorPolicy::default(),
         }
     }
 
+    /// Poll for tokens no more often than `min_poll_interval`, whatever interval the server asks
+    /// for.
+    pub(crate) fn with_min_poll_interval(mut self, min_poll_interval: Duration) -> Self {
+        self.min_poll_interval = min_poll_interval;
+        self
+    }
+
+    /// Handle verification prompt errors according to `policy`.
+    pub(crate) fn with_prompt_error_policy(mut self, policy: PromptErrorPolicy) -> Self {
+        self.prompt_error_policy = policy;
+        self
+    }
+
     pub(crate) async fn register_client(
         &self,
         request: RegisterClientRequest,
-    ) -> Result<RegisterClientResponse, String> {
-        self.inner
-            .register_client()
-            .client_name(request.client_name)
-            .client_type("public")
-            .send()
-            .await
-            .map_err(|error| error.to_string())
-            .and_then(TryInto::try_into)
+    ) -> Result<RegisterClientResponse, SsoApiError> {
+        let res = self.transport.register_client(&request.client_name).await?;
+        res.try_into().map_err(SsoApiError::new)
+    }
+
+    pub(crate) async fn start_device_authorization(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        start_url: &str,
+    ) -> Result<StartDeviceAuthorizationResponse, SsoApiError> {
+        let res = self
+            .transport
+            .start_device_authorization(client_id, client_secret, start_url)
+            .await?;
+        res.try_into().map_err(SsoApiError::new)
+    }
+
+    /// Make a single `CreateToken` request for a device authorization.
+    pub(crate) async fn try_create_token(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        start_device_authorization_response: &StartDeviceAuthorizationResponse,
+    ) -> Result<CreateTokenResponse, SsoApiError> {
+        let res = self
+            .transport
+            .create_token(
+                client_id,
+                client_secret,
+                &start_device_authorization_response.device_code,
+                &start_device_authorization_response.user_code,
+            )
+            .await?;
+        CreateTokenResponse::from_output(res, self.clock.now()).map_err(SsoApiError::new)
     }
 
     pub(crate) async fn create_token<V: VerificationPrompt>(
@@ -38,51 +97,148 @@ impl Client {
         request: CreateTokenRequest,
         prompt: V,
     ) -> Result<CreateTokenResponse, CreateTokenError<V::Error>> {
-        let client_id = request.client_id.clone();
-        let client_secret = request.client_secret.clone();
-
-        let start_device_authorization_response: StartDeviceAuthorizationResponse = self
-            .inner
-            .start_device_authorization()
-            .client_id(request.client_id)
-            .client_secret(request.client_secret)
-            .start_url(request.start_url)
-            .send()
+        let start_device_authorization_response = self
+            .start_device_authorization(
+                &request.client_id,
+                &request.client_secret,
+                &request.config.start_url,
+            )
             .await
-            .map_err(|error| error.to_string())
-            .and_then(TryInto::try_into)
             .map_err(CreateTokenError::Api)?;
 
-        prompt
-            .prompt(start_device_authorization_response.verification_uri_complete)
-            .await
-            .map_err(CreateTokenError::VerificationPrompt)?;
-
-        let create_token_request = self
-            .inner
-            .create_token()
-            .client_id(client_id)
-            .client_secret(client_secret)
-            .code(start_device_authorization_response.user_code)
-            .device_code(start_device_authorization_response.device_code)
-            .grant_type("urn:ietf:params:oauth:grant-type:device_code".to_string());
-        loop {
-            match create_token_request.clone().send().await {
-                Ok(res) => break res.try_into().map_err(CreateTokenError::Api),
-                Err(aws_sdk_ssooidc::error::SdkError::ServiceError(err))
-                    if err.err().is_authorization_pending_exception() =>
+        let issued_at = self.clock.now();
+        let (pending, poller) = PendingVerification::new(
+            start_device_authorization_response.user_code.clone(),
+            issued_at + start_device_authorization_response.expires_in,
+            start_device_authorization_response
+                .interval
+                .max(self.min_poll_interval),
+        );
+        let prompted = self.transport.config().faults.prompt(|| {
+            prompt.prompt_with_context(PromptContext::new(
+                start_device_authorization_response
+                    .verification_uri_complete
+                    .clone(),
+                Arc::clone(&request.config),
+                pending,
+                request.flow_id,
+            ))
+        });
+
+        // Poll while the prompt runs, so prompts that wait for the user (e.g. until a browser
+        // closes) don't delay picking up the token. If the token arrives first, the prompt is
+        // dropped.
+        let outcome = {
+            let mut polling = pin!(self.poll_token(
+                &request,
+                &start_device_authorization_response,
+                &poller,
+                issued_at,
+            ));
+            match future::select(pin!(prompted), polling.as_mut()).await {
+                Either::Left((Ok(()), _)) => Ok((polling.await, None)),
+                Either::Left((Err(error), _))
+                    if self.prompt_error_policy == PromptErrorPolicy::ContinuePolling =>
                 {
-                    tokio::time::sleep(start_device_authorization_response.interval).await;
+                    Ok((polling.await, Some(error)))
                 }
-                Err(aws_sdk_ssooidc::error::SdkError::ServiceError(err))
-                    if err.err().is_expired_token_exception() =>
-                {
-                    return Err(CreateTokenError::VerificationPromptTimeout);
+                Either::Left((Err(error), _)) => Err(error),
+                Either::Right((result, _)) => Ok((result, None)),
+            }
+        };
+        let (result, prompt_error) = match outcome {
+            Ok(outcome) => outcome,
+            Err(error) => {
+                poller.finish(VerificationStatus::Failed);
+                return Err(CreateTokenError::VerificationPrompt(error));
+            }
+        };
+        poller.finish(match &result {
+            Ok(_) => VerificationStatus::Approved,
+            Err(CreateTokenError::VerificationPromptTimeout(_)) => VerificationStatus::Expired,
+            Err(_) => VerificationStatus::Failed,
+        });
+        match (result, prompt_error) {
+            // The prompt failing likely explains why the user didn't grant access in time
+            (Err(CreateTokenError::VerificationPromptTimeout(_)), Some(error)) => {
+                Err(CreateTokenError::VerificationPrompt(error))
+            }
+            (result, _) => result,
+        }
+    }
+
+    async fn poll_token<E>(
+        &self,
+        request: &CreateTokenRequest,
+        start_device_authorization_response: &StartDeviceAuthorizationResponse,
+        poller: &Poller,
+        issued_at: DateTime<Utc>,
+    ) -> Result<CreateTokenResponse, CreateTokenError<E>> {
+        loop {
+            poller.attempt();
+            let result = self
+                .try_create_token(
+                    &request.client_id,
+                    &request.client_secret,
+                    start_device_authorization_response,
+                )
+                .await;
+            match result {
+                Ok(token) => break Ok(token),
+                Err(error) if error.is("AuthorizationPendingException") => {
+                    poller.wait(self.clock.now()).await;
+                }
+                // RFC 8628 asks clients to poll 5s less often each time they're told to slow down
+                Err(error) if error.is("SlowDownException") => {
+                    poller.slow_down(SLOW_DOWN_INCREMENT);
+                    poller.wait(self.clock.now()).await;
+                }
+                Err(error) if error.is("ExpiredTokenException") => {
+                    let elapsed = (self.clock.now() - issued_at).to_std().unwrap_or_default();
+                    return Err(CreateTokenError::VerificationPromptTimeout(
+                        SsoTimeoutError::new(elapsed, poller.expires_at(), poller.attempts()),
+                    ));
                 }
-                Err(error) => return Err(CreateTokenError::Api(error.to_string())),
+                // The user may still grant access while the code is valid, so a network blip or
+                // server error needn't abort the flow
+                Err(error) if error.is_retryable() && self.clock.now() < poller.expires_at() => {
+                    poller.retry();
+                    poller.wait(self.clock.now()).await;
+                }
+                Err(error) => return Err(CreateTokenError::Api(error)),
             }
         }
     }
+
+    /// Obtain a new access token with a refresh token.
+    ///
+    /// The response includes the refresh token to use next time, since it may be rotated.
+    pub(crate) async fn refresh_token(
+        &self,
+        request: RefreshTokenRequest,
+    ) -> Result<RefreshTokenResponse, SsoApiError> {
+        let res = self
+            .transport
+            .refresh_token(
+                &request.client_id,
+                &request.client_secret,
+                &request.refresh_token,
+            )
+            .await?;
+        let refresh_token = res.refresh_token.clone();
+        let token =
+            CreateTokenResponse::from_output(res, self.clock.now()).map_err(SsoApiError::new)?;
+        Ok(RefreshTokenResponse {
+            token,
+            refresh_token,
+        })
+    }
+
+    /// Get the server's current time.
+    #[cfg_attr(not(feature = "doctor"), allow(dead_code))]
+    pub(crate) async fn server_time(&self) -> Result<DateTime<Utc>, BoxError> {
+        self.transport.server_time().await
+    }
 }
 
 impl fmt::Debug for Client {
@@ -96,7 +252,7 @@ pub(crate) struct RegisterClientRequest {
     pub(crate) client_name: String,
 }
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 #[allow(clippy::struct_field_names)]
 pub(crate) struct RegisterClientResponse {
     pub(crate) client_id: String,
@@ -110,25 +266,19 @@ impl cache::Expiry for RegisterClientResponse {
     }
 }
 
-impl TryFrom<aws_sdk_ssooidc::operation::register_client::RegisterClientOutput>
-    for RegisterClientResponse
-{
+impl TryFrom<transport::RegisterClientOutput> for RegisterClientResponse {
     type Error = String;
 
-    fn try_from(
-        res: aws_sdk_ssooidc::operation::register_client::RegisterClientOutput,
-    ) -> Result<Self, Self::Error> {
+    fn try_from(res: transport::RegisterClientOutput) -> Result<Self, Self::Error> {
         macro_rules! invalid_res {
             ($msg:literal) => {
                 concat!("invalid RegisterClient response: ", $msg)
             };
         }
 
-        let chrono::LocalResult::Single(client_secret_expires_at) =
-            Utc.timestamp_opt(res.client_secret_expires_at, 0)
-        else {
-            panic!("invalid client_secret_expires_at");
-        };
+        let client_secret_expires_at =
+            timestamp::from_secs("client_secret_expires_at", res.client_secret_expires_at)
+                .map_err(|error| format!(invalid_res!("{}"), error))?;
         Ok(Self {
             client_id: res.client_id.ok_or(invalid_res!("missing client_id"))?,
             client_secret: res
@@ -143,10 +293,22 @@ impl TryFrom<aws_sdk_ssooidc::operation::register_client::RegisterClientOutput>
 pub(crate) struct CreateTokenRequest {
     pub(crate) client_id: String,
     pub(crate) client_secret: String,
-    pub(crate) start_url: String,
+    pub(crate) config: Arc<SsoConfig>,
+    pub(crate) flow_id: FlowId,
 }
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub(crate) struct RefreshTokenRequest {
+    pub(crate) client_id: String,
+    pub(crate) client_secret: String,
+    pub(crate) refresh_token: String,
+}
+
+pub(crate) struct RefreshTokenResponse {
+    pub(crate) token: CreateTokenResponse,
+    pub(crate) refresh_token: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub(crate) struct CreateTokenResponse {
     pub(crate) access_token: String,
     pub(crate) expires_at: DateTime<Utc>,
@@ -158,12 +320,9 @@ impl cache::Expiry for CreateTokenResponse {
     }
 }
 
-impl TryFrom<aws_sdk_ssooidc::operation::create_token::CreateTokenOutput> for CreateTokenResponse {
-    type Error = String;
-
-    fn try_from(
-        res: aws_sdk_ssooidc::operation::create_token::CreateTokenOutput,
-    ) -> Result<Self, Self::Error> {
+impl CreateTokenResponse {
+    // Not `TryFrom` since the expiry is relative to the time the response was received.
+    fn from_output(res: transport::CreateTokenOutput, now: DateTime<Utc>) -> Result<Self, String> {
         macro_rules! invalid_res {
             ($msg:literal) => {
                 concat!("invalid CreateToken response: ", $msg)
@@ -174,34 +333,31 @@ impl TryFrom<aws_sdk_ssooidc::operation::create_token::CreateTokenOutput> for Cr
             access_token: res
                 .access_token
                 .ok_or(invalid_res!("missing access_token"))?,
-            expires_at: Utc::now() + chrono::Duration::seconds(res.expires_in.into()),
+            expires_at: now + chrono::Duration::seconds(res.expires_in.into()),
         })
     }
 }
 
 #[derive(Debug)]
 pub(crate) enum CreateTokenError<E> {
-    Api(String),
+    Api(SsoApiError),
     VerificationPrompt(E),
-    VerificationPromptTimeout,
+    VerificationPromptTimeout(SsoTimeoutError),
 }
 
 #[derive(Debug)]
-struct StartDeviceAuthorizationResponse {
-    device_code: String,
-    interval: std::time::Duration,
-    user_code: String,
-    verification_uri_complete: Url,
+pub(crate) struct StartDeviceAuthorizationResponse {
+    pub(crate) device_code: String,
+    pub(crate) expires_in: chrono::Duration,
+    pub(crate) interval: std::time::Duration,
+    pub(crate) user_code: String,
+    pub(crate) verification_uri_complete: Url,
 }
 
-impl TryFrom<aws_sdk_ssooidc::operation::start_device_authorization::StartDeviceAuthorizationOutput>
-    for StartDeviceAuthorizationResponse
-{
+impl TryFrom<transport::StartDeviceAuthorizationOutput> for StartDeviceAuthorizationResponse {
     type Error = String;
 
-    fn try_from(
-        res: aws_sdk_ssooidc::operation::start_device_authorization::StartDeviceAuthorizationOutput,
-    ) -> Result<Self, Self::Error> {
+    fn try_from(res: transport::StartDeviceAuthorizationOutput) -> Result<Self, Self::Error> {
         macro_rules! invalid_res {
             ($msg:literal) => {
                 concat!("invalid StartDeviceAuthorization response: ", $msg)
@@ -210,8 +366,11 @@ impl TryFrom<aws_sdk_ssooidc::operation::start_device_authorization::StartDevice
 
         Ok(Self {
             device_code: res.device_code.ok_or(invalid_res!("missing device_code"))?,
+            expires_in: chrono::Duration::seconds(res.expires_in.into()),
             interval: std::time::Duration::from_secs(
-                res.interval.try_into().expect("interval should fit u64"),
+                res.interval
+                    .try_into()
+                    .map_err(|_| invalid_res!("negative interval"))?,
             ),
             user_code: res.user_code.ok_or(invalid_res!("missing user_code"))?,
             verification_uri_complete: res
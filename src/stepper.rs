@@ -0,0 +1,321 @@
+use std::{fmt, time::Duration};
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+use crate::{
+    cache::CacheStage,
+    sso::GetRoleCredentialsResponse,
+    sso_oidc::{
+        CreateTokenResponse, RegisterClientResponse, StartDeviceAuthorizationResponse,
+        SLOW_DOWN_INCREMENT,
+    },
+    FlowId, SessionCredentials, SsoApiError, SsoFlow, SsoFlowError, SsoTimeoutError,
+    VerificationPrompt,
+};
+
+/// The state of a [`FlowStepper`].
+///
+/// A flow moves through the states in order, though it may start part-way through if earlier
+/// stages are cached.
+#[derive(Debug)]
+pub enum FlowState {
+    /// A client registration is needed to start verification.
+    ///
+    /// Advancing registers a client (or reuses a cached registration) and starts verification.
+    NeedsClient,
+
+    /// The user needs to grant access.
+    ///
+    /// The user should be directed to the [`verification_url`](VerificationInfo::verification_url).
+    /// Advancing checks once whether they've granted access, and should be repeated every
+    /// [`interval`](VerificationInfo::interval) until the state changes.
+    NeedsToken(VerificationInfo),
+
+    /// An access token is available, and credentials are needed.
+    ///
+    /// Advancing resolves the account and role, and gets credentials for them.
+    NeedsCredentials,
+
+    /// Credentials were obtained, so there's nothing more to do.
+    Done(SessionCredentials),
+}
+
+/// Details of a verification the user needs to complete.
+///
+/// See [`FlowState::NeedsToken`].
+#[derive(Clone, Debug)]
+pub struct VerificationInfo {
+    /// The URL at which the user grants access, which includes the user code.
+    pub verification_url: Url,
+
+    /// The code the user should see when granting access.
+    pub user_code: String,
+
+    /// When the user code expires, after which the user can no longer grant access.
+    pub expires_at: DateTime<Utc>,
+
+    /// How long to wait before advancing again.
+    pub interval: Duration,
+}
+
+/// An [`SsoFlow`] that's advanced one step at a time.
+///
+/// Whereas [`SsoFlow::authenticate`] runs the whole flow and calls the verification prompt, a
+/// stepper leaves it to the caller to advance the flow and show verification details. This suits
+/// GUIs and TUIs that drive the flow from their own event loops.
+///
+/// Results are cached as they are by [`SsoFlow::authenticate`].
+///
+/// ```no_run
+/// # async fn example(flow: aws_sso_flow::BoxedSsoFlow) -> Result<(), Box<dyn std::error::Error>> {
+/// use aws_sso_flow::FlowState;
+///
+/// let mut stepper = flow.stepper().await?;
+/// let credentials = loop {
+///     match stepper.advance().await? {
+///         FlowState::NeedsToken(info) => {
+///             println!("Go to {} to sign in with SSO", info.verification_url);
+///             tokio::time::sleep(info.interval).await;
+///         }
+///         FlowState::Done(credentials) => break credentials.clone(),
+///         _ => {}
+///     }
+/// };
+/// # Ok(()) }
+/// ```
+pub struct FlowStepper<V> {
+    flow: SsoFlow<V>,
+    flow_id: FlowId,
+    state: FlowState,
+    verification: Option<Verification>,
+    token: Option<CreateTokenResponse>,
+    // Whether a rejected client registration or access token has been replaced, which is only
+    // tried once each
+    reregistered: bool,
+    token_rejected: bool,
+}
+
+/// A started verification, while in [`FlowState::NeedsToken`].
+struct Verification {
+    client: RegisterClientResponse,
+    authorization: StartDeviceAuthorizationResponse,
+    issued_at: DateTime<Utc>,
+    attempts: u32,
+}
+
+impl<V: VerificationPrompt> FlowStepper<V> {
+    /// Construct a stepper, starting from the first stage that isn't cached.
+    pub(crate) async fn new(flow: SsoFlow<V>) -> Result<Self, SsoFlowError<V::Error>> {
+        let flow_id = FlowId::new();
+        let mut stepper = Self {
+            flow,
+            flow_id,
+            state: FlowState::NeedsClient,
+            verification: None,
+            token: None,
+            reregistered: false,
+            token_rejected: false,
+        };
+        flow_id
+            .instrument(stepper.init())
+            .await
+            .map_err(|error| error.with_flow_id(flow_id))?;
+        Ok(stepper)
+    }
+
+    async fn init(&mut self) -> Result<(), SsoFlowError<V::Error>> {
+        let credentials: Option<GetRoleCredentialsResponse> = self
+            .flow
+            .cache
+            .get(CacheStage::Credentials)
+            .await
+            .map_err(|error| SsoFlowError::from_cache(error, |never| match never {}))?;
+        if let Some(credentials) = credentials {
+            self.state = FlowState::Done(credentials.into());
+            return Ok(());
+        }
+        if self.flow.offline {
+            return Err(SsoFlowError::OfflineUnavailable { flow_id: None });
+        }
+
+        if let Some(token) = self.flow.silent_access_token().await? {
+            self.token = Some(token);
+            self.state = FlowState::NeedsCredentials;
+        }
+        Ok(())
+    }
+
+    /// The current state of the flow.
+    #[must_use]
+    pub fn state(&self) -> &FlowState {
+        &self.state
+    }
+
+    /// Perform the next step of the flow, returning the new state.
+    ///
+    /// In [`FlowState::NeedsToken`], the state is unchanged until the user grants access, unless
+    /// the server rejected the client registration. In that case the client is registered again,
+    /// and the new state has a new verification URL. In [`FlowState::NeedsCredentials`], if the
+    /// server rejects the access token, the flow returns to [`FlowState::NeedsClient`] so the user
+    /// can sign in again. In [`FlowState::Done`], this does nothing.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the step fails, including if the user doesn't grant access before
+    /// the user code expires. The state is unchanged, so the step can be retried, though a
+    /// verification that expired needs a new stepper.
+    pub async fn advance(&mut self) -> Result<&FlowState, SsoFlowError<V::Error>> {
+        let flow_id = self.flow_id;
+        flow_id
+            .instrument(self.step())
+            .await
+            .map_err(|error| error.with_flow_id(flow_id))?;
+        Ok(&self.state)
+    }
+
+    async fn step(&mut self) -> Result<(), SsoFlowError<V::Error>> {
+        match &mut self.state {
+            FlowState::NeedsClient => {
+                let client = self.flow.client().await?;
+                match self.start_verification(client.clone()).await {
+                    Err(error) if error.is("InvalidClientException") && !self.reregistered => {
+                        self.reregister(&client).await?;
+                    }
+                    result => result.map_err(SsoFlowError::Api)?,
+                }
+            }
+            FlowState::NeedsToken(info) => {
+                let verification = self
+                    .verification
+                    .as_mut()
+                    .expect("verification should be started");
+                verification.attempts += 1;
+                let result = self
+                    .flow
+                    .sso_oidc_client
+                    .try_create_token(
+                        &verification.client.client_id,
+                        &verification.client.client_secret,
+                        &verification.authorization,
+                    )
+                    .await;
+                match result {
+                    Ok(token) => {
+                        self.flow.shared_session().remember_token(token.clone());
+                        self.flow
+                            .cache
+                            .put(CacheStage::Token, &token)
+                            .await
+                            .map_err(|error| {
+                                SsoFlowError::from_cache(error, |never| match never {})
+                            })?;
+                        self.token = Some(token);
+                        self.verification = None;
+                        self.state = FlowState::NeedsCredentials;
+                    }
+                    Err(error) if error.is("AuthorizationPendingException") => {}
+                    Err(error) if error.is("InvalidClientException") && !self.reregistered => {
+                        let rejected = verification.client.clone();
+                        self.reregister(&rejected).await?;
+                    }
+                    Err(error) if error.is("SlowDownException") => {
+                        info.interval += SLOW_DOWN_INCREMENT;
+                    }
+                    Err(error) if error.is("ExpiredTokenException") => {
+                        let elapsed = (self.flow.clock.now() - verification.issued_at)
+                            .to_std()
+                            .unwrap_or_default();
+                        return Err(SsoFlowError::VerificationPromptTimeout(
+                            SsoTimeoutError::new(elapsed, info.expires_at, verification.attempts),
+                        ));
+                    }
+                    // The user may still grant access while the code is valid
+                    Err(error)
+                        if error.is_retryable() && self.flow.clock.now() < info.expires_at => {}
+                    Err(error) => return Err(SsoFlowError::Api(error)),
+                }
+            }
+            FlowState::NeedsCredentials => {
+                let token = self.token.as_ref().expect("token should be obtained");
+                let account_id = self.flow.account_id(token).await?;
+                let role_name = self.flow.role_name(token, &account_id).await?;
+                match self
+                    .flow
+                    .credentials(token.clone(), account_id, role_name)
+                    .await
+                {
+                    Ok(credentials) => {
+                        self.token = None;
+                        self.state = FlowState::Done(credentials.into());
+                    }
+                    // The token was revoked server-side, so start again from verification
+                    Err(SsoFlowError::Api(error))
+                        if error.is("UnauthorizedException") && !self.token_rejected =>
+                    {
+                        self.token_rejected = true;
+                        self.flow.forget_token(token).await;
+                        self.token = None;
+                        self.state = FlowState::NeedsClient;
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+            FlowState::Done(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Start verification with `client`, moving to [`FlowState::NeedsToken`].
+    async fn start_verification(
+        &mut self,
+        client: RegisterClientResponse,
+    ) -> Result<(), SsoApiError> {
+        let authorization = self
+            .flow
+            .sso_oidc_client
+            .start_device_authorization(
+                &client.client_id,
+                &client.client_secret,
+                &self.flow.config.start_url,
+            )
+            .await?;
+        let issued_at = self.flow.clock.now();
+        self.state = FlowState::NeedsToken(VerificationInfo {
+            verification_url: authorization.verification_uri_complete.clone(),
+            user_code: authorization.user_code.clone(),
+            expires_at: issued_at + authorization.expires_in,
+            interval: authorization
+                .interval
+                .max(self.flow.sso_oidc_client.min_poll_interval),
+        });
+        self.verification = Some(Verification {
+            client,
+            authorization,
+            issued_at,
+            attempts: 0,
+        });
+        Ok(())
+    }
+
+    /// Replace a client registration the server rejected, and start verification again with it.
+    async fn reregister(
+        &mut self,
+        rejected: &RegisterClientResponse,
+    ) -> Result<(), SsoFlowError<V::Error>> {
+        self.reregistered = true;
+        let client = self.flow.reregister_client(rejected).await?;
+        self.start_verification(client)
+            .await
+            .map_err(SsoFlowError::Api)
+    }
+}
+
+impl<V> fmt::Debug for FlowStepper<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FlowStepper")
+            .field("flow_id", &self.flow_id)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,560 @@
+//! A fake SSO and SSO OIDC API server, for testing SSO handling end to end.
+//!
+//! [`TestServer`] listens on a local port and emulates the APIs an [`SsoFlow`](crate::SsoFlow)
+//! calls: `RegisterClient`, `StartDeviceAuthorization`, `CreateToken`, and `GetRoleCredentials`,
+//! as well as `ListAccounts`, `ListAccountRoles`, and `Logout`. How `CreateToken` responds while
+//! the "user" is signing in is scripted with a [`Behavior`], so applications can test how they
+//! handle pending verification, slow-down requests, and expired user codes without an AWS
+//! account. Flows are pointed at the server with
+//! [`SsoFlowBuilder::endpoint_url`](crate::SsoFlowBuilder::endpoint_url):
+//!
+//! ```
+//! # #[tokio::main] async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use std::{convert::Infallible, time::Duration};
+//!
+//! use aws_sso_flow::{
+//!     test_server::{Behavior, TestServer},
+//!     SsoFlowBuilder,
+//! };
+//!
+//! let server = TestServer::start(Behavior::default().pending(2)).await?;
+//! let flow = SsoFlowBuilder::self_contained(server.config())
+//!     .endpoint_url(server.url())
+//!     .min_poll_interval(Duration::from_millis(10))
+//!     .verification_prompt(|_| async { Ok::<_, Infallible>(()) })
+//!     .build()
+//!     .await?;
+//!
+//! flow.authenticate().await?;
+//! assert_eq!(
+//!     server.operations(),
+//!     [
+//!         "RegisterClient",
+//!         "StartDeviceAuthorization",
+//!         "CreateToken",
+//!         "CreateToken",
+//!         "CreateToken",
+//!         "GetRoleCredentials",
+//!     ],
+//! );
+//! # Ok(()) }
+//! ```
+//!
+//! The server grants access as soon as the scripted responses are exhausted, so the verification
+//! prompt needn't do anything. Responses use the same wire format as AWS, so they exercise the
+//! same parsing as production, whichever transport feature is enabled.
+
+use std::{
+    collections::HashSet,
+    fmt::{self, Write as _},
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use chrono::Utc;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    task::{JoinHandle, JoinSet},
+};
+use url::Url;
+
+use crate::{AccountId, Region, RoleName, SsoConfig};
+
+/// The account that [`TestServer::config`] signs in to.
+const ACCOUNT_ID: &str = "123456789012";
+
+/// The role that [`TestServer::config`] assumes, unless [`Behavior::roles`] is set.
+const ROLE_NAME: &str = "TestRole";
+
+/// How a [`TestServer`] responds while a verification is pending.
+///
+/// Each device authorization is answered with `SlowDownException` for the first
+/// [`slow_down`](Self::slow_down) polls, then `AuthorizationPendingException` for the next
+/// [`pending`](Self::pending) polls. After that, the token is issued, unless the behavior
+/// [`expire`](Self::expire)s the user code, in which case polls fail with `ExpiredTokenException`.
+///
+/// By default, the token is issued on the first poll.
+#[derive(Clone, Debug)]
+pub struct Behavior {
+    slow_down: u32,
+    pending: u32,
+    expire: bool,
+    interval: Duration,
+    user_code_lifetime: Duration,
+    token_lifetime: Duration,
+    credentials_lifetime: Duration,
+    roles: Vec<String>,
+}
+
+impl Default for Behavior {
+    fn default() -> Self {
+        Self {
+            slow_down: 0,
+            pending: 0,
+            expire: false,
+            interval: Duration::ZERO,
+            user_code_lifetime: Duration::from_secs(10 * 60),
+            token_lifetime: Duration::from_secs(8 * 60 * 60),
+            credentials_lifetime: Duration::from_secs(60 * 60),
+            roles: vec![ROLE_NAME.to_string()],
+        }
+    }
+}
+
+impl Behavior {
+    /// Answer `n` polls with `AuthorizationPendingException`, as if the user hasn't granted access
+    /// yet.
+    #[must_use]
+    pub fn pending(self, n: u32) -> Self {
+        Self { pending: n, ..self }
+    }
+
+    /// Answer the first `n` polls with `SlowDownException`, as if the client polled too often.
+    #[must_use]
+    pub fn slow_down(self, n: u32) -> Self {
+        Self {
+            slow_down: n,
+            ..self
+        }
+    }
+
+    /// Set whether the user code expires instead of access being granted.
+    #[must_use]
+    pub fn expire(self, expire: bool) -> Self {
+        Self { expire, ..self }
+    }
+
+    /// Set the poll interval returned by `StartDeviceAuthorization`, which is rounded down to
+    /// whole seconds.
+    ///
+    /// The default is zero, so flows poll at their
+    /// [`min_poll_interval`](crate::SsoFlowBuilder::min_poll_interval).
+    #[must_use]
+    pub fn interval(self, interval: Duration) -> Self {
+        Self { interval, ..self }
+    }
+
+    /// Set how long user codes are valid for, which is rounded down to whole seconds.
+    ///
+    /// The server doesn't expire codes itself (see [`expire`](Self::expire)), but flows use this
+    /// to decide when to stop polling. The default is 10 minutes.
+    #[must_use]
+    pub fn user_code_lifetime(self, lifetime: Duration) -> Self {
+        Self {
+            user_code_lifetime: lifetime,
+            ..self
+        }
+    }
+
+    /// Set how long access tokens are valid for, which is rounded down to whole seconds.
+    ///
+    /// The default is 8 hours.
+    #[must_use]
+    pub fn token_lifetime(self, lifetime: Duration) -> Self {
+        Self {
+            token_lifetime: lifetime,
+            ..self
+        }
+    }
+
+    /// Set how long role credentials are valid for.
+    ///
+    /// The default is 1 hour.
+    #[must_use]
+    pub fn credentials_lifetime(self, lifetime: Duration) -> Self {
+        Self {
+            credentials_lifetime: lifetime,
+            ..self
+        }
+    }
+
+    /// Set the roles the server grants access to in its account.
+    ///
+    /// [`TestServer::config`] assumes the first role. The default is a single role, `TestRole`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `roles` is empty.
+    #[must_use]
+    pub fn roles<I>(self, roles: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let roles: Vec<_> = roles.into_iter().map(Into::into).collect();
+        assert!(!roles.is_empty(), "the test server needs at least one role");
+        Self { roles, ..self }
+    }
+}
+
+/// A local server emulating the SSO and SSO OIDC APIs.
+///
+/// See the [module documentation](self) for an example. The server stops when it's dropped,
+/// along with any open connections.
+pub struct TestServer {
+    url: Url,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+struct State {
+    behavior: Behavior,
+    operations: Vec<&'static str>,
+    // The number of `CreateToken` polls for the latest device authorization
+    polls: u32,
+    next_id: u64,
+    access_tokens: HashSet<String>,
+}
+
+impl TestServer {
+    /// Start a server on a local port that responds according to `behavior`.
+    ///
+    /// The server runs on the current tokio runtime.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if a local port can't be bound.
+    pub async fn start(behavior: Behavior) -> io::Result<Self> {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+        let url =
+            Url::parse(&format!("http://{}/", listener.local_addr()?)).map_err(io::Error::other)?;
+        let state = Arc::new(Mutex::new(State {
+            behavior,
+            operations: Vec::new(),
+            polls: 0,
+            next_id: 0,
+            access_tokens: HashSet::new(),
+        }));
+        let task = tokio::spawn(serve(listener, Arc::clone(&state)));
+        Ok(Self { url, state, task })
+    }
+
+    /// The base URL of the server, for
+    /// [`SsoFlowBuilder::endpoint_url`](crate::SsoFlowBuilder::endpoint_url).
+    #[must_use]
+    pub fn url(&self) -> Url {
+        self.url.clone()
+    }
+
+    /// An SSO configuration for the account and role the server grants access to.
+    ///
+    /// The role is the first of the behavior's [`roles`](Behavior::roles). The start URL is on the
+    /// server, though it's never requested.
+    #[must_use]
+    pub fn config(&self) -> SsoConfig {
+        SsoConfig {
+            region: Region::US_EAST_1,
+            start_url: format!("{}start", self.url),
+            account_id: AccountId::new(ACCOUNT_ID),
+            role_name: RoleName::new(self.state().behavior.roles[0].clone()),
+            account_name: None,
+            role_selector: None,
+        }
+    }
+
+    /// Change how the server responds from now on.
+    ///
+    /// A device authorization that's already pending keeps the number of polls it's had.
+    pub fn set_behavior(&self, behavior: Behavior) {
+        self.state().behavior = behavior;
+    }
+
+    /// The API operations called so far, in order, e.g. `CreateToken`.
+    #[must_use]
+    pub fn operations(&self) -> Vec<&'static str> {
+        self.state().operations.clone()
+    }
+
+    /// Revoke every access token issued so far.
+    ///
+    /// Calls to the SSO API with a revoked token fail with `UnauthorizedException`, as they do
+    /// when a user signs out of the access portal.
+    pub fn revoke_tokens(&self) {
+        self.state().access_tokens.clear();
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("poisoned")
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl fmt::Debug for TestServer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TestServer")
+            .field("url", &self.url.as_str())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Accept connections until the server is dropped.
+async fn serve(listener: TcpListener, state: Arc<Mutex<State>>) {
+    // Connections are owned by the set, so they're aborted along with this task. Finished
+    // connections aren't reaped, since a test server only sees a handful.
+    let mut connections = JoinSet::new();
+    loop {
+        if let Ok((stream, _)) = listener.accept().await {
+            connections.spawn(connection(stream, Arc::clone(&state)));
+        }
+    }
+}
+
+/// Serve HTTP/1.1 requests on a connection until the client closes it.
+async fn connection(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let mut stream = BufReader::new(stream);
+    while let Ok(Some(request)) = read_request(&mut stream).await {
+        let response = state.lock().expect("poisoned").respond(&request);
+        if write_response(stream.get_mut(), response).await.is_err() {
+            break;
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    url: Url,
+    access_token: Option<String>,
+    body: Value,
+}
+
+struct Response {
+    status: u16,
+    error_type: Option<&'static str>,
+    body: Value,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self {
+            status: 200,
+            error_type: None,
+            body,
+        }
+    }
+
+    fn error(status: u16, error_type: &'static str, message: &str) -> Self {
+        Self {
+            status,
+            error_type: Some(error_type),
+            body: json!({ "message": message }),
+        }
+    }
+}
+
+/// Read a request, or `None` if the client closed the connection.
+async fn read_request(stream: &mut BufReader<TcpStream>) -> io::Result<Option<Request>> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(io::Error::other("invalid request line"));
+    };
+    let method = method.to_string();
+    let url = Url::parse(&format!("http://localhost{target}")).map_err(io::Error::other)?;
+
+    let (mut content_length, mut access_token) = (0, None);
+    loop {
+        line.clear();
+        stream.read_line(&mut line).await?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse().map_err(io::Error::other)?;
+        } else if name.eq_ignore_ascii_case("x-amz-sso_bearer_token") {
+            access_token = Some(value.to_string());
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    stream.read_exact(&mut body).await?;
+    let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    Ok(Some(Request {
+        method,
+        url,
+        access_token,
+        body,
+    }))
+}
+
+async fn write_response(stream: &mut TcpStream, response: Response) -> io::Result<()> {
+    let body = response.body.to_string();
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\
+         x-amzn-requestid: test-server\r\n",
+        response.status,
+        if response.status == 200 {
+            "OK"
+        } else {
+            "Error"
+        },
+        body.len(),
+    );
+    if let Some(error_type) = response.error_type {
+        let _ = write!(head, "x-amzn-errortype: {error_type}\r\n");
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.flush().await
+}
+
+impl State {
+    fn respond(&mut self, request: &Request) -> Response {
+        let operation = match (request.method.as_str(), request.url.path()) {
+            ("POST", "/client/register") => "RegisterClient",
+            ("POST", "/device_authorization") => "StartDeviceAuthorization",
+            ("POST", "/token") => "CreateToken",
+            ("GET", "/federation/credentials") => "GetRoleCredentials",
+            ("GET", "/assignment/accounts") => "ListAccounts",
+            ("GET", "/assignment/roles") => "ListAccountRoles",
+            ("POST", "/logout") => "Logout",
+            _ => return Response::error(404, "ResourceNotFoundException", "unknown operation"),
+        };
+        self.operations.push(operation);
+
+        match operation {
+            "RegisterClient" => self.register_client(),
+            "StartDeviceAuthorization" => self.start_device_authorization(),
+            "CreateToken" => self.create_token(request),
+            _ => self.portal(operation, request),
+        }
+    }
+
+    fn next_id(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{prefix}-{}", self.next_id)
+    }
+
+    fn register_client(&mut self) -> Response {
+        Response::ok(json!({
+            "clientId": self.next_id("client"),
+            "clientSecret": self.next_id("secret"),
+            "clientIdIssuedAt": Utc::now().timestamp(),
+            "clientSecretExpiresAt": (Utc::now() + chrono::Duration::days(90)).timestamp(),
+        }))
+    }
+
+    fn start_device_authorization(&mut self) -> Response {
+        self.polls = 0;
+        let user_code = self.next_id("CODE");
+        Response::ok(json!({
+            "deviceCode": self.next_id("device"),
+            "userCode": user_code,
+            "verificationUri": "https://device.sso.us-east-1.amazonaws.com/",
+            "verificationUriComplete":
+                format!("https://device.sso.us-east-1.amazonaws.com/?user_code={user_code}"),
+            "expiresIn": self.behavior.user_code_lifetime.as_secs(),
+            "interval": self.behavior.interval.as_secs(),
+        }))
+    }
+
+    fn create_token(&mut self, request: &Request) -> Response {
+        let refreshing = request.body["grantType"] == "refresh_token";
+        if !refreshing {
+            self.polls += 1;
+            let Behavior {
+                slow_down,
+                pending,
+                expire,
+                ..
+            } = self.behavior;
+            if self.polls <= slow_down {
+                return Response::error(400, "SlowDownException", "slow down");
+            }
+            if self.polls <= slow_down + pending {
+                return Response::error(
+                    400,
+                    "AuthorizationPendingException",
+                    "authorization pending",
+                );
+            }
+            if expire {
+                return Response::error(400, "ExpiredTokenException", "user code expired");
+            }
+        }
+
+        let access_token = self.next_id("token");
+        self.access_tokens.insert(access_token.clone());
+        Response::ok(json!({
+            "accessToken": access_token,
+            "tokenType": "Bearer",
+            "expiresIn": self.behavior.token_lifetime.as_secs(),
+            "refreshToken": self.next_id("refresh"),
+        }))
+    }
+
+    fn portal(&mut self, operation: &str, request: &Request) -> Response {
+        if !request
+            .access_token
+            .as_ref()
+            .is_some_and(|token| self.access_tokens.contains(token))
+        {
+            return Response::error(401, "UnauthorizedException", "Session token not found");
+        }
+        let query = |name| {
+            request
+                .url
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        let account_id = query("account_id");
+        let role_name = query("role_name");
+
+        match operation {
+            "GetRoleCredentials" => {
+                if account_id.as_deref() != Some(ACCOUNT_ID)
+                    || !role_name.is_some_and(|role_name| self.behavior.roles.contains(&role_name))
+                {
+                    return Response::error(403, "ForbiddenException", "No access");
+                }
+                let expiration = Utc::now()
+                    + chrono::Duration::from_std(self.behavior.credentials_lifetime)
+                        .unwrap_or(chrono::Duration::MAX);
+                Response::ok(json!({
+                    "roleCredentials": {
+                        "accessKeyId": self.next_id("ASIATEST"),
+                        "secretAccessKey": self.next_id("secret"),
+                        "sessionToken": self.next_id("session"),
+                        "expiration": expiration.timestamp_millis(),
+                    },
+                }))
+            }
+            "ListAccounts" => Response::ok(json!({
+                "accountList": [{
+                    "accountId": ACCOUNT_ID,
+                    "accountName": "Test",
+                    "emailAddress": "test@example.com",
+                }],
+            })),
+            "ListAccountRoles" if account_id.as_deref() == Some(ACCOUNT_ID) => {
+                let roles: Vec<_> = self
+                    .behavior
+                    .roles
+                    .iter()
+                    .map(|role_name| json!({ "accountId": ACCOUNT_ID, "roleName": role_name }))
+                    .collect();
+                Response::ok(json!({ "roleList": roles }))
+            }
+            "ListAccountRoles" => Response::ok(json!({ "roleList": [] })),
+            _ => Response::ok(Value::Null),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+// Checked conversions from timestamps in API responses.
+//
+// Timestamps are limited to the years 1970 to 9999, so that malformed responses can't cause panics
+// later, e.g. when the timestamp is serialized to the cache (RFC 3339 only covers four-digit years),
+// converted to a `SystemTime` (which can't represent times before 1601 on Windows), or offset by a
+// cache buffer.
+
+use std::fmt;
+
+use chrono::{DateTime, TimeZone, Utc};
+
+// 9999-12-31T23:59:59.999Z
+const MAX_MILLIS: i64 = 253_402_300_799_999;
+
+/// A timestamp in an API response that's outside the supported range.
+#[derive(Debug)]
+pub(crate) struct InvalidTimestamp {
+    field: &'static str,
+    value: i64,
+    unit: &'static str,
+}
+
+/// Convert a timestamp in milliseconds since the Unix epoch.
+pub(crate) fn from_millis(
+    field: &'static str,
+    millis: i64,
+) -> Result<DateTime<Utc>, InvalidTimestamp> {
+    let invalid = || InvalidTimestamp {
+        field,
+        value: millis,
+        unit: "ms",
+    };
+    if !(0..=MAX_MILLIS).contains(&millis) {
+        return Err(invalid());
+    }
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .ok_or_else(invalid)
+}
+
+/// Convert a timestamp in seconds since the Unix epoch.
+pub(crate) fn from_secs(field: &'static str, secs: i64) -> Result<DateTime<Utc>, InvalidTimestamp> {
+    let invalid = || InvalidTimestamp {
+        field,
+        value: secs,
+        unit: "s",
+    };
+    if !(0..=MAX_MILLIS / 1000).contains(&secs) {
+        return Err(invalid());
+    }
+    Utc.timestamp_opt(secs, 0).single().ok_or_else(invalid)
+}
+
+impl fmt::Display for InvalidTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} is out of range ({}{} since the Unix epoch)",
+            self.field, self.value, self.unit
+        )
+    }
+}
+
+impl std::error::Error for InvalidTimestamp {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epoch() {
+        assert_eq!(from_millis("t", 0).unwrap(), DateTime::UNIX_EPOCH);
+        assert_eq!(from_secs("t", 0).unwrap(), DateTime::UNIX_EPOCH);
+    }
+
+    #[test]
+    fn negative() {
+        assert!(from_millis("t", -1).is_err());
+        assert!(from_millis("t", i64::MIN).is_err());
+        assert!(from_secs("t", -1).is_err());
+        assert!(from_secs("t", i64::MIN).is_err());
+    }
+
+    #[test]
+    fn far_future() {
+        let max = from_millis("t", MAX_MILLIS).unwrap();
+        assert_eq!(max.to_rfc3339(), "9999-12-31T23:59:59.999+00:00");
+        assert!(serde_json::to_string(&max).is_ok());
+
+        assert!(from_millis("t", MAX_MILLIS + 1).is_err());
+        assert!(from_millis("t", i64::MAX).is_err());
+        assert!(from_secs("t", MAX_MILLIS / 1000 + 1).is_err());
+        assert!(from_secs("t", i64::MAX).is_err());
+    }
+
+    #[test]
+    fn millisecond_accuracy() {
+        let expires_at = from_millis("t", 1_700_000_000_123).unwrap();
+        assert_eq!(expires_at.timestamp_millis(), 1_700_000_000_123);
+
+        let json = serde_json::to_string(&expires_at).unwrap();
+        let parsed: DateTime<Utc> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, expires_at);
+    }
+
+    #[test]
+    fn error_message() {
+        assert_eq!(
+            from_millis("expiration", -5).unwrap_err().to_string(),
+            "expiration is out of range (-5ms since the Unix epoch)"
+        );
+    }
+}
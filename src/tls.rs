@@ -0,0 +1,82 @@
+// Selection of the TLS implementation used by the transports.
+
+#[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+compile_error!(
+    "either the `rustls` or `native-tls` feature must be enabled, e.g. `features = [\"rustls\", \"sdk-clients\"]` with `default-features = false`"
+);
+
+#[cfg(all(feature = "sdk-clients", not(feature = "lite-http")))]
+pub(crate) use sdk::http_client;
+
+#[cfg(feature = "lite-http")]
+pub(crate) use lite::{connector, Connector};
+
+#[cfg(all(feature = "sdk-clients", not(feature = "lite-http")))]
+mod sdk {
+    use aws_sdk_sso::config::SharedHttpClient;
+
+    /// The HTTP client to use for SSO API calls.
+    ///
+    /// `None` means the SDK's default HTTPS client, which uses rustls. If both features are
+    /// enabled, `native-tls` takes precedence since it must have been chosen explicitly.
+    #[cfg(feature = "native-tls")]
+    #[allow(clippy::unnecessary_wraps)]
+    pub(crate) fn http_client() -> Option<SharedHttpClient> {
+        Some(
+            aws_smithy_http_client::hyper_014::HyperClientBuilder::new()
+                .build(hyper_tls::HttpsConnector::new()),
+        )
+    }
+
+    /// The HTTP client to use for SSO API calls.
+    ///
+    /// `None` means the SDK's default HTTPS client, which uses rustls.
+    #[cfg(not(feature = "native-tls"))]
+    pub(crate) fn http_client() -> Option<SharedHttpClient> {
+        None
+    }
+}
+
+#[cfg(feature = "lite-http")]
+mod lite {
+    use std::time::Duration;
+
+    use hyper::client::HttpConnector;
+
+    /// The connector used by the `lite-http` transport.
+    ///
+    /// As for the SDK clients, `native-tls` takes precedence if both features are enabled.
+    #[cfg(feature = "native-tls")]
+    pub(crate) type Connector = hyper_tls::HttpsConnector<HttpConnector>;
+
+    /// The connector used by the `lite-http` transport.
+    #[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+    pub(crate) type Connector = hyper_rustls::HttpsConnector<HttpConnector>;
+
+    // Builds without a TLS feature fail on the guard above, so this only keeps that the sole error
+    #[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+    pub(crate) type Connector = HttpConnector;
+
+    pub(crate) fn connector(connect_timeout: Option<Duration>) -> Connector {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        http.set_connect_timeout(connect_timeout);
+
+        #[cfg(feature = "native-tls")]
+        let connector = hyper_tls::HttpsConnector::new_with_connector(http);
+
+        // AWS endpoints are HTTPS, so plain HTTP is only used for an `endpoint_url` that asks for it,
+        // e.g. a test server
+        #[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(http);
+
+        #[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+        let connector = http;
+
+        connector
+    }
+}
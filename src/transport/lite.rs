@@ -0,0 +1,428 @@
+// A transport that calls the SSO and SSO OIDC REST APIs directly.
+//
+// The APIs are simple JSON over HTTPS, so this needs little more than an HTTP client. Errors are
+// classified by their AWS error code (e.g. `AuthorizationPendingException`), which is resolved the
+// same way as the SDK: from the `x-amzn-ErrorType` header, falling back to the `__type` or `code`
+// fields of the response body.
+
+use std::{
+    fmt::{self, Write},
+    sync::OnceLock,
+};
+
+use chrono::{DateTime, Utc};
+use hyper::{
+    body::{self, Bytes},
+    header, Body, HeaderMap, Method, Request, StatusCode,
+};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use url::Url;
+
+use super::{
+    Config, CreateTokenOutput, GetRoleCredentialsOutput, ListAccountRolesOutput,
+    ListAccountsOutput, RegisterClientOutput, StartDeviceAuthorizationOutput,
+};
+use crate::{tls, BoxError, SsoApiError, CLIENT_NAME};
+
+// The maximum page size for `ListAccounts` and `ListAccountRoles`.
+const MAX_RESULTS: &str = "100";
+
+// The client is constructed on first use, since that involves setting up TLS, and flows that are
+// served entirely from the cache never need one.
+pub(crate) struct Transport {
+    config: Config,
+    client: OnceLock<hyper::Client<tls::Connector>>,
+}
+
+#[derive(Clone, Copy)]
+enum Service {
+    Sso,
+    SsoOidc,
+}
+
+impl Transport {
+    pub(crate) fn new(config: Config) -> Self {
+        Self {
+            config,
+            client: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn config(&self) -> &Config {
+        &self.config
+    }
+
+    fn client(&self) -> &hyper::Client<tls::Connector> {
+        self.client.get_or_init(|| {
+            hyper::Client::builder().build(tls::connector(self.config.connect_timeout))
+        })
+    }
+
+    fn url(&self, service: Service, path: &str) -> Url {
+        if let Some(endpoint_url) = &self.config.endpoint_url {
+            return Url::parse(&format!(
+                "{}{path}",
+                endpoint_url.as_str().trim_end_matches('/')
+            ))
+            .expect("endpoint should be a valid URL");
+        }
+        let region = self.config.region.as_ref();
+        let host = match service {
+            Service::Sso => "portal.sso",
+            Service::SsoOidc => "oidc",
+        };
+        let domain = if region.starts_with("cn-") {
+            "amazonaws.com.cn"
+        } else {
+            "amazonaws.com"
+        };
+        Url::parse(&format!("https://{host}.{region}.{domain}{path}"))
+            .expect("endpoint should be a valid URL")
+    }
+
+    pub(crate) async fn register_client(
+        &self,
+        client_name: &str,
+    ) -> Result<RegisterClientOutput, SsoApiError> {
+        self.config.faults.api("RegisterClient").await?;
+        let request = Self::post(
+            &self.url(Service::SsoOidc, "/client/register"),
+            &json!({ "clientName": client_name, "clientType": "public" }),
+        );
+        self.call(request).await
+    }
+
+    pub(crate) async fn start_device_authorization(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        start_url: &str,
+    ) -> Result<StartDeviceAuthorizationOutput, SsoApiError> {
+        self.config.faults.api("StartDeviceAuthorization").await?;
+        let request = Self::post(
+            &self.url(Service::SsoOidc, "/device_authorization"),
+            &json!({
+                "clientId": client_id,
+                "clientSecret": client_secret,
+                "startUrl": start_url,
+            }),
+        );
+        self.call(request).await
+    }
+
+    pub(crate) async fn create_token(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        device_code: &str,
+        user_code: &str,
+    ) -> Result<CreateTokenOutput, SsoApiError> {
+        self.config.faults.api("CreateToken").await?;
+        let request = Self::post(
+            &self.url(Service::SsoOidc, "/token"),
+            &json!({
+                "clientId": client_id,
+                "clientSecret": client_secret,
+                "code": user_code,
+                "deviceCode": device_code,
+                "grantType": "urn:ietf:params:oauth:grant-type:device_code",
+            }),
+        );
+        self.call(request).await
+    }
+
+    pub(crate) async fn refresh_token(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        refresh_token: &str,
+    ) -> Result<CreateTokenOutput, SsoApiError> {
+        self.config.faults.api("CreateToken").await?;
+        let request = Self::post(
+            &self.url(Service::SsoOidc, "/token"),
+            &json!({
+                "clientId": client_id,
+                "clientSecret": client_secret,
+                "grantType": "refresh_token",
+                "refreshToken": refresh_token,
+            }),
+        );
+        self.call(request).await
+    }
+
+    /// Get the server's current time.
+    ///
+    /// This sends a deliberately invalid `CreateToken` request, which doesn't require any
+    /// credentials, and reads the `Date` header from the error response.
+    #[cfg_attr(not(feature = "doctor"), allow(dead_code))]
+    pub(crate) async fn server_time(&self) -> Result<DateTime<Utc>, BoxError> {
+        let request = Self::post(
+            &self.url(Service::SsoOidc, "/token"),
+            &json!({
+                "clientId": "invalid",
+                "clientSecret": "invalid",
+                "grantType": "invalid",
+            }),
+        );
+        let (_, headers, _) = self.send(request).await?;
+        let date = headers
+            .get(header::DATE)
+            .ok_or("missing Date header in CreateToken response")?
+            .to_str()?;
+        Ok(DateTime::parse_from_rfc2822(date)?.with_timezone(&Utc))
+    }
+
+    pub(crate) async fn get_role_credentials(
+        &self,
+        access_token: &str,
+        account_id: &str,
+        role_name: &str,
+    ) -> Result<GetRoleCredentialsOutput, SsoApiError> {
+        self.config.faults.api("GetRoleCredentials").await?;
+        let mut url = self.url(Service::Sso, "/federation/credentials");
+        url.set_query(Some(&encode_query(&[
+            ("account_id", account_id),
+            ("role_name", role_name),
+        ])));
+        self.call(Self::get(&url, access_token)).await
+    }
+
+    pub(crate) async fn list_accounts(
+        &self,
+        access_token: &str,
+        next_token: Option<String>,
+    ) -> Result<ListAccountsOutput, SsoApiError> {
+        self.config.faults.api("ListAccounts").await?;
+        let mut url = self.url(Service::Sso, "/assignment/accounts");
+        let mut query = vec![("max_result", MAX_RESULTS)];
+        if let Some(next_token) = &next_token {
+            query.push(("next_token", next_token));
+        }
+        url.set_query(Some(&encode_query(&query)));
+        self.call(Self::get(&url, access_token)).await
+    }
+
+    pub(crate) async fn list_account_roles(
+        &self,
+        access_token: &str,
+        account_id: &str,
+        next_token: Option<String>,
+    ) -> Result<ListAccountRolesOutput, SsoApiError> {
+        self.config.faults.api("ListAccountRoles").await?;
+        let mut url = self.url(Service::Sso, "/assignment/roles");
+        let mut query = vec![("account_id", account_id), ("max_result", MAX_RESULTS)];
+        if let Some(next_token) = &next_token {
+            query.push(("next_token", next_token));
+        }
+        url.set_query(Some(&encode_query(&query)));
+        self.call(Self::get(&url, access_token)).await
+    }
+
+    pub(crate) async fn logout(&self, access_token: &str) -> Result<(), SsoApiError> {
+        self.config.faults.api("Logout").await?;
+        let url = self.url(Service::Sso, "/logout");
+        let mut request = Self::get(&url, access_token);
+        *request.method_mut() = Method::POST;
+        // The response has no body
+        self.call_raw(request).await.map(|_| ())
+    }
+
+    fn get(url: &Url, access_token: &str) -> Request<Body> {
+        Request::builder()
+            .method(Method::GET)
+            .uri(url.as_str())
+            .header(header::USER_AGENT, CLIENT_NAME)
+            .header("x-amz-sso_bearer_token", access_token)
+            .body(Body::empty())
+            .expect("request should be valid")
+    }
+
+    fn post(url: &Url, body: &serde_json::Value) -> Request<Body> {
+        Request::builder()
+            .method(Method::POST)
+            .uri(url.as_str())
+            .header(header::USER_AGENT, CLIENT_NAME)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .expect("request should be valid")
+    }
+
+    /// Send a request and deserialize a successful response, or classify an error response.
+    async fn call<T: DeserializeOwned>(&self, request: Request<Body>) -> Result<T, SsoApiError> {
+        let (status, request_id, body) = self.call_raw(request).await?;
+        serde_json::from_slice(&body).map_err(|error| {
+            SsoApiError::from_response(request_id, Some(status.as_u16()), None, error)
+        })
+    }
+
+    /// Send a request and return the body of a successful response, or classify an error response.
+    async fn call_raw(
+        &self,
+        request: Request<Body>,
+    ) -> Result<(StatusCode, Option<String>, Bytes), SsoApiError> {
+        let (status, headers, body) = self.send(request).await?;
+        let request_id = headers
+            .get("x-amzn-requestid")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        if status.is_success() {
+            return Ok((status, request_id, body));
+        }
+
+        let error = ServiceError::from_response(status, &headers, &body);
+        Err(SsoApiError::from_response(
+            request_id,
+            Some(status.as_u16()),
+            error.code.clone(),
+            error,
+        ))
+    }
+
+    async fn send(
+        &self,
+        request: Request<Body>,
+    ) -> Result<(StatusCode, HeaderMap, Bytes), SsoApiError> {
+        let send = async {
+            let res = self.client().request(request).await?;
+            let (parts, body) = res.into_parts();
+            let body = body::to_bytes(body).await?;
+            Ok::<_, hyper::Error>((parts.status, parts.headers, body))
+        };
+        match self.config.api_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, send).await.map_err(|_| {
+                SsoApiError::new(format!("request timed out after {timeout:?}")).transient()
+            })?,
+            None => send.await,
+        }
+        .map_err(|error| SsoApiError::new(error).transient())
+    }
+}
+
+impl fmt::Debug for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Transport")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Encode query parameters as the AWS SDK does.
+///
+/// Everything but unreserved characters is percent-encoded, including spaces (`%20`, rather than
+/// the `+` of form encoding) and `/`, so values such as role names with spaces or path-like
+/// prefixes reach the API unchanged.
+fn encode_query(pairs: &[(&str, &str)]) -> String {
+    let encode = |value: &str| {
+        value.bytes().fold(String::new(), |mut encoded, byte| {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                encoded.push(char::from(byte));
+            } else {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+            encoded
+        })
+    };
+    pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", encode(key), encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// An error response from the SSO or SSO OIDC API.
+#[derive(Debug)]
+struct ServiceError {
+    status: StatusCode,
+    code: Option<String>,
+    message: Option<String>,
+}
+
+impl ServiceError {
+    fn from_response(status: StatusCode, headers: &HeaderMap, body: &[u8]) -> Self {
+        let body: serde_json::Value = serde_json::from_slice(body).unwrap_or_default();
+        let field = |name: &str| body.get(name).and_then(|value| value.as_str());
+
+        // e.g. `AuthorizationPendingException:http://internal.amazon.com/coral/...`
+        let header_code = headers
+            .get("x-amzn-errortype")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(':').next());
+        // e.g. `com.amazon.switchboard.portal#UnauthorizedException`
+        let body_code = field("__type")
+            .or_else(|| field("code"))
+            .and_then(|value| value.rsplit('#').next());
+        let code = header_code
+            .or(body_code)
+            .filter(|code| !code.is_empty())
+            .map(str::to_string);
+
+        let message = field("message")
+            .or_else(|| field("Message"))
+            .or_else(|| field("error_description"))
+            .map(str::to_string);
+
+        Self {
+            status,
+            code,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.code, &self.message) {
+            (Some(code), Some(message)) => write!(f, "{code}: {message}"),
+            (Some(code), None) => write!(f, "{code}"),
+            (None, Some(message)) => write!(f, "{message} (HTTP {})", self.status),
+            (None, None) => write!(f, "unexpected HTTP {}", self.status),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_query_plain() {
+        assert_eq!(
+            encode_query(&[("account_id", "012345678910"), ("role_name", "PowerUser")]),
+            "account_id=012345678910&role_name=PowerUser"
+        );
+    }
+
+    #[test]
+    fn encode_query_special_characters() {
+        assert_eq!(
+            encode_query(&[("role_name", "Power User")]),
+            "role_name=Power%20User"
+        );
+        assert_eq!(
+            encode_query(&[("role_name", "team/admin+ops=1,a@b")]),
+            "role_name=team%2Fadmin%2Bops%3D1%2Ca%40b"
+        );
+        assert_eq!(
+            encode_query(&[("next_token", "a&b=c")]),
+            "next_token=a%26b%3Dc"
+        );
+        assert_eq!(
+            encode_query(&[("role_name", "Rôle")]),
+            "role_name=R%C3%B4le"
+        );
+    }
+
+    #[test]
+    fn encode_query_round_trips_through_url() {
+        let mut url =
+            Url::parse("https://portal.sso.eu-west-1.amazonaws.com/federation/credentials")
+                .unwrap();
+        url.set_query(Some(&encode_query(&[("role_name", "Power User/Ops")])));
+        let pairs: Vec<_> = url.query_pairs().collect();
+        assert_eq!(pairs, [("role_name".into(), "Power User/Ops".into())]);
+    }
+}
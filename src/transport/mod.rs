@@ -0,0 +1,121 @@
+//! Backends for SSO and SSO OIDC API calls.
+//!
+//! By default API calls are made with the AWS SDK clients. With the `lite-http` feature they're
+//! made with a minimal HTTP client instead, which takes precedence if both are enabled. Either way,
+//! the [`Transport`] performs single API calls and returns the raw outputs defined here. Retries,
+//! polling, and validation are handled by the `sso` and `sso_oidc` clients, so they behave the same
+//! regardless of the backend.
+
+use std::time::Duration;
+
+use url::Url;
+
+use crate::{fault::Faults, Region};
+
+#[cfg(feature = "lite-http")]
+mod lite;
+#[cfg(all(feature = "sdk-clients", not(feature = "lite-http")))]
+mod sdk;
+
+#[cfg(feature = "lite-http")]
+pub(crate) use lite::Transport;
+#[cfg(all(feature = "sdk-clients", not(feature = "lite-http")))]
+pub(crate) use sdk::Transport;
+
+#[cfg(not(any(feature = "sdk-clients", feature = "lite-http")))]
+compile_error!(
+    "either the `sdk-clients` or `lite-http` feature must be enabled, e.g. `features = [\"rustls\", \"sdk-clients\"]` with `default-features = false`"
+);
+
+/// Configuration for a [`Transport`].
+#[derive(Clone, Debug)]
+pub(crate) struct Config {
+    pub(crate) region: Region,
+    pub(crate) api_timeout: Option<Duration>,
+    pub(crate) connect_timeout: Option<Duration>,
+    /// A base URL to send API calls to, instead of the regional AWS endpoints.
+    pub(crate) endpoint_url: Option<Url>,
+    pub(crate) faults: Faults,
+}
+
+// The outputs mirror the JSON responses of the REST APIs, so the `lite` transport can deserialize
+// them directly. Fields are optional where the API models them as optional, and are validated when
+// converted to the `sso` and `sso_oidc` response types.
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(clippy::struct_field_names)]
+pub(crate) struct RegisterClientOutput {
+    pub(crate) client_id: Option<String>,
+    pub(crate) client_secret: Option<String>,
+    #[serde(default)]
+    pub(crate) client_secret_expires_at: i64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StartDeviceAuthorizationOutput {
+    pub(crate) device_code: Option<String>,
+    pub(crate) user_code: Option<String>,
+    pub(crate) verification_uri_complete: Option<String>,
+    #[serde(default)]
+    pub(crate) expires_in: i32,
+    #[serde(default)]
+    pub(crate) interval: i32,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CreateTokenOutput {
+    pub(crate) access_token: Option<String>,
+    #[serde(default)]
+    pub(crate) expires_in: i32,
+    pub(crate) refresh_token: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetRoleCredentialsOutput {
+    pub(crate) role_credentials: Option<RoleCredentials>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RoleCredentials {
+    pub(crate) access_key_id: Option<String>,
+    pub(crate) secret_access_key: Option<String>,
+    pub(crate) session_token: Option<String>,
+    #[serde(default)]
+    pub(crate) expiration: i64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ListAccountsOutput {
+    pub(crate) next_token: Option<String>,
+    #[serde(default)]
+    pub(crate) account_list: Vec<AccountInfo>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AccountInfo {
+    pub(crate) account_id: Option<String>,
+    pub(crate) account_name: Option<String>,
+    pub(crate) email_address: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ListAccountRolesOutput {
+    pub(crate) next_token: Option<String>,
+    #[serde(default)]
+    pub(crate) role_list: Vec<RoleInfo>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RoleInfo {
+    pub(crate) account_id: Option<String>,
+    pub(crate) role_name: Option<String>,
+}
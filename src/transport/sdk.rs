@@ -0,0 +1,329 @@
+// A transport using the AWS SDK clients.
+
+use std::sync::OnceLock;
+
+use aws_sdk_sso::{
+    config::{retry::RetryConfig, timeout::TimeoutConfig, BehaviorVersion, Region as SdkRegion},
+    error::{ProvideErrorMetadata, SdkError},
+    operation::RequestId,
+};
+use chrono::{DateTime, Utc};
+
+use super::{
+    AccountInfo, Config, CreateTokenOutput, GetRoleCredentialsOutput, ListAccountRolesOutput,
+    ListAccountsOutput, RegisterClientOutput, RoleCredentials, RoleInfo,
+    StartDeviceAuthorizationOutput,
+};
+use crate::{BoxError, SsoApiError};
+
+// The SDK clients are constructed on first use, since that involves setting up an HTTP client, and
+// flows that are served entirely from the cache never need one.
+#[derive(Debug)]
+pub(crate) struct Transport {
+    config: Config,
+    sso: OnceLock<aws_sdk_sso::Client>,
+    sso_oidc: OnceLock<aws_sdk_ssooidc::Client>,
+}
+
+impl Transport {
+    pub(crate) fn new(config: Config) -> Self {
+        Self {
+            config,
+            sso: OnceLock::new(),
+            sso_oidc: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn config(&self) -> &Config {
+        &self.config
+    }
+
+    fn sso(&self) -> &aws_sdk_sso::Client {
+        self.sso.get_or_init(|| {
+            let mut config = aws_sdk_sso::Config::builder()
+                .behavior_version(BehaviorVersion::latest())
+                .region(SdkRegion::new(self.config.region.to_string()));
+            config.set_timeout_config(self.timeout_config());
+            config.set_http_client(crate::tls::http_client());
+            config.set_endpoint_url(self.endpoint_url());
+            aws_sdk_sso::Client::from_conf(config.build())
+        })
+    }
+
+    fn sso_oidc(&self) -> &aws_sdk_ssooidc::Client {
+        self.sso_oidc.get_or_init(|| {
+            let mut config = aws_sdk_ssooidc::Config::builder()
+                .behavior_version(BehaviorVersion::latest())
+                .region(SdkRegion::new(self.config.region.to_string()));
+            config.set_timeout_config(self.timeout_config());
+            config.set_http_client(crate::tls::http_client());
+            config.set_endpoint_url(self.endpoint_url());
+            aws_sdk_ssooidc::Client::from_conf(config.build())
+        })
+    }
+
+    fn endpoint_url(&self) -> Option<String> {
+        self.config
+            .endpoint_url
+            .as_ref()
+            .map(|url| url.as_str().trim_end_matches('/').to_string())
+    }
+
+    // Only override the timeout config if needed, so the SDK defaults apply otherwise
+    fn timeout_config(&self) -> Option<TimeoutConfig> {
+        let Config {
+            api_timeout,
+            connect_timeout,
+            ..
+        } = self.config;
+        (api_timeout.is_some() || connect_timeout.is_some()).then(|| {
+            let mut timeout_config = TimeoutConfig::builder();
+            timeout_config
+                .set_operation_attempt_timeout(api_timeout)
+                .set_connect_timeout(connect_timeout);
+            timeout_config.build()
+        })
+    }
+
+    pub(crate) async fn register_client(
+        &self,
+        client_name: &str,
+    ) -> Result<RegisterClientOutput, SsoApiError> {
+        self.config.faults.api("RegisterClient").await?;
+        let res = self
+            .sso_oidc()
+            .register_client()
+            .client_name(client_name)
+            .client_type("public")
+            .send()
+            .await
+            .map_err(api_error)?;
+        Ok(RegisterClientOutput {
+            client_id: res.client_id,
+            client_secret: res.client_secret,
+            client_secret_expires_at: res.client_secret_expires_at,
+        })
+    }
+
+    pub(crate) async fn start_device_authorization(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        start_url: &str,
+    ) -> Result<StartDeviceAuthorizationOutput, SsoApiError> {
+        self.config.faults.api("StartDeviceAuthorization").await?;
+        let res = self
+            .sso_oidc()
+            .start_device_authorization()
+            .client_id(client_id)
+            .client_secret(client_secret)
+            .start_url(start_url)
+            .send()
+            .await
+            .map_err(api_error)?;
+        Ok(StartDeviceAuthorizationOutput {
+            device_code: res.device_code,
+            user_code: res.user_code,
+            verification_uri_complete: res.verification_uri_complete,
+            expires_in: res.expires_in,
+            interval: res.interval,
+        })
+    }
+
+    pub(crate) async fn create_token(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        device_code: &str,
+        user_code: &str,
+    ) -> Result<CreateTokenOutput, SsoApiError> {
+        self.config.faults.api("CreateToken").await?;
+        let res = self
+            .sso_oidc()
+            .create_token()
+            .client_id(client_id)
+            .client_secret(client_secret)
+            .code(user_code)
+            .device_code(device_code)
+            .grant_type("urn:ietf:params:oauth:grant-type:device_code")
+            .send()
+            .await
+            .map_err(api_error)?;
+        Ok(CreateTokenOutput {
+            access_token: res.access_token,
+            expires_in: res.expires_in,
+            refresh_token: res.refresh_token,
+        })
+    }
+
+    pub(crate) async fn refresh_token(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        refresh_token: &str,
+    ) -> Result<CreateTokenOutput, SsoApiError> {
+        self.config.faults.api("CreateToken").await?;
+        let res = self
+            .sso_oidc()
+            .create_token()
+            .client_id(client_id)
+            .client_secret(client_secret)
+            .refresh_token(refresh_token)
+            .grant_type("refresh_token")
+            .send()
+            .await
+            .map_err(api_error)?;
+        Ok(CreateTokenOutput {
+            access_token: res.access_token,
+            expires_in: res.expires_in,
+            refresh_token: res.refresh_token,
+        })
+    }
+
+    /// Get the server's current time.
+    ///
+    /// This sends a deliberately invalid `CreateToken` request, which doesn't require any
+    /// credentials, and reads the `Date` header from the error response.
+    #[cfg_attr(not(feature = "doctor"), allow(dead_code))]
+    pub(crate) async fn server_time(&self) -> Result<DateTime<Utc>, BoxError> {
+        let Err(error) = self
+            .sso_oidc()
+            .create_token()
+            .client_id("invalid")
+            .client_secret("invalid")
+            .grant_type("invalid")
+            .send()
+            .await
+        else {
+            return Err("unexpected success for invalid CreateToken request".into());
+        };
+        let Some(date) = error
+            .raw_response()
+            .and_then(|res| res.headers().get("date"))
+        else {
+            return Err(error.into());
+        };
+        Ok(DateTime::parse_from_rfc2822(date)?.with_timezone(&Utc))
+    }
+
+    pub(crate) async fn get_role_credentials(
+        &self,
+        access_token: &str,
+        account_id: &str,
+        role_name: &str,
+    ) -> Result<GetRoleCredentialsOutput, SsoApiError> {
+        self.config.faults.api("GetRoleCredentials").await?;
+        // Throttled calls are retried by `sso::Client`, so the SDK's own retries would multiply
+        // the number of attempts
+        let res = self
+            .sso()
+            .get_role_credentials()
+            .access_token(access_token)
+            .account_id(account_id)
+            .role_name(role_name)
+            .customize()
+            .config_override(aws_sdk_sso::Config::builder().retry_config(RetryConfig::disabled()))
+            .send()
+            .await
+            .map_err(api_error)?;
+        Ok(GetRoleCredentialsOutput {
+            role_credentials: res.role_credentials.map(|credentials| RoleCredentials {
+                access_key_id: credentials.access_key_id,
+                secret_access_key: credentials.secret_access_key,
+                session_token: credentials.session_token,
+                expiration: credentials.expiration,
+            }),
+        })
+    }
+
+    pub(crate) async fn list_accounts(
+        &self,
+        access_token: &str,
+        next_token: Option<String>,
+    ) -> Result<ListAccountsOutput, SsoApiError> {
+        self.config.faults.api("ListAccounts").await?;
+        let res = self
+            .sso()
+            .list_accounts()
+            .access_token(access_token)
+            .set_next_token(next_token)
+            .send()
+            .await
+            .map_err(api_error)?;
+        Ok(ListAccountsOutput {
+            next_token: res.next_token,
+            account_list: res
+                .account_list
+                .unwrap_or_default()
+                .into_iter()
+                .map(|account| AccountInfo {
+                    account_id: account.account_id,
+                    account_name: account.account_name,
+                    email_address: account.email_address,
+                })
+                .collect(),
+        })
+    }
+
+    pub(crate) async fn list_account_roles(
+        &self,
+        access_token: &str,
+        account_id: &str,
+        next_token: Option<String>,
+    ) -> Result<ListAccountRolesOutput, SsoApiError> {
+        self.config.faults.api("ListAccountRoles").await?;
+        let res = self
+            .sso()
+            .list_account_roles()
+            .access_token(access_token)
+            .account_id(account_id)
+            .set_next_token(next_token)
+            .send()
+            .await
+            .map_err(api_error)?;
+        Ok(ListAccountRolesOutput {
+            next_token: res.next_token,
+            role_list: res
+                .role_list
+                .unwrap_or_default()
+                .into_iter()
+                .map(|role| RoleInfo {
+                    account_id: role.account_id,
+                    role_name: role.role_name,
+                })
+                .collect(),
+        })
+    }
+
+    pub(crate) async fn logout(&self, access_token: &str) -> Result<(), SsoApiError> {
+        self.config.faults.api("Logout").await?;
+        self.sso()
+            .logout()
+            .access_token(access_token)
+            .send()
+            .await
+            .map_err(api_error)?;
+        Ok(())
+    }
+}
+
+fn api_error<E>(error: SdkError<E>) -> SsoApiError
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+{
+    let transient = matches!(
+        error,
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_)
+    );
+    let error = SsoApiError::from_response(
+        error.request_id().map(str::to_string),
+        error.raw_response().map(|res| res.status().as_u16()),
+        error.code().map(str::to_string),
+        error,
+    );
+    if transient {
+        error.transient()
+    } else {
+        error
+    }
+}
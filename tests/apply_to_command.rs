@@ -0,0 +1,40 @@
+use std::{ffi::OsStr, process::Command};
+
+use aws_sso_flow::SessionCredentials;
+use chrono::{TimeZone, Utc};
+
+#[test]
+fn sets_credentials_and_removes_conflicts() {
+    let credentials = SessionCredentials {
+        access_key_id: "ASIAEXAMPLE".to_string(),
+        secret_access_key: "secret".to_string(),
+        session_token: "token".to_string(),
+        expires_at: Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap(),
+    };
+    let mut command = Command::new("env");
+    command.env("AWS_PROFILE", "other");
+    credentials.apply_to_command(&mut command);
+
+    let envs: Vec<_> = command.get_envs().collect();
+    let get = |name: &str| {
+        envs.iter()
+            .find(|(key, _)| *key == OsStr::new(name))
+            .map(|(_, value)| *value)
+    };
+    assert_eq!(
+        get("AWS_ACCESS_KEY_ID"),
+        Some(Some(OsStr::new("ASIAEXAMPLE")))
+    );
+    assert_eq!(
+        get("AWS_SECRET_ACCESS_KEY"),
+        Some(Some(OsStr::new("secret")))
+    );
+    assert_eq!(get("AWS_SESSION_TOKEN"), Some(Some(OsStr::new("token"))));
+    assert_eq!(
+        get("AWS_CREDENTIAL_EXPIRATION"),
+        Some(Some(OsStr::new("2024-01-02T03:04:05Z")))
+    );
+    // Removed variables are reported with no value
+    assert_eq!(get("AWS_PROFILE"), Some(None));
+    assert_eq!(get("AWS_DEFAULT_PROFILE"), Some(None));
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "aws-sdk")]
+
+use std::fs;
+
+use aws_sso_flow::{
+    aws::{ProvideCredentials, SsoCredentialsProvider},
+    CacheStage, ProfileSource, SsoFlowBuilder,
+};
+use chrono::{Duration, Utc};
+
+mod common;
+
+#[tokio::test]
+async fn provider_name_includes_profile() {
+    let dir = common::temp_dir("aws_provider_name");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config");
+    fs::write(
+        &path,
+        "[profile dev]
+sso_start_url = https://myorg.awsapps.com/start
+sso_region = eu-west-1
+sso_account_id = 012345678910
+sso_role_name = PowerUser
+",
+    )
+    .unwrap();
+
+    let flow = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .offline(true)
+        .config(
+            ProfileSource::default()
+                .with_config_file(&path)
+                .with_profile("dev"),
+        )
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+    let derived = flow.with_config(common::config());
+    for flow in [&flow, &derived] {
+        fs::write(
+            flow.cache_path(CacheStage::Credentials).unwrap(),
+            serde_json::json!({
+                "access_key_id": "ASIAEXAMPLE",
+                "secret_access_key": "secret",
+                "session_token": "token",
+                "expires_at": Utc::now() + Duration::hours(1),
+            })
+            .to_string(),
+        )
+        .unwrap();
+    }
+
+    let provider = SsoCredentialsProvider::from_flow(flow.clone()).provider_name_context(true);
+    let credentials = provider.provide_credentials().await.unwrap();
+    assert!(
+        format!("{credentials:?}").contains(
+            r#"provider_name: "SsoFlow(profile=dev, account=012345678910, role=PowerUser)""#
+        ),
+        "{credentials:?}"
+    );
+
+    // Flows that aren't loaded from a profile only have the account and role
+    let provider = SsoCredentialsProvider::from_flow(derived).provider_name_context(true);
+    let credentials = provider.provide_credentials().await.unwrap();
+    assert!(
+        format!("{credentials:?}")
+            .contains(r#"provider_name: "SsoFlow(account=012345678910, role=PowerUser)""#),
+        "{credentials:?}"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,70 @@
+use std::{path::PathBuf, time::Duration};
+
+use aws_sso_flow::{CacheStage, SsoFlowBuilder};
+
+mod common;
+
+fn cache_dir(name: &str) -> PathBuf {
+    common::temp_dir(&format!("builder_presets-{name}"))
+}
+
+#[tokio::test]
+async fn interactive_cli() {
+    let flow = SsoFlowBuilder::interactive_cli()
+        .cache_dir(cache_dir("cli"))
+        .config(common::config())
+        .build()
+        .await
+        .unwrap();
+    let snapshot = flow.debug_snapshot();
+    assert_eq!(snapshot.connect_timeout, Some(Duration::from_secs(5)));
+    assert_eq!(snapshot.api_timeout, Some(Duration::from_secs(30)));
+    assert_eq!(snapshot.refresh_ahead, None);
+    assert!(flow.cache_path(CacheStage::Credentials).is_some());
+}
+
+#[tokio::test]
+async fn headless_service() {
+    let flow = SsoFlowBuilder::headless_service()
+        .cache_dir(cache_dir("service"))
+        .config(common::config())
+        .build()
+        .await
+        .unwrap();
+    let snapshot = flow.debug_snapshot();
+    assert_eq!(snapshot.connect_timeout, Some(Duration::from_secs(3)));
+    assert_eq!(snapshot.api_timeout, Some(Duration::from_secs(10)));
+    assert_eq!(snapshot.refresh_ahead, Some(Duration::from_secs(15 * 60)));
+    assert!(flow.cache_path(CacheStage::Token).is_some());
+    assert_eq!(flow.cache_path(CacheStage::Credentials), None);
+}
+
+#[tokio::test]
+async fn desktop_app() {
+    let flow = SsoFlowBuilder::desktop_app()
+        .cache_dir(cache_dir("desktop"))
+        .config(common::config())
+        .build()
+        .await
+        .unwrap();
+    let snapshot = flow.debug_snapshot();
+    assert_eq!(snapshot.connect_timeout, Some(Duration::from_secs(10)));
+    assert_eq!(snapshot.api_timeout, Some(Duration::from_secs(30)));
+    assert_eq!(snapshot.refresh_ahead, Some(Duration::from_secs(10 * 60)));
+    assert!(flow.cache_path(CacheStage::Credentials).is_some());
+}
+
+#[tokio::test]
+async fn preset_options_can_be_overridden() {
+    let flow = SsoFlowBuilder::headless_service()
+        .api_timeout(Duration::from_secs(60))
+        .cache_dir(cache_dir("override"))
+        .config(common::config())
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(
+        flow.debug_snapshot().api_timeout,
+        Some(Duration::from_secs(60))
+    );
+}
@@ -0,0 +1,25 @@
+use aws_sso_flow::{CacheStage, SsoFlowBuilder};
+
+mod common;
+
+// This is the only test in this binary, so setting the environment can't race with other tests
+#[tokio::test]
+async fn env_override() {
+    let dir = std::env::temp_dir().join("aws_sso_flow-cache_dir_env");
+    std::env::set_var("AWS_SSO_FLOW_CACHE_DIR", &dir);
+
+    let builder = || {
+        SsoFlowBuilder::new()
+            .config(common::config())
+            .verification_prompt(common::no_prompt())
+    };
+
+    let flow = builder().build().await.unwrap();
+    let path = flow.cache_path(CacheStage::Token).unwrap();
+    assert_eq!(path.parent(), Some(dir.as_path()));
+
+    // An explicit directory takes precedence
+    let flow = builder().cache_dir("explicit").build().await.unwrap();
+    let path = flow.cache_path(CacheStage::Token).unwrap();
+    assert!(path.starts_with("explicit"), "{}", path.display());
+}
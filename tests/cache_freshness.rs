@@ -0,0 +1,59 @@
+use std::fs;
+
+use aws_sso_flow::{CacheStage, Clock, ErrorCode, SsoFlowBuilder};
+use chrono::Utc;
+
+mod common;
+
+#[derive(Debug)]
+struct FixedClock(chrono::DateTime<Utc>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> chrono::DateTime<Utc> {
+        self.0
+    }
+}
+
+#[tokio::test]
+async fn cached_values_expire_a_minute_early() {
+    let dir = common::temp_dir("cache_freshness");
+
+    let now = Utc::now();
+    let flow = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .offline(true)
+        .clock(FixedClock(now))
+        .config(common::config())
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+
+    let path = flow.cache_path(CacheStage::Credentials).unwrap();
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let write = |expires_at: chrono::DateTime<Utc>| {
+        fs::write(
+            &path,
+            serde_json::json!({
+                "access_key_id": "ASIAEXAMPLE",
+                "secret_access_key": "secret",
+                "session_token": "token",
+                "expires_at": expires_at,
+            })
+            .to_string(),
+        )
+        .unwrap();
+    };
+
+    // Values within a minute of expiry aren't served, so callers never receive credentials that
+    // expire as they're used
+    write(now + chrono::Duration::seconds(60));
+    let error = flow.authenticate().await.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::OfflineUnavailable);
+
+    write(now + chrono::Duration::seconds(61));
+    let credentials = flow.authenticate().await.unwrap();
+    assert_eq!(credentials.access_key_id, "ASIAEXAMPLE");
+
+    let _ = fs::remove_dir_all(&dir);
+}
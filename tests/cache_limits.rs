@@ -0,0 +1,62 @@
+use std::fs;
+
+use aws_sso_flow::{CacheStage, SsoConfig, SsoFlowBuilder};
+use chrono::{Duration, Utc};
+
+mod common;
+
+#[tokio::test]
+async fn evicts_expired_entries_first() {
+    let dir = common::temp_dir("cache_limits");
+
+    let builder = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .max_cache_entries(2)
+        .verification_prompt(common::no_prompt());
+    let mut paths = Vec::new();
+    for (i, expires_in) in [Duration::hours(1), -Duration::hours(1), Duration::hours(2)]
+        .into_iter()
+        .enumerate()
+    {
+        let flow = builder
+            .clone()
+            .config(SsoConfig {
+                start_url: format!("https://cache-limits-{i}.awsapps.com/start"),
+                ..common::config()
+            })
+            .build()
+            .await
+            .unwrap();
+        flow.import_access_token("token", Utc::now() + expires_in)
+            .await
+            .unwrap();
+        paths.push(flow.cache_path(CacheStage::Token).unwrap());
+    }
+
+    // The expired token is evicted, even though it's newer than the first one
+    assert!(paths[0].exists());
+    assert!(!paths[1].exists());
+    assert!(paths[2].exists());
+
+    // Unrelated files are left alone
+    fs::write(dir.join("notes.txt"), "keep me").unwrap();
+    let flow = builder
+        .clone()
+        .max_cache_bytes(1)
+        .config(SsoConfig {
+            start_url: "https://cache-limits-3.awsapps.com/start".to_string(),
+            ..common::config()
+        })
+        .build()
+        .await
+        .unwrap();
+    flow.import_access_token("token", Utc::now() + Duration::hours(1))
+        .await
+        .unwrap();
+    assert!(!paths[0].exists());
+    assert!(!paths[2].exists());
+    assert!(flow.cache_path(CacheStage::Token).unwrap().exists());
+    assert!(dir.join("notes.txt").exists());
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,60 @@
+#![cfg(all(unix, not(target_os = "macos")))]
+
+use std::fs;
+
+use aws_sso_flow::{CacheStage, SsoFlowBuilder};
+use chrono::Utc;
+
+mod common;
+
+// This is the only test in this binary, so setting the environment can't race with other tests
+#[tokio::test]
+async fn previous_layout_client_registration_is_migrated() {
+    let cache_home = common::temp_dir("cache_migration");
+    std::env::remove_var("AWS_SSO_FLOW_CACHE_DIR");
+    std::env::set_var("XDG_CACHE_HOME", &cache_home);
+
+    let flow = SsoFlowBuilder::new()
+        .config(common::config())
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+
+    // Before registrations were shared by start URL and region, they had the same suffix as the
+    // configuration's other entries
+    let token_path = flow.cache_path(CacheStage::Token).unwrap();
+    let suffix = token_path
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .strip_prefix("token-")
+        .unwrap();
+    let previous_dir = cache_home.join(format!(
+        "aws_sso_flow@0.{}",
+        env!("CARGO_PKG_VERSION_MINOR").parse::<u32>().unwrap() - 1
+    ));
+    fs::create_dir_all(&previous_dir).unwrap();
+    fs::write(
+        previous_dir.join(format!("client-{suffix}")),
+        serde_json::json!({
+            "client_id": "client",
+            "client_secret": "secret",
+            "client_secret_expires_at": Utc::now() + chrono::Duration::days(30),
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let plan = flow.plan().await.unwrap();
+    let client = plan
+        .steps
+        .iter()
+        .find(|step| step.stage == CacheStage::Client)
+        .unwrap();
+    assert!(client.cached);
+    assert!(flow.cache_path(CacheStage::Client).unwrap().exists());
+
+    let _ = fs::remove_dir_all(&cache_home);
+}
@@ -0,0 +1,33 @@
+// Fixtures shared by the integration tests. Each test binary uses a subset of them.
+#![allow(dead_code)]
+
+use std::{convert::Infallible, fs, path::PathBuf};
+
+use aws_sso_flow::{Region, SsoConfig, VerificationPrompt};
+
+/// A configuration for a fake portal.
+///
+/// Flows in the same process share registrations and tokens by start URL, region and endpoint, so
+/// tests in one binary that obtain them should use distinct start URLs or endpoints.
+pub fn config() -> SsoConfig {
+    SsoConfig {
+        region: Region::new("eu-west-1"),
+        start_url: "https://myorg.awsapps.com/start".to_string(),
+        account_id: "012345678910".into(),
+        role_name: "PowerUser".into(),
+        account_name: None,
+        role_selector: None,
+    }
+}
+
+/// A prompt that does nothing, for flows that aren't expected to prompt.
+pub fn no_prompt() -> impl VerificationPrompt<Error = Infallible> {
+    |_| async { Ok::<_, Infallible>(()) }
+}
+
+/// An empty temporary directory named for the test and process, which the test should remove.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("aws_sso_flow-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
@@ -0,0 +1,34 @@
+#![cfg(unix)]
+
+use std::fs;
+
+use aws_sso_flow::{ProfileSource, SsoConfigSource};
+
+mod common;
+
+// This is the only test in this binary, so setting the environment can't race with other tests
+#[tokio::test]
+async fn config_file_is_a_single_path() {
+    let dir = common::temp_dir("config_file_env");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("team:config");
+    fs::write(
+        &path,
+        "[profile dev]
+sso_start_url = https://myorg.awsapps.com/start
+sso_region = eu-west-1
+sso_account_id = 123456789012
+sso_role_name = Developer
+",
+    )
+    .unwrap();
+    std::env::set_var("AWS_CONFIG_FILE", &path);
+
+    let source = ProfileSource::default().with_profile("dev");
+    let (paths, _) = source.resolve_paths().unwrap();
+    assert_eq!(paths, [path]);
+    let config = source.load().await.unwrap();
+    assert_eq!(config.account_id, "123456789012");
+
+    let _ = fs::remove_dir_all(&dir);
+}
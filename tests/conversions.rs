@@ -0,0 +1,43 @@
+#![cfg(any(feature = "aws-sdk", feature = "http"))]
+
+use aws_sso_flow::SessionCredentials;
+use chrono::{TimeZone, Utc};
+
+fn credentials() -> SessionCredentials {
+    SessionCredentials {
+        access_key_id: "ASIAEXAMPLE".to_string(),
+        secret_access_key: "secret".to_string(),
+        session_token: "IQoJb3JpZ2luX2VjE/abc+def==".to_string(),
+        expires_at: Utc.with_ymd_and_hms(2024, 3, 5, 12, 0, 0).unwrap(),
+    }
+}
+
+#[cfg(feature = "aws-sdk")]
+#[test]
+fn identity() {
+    use aws_sso_flow::aws::{Credentials, Identity};
+
+    let identity = Identity::from(credentials());
+    assert_eq!(identity.expiration(), Some(credentials().expires_at.into()));
+    let aws_credentials = identity.data::<Credentials>().unwrap();
+    assert_eq!(aws_credentials.access_key_id(), "ASIAEXAMPLE");
+    assert_eq!(
+        aws_credentials.session_token(),
+        Some("IQoJb3JpZ2luX2VjE/abc+def==")
+    );
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn header_map() {
+    let headers = http::HeaderMap::try_from(&credentials()).unwrap();
+    let session_token = &headers["x-amz-security-token"];
+    assert_eq!(session_token, "IQoJb3JpZ2luX2VjE/abc+def==");
+    assert!(session_token.is_sensitive());
+
+    let invalid = SessionCredentials {
+        session_token: "line\nbreak".to_string(),
+        ..credentials()
+    };
+    assert!(http::HeaderMap::try_from(&invalid).is_err());
+}
@@ -0,0 +1,59 @@
+use std::fs;
+
+use aws_sso_flow::{ErrorCode, SessionCredentials, SsoFlowBuilder};
+use chrono::{Duration, SubsecRound, Utc};
+
+mod common;
+
+#[tokio::test]
+async fn reuses_fresh_file() {
+    let dir = common::temp_dir("credential_process_file");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("credentials.json");
+
+    let flow = SsoFlowBuilder::self_contained(common::config())
+        .offline(true)
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+
+    // A missing or expired file can't be refreshed offline, and is left alone
+    let error = flow.sync_credential_process_file(&path).await.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::OfflineUnavailable);
+    assert!(!path.exists());
+
+    let expired = SessionCredentials {
+        access_key_id: "AKIAEXAMPLE".to_string(),
+        secret_access_key: "secret".to_string(),
+        session_token: "token".to_string(),
+        expires_at: Utc::now().trunc_subsecs(0) - Duration::minutes(1),
+    };
+    fs::write(&path, expired.to_credential_process_json()).unwrap();
+    let error = flow.sync_credential_process_file(&path).await.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::OfflineUnavailable);
+
+    let fresh = SessionCredentials {
+        expires_at: Utc::now().trunc_subsecs(0) + Duration::hours(1),
+        ..expired
+    };
+    let json = fresh.to_credential_process_json();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["Version"], 1);
+    assert_eq!(value["AccessKeyId"], "AKIAEXAMPLE");
+    assert_eq!(value["SecretAccessKey"], "secret");
+    assert_eq!(value["SessionToken"], "token");
+    assert_eq!(
+        value["Expiration"],
+        fresh
+            .expires_at
+            .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+    );
+
+    fs::write(&path, &json).unwrap();
+    let credentials = flow.sync_credential_process_file(&path).await.unwrap();
+    assert_eq!(credentials.access_key_id, fresh.access_key_id);
+    assert_eq!(credentials.expires_at, fresh.expires_at);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,50 @@
+use std::{fs, time::Duration};
+
+use aws_sso_flow::{CacheStage, ErrorCode, SsoFlowBuilder};
+use chrono::Utc;
+use futures::StreamExt;
+
+mod common;
+
+#[tokio::test]
+async fn yields_current_credentials_then_ends_on_error() {
+    let dir = common::temp_dir("credentials_stream");
+
+    let flow = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .offline(true)
+        .config(common::config())
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+
+    // Nothing is cached, so the offline flow can't obtain credentials
+    let items: Vec<_> = flow
+        .credentials_stream(Duration::from_secs(300))
+        .collect()
+        .await;
+    assert_eq!(items.len(), 1);
+    let error = items.into_iter().next().unwrap().unwrap_err();
+    assert_eq!(error.code(), ErrorCode::OfflineUnavailable);
+
+    let path = flow.cache_path(CacheStage::Credentials).unwrap();
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        &path,
+        serde_json::json!({
+            "access_key_id": "ASIAEXAMPLE",
+            "secret_access_key": "secret",
+            "session_token": "token",
+            "expires_at": Utc::now() + chrono::Duration::hours(1),
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let mut stream = std::pin::pin!(flow.credentials_stream(Duration::from_secs(300)));
+    let credentials = stream.next().await.unwrap().unwrap();
+    assert_eq!(credentials.access_key_id, "ASIAEXAMPLE");
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use aws_sso_flow::{CacheStage, Region, SsoConfig, SsoFlowBuilder};
+
+mod common;
+
+async fn client_cache_path(start_url: &str, normalize: bool) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "aws_sso_flow-custom_domain_start_url-{}",
+        std::process::id()
+    ));
+    let flow = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .offline(true)
+        .normalize_start_url(normalize)
+        .config(SsoConfig {
+            start_url: start_url.to_string(),
+            ..common::config()
+        })
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+    flow.cache_path(CacheStage::Client).unwrap()
+}
+
+#[tokio::test]
+async fn equivalent_start_urls_share_registrations() {
+    let cases = [
+        (
+            "https://myorg.awsapps.com/start",
+            &[
+                "https://myorg.awsapps.com/start/",
+                "https://MyOrg.awsapps.com/start#/",
+                "myorg.awsapps.com/start",
+            ][..],
+        ),
+        (
+            "https://sso.example.com/start",
+            &[
+                "https://SSO.Example.com/start/",
+                "https://sso.example.com:443/start#/",
+                "sso.example.com/start",
+            ][..],
+        ),
+        (
+            "https://example.com",
+            &["https://example.com/", "HTTPS://EXAMPLE.COM"][..],
+        ),
+    ];
+    for (start_url, equivalents) in cases {
+        let expected = client_cache_path(start_url, true).await;
+        for equivalent in equivalents {
+            assert_eq!(
+                client_cache_path(equivalent, true).await,
+                expected,
+                "{equivalent} should be the same portal as {start_url}"
+            );
+        }
+    }
+
+    let distinct = [
+        "https://sso.example.com/start",
+        "https://sso.example.org/start",
+        "https://sso.example.com/other",
+        "https://login.sso.example.com/start",
+    ];
+    for (i, a) in distinct.iter().enumerate() {
+        for b in &distinct[i + 1..] {
+            assert_ne!(
+                client_cache_path(a, true).await,
+                client_cache_path(b, true).await,
+                "{a} and {b} should be different portals"
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn normalization_can_be_disabled() {
+    assert_eq!(
+        client_cache_path("https://sso.example.com/start/", false).await,
+        client_cache_path("https://sso.example.com/start", false).await,
+    );
+    assert_ne!(
+        client_cache_path("https://SSO.Example.com/start", false).await,
+        client_cache_path("https://sso.example.com/start", false).await,
+    );
+}
+
+#[test]
+fn custom_domains_are_valid() {
+    let config = SsoConfig::builder()
+        .region(Region::EU_WEST_1)
+        .start_url("https://sso.example.com/start")
+        .account("012345678910")
+        .role("PowerUser")
+        .build()
+        .unwrap();
+    assert_eq!(config.portal_name(), None);
+    assert_eq!(
+        config.to_string(),
+        "account 012345678910, role PowerUser, region eu-west-1, portal sso.example.com"
+    );
+}
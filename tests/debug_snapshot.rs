@@ -0,0 +1,79 @@
+use std::{fs, time::Duration};
+
+use aws_sso_flow::{CacheStage, ProfileSource, SsoFlowBuilder};
+
+mod common;
+
+#[tokio::test]
+async fn snapshot_is_redacted() {
+    let dir = common::temp_dir("debug_snapshot");
+    let flow = SsoFlowBuilder::self_contained(common::config())
+        .cache_dir(&dir)
+        .api_timeout(Duration::from_secs(30))
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+
+    let snapshot = flow.debug_snapshot();
+    assert_eq!(snapshot.config_source, "explicit configuration");
+    assert_eq!(snapshot.start_url, "https://my***.awsapps.com/start");
+    assert_eq!(snapshot.account, "********8910");
+    assert_eq!(snapshot.role, "PowerUser");
+    assert_eq!(
+        snapshot.oidc_endpoint,
+        "https://oidc.eu-west-1.amazonaws.com"
+    );
+    assert_eq!(snapshot.api_timeout, Some(Duration::from_secs(30)));
+    assert_eq!(snapshot.cache.len(), 5);
+    assert_eq!(snapshot.cache[1].stage, "token");
+    assert_eq!(snapshot.cache[1].path, flow.cache_path(CacheStage::Token));
+
+    let json = serde_json::to_value(&snapshot).unwrap();
+    assert_eq!(json["api_timeout"], 30.0);
+    assert_eq!(json["connect_timeout"], serde_json::Value::Null);
+    let json = json.to_string();
+    assert!(!json.contains("myorg"));
+    assert!(!json.contains("012345678910"));
+}
+
+#[tokio::test]
+async fn snapshot_describes_profile_source() {
+    let dir = common::temp_dir("debug_snapshot-profile");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config");
+    fs::write(
+        &path,
+        "[profile dev]
+sso_start_url = https://myorg.awsapps.com/start
+sso_region = eu-west-1
+sso_account_id = 123456789012
+sso_role_name = Developer
+",
+    )
+    .unwrap();
+
+    let flow = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .config(
+            ProfileSource::default()
+                .with_config_file(&path)
+                .with_profile("dev"),
+        )
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(
+        flow.debug_snapshot().config_source,
+        format!("profile dev in {}", path.display())
+    );
+    assert_eq!(flow.profile(), Some("dev"));
+
+    let derived = flow.with_config(common::config());
+    assert_eq!(
+        derived.debug_snapshot().config_source,
+        "explicit configuration"
+    );
+    assert_eq!(derived.profile(), None);
+}
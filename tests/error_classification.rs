@@ -0,0 +1,33 @@
+use std::fs;
+
+use aws_sso_flow::{CacheStage, ErrorCode, SsoFlowBuilder};
+
+mod common;
+
+#[tokio::test]
+async fn offline_errors() {
+    let dir = common::temp_dir("error_classification");
+
+    let flow = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .offline(true)
+        .config(common::config())
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+
+    let error = flow.authenticate().await.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::OfflineUnavailable);
+    assert!(error.is_interaction_required());
+    assert!(!error.is_retryable());
+
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(flow.cache_path(CacheStage::Credentials).unwrap(), "corrupt").unwrap();
+    let error = flow.authenticate().await.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::Cache);
+    assert!(!error.is_interaction_required());
+    assert!(!error.is_retryable());
+
+    let _ = fs::remove_dir_all(&dir);
+}
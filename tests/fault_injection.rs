@@ -0,0 +1,169 @@
+#![cfg(all(feature = "test-util", feature = "test-server"))]
+
+use std::{
+    collections::HashMap,
+    error::Error as _,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+mod common;
+
+use aws_sso_flow::{
+    test_server::{Behavior, TestServer},
+    CacheStage, Fault, FaultPoint, FaultPolicy, SsoFlowBuilder, SsoFlowError,
+};
+
+/// Inject the scripted faults at each point, in order, then nothing.
+#[derive(Debug, Default)]
+struct Script(Mutex<HashMap<FaultPoint, Vec<Fault>>>);
+
+impl Script {
+    fn with<const N: usize>(self, point: FaultPoint, faults: [Fault; N]) -> Self {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(point, faults.into_iter().rev().collect());
+        self
+    }
+}
+
+impl FaultPolicy for Script {
+    fn inject(&self, point: FaultPoint) -> Option<Fault> {
+        self.0.lock().unwrap().get_mut(&point)?.pop()
+    }
+}
+
+async fn authenticate(
+    name: &str,
+    script: Script,
+) -> (Result<(), SsoFlowError<std::io::Error>>, TestServer, bool) {
+    let server = TestServer::start(Behavior::default()).await.unwrap();
+    let dir = common::temp_dir(&format!("fault_injection-{name}"));
+
+    let prompted = Arc::new(AtomicBool::new(false));
+    let flow = SsoFlowBuilder::self_contained(server.config())
+        .cache_dir(dir)
+        .endpoint_url(server.url())
+        .min_poll_interval(Duration::from_millis(10))
+        .fault_policy(script)
+        .verification_prompt({
+            let prompted = Arc::clone(&prompted);
+            move |_| {
+                prompted.store(true, Ordering::Relaxed);
+                async { Ok::<_, std::io::Error>(()) }
+            }
+        })
+        .build()
+        .await
+        .unwrap();
+    let result = flow.authenticate().await.map(|_| ());
+    (result, server, prompted.load(Ordering::Relaxed))
+}
+
+#[tokio::test]
+async fn cache_write_failure() {
+    let script = Script::default().with(
+        FaultPoint::CacheWrite(CacheStage::Credentials),
+        [Fault::Fail],
+    );
+    let (result, _, _) = authenticate("cache_write", script).await;
+    let error = result.unwrap_err();
+    assert!(matches!(error, SsoFlowError::Cache(_)), "{error:?}");
+
+    // The cache error and the failure it wraps are sources, rather than repeated in the message
+    assert_eq!(error.to_string(), "SSO authentication failed");
+    let source = error.source().unwrap();
+    assert_eq!(
+        source.to_string(),
+        "cache error when attempting authentication: failed to write credentials cache entry"
+    );
+    assert!(source.source().is_some());
+}
+
+#[tokio::test]
+async fn cache_read_failure() {
+    let script = Script::default().with(FaultPoint::CacheRead(CacheStage::Client), [Fault::Fail]);
+    let (result, server, _) = authenticate("cache_read", script).await;
+    assert!(matches!(result, Err(SsoFlowError::Cache(_))));
+    assert_eq!(server.operations(), Vec::<&str>::new());
+}
+
+#[tokio::test]
+async fn network_failure() {
+    let script = Script::default().with(FaultPoint::Api("RegisterClient"), [Fault::Fail]);
+    let (result, _, _) = authenticate("network", script).await;
+    let error = result.unwrap_err();
+    assert!(matches!(error, SsoFlowError::Api(_)), "{error:?}");
+    assert!(error.is_retryable());
+}
+
+#[tokio::test]
+async fn throttled_api_call_is_retried() {
+    let throttled = Fault::api_error("TooManyRequestsException", 429);
+    let script = Script::default().with(
+        FaultPoint::Api("GetRoleCredentials"),
+        [throttled.clone(), throttled],
+    );
+    let (result, server, _) = authenticate("throttled", script).await;
+    result.unwrap();
+    // Injected faults fail before the request is sent
+    assert_eq!(
+        server
+            .operations()
+            .into_iter()
+            .filter(|operation| *operation == "GetRoleCredentials")
+            .count(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn throttling_is_retried_by_status_or_code() {
+    // `ThrottlingException` usually comes with a 400, and a 429 may not have a throttling code
+    let script = Script::default().with(
+        FaultPoint::Api("GetRoleCredentials"),
+        [
+            Fault::api_error("ThrottlingException", 400),
+            Fault::api_error("UnknownError", 429),
+        ],
+    );
+    let (result, _, _) = authenticate("throttling", script).await;
+    result.unwrap();
+}
+
+#[tokio::test]
+async fn aws_errors_are_surfaced() {
+    let script = Script::default().with(
+        FaultPoint::Api("GetRoleCredentials"),
+        [Fault::api_error("ForbiddenException", 403)],
+    );
+    let (result, _, _) = authenticate("forbidden", script).await;
+    match result.unwrap_err() {
+        SsoFlowError::AccessRevoked(error) => assert_eq!(error.status(), Some(403)),
+        error => panic!("unexpected error: {error:?}"),
+    }
+}
+
+#[tokio::test]
+async fn failed_prompt_is_silent() {
+    let script = Script::default().with(FaultPoint::Prompt, [Fault::Fail]);
+    let (result, _, prompted) = authenticate("prompt", script).await;
+    // The test server grants access without the user
+    result.unwrap();
+    assert!(!prompted);
+}
+
+#[tokio::test]
+async fn delays() {
+    let script = Script::default().with(
+        FaultPoint::Api("CreateToken"),
+        [Fault::Delay(Duration::from_millis(50))],
+    );
+    let (result, _, prompted) = authenticate("delay", script).await;
+    result.unwrap();
+    assert!(prompted);
+}
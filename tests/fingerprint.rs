@@ -0,0 +1,26 @@
+use aws_sso_flow::SessionCredentials;
+use chrono::Utc;
+
+fn credentials(access_key_id: &str, secret_access_key: &str) -> SessionCredentials {
+    SessionCredentials {
+        access_key_id: access_key_id.to_string(),
+        secret_access_key: secret_access_key.to_string(),
+        session_token: "token".to_string(),
+        expires_at: Utc::now(),
+    }
+}
+
+#[test]
+fn stable_and_log_safe() {
+    let fingerprint = credentials("ASIAEXAMPLE", "secret").fingerprint();
+    assert_eq!(fingerprint, "3fb6ca7885c9");
+    assert_eq!(
+        credentials("ASIAEXAMPLE", "other").fingerprint(),
+        fingerprint
+    );
+    assert_ne!(
+        credentials("ASIAOTHER", "secret").fingerprint(),
+        fingerprint
+    );
+    assert!(!fingerprint.contains("ASIA"));
+}
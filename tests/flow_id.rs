@@ -0,0 +1,42 @@
+use std::fs;
+
+use aws_sso_flow::{CacheStage, ErrorCode, SsoFlowBuilder};
+
+mod common;
+
+#[tokio::test]
+async fn errors_and_audit_log_share_flow_id() {
+    let dir = common::temp_dir("flow_id");
+    let audit_log = dir.join("audit.log");
+
+    let flow = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .audit_log(&audit_log)
+        .offline(true)
+        .config(common::config())
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(flow.cache_path(CacheStage::Credentials).unwrap(), "corrupt").unwrap();
+
+    let first = flow.authenticate().await.unwrap_err();
+    let second = flow.authenticate().await.unwrap_err();
+    assert_eq!(first.code(), ErrorCode::Cache);
+    let (first, second) = (first.flow_id().unwrap(), second.flow_id().unwrap());
+    assert_ne!(first, second);
+
+    let log = fs::read_to_string(&audit_log).unwrap();
+    let ids: Vec<String> = log
+        .lines()
+        .map(|line| {
+            let event: serde_json::Value = serde_json::from_str(line).unwrap();
+            event["flow_id"].as_str().unwrap().to_string()
+        })
+        .collect();
+    assert_eq!(ids, [first.to_string(), second.to_string()]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
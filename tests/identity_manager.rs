@@ -0,0 +1,68 @@
+use std::fs;
+
+use aws_sso_flow::{CacheStage, IdentityManager, SsoConfig, SsoFlowBuilder};
+use chrono::Utc;
+
+mod common;
+
+#[tokio::test]
+async fn identities_have_separate_flows() {
+    let dir = common::temp_dir("identity_manager");
+
+    let template = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .offline(true)
+        .config(common::config())
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+
+    let identities = IdentityManager::new(template.clone());
+    assert!(identities.is_empty());
+    for (label, account) in [("acme", "111111111111"), ("globex", "222222222222")] {
+        let flow = identities.insert(
+            label,
+            SsoConfig {
+                account_id: account.into(),
+                ..template.config().clone()
+            },
+        );
+        let path = flow.cache_path(CacheStage::Credentials).unwrap();
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            &path,
+            serde_json::json!({
+                "access_key_id": format!("ASIA{label}"),
+                "secret_access_key": "secret",
+                "session_token": "token",
+                "expires_at": Utc::now() + chrono::Duration::hours(1),
+            })
+            .to_string(),
+        )
+        .unwrap();
+    }
+    assert_eq!(identities.labels(), ["acme", "globex"]);
+
+    let acme = identities
+        .get("acme")
+        .unwrap()
+        .authenticate()
+        .await
+        .unwrap();
+    assert_eq!(acme.access_key_id, "ASIAacme");
+    let globex = identities
+        .get("globex")
+        .unwrap()
+        .authenticate()
+        .await
+        .unwrap();
+    assert_eq!(globex.access_key_id, "ASIAglobex");
+    assert!(identities.get("initech").is_none());
+
+    assert!(identities.remove("acme").is_some());
+    assert_eq!(identities.len(), 1);
+    assert!(identities.get("acme").is_none());
+
+    let _ = fs::remove_dir_all(&dir);
+}
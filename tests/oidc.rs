@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use aws_sso_flow::oidc::{AccessToken, ClientRegistration, CreateTokenError, DeviceAuthorization};
+use chrono::Utc;
+
+#[test]
+fn debug_omits_secrets() {
+    let registration = ClientRegistration {
+        client_id: "client-id".to_string(),
+        client_secret: "client-secret".to_string(),
+        expires_at: Utc::now(),
+    };
+    let debug = format!("{registration:?}");
+    assert!(debug.contains("client-id"));
+    assert!(!debug.contains("client-secret"));
+
+    let authorization = DeviceAuthorization {
+        device_code: "device-code".to_string(),
+        user_code: "ABCD-EFGH".to_string(),
+        verification_url: "https://device.sso.eu-west-1.amazonaws.com/?user_code=ABCD-EFGH"
+            .parse()
+            .unwrap(),
+        expires_at: Utc::now(),
+        interval: Duration::from_secs(1),
+    };
+    let debug = format!("{authorization:?}");
+    assert!(debug.contains("ABCD-EFGH"));
+    assert!(!debug.contains("device-code"));
+
+    let token = AccessToken {
+        access_token: "access-token".to_string(),
+        expires_at: Utc::now(),
+    };
+    assert!(!format!("{token:?}").contains("access-token"));
+}
+
+#[test]
+fn create_token_error_display() {
+    assert_eq!(
+        CreateTokenError::AuthorizationPending.to_string(),
+        "the user has not granted access yet"
+    );
+    assert_eq!(
+        CreateTokenError::Expired.to_string(),
+        "the device authorization expired"
+    );
+}
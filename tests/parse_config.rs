@@ -0,0 +1,81 @@
+use std::{fs, path::Path};
+
+use aws_sso_flow::profile::{parse_config, Profile};
+use proptest::prelude::*;
+
+#[test]
+fn golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut checked = 0;
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "config") {
+            continue;
+        }
+
+        let config = fs::read_to_string(&path).unwrap();
+        let actual = format!("{:#?}\n", parse_config(&config));
+
+        let expected_path = path.with_extension("expected");
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&expected_path, &actual).unwrap();
+        }
+        let expected = fs::read_to_string(&expected_path).unwrap();
+        assert_eq!(actual, expected, "{} did not match", path.display());
+        checked += 1;
+    }
+    assert!(checked > 0, "no golden files found in {}", dir.display());
+}
+
+fn name() -> impl Strategy<Value = String> {
+    "[A-Za-z0-9_.-]{1,16}"
+}
+
+fn value() -> impl Strategy<Value = String> {
+    "[!-~]([ -~]{0,30}[!-~])?"
+}
+
+fn profile() -> impl Strategy<Value = Profile> {
+    (name(), prop::collection::vec((name(), value()), 0..8))
+        .prop_map(|(name, properties)| Profile { name, properties })
+}
+
+fn render(profiles: &[Profile], use_profile_prefix: bool, indent: &str) -> String {
+    let mut config = String::new();
+    for profile in profiles {
+        if use_profile_prefix {
+            config.push_str(&format!("[profile {}]\n", profile.name));
+        } else {
+            config.push_str(&format!("[{}]\n", profile.name));
+        }
+        for (key, value) in &profile.properties {
+            config.push_str(&format!("{indent}{key} = {value}\n"));
+        }
+        config.push('\n');
+    }
+    config
+}
+
+proptest! {
+    #[test]
+    fn roundtrip(
+        profiles in prop::collection::vec(profile(), 0..6),
+        use_profile_prefix: bool,
+        indent in "[ \t]{0,4}",
+    ) {
+        let config = render(&profiles, use_profile_prefix, &indent);
+        prop_assert_eq!(parse_config(&config), profiles);
+    }
+
+    #[test]
+    fn never_panics(config in "\\PC*") {
+        let _ = parse_config(&config);
+    }
+
+    #[test]
+    fn get_returns_last(name in name(), key in name(), values in prop::collection::vec(value(), 1..4)) {
+        let properties = values.iter().map(|value| (key.clone(), value.clone())).collect();
+        let profile = Profile { name, properties };
+        prop_assert_eq!(profile.get(&key), values.last().map(String::as_str));
+    }
+}
@@ -0,0 +1,20 @@
+use aws_sso_flow::{portal, AccountInfo, Region, SsoApiError};
+use futures::stream::BoxStream;
+
+#[test]
+fn streams_outlive_client() {
+    // Listing is lazy, and the streams don't borrow the client or token, so they can be stored or
+    // sent to other tasks
+    let accounts: BoxStream<'static, Result<AccountInfo, SsoApiError>> = {
+        let client = portal::Client::new(Region::EU_WEST_1);
+        let token = String::from("access-token");
+        client.list_accounts(&token)
+    };
+    drop(accounts);
+}
+
+#[test]
+fn debug_omits_transport() {
+    let client = portal::Client::new(Region::EU_WEST_1);
+    assert!(format!("{client:?}").starts_with("Client"));
+}
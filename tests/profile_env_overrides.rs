@@ -0,0 +1,73 @@
+use std::fs;
+
+use aws_sso_flow::{ErrorCode, ProfileSource, SsoConfigOverrides, SsoConfigSource};
+
+mod common;
+
+// This is the only test in this binary, so setting the environment can't race with other tests
+#[tokio::test]
+async fn env_overrides() {
+    let dir = common::temp_dir("profile_env_overrides");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config");
+    fs::write(
+        &path,
+        "[profile dev]
+sso_start_url = https://myorg.awsapps.com/start
+sso_region = eu-west-1
+sso_account_name = Development
+",
+    )
+    .unwrap();
+    let source = ProfileSource::default()
+        .with_config_file(&path)
+        .with_profile("dev");
+    std::env::set_var("AWS_SSO_ACCOUNT_ID", "123456789012");
+    std::env::set_var("AWS_SSO_ROLE_NAME", "Power User");
+
+    // The environment is ignored unless overrides from it are enabled
+    let error = source.clone().load().await.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ProfileMissingKeys);
+
+    let source = source.with_env_overrides();
+    let config = source.clone().load().await.unwrap();
+    assert_eq!(config.account(), "123456789012".into());
+    assert_eq!(config.role().to_string(), "Power User");
+    assert!(source
+        .describe()
+        .ends_with("overriding sso_account_id, sso_role_name"));
+
+    // Explicit overrides take precedence over the environment
+    let config = source
+        .clone()
+        .with_overrides(SsoConfigOverrides {
+            role_name: Some("Admin".into()),
+            ..SsoConfigOverrides::default()
+        })
+        .load()
+        .await
+        .unwrap();
+    assert_eq!(config.role().to_string(), "Admin");
+
+    // Empty values are ignored
+    std::env::set_var("AWS_SSO_ACCOUNT_ID", "");
+    std::env::remove_var("AWS_SSO_ROLE_NAME");
+    let error = source.clone().load().await.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ProfileMissingKeys);
+    assert!(
+        error.to_string().ends_with("missing: sso_role_name"),
+        "{error}"
+    );
+
+    // Both variables are validated
+    std::env::set_var("AWS_SSO_ACCOUNT_ID", "1234");
+    let error = source.clone().load().await.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ProfileEnvironment);
+    assert!(error.to_string().contains("AWS_SSO_ACCOUNT_ID"), "{error}");
+
+    std::env::set_var("AWS_SSO_ACCOUNT_ID", "123456789012");
+    std::env::set_var("AWS_SSO_ROLE_NAME", "Admin\n");
+    let error = source.load().await.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ProfileEnvironment);
+    assert!(error.to_string().contains("AWS_SSO_ROLE_NAME"), "{error}");
+}
@@ -0,0 +1,299 @@
+use std::{fs, path::PathBuf};
+
+use aws_sso_flow::{
+    profile::SymlinkPolicy, AccountSelector, ErrorCode, ProfileSource, Region, SsoConfigOverrides,
+    SsoConfigSource, SsoProfileError,
+};
+
+mod common;
+
+const CONFIG: &str = "[profile dev]
+sso_start_url = https://myorg.awsapps.com/start
+sso_region = eu-west-1
+sso_account_id = 123456789012
+sso_role_name = Developer
+";
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = common::temp_dir(&format!("profile_source-{name}"));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+async fn load(path: PathBuf, symlinks: SymlinkPolicy) -> Result<(), SsoProfileError> {
+    ProfileSource::default()
+        .with_config_file(path)
+        .with_profile("dev")
+        .symlinks(symlinks)
+        .load()
+        .await
+        .map(drop)
+}
+
+#[tokio::test]
+async fn missing_file() {
+    let dir = temp_dir("missing");
+    let error = load(dir.join("config"), SymlinkPolicy::Follow)
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ProfileConfigFile);
+}
+
+#[tokio::test]
+async fn directory() {
+    let dir = temp_dir("directory");
+    let error = load(dir.clone(), SymlinkPolicy::Follow).await.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ProfileConfigFile);
+    assert!(error.to_string().contains("not a file"), "{error}");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn symlinks() {
+    let dir = temp_dir("symlinks");
+    fs::write(dir.join("real"), CONFIG).unwrap();
+    std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+
+    load(dir.join("real"), SymlinkPolicy::Deny).await.unwrap();
+    load(dir.join("link"), SymlinkPolicy::Follow).await.unwrap();
+
+    let error = load(dir.join("link"), SymlinkPolicy::Deny)
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ProfileConfigFile);
+    assert!(error.to_string().contains("symbolic link"), "{error}");
+}
+
+const SECTION_FORMS: &str = "[default]
+sso_start_url = https://myorg.awsapps.com/start
+sso_region = eu-west-1
+sso_account_id = 111111111111
+sso_role_name = Unprefixed
+
+[profile default]
+sso_start_url = https://myorg.awsapps.com/start
+sso_region = eu-west-1
+sso_account_id = 222222222222
+sso_role_name = Prefixed
+
+[dev]
+sso_start_url = https://myorg.awsapps.com/start
+sso_region = eu-west-1
+sso_account_id = 333333333333
+sso_role_name = Dev
+";
+
+#[tokio::test]
+async fn profile_prefix() {
+    let dir = temp_dir("profile_prefix");
+    fs::write(dir.join("config"), SECTION_FORMS).unwrap();
+    let source = |profile: &str, require_profile_prefix| {
+        ProfileSource::default()
+            .with_config_file(dir.join("config"))
+            .with_profile(profile)
+            .require_profile_prefix(require_profile_prefix)
+            .strict(true)
+            .load()
+    };
+
+    // `[profile default]` takes precedence over `[default]`, and they aren't duplicates
+    let config = source("default", true).await.unwrap();
+    assert_eq!(config.account(), "222222222222".into());
+
+    let config = source("dev", false).await.unwrap();
+    assert_eq!(config.account(), "333333333333".into());
+
+    let error = source("dev", true).await.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ProfileMissing);
+    assert!(error.to_string().contains("[profile dev]"), "{error}");
+}
+
+#[tokio::test]
+async fn multiple_files() {
+    let dir = temp_dir("multiple_files");
+    fs::write(dir.join("base"), CONFIG).unwrap();
+    fs::write(
+        dir.join("team"),
+        "[profile dev]\nsso_role_name = Admin\n\n[profile other]\nsso_region = us-east-1\n",
+    )
+    .unwrap();
+
+    let source = ProfileSource::default()
+        .with_config_files([dir.join("base"), dir.join("team")])
+        .with_profile("dev")
+        .strict(true);
+    let (paths, _) = source.resolve_paths().unwrap();
+    assert_eq!(paths, [dir.join("base"), dir.join("team")]);
+
+    // Later files take precedence, and aren't duplicates in strict mode
+    let config = source.load().await.unwrap();
+    assert_eq!(config.account(), "123456789012".into());
+    assert_eq!(config.role().to_string(), "Admin");
+
+    let error = ProfileSource::default()
+        .with_config_files([dir.join("base"), dir.join("missing")])
+        .with_profile("dev")
+        .load()
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ProfileConfigFile);
+}
+
+#[tokio::test]
+async fn role_names_with_special_characters() {
+    let dir = temp_dir("role_names");
+    let cases = [
+        ("Power User", "Power User"),
+        ("\"Power User\"", "Power User"),
+        ("'Power User'", "Power User"),
+        (r#""Say \"hi\" \\ bye""#, r#"Say "hi" \ bye"#),
+        ("Admin=Full,Access@Team", "Admin=Full,Access@Team"),
+        (
+            "aws-reserved/sso.amazonaws.com/AWSReservedSSO_Admin_0123456789abcdef",
+            "aws-reserved/sso.amazonaws.com/AWSReservedSSO_Admin_0123456789abcdef",
+        ),
+        // Unbalanced or partial quotes are kept as they are
+        ("\"Power User", "\"Power User"),
+        ("Power \"User\"", "Power \"User\""),
+        (r#""Power User\""#, r#""Power User\""#),
+    ];
+    for (value, expected) in cases {
+        fs::write(
+            dir.join("config"),
+            format!(
+                "[profile dev]
+sso_start_url = https://myorg.awsapps.com/start
+sso_region = eu-west-1
+sso_account_id = 123456789012
+sso_role_name = {value}
+"
+            ),
+        )
+        .unwrap();
+        let config = ProfileSource::default()
+            .with_config_file(dir.join("config"))
+            .with_profile("dev")
+            .load()
+            .await
+            .unwrap();
+        assert_eq!(config.role().to_string(), expected, "for {value}");
+    }
+}
+
+#[tokio::test]
+async fn overrides() {
+    let dir = temp_dir("overrides");
+    fs::write(dir.join("config"), CONFIG).unwrap();
+    let source = ProfileSource::default()
+        .with_config_file(dir.join("config"))
+        .with_profile("dev");
+
+    let config = source
+        .clone()
+        .with_overrides(SsoConfigOverrides {
+            role_name: Some("ReadOnly".into()),
+            ..SsoConfigOverrides::default()
+        })
+        .load()
+        .await
+        .unwrap();
+    assert_eq!(config.region, Region::EU_WEST_1);
+    assert_eq!(config.account(), "123456789012".into());
+    assert_eq!(config.role().to_string(), "ReadOnly");
+
+    // Overridden properties needn't be in the profile
+    fs::write(
+        dir.join("config"),
+        "[profile dev]\nsso_start_url = https://myorg.awsapps.com/start\nsso_account_name = Dev\n",
+    )
+    .unwrap();
+    let source = source.with_overrides(SsoConfigOverrides {
+        region: Some(Region::US_EAST_1),
+        account_id: Some("333333333333".parse().unwrap()),
+        role_name: Some("Admin".into()),
+        ..SsoConfigOverrides::default()
+    });
+    assert_eq!(
+        source.describe(),
+        format!(
+            "profile dev in {}, overriding sso_region, sso_account_id, sso_role_name",
+            dir.join("config").display()
+        )
+    );
+    let config = source.load().await.unwrap();
+    assert_eq!(config.region, Region::US_EAST_1);
+    assert_eq!(config.start_url, "https://myorg.awsapps.com/start");
+    assert_eq!(config.account(), "333333333333".into());
+    assert_eq!(config.role().to_string(), "Admin");
+}
+
+#[tokio::test]
+async fn account_name() {
+    let dir = temp_dir("account_name");
+    fs::write(
+        dir.join("config"),
+        CONFIG.replace("sso_account_id = 123456789012", "sso_account_name = Dev"),
+    )
+    .unwrap();
+    let source = ProfileSource::default()
+        .with_config_file(dir.join("config"))
+        .with_profile("dev");
+
+    let config = source.clone().load().await.unwrap();
+    assert_eq!(config.account_name.as_deref(), Some("Dev"));
+    assert_eq!(config.account(), AccountSelector::Name("Dev".to_string()));
+    assert_eq!(config.role_name, "Developer");
+
+    // `sso_account_id` takes precedence, since it doesn't need to be resolved
+    fs::write(
+        dir.join("config"),
+        format!("{CONFIG}sso_account_name = Dev\n"),
+    )
+    .unwrap();
+    let config = source.load().await.unwrap();
+    assert_eq!(config.account_id, "123456789012");
+    assert_eq!(config.account_name, None);
+}
+
+#[tokio::test]
+async fn only_role_names_are_unquoted() {
+    let dir = temp_dir("unquoted");
+    fs::write(
+        dir.join("config"),
+        "[profile dev]
+sso_start_url = https://myorg.awsapps.com/start
+sso_region = eu-west-1
+sso_account_name = \"Team Ops\"
+sso_role_name = \"Power User\"
+",
+    )
+    .unwrap();
+    let config = ProfileSource::default()
+        .with_config_file(dir.join("config"))
+        .with_profile("dev")
+        .load()
+        .await
+        .unwrap();
+    assert_eq!(config.account_name.as_deref(), Some("\"Team Ops\""));
+    assert_eq!(config.role_name, "Power User");
+}
+
+#[tokio::test]
+async fn invalid_values() {
+    let dir = temp_dir("invalid_values");
+    let source = ProfileSource::default()
+        .with_config_file(dir.join("config"))
+        .with_profile("dev");
+
+    for (valid, invalid) in [
+        ("sso_account_id = 123456789012", "sso_account_id = 1234"),
+        ("sso_role_name = Developer", "sso_role_name = \"\""),
+    ] {
+        fs::write(dir.join("config"), CONFIG.replace(valid, invalid)).unwrap();
+        let error = source.clone().load().await.unwrap_err();
+        assert_eq!(error.code(), ErrorCode::ProfileInvalidValue);
+        assert_eq!(error.profile(), Some("dev"));
+        let key = invalid.split(' ').next().unwrap();
+        assert!(error.to_string().contains(key), "{error}");
+    }
+}
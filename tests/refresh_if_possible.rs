@@ -0,0 +1,35 @@
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use aws_sso_flow::{ErrorCode, RefreshOutcome, SsoFlowBuilder};
+
+mod common;
+
+#[tokio::test]
+async fn never_prompts() {
+    let prompted = Arc::new(AtomicBool::new(false));
+    let config = common::config();
+    let builder = SsoFlowBuilder::self_contained(config).verification_prompt({
+        let prompted = Arc::clone(&prompted);
+        move |_| {
+            prompted.store(true, Ordering::Relaxed);
+            async { Ok::<_, Infallible>(()) }
+        }
+    });
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    let flow = builder.clone().build().await.unwrap();
+    let outcome = flow.refresh_if_possible(deadline).await.unwrap();
+    assert_eq!(outcome, RefreshOutcome::InteractionRequired);
+    assert!(!prompted.load(Ordering::Relaxed));
+
+    let flow = builder.offline(true).build().await.unwrap();
+    let error = flow.refresh_if_possible(deadline).await.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::OfflineUnavailable);
+}
@@ -0,0 +1,75 @@
+#![cfg(all(unix, feature = "process"))]
+
+use std::fs;
+
+use aws_sso_flow::{CacheStage, ErrorCode, RunError, SsoFlowBuilder};
+use chrono::{Duration, Utc};
+
+mod common;
+
+#[tokio::test]
+async fn runs_command_with_credential_process_profile() {
+    let dir = common::temp_dir("run_with_credentials");
+
+    let flow = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .offline(true)
+        .config(common::config())
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+
+    let error = flow
+        .run_with_credentials("true", [""; 0])
+        .await
+        .unwrap_err();
+    assert!(matches!(error, RunError::Flow(_)));
+    assert_eq!(error.code(), ErrorCode::OfflineUnavailable);
+    let RunError::Flow(error) = error else {
+        unreachable!()
+    };
+    assert!(error.flow_id().is_some());
+
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        flow.cache_path(CacheStage::Credentials).unwrap(),
+        serde_json::json!({
+            "access_key_id": "ASIAEXAMPLE",
+            "secret_access_key": "secret",
+            "session_token": "token",
+            "expires_at": Utc::now() + Duration::hours(1),
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    // The command sees the profile, not credential variables, and the profile's
+    // `credential_process` prints the credentials. Only the current user can access the files.
+    let script = r#"
+        test "$AWS_PROFILE" = aws_sso_flow &&
+        test -z "$AWS_ACCESS_KEY_ID" &&
+        ls -ld "$(dirname "$AWS_CONFIG_FILE")" | grep -q '^drwx------' &&
+        ls -l "$AWS_CONFIG_FILE" | grep -q '^-rw-------' &&
+        eval "$(sed -n 's/^credential_process = //p' "$AWS_CONFIG_FILE")" | grep -q ASIAEXAMPLE
+    "#;
+    let status = flow
+        .run_with_credentials("sh", ["-c", script])
+        .await
+        .unwrap();
+    assert!(status.success());
+
+    let status = flow
+        .run_with_credentials("sh", ["-c", "exit 3"])
+        .await
+        .unwrap();
+    assert_eq!(status.code(), Some(3));
+
+    let error = flow
+        .run_with_credentials("aws_sso_flow-no-such-command", [""; 0])
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::Command);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "rusoto")]
+
+use std::fs;
+
+use async_trait::async_trait;
+use aws_sso_flow::{ChainProvider, SsoFlowBuilder};
+use rusoto_credential::{AwsCredentials, CredentialsError, ProvideAwsCredentials, StaticProvider};
+
+mod common;
+
+/// A provider that always fails with a message like an SSO authentication error's.
+struct Failing;
+
+#[async_trait]
+impl ProvideAwsCredentials for Failing {
+    async fn credentials(&self) -> Result<AwsCredentials, CredentialsError> {
+        Err(CredentialsError::new(
+            "SSO authentication failed: not really",
+        ))
+    }
+}
+
+#[tokio::test]
+async fn authentication_errors_stop_the_chain() {
+    let dir = common::temp_dir("rusoto_chain");
+
+    // Nothing is cached, so the offline flow fails to authenticate
+    let flow = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .offline(true)
+        .config(common::config())
+        .verification_prompt(common::no_prompt());
+    let chain = ChainProvider::new()
+        .push(flow)
+        .push(StaticProvider::new_minimal(
+            "AKIAEXAMPLE".to_string(),
+            "secret".to_string(),
+        ));
+
+    let error = chain.credentials().await.unwrap_err();
+    assert_eq!(
+        error.message,
+        "SSO authentication failed: no fresh cached credentials are available offline"
+    );
+    let (result, fatal) = ChainProvider::track_fatal(chain.credentials()).await;
+    assert!(result.is_err());
+    assert!(fatal);
+
+    // Other providers' errors fall through, whatever their message
+    let chain = ChainProvider::new()
+        .push(Failing)
+        .push(StaticProvider::new_minimal(
+            "AKIAEXAMPLE".to_string(),
+            "secret".to_string(),
+        ));
+    let credentials = chain.credentials().await.unwrap();
+    assert_eq!(credentials.aws_access_key_id(), "AKIAEXAMPLE");
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,27 @@
+use aws_sso_flow::{SsoConfig, SsoFlowBuilder};
+
+mod common;
+
+#[tokio::test]
+async fn shutdown_covers_derived_flows() {
+    let dir = common::temp_dir("shutdown");
+    let flow = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .config(common::config())
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+    let derived = flow.with_config(SsoConfig {
+        role_name: "ReadOnly".into(),
+        ..flow.config().clone()
+    });
+
+    let shutdown = flow.shutdown_handle();
+    assert!(shutdown.running().is_empty());
+    assert!(!derived.shutdown_handle().is_shut_down());
+
+    shutdown.shutdown().await;
+    assert!(shutdown.is_shut_down());
+    assert!(derived.shutdown_handle().is_shut_down());
+}
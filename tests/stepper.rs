@@ -0,0 +1,32 @@
+use std::fs;
+
+use aws_sso_flow::{ErrorCode, FlowState, SsoFlowBuilder};
+use chrono::{Duration, Utc};
+
+mod common;
+
+#[tokio::test]
+async fn starts_from_first_uncached_stage() {
+    let dir = common::temp_dir("stepper");
+
+    let builder = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .config(common::config())
+        .verification_prompt(common::no_prompt());
+
+    let flow = builder.clone().build().await.unwrap();
+    let stepper = flow.stepper().await.unwrap();
+    assert!(matches!(stepper.state(), FlowState::NeedsClient));
+
+    flow.import_access_token("token", Utc::now() + Duration::hours(1))
+        .await
+        .unwrap();
+    let stepper = flow.stepper().await.unwrap();
+    assert!(matches!(stepper.state(), FlowState::NeedsCredentials));
+
+    let offline = builder.offline(true).build().await.unwrap();
+    let error = offline.stepper().await.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::OfflineUnavailable);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,401 @@
+#![cfg(feature = "test-server")]
+
+use std::{
+    convert::Infallible,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use aws_sso_flow::{
+    test_server::{Behavior, TestServer},
+    CacheStage, Clock, PendingVerification, PromptContext, SsoConfig, SsoFlow, SsoFlowBuilder,
+    SsoFlowError, VerificationPrompt,
+};
+use chrono::{DateTime, Utc};
+use futures::future::{self, Ready};
+use url::Url;
+
+mod common;
+
+fn cache_dir(name: &str) -> PathBuf {
+    common::temp_dir(&format!("test_server-{name}"))
+}
+
+async fn flow(
+    server: &TestServer,
+    name: &str,
+) -> SsoFlow<impl VerificationPrompt<Error = Infallible>> {
+    let dir = cache_dir(name);
+    SsoFlowBuilder::self_contained(server.config())
+        .cache_dir(dir)
+        .endpoint_url(server.url())
+        .min_poll_interval(Duration::from_millis(10))
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn pending_then_granted() {
+    let server = TestServer::start(Behavior::default().pending(3))
+        .await
+        .unwrap();
+    let flow = flow(&server, "pending").await;
+
+    let credentials = flow.authenticate().await.unwrap();
+    assert!(credentials.access_key_id.starts_with("ASIATEST"));
+    assert_eq!(
+        server.operations(),
+        [
+            "RegisterClient",
+            "StartDeviceAuthorization",
+            "CreateToken",
+            "CreateToken",
+            "CreateToken",
+            "CreateToken",
+            "GetRoleCredentials",
+        ]
+    );
+
+    // Credentials are cached
+    flow.authenticate().await.unwrap();
+    assert_eq!(server.operations().len(), 7);
+}
+
+/// A prompt that records the pending verification.
+#[derive(Clone)]
+struct RecordPending(Arc<Mutex<Option<PendingVerification>>>);
+
+impl VerificationPrompt for RecordPending {
+    type Future = Ready<Result<(), Infallible>>;
+    type Error = Infallible;
+
+    fn prompt(self, _verification_url: Url) -> Self::Future {
+        future::ok(())
+    }
+
+    fn prompt_with_context(self, context: PromptContext) -> Self::Future {
+        *self.0.lock().unwrap() = Some(context.pending().clone());
+        future::ok(())
+    }
+}
+
+#[tokio::test]
+async fn slow_down_is_retried_less_often() {
+    let server = TestServer::start(Behavior::default().slow_down(1).pending(1))
+        .await
+        .unwrap();
+    let dir = cache_dir("slow_down");
+    let pending = Arc::new(Mutex::new(None));
+    let flow = SsoFlowBuilder::self_contained(server.config())
+        .cache_dir(dir)
+        .endpoint_url(server.url())
+        .min_poll_interval(Duration::from_millis(10))
+        .verification_prompt(RecordPending(Arc::clone(&pending)))
+        .build()
+        .await
+        .unwrap();
+
+    flow.authenticate().await.unwrap();
+    let polls = server
+        .operations()
+        .into_iter()
+        .filter(|operation| *operation == "CreateToken")
+        .count();
+    assert_eq!(polls, 3);
+
+    // The interval grows by 5s, as RFC 8628 requires, and the slow down isn't counted as a retry
+    let pending = pending.lock().unwrap().take().unwrap();
+    assert_eq!(
+        pending.interval(),
+        Duration::from_millis(10) + Duration::from_secs(5)
+    );
+    assert_eq!(pending.retries(), 0);
+}
+
+#[tokio::test]
+async fn zero_interval_polls_at_the_minimum_interval() {
+    let server = TestServer::start(Behavior::default().interval(Duration::ZERO).pending(3))
+        .await
+        .unwrap();
+    let pending = Arc::new(Mutex::new(None));
+    let flow = SsoFlowBuilder::self_contained(server.config())
+        .cache_dir(cache_dir("zero_interval"))
+        .endpoint_url(server.url())
+        .min_poll_interval(Duration::from_millis(100))
+        .verification_prompt(RecordPending(Arc::clone(&pending)))
+        .build()
+        .await
+        .unwrap();
+
+    let started = Instant::now();
+    flow.authenticate().await.unwrap();
+    let polls = server
+        .operations()
+        .into_iter()
+        .filter(|operation| *operation == "CreateToken")
+        .count();
+    assert_eq!(polls, 4);
+    assert!(started.elapsed() >= Duration::from_millis(300));
+
+    let pending = pending.lock().unwrap().take().unwrap();
+    assert_eq!(pending.interval(), Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn expired_user_code() {
+    let server = TestServer::start(Behavior::default().pending(1).expire(true))
+        .await
+        .unwrap();
+    let flow = flow(&server, "expire").await;
+
+    let error = flow.authenticate().await.unwrap_err();
+    assert!(
+        matches!(error, SsoFlowError::VerificationPromptTimeout(_)),
+        "{error:?}"
+    );
+    assert!(error.flow_id().is_some());
+    assert!(!server.operations().contains(&"GetRoleCredentials"));
+}
+
+#[tokio::test]
+async fn revoked_token_is_replaced() {
+    let server = TestServer::start(Behavior::default()).await.unwrap();
+    let flow = flow(&server, "revoke").await;
+    flow.authenticate().await.unwrap();
+
+    server.revoke_tokens();
+    fs::remove_file(flow.cache_path(CacheStage::Credentials).unwrap()).unwrap();
+    flow.authenticate().await.unwrap();
+    assert_eq!(
+        server.operations()[3..],
+        [
+            "GetRoleCredentials",
+            "GetRoleCredentials",
+            "StartDeviceAuthorization",
+            "CreateToken",
+            "GetRoleCredentials",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn sessions_are_not_shared_between_endpoints() {
+    let first = TestServer::start(Behavior::default()).await.unwrap();
+    let second = TestServer::start(Behavior::default()).await.unwrap();
+    flow(&first, "endpoints-first")
+        .await
+        .authenticate()
+        .await
+        .unwrap();
+
+    // The same start URL and region at another endpoint needs its own registration and token
+    let flow = SsoFlowBuilder::self_contained(first.config())
+        .cache_dir(cache_dir("endpoints-second"))
+        .endpoint_url(second.url())
+        .min_poll_interval(Duration::from_millis(10))
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+    flow.authenticate().await.unwrap();
+    assert_eq!(
+        second.operations(),
+        [
+            "RegisterClient",
+            "StartDeviceAuthorization",
+            "CreateToken",
+            "GetRoleCredentials",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn role_names_with_special_characters() {
+    let server = TestServer::start(Behavior::default().roles(["Power User/Ops+1"]))
+        .await
+        .unwrap();
+    let flow = flow(&server, "role_names").await;
+    assert_eq!(flow.config().role_name, "Power User/Ops+1");
+
+    // The server only grants the role if its name survives the transport's query encoding
+    flow.authenticate().await.unwrap();
+    assert_eq!(server.operations().last(), Some(&"GetRoleCredentials"));
+}
+
+#[tokio::test]
+async fn flows_for_other_roles_share_the_registration() {
+    let server = TestServer::start(Behavior::default().roles(["TestRole", "OtherRole"]))
+        .await
+        .unwrap();
+    let other_role = |dir| {
+        SsoFlowBuilder::self_contained(SsoConfig {
+            role_name: "OtherRole".into(),
+            ..server.config()
+        })
+        .cache_dir(dir)
+        .endpoint_url(server.url())
+        .min_poll_interval(Duration::from_millis(10))
+        .verification_prompt(common::no_prompt())
+        .build()
+    };
+
+    // Flows with separate caches share the registration and token within the process
+    let first = flow(&server, "roles-first").await;
+    first.authenticate().await.unwrap();
+    other_role(cache_dir("roles-second"))
+        .await
+        .unwrap()
+        .authenticate()
+        .await
+        .unwrap();
+    assert_eq!(
+        server.operations(),
+        [
+            "RegisterClient",
+            "StartDeviceAuthorization",
+            "CreateToken",
+            "GetRoleCredentials",
+            "GetRoleCredentials",
+        ]
+    );
+
+    // Flows with the same cache share the cached registration, but not the cached credentials
+    let client_path = first.cache_path(CacheStage::Client).unwrap();
+    assert!(client_path.exists());
+    let second = other_role(client_path.parent().unwrap().to_path_buf())
+        .await
+        .unwrap();
+    assert_eq!(second.cache_path(CacheStage::Client), Some(client_path));
+    assert_ne!(
+        first.cache_path(CacheStage::Credentials),
+        second.cache_path(CacheStage::Credentials)
+    );
+}
+
+/// A prompt that counts how often it's called.
+#[derive(Clone)]
+struct CountPrompts(Arc<AtomicUsize>);
+
+impl VerificationPrompt for CountPrompts {
+    type Future = Ready<Result<(), Infallible>>;
+    type Error = Infallible;
+
+    fn prompt(self, _verification_url: Url) -> Self::Future {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        future::ok(())
+    }
+}
+
+#[tokio::test]
+async fn concurrent_flows_prompt_once() {
+    let server = TestServer::start(Behavior::default().pending(3))
+        .await
+        .unwrap();
+    let prompts = Arc::new(AtomicUsize::new(0));
+    let flow = |name| {
+        SsoFlowBuilder::self_contained(server.config())
+            .cache_dir(cache_dir(name))
+            .endpoint_url(server.url())
+            .min_poll_interval(Duration::from_millis(10))
+            .verification_prompt(CountPrompts(Arc::clone(&prompts)))
+            .build()
+    };
+    let first = flow("concurrent-first").await.unwrap();
+    let second = flow("concurrent-second").await.unwrap();
+
+    let (first, second) = future::join(first.authenticate(), second.authenticate()).await;
+    first.unwrap();
+    second.unwrap();
+    assert_eq!(prompts.load(Ordering::SeqCst), 1);
+    let operations = server.operations();
+    for operation in ["RegisterClient", "StartDeviceAuthorization"] {
+        let calls = operations.iter().filter(|op| **op == operation).count();
+        assert_eq!(calls, 1, "{operation}: {operations:?}");
+    }
+}
+
+#[tokio::test]
+async fn corrupt_token_cache_does_not_prompt() {
+    let server = TestServer::start(Behavior::default()).await.unwrap();
+    let prompts = Arc::new(AtomicUsize::new(0));
+    let flow = SsoFlowBuilder::self_contained(server.config())
+        .cache_dir(cache_dir("corrupt_token"))
+        .endpoint_url(server.url())
+        .min_poll_interval(Duration::from_millis(10))
+        .verification_prompt(CountPrompts(Arc::clone(&prompts)))
+        .build()
+        .await
+        .unwrap();
+    flow.authenticate().await.unwrap();
+
+    // The token the flow obtained is used instead of prompting again
+    fs::write(flow.cache_path(CacheStage::Token).unwrap(), "{").unwrap();
+    fs::remove_file(flow.cache_path(CacheStage::Credentials).unwrap()).unwrap();
+    flow.authenticate().await.unwrap();
+    assert_eq!(prompts.load(Ordering::SeqCst), 1);
+    assert_eq!(server.operations().last(), Some(&"GetRoleCredentials"));
+}
+
+/// A clock that can be moved forward.
+#[derive(Clone, Debug)]
+struct FakeClock(Arc<Mutex<DateTime<Utc>>>);
+
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[tokio::test]
+async fn refresh_ahead_replaces_cached_credentials() {
+    let server = TestServer::start(Behavior::default()).await.unwrap();
+    let prompts = Arc::new(AtomicUsize::new(0));
+    let now = Arc::new(Mutex::new(Utc::now()));
+    let flow = SsoFlowBuilder::self_contained(server.config())
+        .cache_dir(cache_dir("refresh_ahead"))
+        .endpoint_url(server.url())
+        .min_poll_interval(Duration::from_millis(10))
+        .refresh_ahead(Duration::from_secs(15 * 60))
+        .clock(FakeClock(Arc::clone(&now)))
+        .verification_prompt(CountPrompts(Arc::clone(&prompts)))
+        .build()
+        .await
+        .unwrap();
+    let cached = flow.authenticate().await.unwrap();
+
+    // Within the refresh window, the cached credentials are served while they're refreshed
+    *now.lock().unwrap() += chrono::Duration::minutes(50);
+    let credentials = flow.authenticate().await.unwrap();
+    assert_eq!(credentials.access_key_id, cached.access_key_id);
+
+    let mut refreshed = None;
+    for _ in 0..100 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let credentials = flow.authenticate().await.unwrap();
+        if credentials.access_key_id != cached.access_key_id {
+            refreshed = Some(credentials);
+            break;
+        }
+    }
+    assert!(refreshed.is_some(), "credentials weren't refreshed");
+    assert_eq!(prompts.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        server.operations()[..4],
+        [
+            "RegisterClient",
+            "StartDeviceAuthorization",
+            "CreateToken",
+            "GetRoleCredentials",
+        ]
+    );
+    assert!(server.operations()[4..]
+        .iter()
+        .all(|operation| *operation == "GetRoleCredentials"));
+}
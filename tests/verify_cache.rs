@@ -0,0 +1,55 @@
+use std::fs;
+
+use aws_sso_flow::{CacheEntryStatus, CacheStage, SsoFlowBuilder};
+
+mod common;
+
+#[tokio::test]
+async fn verify_and_repair() {
+    let dir = common::temp_dir("verify_cache");
+
+    let flow = SsoFlowBuilder::new()
+        .cache_dir(&dir)
+        .config(common::config())
+        .verification_prompt(common::no_prompt())
+        .build()
+        .await
+        .unwrap();
+
+    let report = flow.verify_cache(false).await;
+    assert!(report.is_healthy(), "{report}");
+    assert!(report
+        .entries
+        .iter()
+        .all(|entry| entry.status == CacheEntryStatus::Missing));
+
+    let token_path = flow.cache_path(CacheStage::Token).unwrap();
+    let credentials_path = flow.cache_path(CacheStage::Credentials).unwrap();
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(&token_path, "{ not json").unwrap();
+    fs::write(&credentials_path, r#"{"format": "from the future"}"#).unwrap();
+
+    let report = flow.verify_cache(false).await;
+    assert!(!report.is_healthy(), "{report}");
+    let status = |stage| {
+        let entry = report.entries.iter().find(|entry| entry.stage == stage);
+        entry.unwrap().status.clone()
+    };
+    assert!(matches!(
+        status(CacheStage::Token),
+        CacheEntryStatus::Corrupt(_)
+    ));
+    assert!(matches!(
+        status(CacheStage::Credentials),
+        CacheEntryStatus::Incompatible(_)
+    ));
+    assert!(token_path.exists());
+
+    let report = flow.verify_cache(true).await;
+    assert!(report.is_healthy(), "{report}");
+    assert!(report.entries.iter().any(|entry| entry.repaired));
+    assert!(!token_path.exists());
+    assert!(!credentials_path.exists());
+
+    let _ = fs::remove_dir_all(&dir);
+}